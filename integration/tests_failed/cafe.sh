@@ -1,3 +1,3 @@
 #!/usr/bin/env bash
 
-cat tests_failed/cafe_latin.out
+cat cafe_latin.out
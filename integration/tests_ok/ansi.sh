@@ -1,3 +1,3 @@
 #!/usr/bin/env bash
 
-cat integration/tests_ok/data/ansi.txt
+cat data/ansi.txt
@@ -1,3 +1,3 @@
 #!/usr/bin/env bash
 
-cat tests_ok/data/cars.csv
+cat data/cars.csv
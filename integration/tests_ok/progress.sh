@@ -0,0 +1,5 @@
+#!/usr/bin/env bash
+for i in 1 2 3 4; do
+  printf '\r\033[32m[%-4s]\033[0m %d%%\033[K' "$(printf '#%.0s' $(seq 1 "$i"))" $((i * 25))
+done
+printf '\n\033[1mdone\033[0m on %s columns\n' "$COLUMNS"
@@ -0,0 +1,5 @@
+#!/bin/sh
+echo "Compiling foo"
+echo "warning: unused variable" >&2
+echo "warning: deprecated function" >&2
+echo "Done"
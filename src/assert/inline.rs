@@ -0,0 +1,149 @@
+//! Rewriting of inline snapshots in Rust source files.
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{env, fs, io};
+
+/// Environment variable that enables the update of inline snapshots.
+pub const UPDATE_ENV_VAR: &str = "CLICHE_UPDATE";
+
+/// Lines added or removed by the rewrites already done, by file and original line. Call site
+/// locations are relative to the compiled source, so we need them to locate the following
+/// snapshots of a rewritten file.
+static LINE_SHIFTS: Mutex<Vec<(PathBuf, u32, isize)>> = Mutex::new(Vec::new());
+
+/// Returns `true` if inline snapshots must be updated instead of failing.
+pub fn is_update_enabled() -> bool {
+    env::var(UPDATE_ENV_VAR).is_ok_and(|v| v == "1")
+}
+
+/// Replaces the inline snapshot passed to the method called at `location` by `actual`.
+pub fn update(location: &Location<'static>, actual: &str) -> Result<(), io::Error> {
+    let mut shifts = LINE_SHIFTS.lock().unwrap_or_else(|e| e.into_inner());
+    let path = source_path(location.file());
+    let shift = shifts
+        .iter()
+        .filter(|(p, line, _)| *p == path && *line < location.line())
+        .map(|(_, _, delta)| delta)
+        .sum::<isize>();
+    let line = location.line() as isize + shift;
+
+    let source = fs::read_to_string(&path)?;
+    let Some((source, delta)) = rewrite(&source, line as usize, location.column() as usize, actual)
+    else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "no inline snapshot found at {}:{}:{}",
+                path.display(),
+                line,
+                location.column()
+            ),
+        ));
+    };
+    fs::write(&path, source)?;
+    shifts.push((path, location.line(), delta));
+    Ok(())
+}
+
+/// Returns the path of a source `file`, as given by a caller location.
+///
+/// Locations are relative to the directory where cargo has been run, we try the crate directory
+/// as a fallback.
+fn source_path(file: &str) -> PathBuf {
+    let path = Path::new(file);
+    if path.is_relative()
+        && !path.exists()
+        && let Ok(dir) = env::var("CARGO_MANIFEST_DIR")
+    {
+        return Path::new(&dir).join(path);
+    }
+    path.to_path_buf()
+}
+
+/// Rewrites the inline snapshot of the method called at the 1-based (`line`, `column`) position
+/// in `source` with `actual`, and returns the new source with the number of added lines.
+fn rewrite(source: &str, line: usize, column: usize, actual: &str) -> Option<(String, isize)> {
+    let line_start = source
+        .split_inclusive('\n')
+        .take(line.checked_sub(1)?)
+        .map(str::len)
+        .sum::<usize>();
+    let offset = source[line_start..]
+        .char_indices()
+        .nth(column.checked_sub(1)?)
+        .map(|(i, _)| line_start + i)?;
+
+    let (start, end) = find_str_literal(source, offset)?;
+    let literal = format!("{actual:?}");
+    let delta = literal.lines().count() as isize - source[start..end].lines().count() as isize;
+    let source = format!("{}{literal}{}", &source[..start], &source[end..]);
+    Some((source, delta))
+}
+
+/// Returns the byte range of the string literal, plain or raw, passed as first argument of the
+/// method whose name starts at `offset`.
+fn find_str_literal(source: &str, offset: usize) -> Option<(usize, usize)> {
+    let rest = &source[offset..];
+    let rest = rest.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_');
+    let rest = rest.trim_start().strip_prefix('(')?.trim_start();
+    let start = source.len() - rest.len();
+
+    let bytes = rest.as_bytes();
+    let len = match bytes.first()? {
+        b'"' => {
+            let mut i = 1;
+            loop {
+                match bytes.get(i)? {
+                    b'\\' => i += 2,
+                    b'"' => break i + 1,
+                    _ => i += 1,
+                }
+            }
+        }
+        b'r' => {
+            let hashes = bytes[1..].iter().take_while(|b| **b == b'#').count();
+            let open = 1 + hashes;
+            if bytes.get(open) != Some(&b'"') {
+                return None;
+            }
+            let close = format!("\"{}", "#".repeat(hashes));
+            open + 1 + rest[open + 1..].find(&close)? + close.len()
+        }
+        _ => return None,
+    };
+    Some((start, start + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite() {
+        let source = "fn test() {\n    assert_cmd(\"echo\")\n        .stdout_inline(\"Bye\\n\")\n        .assert();\n}\n";
+        assert_eq!(
+            rewrite(source, 3, 10, "Hello \"you\"\n"),
+            Some((
+                "fn test() {\n    assert_cmd(\"echo\")\n        .stdout_inline(\"Hello \\\"you\\\"\\n\")\n        .assert();\n}\n"
+                    .to_string(),
+                0
+            ))
+        );
+    }
+
+    #[test]
+    fn test_rewrite_raw_literal() {
+        let source = "x.stdout_inline(r#\"a\n\"b\"\n\"#).assert();\n";
+        assert_eq!(
+            rewrite(source, 1, 3, "c\n"),
+            Some(("x.stdout_inline(\"c\\n\").assert();\n".to_string(), -2))
+        );
+    }
+
+    #[test]
+    fn test_rewrite_not_found() {
+        assert_eq!(rewrite("x.stdout_inline(value)\n", 1, 3, "a"), None);
+        assert_eq!(rewrite("x\n", 3, 1, "a"), None);
+    }
+}
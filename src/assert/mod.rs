@@ -0,0 +1,333 @@
+//! Assertions on a single command, to be used in Rust tests.
+use crate::command::{CommandResult, ExitCode, Stdin, capture};
+use crate::error::Error;
+use crate::text::Format;
+use crate::verify;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod inline;
+
+pub use self::inline::UPDATE_ENV_VAR;
+
+/// Creates an assertion on the command `program`.
+///
+/// Expectations are added with the builder methods, and checked by [`AssertCmd::assert`]. On
+/// failure, the test panics with the same rendered message as the cliche binary.
+pub fn assert_cmd(program: impl AsRef<OsStr>) -> AssertCmd {
+    AssertCmd {
+        program: PathBuf::from(program.as_ref()),
+        args: vec![],
+        stdin: None,
+        stdout: None,
+        stderr: None,
+        exit_code: ExitCode::from(0),
+    }
+}
+
+/// The input fed to the command.
+#[derive(Clone, Debug)]
+enum Input {
+    Bytes(Vec<u8>),
+    /// A fixture file, read by the command without being copied.
+    File(PathBuf),
+}
+
+/// An expected output.
+#[derive(Clone, Debug)]
+enum Expected {
+    /// The output must equal this value.
+    Exact(Vec<u8>),
+    /// The output must match this pattern (see `.out.pattern` files).
+    Pattern(String),
+    /// The output must equal the content of this snapshot file.
+    Snapshot(PathBuf),
+    /// The output must equal this string literal, written in the Rust source at `location`.
+    Inline {
+        value: String,
+        location: &'static Location<'static>,
+    },
+}
+
+/// Expectations on a single command, see [`assert_cmd`].
+#[derive(Clone, Debug)]
+pub struct AssertCmd {
+    program: PathBuf,
+    args: Vec<OsString>,
+    stdin: Option<Input>,
+    stdout: Option<Expected>,
+    stderr: Option<Expected>,
+    exit_code: ExitCode,
+}
+
+impl AssertCmd {
+    /// Adds an argument to the command.
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Adds arguments to the command.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|a| a.as_ref().to_os_string()));
+        self
+    }
+
+    /// Feeds the command with `stdin`. Without stdin, the command standard input is null.
+    pub fn stdin(mut self, stdin: impl AsRef<[u8]>) -> Self {
+        self.stdin = Some(Input::Bytes(stdin.as_ref().to_vec()));
+        self
+    }
+
+    /// Feeds the command with the content of the file at `path`, binary or not. The file is read
+    /// directly by the command, so large fixtures are neither loaded nor copied.
+    pub fn stdin_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.stdin = Some(Input::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Expects the command stdout to equal `expected`.
+    pub fn stdout(mut self, expected: impl AsRef<[u8]>) -> Self {
+        self.stdout = Some(Expected::Exact(expected.as_ref().to_vec()));
+        self
+    }
+
+    /// Expects the command stdout to match the pattern `expected`, with the syntax of
+    /// `.out.pattern` files.
+    pub fn stdout_matches(mut self, expected: &str) -> Self {
+        self.stdout = Some(Expected::Pattern(expected.to_string()));
+        self
+    }
+
+    /// Expects the command stdout to equal the content of the snapshot file at `path`.
+    pub fn stdout_snapshot(mut self, path: impl AsRef<Path>) -> Self {
+        self.stdout = Some(Expected::Snapshot(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Expects the command stdout to equal the string literal `expected`.
+    ///
+    /// When the environment variable `CLICHE_UPDATE` is set to `1`, a mismatching literal is
+    /// rewritten in the Rust source with the actual stdout instead of failing.
+    #[track_caller]
+    pub fn stdout_inline(mut self, expected: &str) -> Self {
+        self.stdout = Some(Expected::Inline {
+            value: expected.to_string(),
+            location: Location::caller(),
+        });
+        self
+    }
+
+    /// Expects the command stderr to equal `expected`.
+    pub fn stderr(mut self, expected: impl AsRef<[u8]>) -> Self {
+        self.stderr = Some(Expected::Exact(expected.as_ref().to_vec()));
+        self
+    }
+
+    /// Expects the command stderr to equal the content of the snapshot file at `path`.
+    pub fn stderr_snapshot(mut self, path: impl AsRef<Path>) -> Self {
+        self.stderr = Some(Expected::Snapshot(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Expects the command stderr to equal the string literal `expected`, see
+    /// [`AssertCmd::stdout_inline`].
+    #[track_caller]
+    pub fn stderr_inline(mut self, expected: &str) -> Self {
+        self.stderr = Some(Expected::Inline {
+            value: expected.to_string(),
+            location: Location::caller(),
+        });
+        self
+    }
+
+    /// Expects the command to exit with `exit_code` (`0` by default).
+    pub fn exit_code(mut self, exit_code: i32) -> Self {
+        self.exit_code = ExitCode::from(exit_code);
+        self
+    }
+
+    /// Runs the command and checks every expectation, returning the first failure.
+    ///
+    /// Mismatching inline snapshots are updated instead if `CLICHE_UPDATE` is set to `1`.
+    pub fn check(&self) -> Result<CommandResult, Error> {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        let stdin = match &self.stdin {
+            None => Stdin::Null,
+            Some(Input::Bytes(bytes)) => Stdin::Bytes(bytes),
+            Some(Input::File(path)) => {
+                if let Err(err) = fs::metadata(path) {
+                    return Err(Error::FileRead {
+                        path: path.clone(),
+                        cause: err.to_string(),
+                    });
+                }
+                Stdin::File(path)
+            }
+        };
+        let result = match capture(cmd, stdin, false) {
+            Ok(r) => r,
+            Err(err) => {
+                return Err(Error::FileRead {
+                    path: self.program.clone(),
+                    cause: err.to_string(),
+                });
+            }
+        };
+
+        verify::check_exit_code_value(&self.program, self.exit_code.into(), &result)?;
+        match &self.stdout {
+            Some(Expected::Pattern(expected)) => {
+                verify::check_stdout_pat_value(&self.program, expected, result.stdout())?;
+            }
+            Some(expected) => {
+                let check =
+                    verify::check_stdout_value(&self.program, &expected.bytes()?, result.stdout());
+                expected.update_or(check, result.stdout())?;
+            }
+            None => {}
+        }
+        match &self.stderr {
+            Some(Expected::Pattern(_)) => unreachable!(),
+            Some(expected) => {
+                let check =
+                    verify::check_stderr_value(&self.program, &expected.bytes()?, result.stderr());
+                expected.update_or(check, result.stderr())?;
+            }
+            None => {}
+        }
+        Ok(result)
+    }
+
+    /// Runs the command and checks every expectation, panicking with a rendered error on failure.
+    #[track_caller]
+    pub fn assert(&self) -> CommandResult {
+        match self.check() {
+            Ok(result) => result,
+            Err(err) => panic!("{}", err.render(Format::Plain)),
+        }
+    }
+}
+
+impl Expected {
+    /// Returns the expected bytes of an exact or snapshot expectation.
+    fn bytes(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            Expected::Exact(bytes) => Ok(bytes.clone()),
+            Expected::Snapshot(path) => fs::read(path).map_err(|err| Error::FileRead {
+                path: path.clone(),
+                cause: err.to_string(),
+            }),
+            Expected::Inline { value, .. } => Ok(value.clone().into_bytes()),
+            Expected::Pattern(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the result of a failed `check`, unless this is an inline snapshot that can be
+    /// updated with the `actual` output.
+    fn update_or(&self, check: Result<(), Error>, actual: &[u8]) -> Result<(), Error> {
+        let Err(err) = check else {
+            return Ok(());
+        };
+        let Expected::Inline { location, .. } = self else {
+            return Err(err);
+        };
+        if !inline::is_update_enabled() {
+            return Err(err);
+        }
+        let actual = String::from_utf8_lossy(actual);
+        match inline::update(location, &actual) {
+            Ok(()) => {
+                eprintln!("cliche: updated inline snapshot at {location}");
+                Ok(())
+            }
+            Err(cause) => Err(Error::FileRead {
+                path: PathBuf::from(location.file()),
+                cause: cause.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_assert_stdout() {
+        assert_cmd("echo").arg("Hello").stdout("Hello\n").assert();
+        assert_cmd("sh")
+            .args(["-c", "echo \"Hello $(cat)\"; echo oops >&2; exit 3"])
+            .stdin("Bob")
+            .stdout_matches("Hello <<<\\w+>>>\n")
+            .stderr("oops\n")
+            .exit_code(3)
+            .assert();
+    }
+
+    #[test]
+    fn test_assert_inline() {
+        assert_cmd("echo")
+            .arg("Hello")
+            .stdout_inline("Hello\n")
+            .stderr_inline("")
+            .assert();
+    }
+
+    #[test]
+    fn test_assert_snapshot() {
+        let tmp_dir = TempDir::new().unwrap();
+        let snapshot = tmp_dir.path().join("hello.out");
+        fs::write(&snapshot, "Hello\n").unwrap();
+        assert_cmd("echo")
+            .arg("Hello")
+            .stdout_snapshot(&snapshot)
+            .assert();
+    }
+
+    #[test]
+    fn test_assert_stdin_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        let fixture = tmp_dir.path().join("fixture.bin");
+        let input = [0xff, 0xfe, b'\r', b'\n', 0x00, 0x80];
+        fs::write(&fixture, input).unwrap();
+        let result = assert_cmd("cat").stdin_file(&fixture).assert();
+        assert_eq!(result.stdout(), input);
+
+        let err = assert_cmd("cat")
+            .stdin_file(tmp_dir.path().join("missing.bin"))
+            .check();
+        assert!(matches!(err, Err(Error::FileRead { .. })));
+    }
+
+    #[test]
+    fn test_check_failure() {
+        let err = assert_cmd("echo").arg("Hello").stdout("Bye\n").check();
+        assert_eq!(
+            err.err(),
+            Some(Error::CheckStdoutLine {
+                cmd_path: PathBuf::from("echo"),
+                expected: Some("Bye\n".to_string()),
+                actual: Some("Hello\n".to_string()),
+                row: 1,
+                chunk: 0,
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Stdout doesn't match at line 1")]
+    fn test_assert_failure_panics() {
+        assert_cmd("echo").arg("Hello").stdout("Bye\n").assert();
+    }
+}
@@ -0,0 +1,303 @@
+//! Reproduction bundles: a test packaged as a tarball to be run on another machine.
+//!
+//! `cliche bundle [-o FILE] [--snapshot-root SCRIPTS_DIR=SNAPSHOTS_DIR]... SCRIPT` writes a tar
+//! archive with:
+//! - the test script, its post-hook and all its expectation files,
+//! - the fixtures of the script: files and directories whose relative path appears in the script
+//!   (tests run in the current directory, so paths are relative to it),
+//! - a `bundle.txt` file describing the resolved spec and the environment of the bundle.
+//!
+//! Files are stored under a directory named after the script, with their path relative to the
+//! current directory: the test can be run again from this directory once the bundle extracted.
+use crate::command::CommandSpec;
+use crate::suite::{self, SnapshotRoot};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+/// Size of a tar block.
+const BLOCK_SIZE: usize = 512;
+
+/// Environment variables described in a bundle. Other variables are not included, as they may
+/// contain secrets.
+const ENV_VARS: [&str; 8] = [
+    "PATH", "SHELL", "LANG", "LC_ALL", "LC_CTYPE", "TERM", "TZ", "COLUMNS",
+];
+
+/// A file of a bundle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+    /// Path in the archive.
+    path: String,
+    content: Vec<u8>,
+    /// Unix permissions.
+    mode: u32,
+}
+
+/// Runs `cliche bundle`, given the arguments after `bundle`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut roots: Vec<SnapshotRoot> = vec![];
+    let mut script = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(file) => output = Some(PathBuf::from(file)),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--snapshot-root" => match args.next() {
+                Some(root) => roots.push(root.parse()?),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            file if script.is_none() => script = Some(PathBuf::from(file)),
+            file => {
+                return Err(format!(
+                    "unexpected argument <{file}>, only one test is bundled"
+                ));
+            }
+        }
+    }
+    let Some(script) = script else {
+        return Err("no test to bundle".to_string());
+    };
+    let stem = script.file_stem().unwrap_or_default().to_string_lossy();
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("{stem}.bundle.tar")));
+
+    let cwd = env::current_dir().map_err(|err| err.to_string())?;
+    let entries = collect(&script, &roots, &cwd)?;
+    fs::write(&output, tar(&entries)?)
+        .map_err(|err| format!("cannot write file {}: {err}", output.display()))?;
+    for entry in &entries {
+        eprintln!("  {}", entry.path);
+    }
+    eprintln!("Created {}", output.display());
+    Ok(())
+}
+
+/// Collects the files of the bundle of the test script at `script`, run in the directory `cwd`.
+fn collect(script: &Path, roots: &[SnapshotRoot], cwd: &Path) -> Result<Vec<Entry>, String> {
+    let snapshot_base = suite::snapshot_base(roots, script);
+    let spec = CommandSpec::with_snapshot_base(script, &snapshot_base)
+        .map_err(|err| format!("cannot read test {}: {err}", script.display()))?;
+    let cmd_path = spec.cmd_path();
+    let stem = cmd_path.file_stem().unwrap_or_default().to_string_lossy();
+    let cwd = fs::canonicalize(cwd).map_err(|err| err.to_string())?;
+
+    let mut files = vec![cmd_path.to_path_buf()];
+    files.extend(
+        spec.expectation_files()
+            .iter()
+            .map(|(_, p)| p.to_path_buf()),
+    );
+    if let Ok(sizes) = spec.screen_sizes() {
+        files.extend(
+            sizes
+                .into_iter()
+                .map(|size| spec.sized_screen_path(size))
+                .filter(|path| path.exists()),
+        );
+    }
+    let text = fs::read_to_string(cmd_path).unwrap_or_default();
+    files.extend(fixtures(&text, &cwd));
+
+    let mut entries = vec![];
+    for file in files {
+        for file in walk(&file)? {
+            // Files outside of the current directory are stored at the root of the bundle.
+            let file = fs::canonicalize(&file).unwrap_or(file);
+            let relative = match file.strip_prefix(&cwd) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => PathBuf::from(file.file_name().unwrap_or_default()),
+            };
+            let path = format!("{stem}/{}", relative.display());
+            if entries.iter().any(|e: &Entry| e.path == path) {
+                continue;
+            }
+            let content = fs::read(&file)
+                .map_err(|err| format!("cannot read file {}: {err}", file.display()))?;
+            entries.push(Entry {
+                path,
+                content,
+                mode: mode(&file),
+            });
+        }
+    }
+    entries.push(Entry {
+        path: format!("{stem}/bundle.txt"),
+        content: description(&spec).into_bytes(),
+        mode: 0o644,
+    });
+    Ok(entries)
+}
+
+/// Returns the existing files and directories under `dir` whose relative path appears in a
+/// script `text`. To avoid bundling unrelated directories, a fixture path must contain a slash or
+/// a dot (`data/input.txt`, `config.json`).
+pub fn fixtures(text: &str, dir: &Path) -> Vec<PathBuf> {
+    let is_path_char = |c: char| c.is_alphanumeric() || "-_./".contains(c);
+    let mut fixtures = text
+        .split(|c: char| !is_path_char(c))
+        .map(|word| word.trim_start_matches("./").trim_end_matches('/'))
+        .filter(|word| word.contains(['/', '.']))
+        .filter(|word| !word.starts_with('/') && !word.contains(".."))
+        .map(|word| dir.join(word))
+        .filter(|path| path.exists() && path != dir)
+        .collect::<Vec<_>>();
+    fixtures.sort();
+    fixtures.dedup();
+    fixtures
+}
+
+/// Returns the file at `path`, or all the files under `path` if it's a directory.
+fn walk(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = fs::read_dir(path)
+        .map_err(|err| format!("cannot read directory {}: {err}", path.display()))?;
+    let mut paths = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut files = vec![];
+    for path in paths {
+        files.extend(walk(&path)?);
+    }
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|m| m.permissions().mode() & 0o777)
+        .unwrap_or(0o644)
+}
+
+#[cfg(not(unix))]
+fn mode(path: &Path) -> u32 {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("sh") => 0o755,
+        _ => 0o644,
+    }
+}
+
+/// Returns the description of a bundle: the resolved spec of the test and its environment.
+fn description(spec: &CommandSpec) -> String {
+    let mut s = String::new();
+    let _ = writeln!(s, "cliche {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(s, "platform: {} {}", env::consts::OS, env::consts::ARCH);
+    let _ = writeln!(s, "script: {}", spec.cmd_path().display());
+    let _ = writeln!(s, "snapshots: {}", spec.snapshot_path("*").display());
+    let _ = writeln!(s, "files:");
+    for (kind, path) in spec.expectation_files() {
+        let _ = writeln!(s, "  {kind}: {}", path.display());
+    }
+    let _ = writeln!(s, "env:");
+    for key in ENV_VARS {
+        if let Ok(value) = env::var(key) {
+            let _ = writeln!(s, "  {key}={value}");
+        }
+    }
+    s
+}
+
+/// Returns a tar archive (ustar format) of `entries`.
+fn tar(entries: &[Entry]) -> Result<Vec<u8>, String> {
+    let mut archive = vec![];
+    for entry in entries {
+        archive.extend(header(entry)?);
+        archive.extend(&entry.content);
+        let padding = (BLOCK_SIZE - entry.content.len() % BLOCK_SIZE) % BLOCK_SIZE;
+        archive.extend(vec![0; padding]);
+    }
+    // The archive ends with two empty blocks.
+    archive.extend(vec![0; 2 * BLOCK_SIZE]);
+    Ok(archive)
+}
+
+/// Returns the ustar header block of an `entry`.
+fn header(entry: &Entry) -> Result<[u8; BLOCK_SIZE], String> {
+    let mut header = [0; BLOCK_SIZE];
+    let name = entry.path.as_bytes();
+    if name.len() > 100 {
+        return Err(format!("path <{}> is too long to be bundled", entry.path));
+    }
+    let mut put = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    put(0, name);
+    put(100, format!("{:07o}\0", entry.mode).as_bytes());
+    put(108, b"0000000\0");
+    put(116, b"0000000\0");
+    put(124, format!("{:011o}\0", entry.content.len()).as_bytes());
+    put(136, b"00000000000\0");
+    put(148, b"        ");
+    put(156, b"0");
+    put(257, b"ustar\0");
+    put(263, b"00");
+    let checksum = header.iter().map(|b| *b as u32).sum::<u32>();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::create_dir_all(dir.join("data/sub")).unwrap();
+        fs::write(dir.join("data/sub/input.txt"), "foo").unwrap();
+        fs::write(dir.join("config.json"), "{}").unwrap();
+        fs::write(dir.join("other.txt"), "").unwrap();
+        fs::create_dir_all(dir.join("tests")).unwrap();
+        fs::write(
+            dir.join("tests/cat.sh"),
+            "#!/bin/sh\ncat ./data/sub/input.txt config.json\n",
+        )
+        .unwrap();
+        fs::write(dir.join("tests/cat.out"), "foo{}").unwrap();
+        fs::write(dir.join("tests/cat.exit"), "0").unwrap();
+
+        let entries = collect(&dir.join("tests/cat.sh"), &[], dir).unwrap();
+        let paths = entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "cat/tests/cat.sh",
+                "cat/tests/cat.out",
+                "cat/tests/cat.exit",
+                "cat/config.json",
+                "cat/data/sub/input.txt",
+                "cat/bundle.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tar() {
+        let entries = vec![Entry {
+            path: "foo/bar.txt".to_string(),
+            content: b"Hello".to_vec(),
+            mode: 0o644,
+        }];
+        let archive = tar(&entries).unwrap();
+        assert_eq!(archive.len(), 4 * BLOCK_SIZE);
+        assert_eq!(&archive[0..11], b"foo/bar.txt");
+        assert_eq!(&archive[124..136], b"00000000005\0");
+        assert_eq!(&archive[257..263], b"ustar\0");
+        assert_eq!(&archive[BLOCK_SIZE..BLOCK_SIZE + 5], b"Hello");
+
+        // The checksum is computed with the checksum field filled with spaces.
+        let mut header = archive[..BLOCK_SIZE].to_vec();
+        let checksum = String::from_utf8(header[148..154].to_vec()).unwrap();
+        header[148..156].copy_from_slice(b"        ");
+        let sum = header.iter().map(|b| *b as u32).sum::<u32>();
+        assert_eq!(u32::from_str_radix(&checksum, 8).unwrap(), sum);
+    }
+}
@@ -0,0 +1,108 @@
+/// Maximum number of chars of a chunk.
+pub const CHUNK_SIZE: usize = 64;
+
+/// A chunk of a line, of at most [`CHUNK_SIZE`] chars.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    pub text: &'a str,
+    /// 1-based line index.
+    pub row: usize,
+    /// 0-based index of this chunk in its line.
+    pub index: usize,
+}
+
+/// Iterates over the lines of a text, split in chunks of at most [`CHUNK_SIZE`] chars.
+///
+/// Newlines are kept at the end of the last chunk of each line. Comparing texts chunk by chunk
+/// keeps the memory bounded, even for very long single lines (minified JSON for instance).
+#[derive(Clone, Debug)]
+pub struct ChunkedLines<'a> {
+    text: &'a str,
+    row: usize,
+    index: usize,
+}
+
+impl<'a> ChunkedLines<'a> {
+    pub fn new(text: &'a str) -> Self {
+        ChunkedLines {
+            text,
+            row: 1,
+            index: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for ChunkedLines<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let mut end = self.text.len();
+        let mut eol = false;
+        for (count, (i, c)) in self.text.char_indices().enumerate() {
+            if count == CHUNK_SIZE {
+                end = i;
+                break;
+            }
+            if c == '\n' {
+                end = i + 1;
+                eol = true;
+                break;
+            }
+        }
+        let (text, rest) = self.text.split_at(end);
+        let chunk = Chunk {
+            text,
+            row: self.row,
+            index: self.index,
+        };
+        self.text = rest;
+        if eol {
+            self.row += 1;
+            self.index = 0;
+        } else {
+            self.index += 1;
+        }
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunked_lines() {
+        let long = "é".repeat(70);
+        let text = format!("foo\n{long}\nbar");
+        let chunks = ChunkedLines::new(&text).collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            vec![
+                Chunk {
+                    text: "foo\n",
+                    row: 1,
+                    index: 0
+                },
+                Chunk {
+                    text: &long[..64 * 2],
+                    row: 2,
+                    index: 0
+                },
+                Chunk {
+                    text: &text[4 + 64 * 2..4 + 70 * 2 + 1],
+                    row: 2,
+                    index: 1
+                },
+                Chunk {
+                    text: "bar",
+                    row: 3,
+                    index: 0
+                },
+            ]
+        );
+        assert_eq!(ChunkedLines::new("").next(), None);
+    }
+}
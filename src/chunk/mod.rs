@@ -1,3 +1,8 @@
+pub mod line;
 mod pattern;
 
-pub use self::pattern::{PatternLine, PatternLines};
+pub use self::line::{Chunk, ChunkedLines};
+pub use self::pattern::{
+    Delimiters, EmptyPattern, PatternLine, PatternLines, Regex, SegmentMismatch,
+    find_empty_patterns, find_segment_mismatch,
+};
@@ -4,6 +4,62 @@ use std::fmt::{Debug, Formatter};
 use std::iter::Peekable;
 use std::str::Chars;
 
+/// Named regexes of common volatile values, used in patterns as `<<<%{NAME}>>>`.
+const MACROS: [(&str, &str); 8] = [
+    ("NUMBER", r"-?\d+(?:\.\d+)?"),
+    (
+        "UUID",
+        r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}",
+    ),
+    (
+        "IPV4",
+        r"(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)",
+    ),
+    (
+        "ISO8601",
+        r"\d{4}-\d{2}-\d{2}(?:[T ]\d{2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?(?:Z|[+-]\d{2}(?::?\d{2})?)?)?",
+    ),
+    (
+        "TIMESTAMP",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}(?::?\d{2})?)?",
+    ),
+    (
+        "SEMVER",
+        r"\d+\.\d+\.\d+(?:-[0-9A-Za-z.-]+)?(?:\+[0-9A-Za-z.-]+)?",
+    ),
+    ("PATH", r"(?:[A-Za-z]:)?[\w.~/\\-]+"),
+    ("DURATION", r"(?:\d+(?:\.\d+)?(?:ns|us|µs|ms|s|m|h))+"),
+];
+
+/// Expands the macros `%{NAME}` of a regex `pattern` (like `%{UUID}`) to their regex.
+///
+/// Returns the name of the first unknown macro as an error.
+fn expand_macros(pattern: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("%{") {
+        let name_len = rest[start + 2..]
+            .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'))
+            .unwrap_or(rest.len() - start - 2);
+        let after = &rest[start + 2 + name_len..];
+        let name = &rest[start + 2..start + 2 + name_len];
+        // Not a macro, `%{2}` is a valid regex for instance.
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) || !after.starts_with('}') {
+            expanded.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let Some((_, regex)) = MACROS.iter().find(|(n, _)| *n == name) else {
+            return Err(name.to_string());
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&format!("(?:{regex})"));
+        rest = &after[1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReadState {
     WithoutPattern,
@@ -12,26 +68,100 @@ enum ReadState {
     Eof,
 }
 
+/// Prefix of the header line of a pattern text changing the delimiters of its patterns, like
+/// `# cliche: delimiters={{ }}` for patterns written `{{\d+}}`.
+const DELIMITERS_DIRECTIVE: &str = "# cliche: delimiters=";
+
+/// Start and end delimiters of the patterns of a pattern text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Delimiters {
+    pub start: String,
+    pub end: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters {
+            start: "<<<".to_string(),
+            end: ">>>".to_string(),
+        }
+    }
+}
+
+impl Delimiters {
+    /// Parses the delimiters declared by the header line of a pattern `text`, if any. Returns the
+    /// delimiters with the number of header lines (0 or 1), or the reason of an invalid header.
+    pub fn parse_header(text: &str) -> Result<(Delimiters, usize), String> {
+        let first = text.lines().next().unwrap_or_default();
+        let Some(value) = first.trim_end().strip_prefix(DELIMITERS_DIRECTIVE) else {
+            return Ok((Delimiters::default(), 0));
+        };
+        let delimiters = value.split_whitespace().collect::<Vec<_>>();
+        let [start, end] = delimiters[..] else {
+            return Err(format!(
+                "expecting delimiters=START END, found <{}>",
+                value.trim()
+            ));
+        };
+        Ok((
+            Delimiters {
+                start: start.to_string(),
+                end: end.to_string(),
+            },
+            1,
+        ))
+    }
+}
+
 pub struct PatternLines<'input> {
     chars: Peekable<Chars<'input>>,
     read_state: ReadState,
     line: String,
     pattern_start: String,
     pattern_end: String,
+    /// Number of header lines of the text, skipped.
+    header_rows: usize,
+    /// Reason of an invalid header, returned as the first line.
+    header_error: Option<String>,
 }
 
 impl<'input> PatternLines<'input> {
+    /// Creates the pattern lines of a `text`. The delimiters of the patterns can be changed by a
+    /// header line like `# cliche: delimiters={{ }}`, that is not a pattern line.
     pub fn new(text: &'input str) -> Self {
-        let chars = text.chars().peekable();
-        let line = String::new();
-        let pattern_start = "<<<".to_string();
-        let pattern_end = ">>>".to_string();
+        let (delimiters, header_rows, header_error) = match Delimiters::parse_header(text) {
+            Ok((delimiters, rows)) => (delimiters, rows, None),
+            Err(reason) => (Delimiters::default(), 0, Some(reason)),
+        };
+        let mut chars = text.chars().peekable();
+        if header_rows > 0 {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        }
         PatternLines {
             chars,
             read_state: ReadState::WithoutPattern,
-            line,
-            pattern_start,
-            pattern_end,
+            line: String::new(),
+            pattern_start: delimiters.start,
+            pattern_end: delimiters.end,
+            header_rows,
+            header_error,
+        }
+    }
+
+    /// Returns the number of header lines of the text (0 or 1), that are not pattern lines.
+    pub fn header_rows(&self) -> usize {
+        self.header_rows
+    }
+
+    /// Returns the delimiters of the patterns of the text.
+    pub fn delimiters(&self) -> Delimiters {
+        Delimiters {
+            start: self.pattern_start.clone(),
+            end: self.pattern_end.clone(),
         }
     }
 }
@@ -55,6 +185,10 @@ impl Regex {
     pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
         self.0.find(haystack)
     }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.0.is_match(haystack)
+    }
 }
 
 impl PartialEq for Regex {
@@ -75,6 +209,10 @@ impl Iterator for PatternLines<'_> {
     type Item = Result<PatternLine, String>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(reason) = self.header_error.take() {
+            self.read_state = ReadState::Error;
+            return Some(Err(reason));
+        }
         if self.read_state == ReadState::Error || self.read_state == ReadState::Eof {
             return None;
         }
@@ -98,6 +236,13 @@ impl Iterator for PatternLines<'_> {
                         return Some(Err("pattern is invalid".to_string()));
                     }
                 };
+                let pat = match expand_macros(&pat) {
+                    Ok(p) => p,
+                    Err(name) => {
+                        self.read_state = ReadState::Error;
+                        return Some(Err(format!("unknown macro <%{{{name}}}>")));
+                    }
+                };
                 self.line.push_str(&pat);
             } else {
                 self.chars.next();
@@ -196,10 +341,288 @@ impl PatternLines<'_> {
     }
 }
 
+/// A pattern of a pattern text that can match an empty string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmptyPattern {
+    /// 1-based line index of the pattern.
+    pub row: usize,
+    /// The regex of the pattern, without its delimiters.
+    pub pattern: String,
+    /// `true` if the line has only patterns, all of them matching an empty string: the line can
+    /// match an empty line, and probably any line.
+    pub whole_line: bool,
+}
+
+/// A segment of a pattern line: either literal text, or the regex of a `<<<…>>>` pattern.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Segment<'line> {
+    Literal(&'line str),
+    Pattern(&'line str),
+}
+
+/// Splits a pattern `line` into its literal and pattern segments, patterns being delimited by
+/// `delimiters`. An unterminated pattern is kept as literal text.
+fn segments<'line>(line: &'line str, delimiters: &Delimiters) -> Vec<Segment<'line>> {
+    let (open, close) = (delimiters.start.as_str(), delimiters.end.as_str());
+    let mut segments = vec![];
+    let mut rest = line;
+    while let Some(start) = rest.find(open) {
+        let from = start + open.len();
+        let Some(end) = rest[from..].find(close) else {
+            break;
+        };
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+        segments.push(Segment::Pattern(&rest[from..from + end]));
+        rest = &rest[from + end + close.len()..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    segments
+}
+
+/// The first segment of a composite pattern line that doesn't match an actual line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SegmentMismatch {
+    /// The segment as written in the pattern line, `<<<\d+>>>` or ` items` for instance.
+    pub segment: String,
+    /// 1-based column of the actual line where the segment is expected.
+    pub column: usize,
+}
+
+/// Returns the first segment of a pattern `line` that stops matching an `actual` line.
+///
+/// Segments are matched one after the other from the start of the actual line, the mismatching
+/// segment being the first one that can't extend the match of the previous ones. `None` is
+/// returned if the line has a single segment (the whole line is the culprit), or if every segment
+/// matches.
+pub fn find_segment_mismatch(
+    line: &str,
+    actual: &str,
+    delimiters: &Delimiters,
+) -> Option<SegmentMismatch> {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let segments = segments(line, delimiters);
+    if segments.len() < 2 {
+        return None;
+    }
+    let mut regex = "^".to_string();
+    let mut end = 0;
+    for segment in segments {
+        let written = match segment {
+            Segment::Literal(text) => {
+                regex.push_str(&regex::escape(text));
+                text.to_string()
+            }
+            Segment::Pattern(pattern) => {
+                regex.push_str(&expand_macros(pattern).ok()?);
+                format!("{}{pattern}{}", delimiters.start, delimiters.end)
+            }
+        };
+        let mat = regex::Regex::new(&regex).ok()?.find(actual);
+        match mat {
+            Some(mat) => end = mat.end(),
+            None => {
+                return Some(SegmentMismatch {
+                    segment: written,
+                    column: actual[..end].chars().count() + 1,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Returns the patterns of a pattern `text` that can match an empty string (like `<<<.*>>>`).
+pub fn find_empty_patterns(text: &str) -> Vec<EmptyPattern> {
+    // An invalid header is reported by the verification of the text.
+    let Ok((delimiters, header_rows)) = Delimiters::parse_header(text) else {
+        return vec![];
+    };
+    let mut found = vec![];
+    for (index, line) in text.lines().enumerate().skip(header_rows) {
+        let mut literal = String::new();
+        let mut patterns = vec![];
+        for segment in segments(line, &delimiters) {
+            match segment {
+                Segment::Literal(text) => literal.push_str(text),
+                Segment::Pattern(pattern) => patterns.push(pattern),
+            }
+        }
+        let empty = patterns
+            .iter()
+            .filter(|p| matches_empty(p))
+            .collect::<Vec<_>>();
+        let whole_line = literal.is_empty() && !empty.is_empty() && empty.len() == patterns.len();
+        found.extend(empty.into_iter().map(|pattern| EmptyPattern {
+            row: index + 1,
+            pattern: pattern.to_string(),
+            whole_line,
+        }));
+    }
+    found
+}
+
+/// Returns `true` if the regex `pattern` can match an empty string.
+fn matches_empty(pattern: &str) -> bool {
+    let Ok(pattern) = expand_macros(pattern) else {
+        return false;
+    };
+    regex::Regex::new(&format!("^(?:{pattern})$")).is_ok_and(|re| re.is_match(""))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_expand_macros() {
+        assert_eq!(
+            expand_macros(r"v%{NUMBER}\.x"),
+            Ok(r"v(?:-?\d+(?:\.\d+)?)\.x".to_string())
+        );
+        assert_eq!(expand_macros(r"a%{2}|%{ x"), Ok(r"a%{2}|%{ x".to_string()));
+        assert_eq!(expand_macros("%{NUMBERS}"), Err("NUMBERS".to_string()));
+
+        let input = "id: <<<%{UUID}>>> at <<<%{TIMESTAMP}>>> from <<<%{IPV4}>>>\n";
+        let Some(Ok(PatternLine::Pattern(re))) = PatternLines::new(input).next() else {
+            panic!("expecting a pattern line");
+        };
+        assert!(re.is_match(
+            "id: 0b9f3c1e-5d2a-4c3b-9e8f-7a6b5c4d3e2f at 2024-03-01T12:34:56.789Z from 10.0.0.255\n"
+        ));
+        assert!(!re.is_match("id: 42 at 2024-03-01T12:34:56Z from 10.0.0.1\n"));
+
+        for (name, valid, invalid) in [
+            ("NUMBER", "-12.5", "1e3"),
+            ("IPV4", "192.168.1.1", "256.1.1.1"),
+            ("ISO8601", "2024-03-01", "01/03/2024"),
+            ("SEMVER", "1.2.3-rc.1+build.5", "1.2"),
+            ("PATH", "/usr/local/bin/cliche", "two words"),
+            ("DURATION", "1m30.5s", "fast"),
+        ] {
+            let re = Regex::new(&format!(
+                "^{}$",
+                expand_macros(&format!("%{{{name}}}")).unwrap()
+            ))
+            .unwrap();
+            assert!(re.is_match(valid), "{name} should match {valid}");
+            assert!(!re.is_match(invalid), "{name} should not match {invalid}");
+        }
+
+        let mut lines = PatternLines::new("<<<%{NUMBR}>>>");
+        assert_eq!(
+            lines.next(),
+            Some(Err("unknown macro <%{NUMBR}>".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_empty_patterns() {
+        let text = "<<<.*>>>\nid: <<<\\d*>>>\n<<<\\d+>>>\n<<<a?>>><<<b?>>>\nversion <<<.+>>>\n";
+        assert_eq!(
+            find_empty_patterns(text),
+            vec![
+                EmptyPattern {
+                    row: 1,
+                    pattern: ".*".to_string(),
+                    whole_line: true,
+                },
+                EmptyPattern {
+                    row: 2,
+                    pattern: "\\d*".to_string(),
+                    whole_line: false,
+                },
+                EmptyPattern {
+                    row: 4,
+                    pattern: "a?".to_string(),
+                    whole_line: true,
+                },
+                EmptyPattern {
+                    row: 4,
+                    pattern: "b?".to_string(),
+                    whole_line: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_segment_mismatch() {
+        let delimiters = Delimiters::default();
+        let line = "id: <<<\\d+>>> items: <<<[a-z]+>>>\n";
+        assert_eq!(
+            find_segment_mismatch(line, "id: 12 items: abc\n", &delimiters),
+            None
+        );
+        assert_eq!(
+            find_segment_mismatch(line, "id: ab items: abc\n", &delimiters),
+            Some(SegmentMismatch {
+                segment: "<<<\\d+>>>".to_string(),
+                column: 5,
+            })
+        );
+        assert_eq!(
+            find_segment_mismatch(line, "id: 12 item: abc\n", &delimiters),
+            Some(SegmentMismatch {
+                segment: " items: ".to_string(),
+                column: 7,
+            })
+        );
+        assert_eq!(
+            find_segment_mismatch(line, "id: 12 items: ABC\n", &delimiters),
+            Some(SegmentMismatch {
+                segment: "<<<[a-z]+>>>".to_string(),
+                column: 15,
+            })
+        );
+        assert_eq!(
+            find_segment_mismatch("<<<\\d+>>>", "abc", &delimiters),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let input = "# cliche: delimiters={{ }}\n<<<<<<< HEAD\nid: {{\\d+}}\n";
+        let mut lines = PatternLines::new(input);
+        assert_eq!(lines.header_rows(), 1);
+        assert_eq!(
+            lines.delimiters(),
+            Delimiters {
+                start: "{{".to_string(),
+                end: "}}".to_string(),
+            }
+        );
+        assert_eq!(
+            lines.next(),
+            Some(Ok(PatternLine::NoPattern("<<<<<<< HEAD\n".to_string())))
+        );
+        assert_eq!(
+            lines.next(),
+            Some(Ok(PatternLine::Pattern(Regex::new("id: \\d+\n").unwrap())))
+        );
+        assert_eq!(lines.next(), None);
+        assert_eq!(
+            find_segment_mismatch("id: {{\\d+}} ok", "id: 12 ko", &lines.delimiters()),
+            Some(SegmentMismatch {
+                segment: " ok".to_string(),
+                column: 7,
+            })
+        );
+        assert_eq!(find_empty_patterns(input), vec![]);
+
+        let mut lines = PatternLines::new("# cliche: delimiters={{\nfoo\n");
+        assert_eq!(lines.header_rows(), 0);
+        assert_eq!(
+            lines.next(),
+            Some(Err("expecting delimiters=START END, found <{{>".to_string()))
+        );
+        assert_eq!(lines.next(), None);
+    }
+
     #[test]
     fn test_valid_chunk() {
         let input = "Hello <<<.*>>>!\nabcdef\n<<<[abcd]>>>foo bar baz<<<1234567891\\d>>>dummy";
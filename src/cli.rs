@@ -0,0 +1,977 @@
+//! Command line parsing.
+use crate::chunk::Regex;
+use crate::command;
+use crate::glob::Glob;
+use crate::job;
+use crate::suite::{Shard, SnapshotRoot, TestFilter};
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Format of the messages describing failures.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Rendered messages for humans, on stderr.
+    #[default]
+    Human,
+    /// In addition to human messages, one JSON diagnostic per failure on stdout.
+    Json,
+}
+
+/// When to use colors in the messages printed on stderr.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Colors if stderr is a terminal, following `NO_COLOR`, `CLICOLOR` and `CLICOLOR_FORCE`.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("expecting auto, always or never, found <{s}>")),
+        }
+    }
+}
+
+/// Options of a cliche run, parsed from the command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CliOptions {
+    /// Test scripts, manifest files or directories with a manifest.
+    pub files: Vec<PathBuf>,
+    /// Only runs tests having at least one of these tags.
+    pub tags: Vec<String>,
+    /// Only runs tests matching at least one of these filters.
+    pub filters: Vec<TestFilter>,
+    /// Runs only the test with this name, with maximum verbosity.
+    pub only: Option<String>,
+    /// Updates snapshot files from actual results instead of verifying them.
+    pub update: bool,
+    /// In update mode, only updates snapshots of failing tests.
+    pub update_failed: bool,
+    /// In update mode, only modifies or deletes existing snapshots, never creates new ones.
+    pub update_existing: bool,
+    /// In update mode, only updates snapshots of tests matching this glob.
+    pub update_glob: Option<Glob>,
+    /// Creates the snapshots of tests without any expectation file, other tests being verified.
+    pub bless_new_tests: bool,
+    /// Only previews the snapshot updates, without applying them.
+    pub dry_run: bool,
+    /// Applies the snapshot updates without confirmation.
+    pub force: bool,
+    /// Makes test scripts without executable bit executable instead of failing.
+    pub fix_perms: bool,
+    /// Prints a summary of the streams asserted by tests.
+    pub coverage: bool,
+    /// Reports identical snapshots of several tests, that could share a snapshot.
+    pub suggest_shared: bool,
+    /// Runs all tests instead of stopping at the first failure.
+    pub no_fail_fast: bool,
+    /// Directories of snapshots stored separately from their scripts.
+    pub snapshot_roots: Vec<SnapshotRoot>,
+    /// Suffixes of the preferred variants of the expectation files, in order.
+    pub snapshot_variants: Vec<String>,
+    /// Fails tests having companion files that look like misspelled expectation files, or leaving
+    /// processes running.
+    pub strict: bool,
+    /// Only verifies the exit codes of the tests.
+    pub exit_only: bool,
+    /// Streams the outputs of the tests to the terminal, only verifying their exit codes.
+    pub no_capture: bool,
+    /// Prefixes the streamed output lines with a tag of their test.
+    pub prefix_output: bool,
+    /// Discovers the tests of directories without skipping the paths of `.gitignore` files.
+    pub no_ignore: bool,
+    /// Fails tests having patterns that can match an empty string.
+    pub non_empty_patterns: bool,
+    /// File or file descriptor where progress events are written.
+    pub event_stream: Option<String>,
+    /// File where test durations are recorded, to flag tests slower than usual.
+    pub history: Option<PathBuf>,
+    /// Writes a JSON report of the results to this file.
+    pub report_json: Option<PathBuf>,
+    /// Format of the failure messages.
+    pub message_format: MessageFormat,
+    /// When to use colors in messages, if given on the command line.
+    pub color: Option<ColorChoice>,
+    /// Prints the explanation of an error code and exits.
+    pub explain: Option<String>,
+    /// Prints additional information for each test.
+    pub verbose: bool,
+    /// Patterns of lines that must only appear on stderr, for every test.
+    pub stderr_only: Vec<Regex>,
+    /// Captures the outputs of tests in temporary files instead of pipes.
+    pub capture_files: bool,
+    /// Directory where the outputs of each test are recorded.
+    pub record_exec: Option<PathBuf>,
+    /// Records each run in its own directory, keeping this number of runs.
+    pub record_keep: Option<usize>,
+    /// Maximum size in bytes of the recorded outputs, the oldest ones being removed first.
+    pub record_max_size: Option<u64>,
+    /// Only keeps the recorded outputs of failing tests.
+    pub record_failed_only: bool,
+    /// Directory of recorded outputs verified instead of running the tests.
+    pub replay: Option<PathBuf>,
+    /// Kills tests running longer than this duration, unless they have their own timeout file.
+    pub timeout: Option<Duration>,
+    /// Factor scaling the timeouts of the tests, for slow machines.
+    pub timeout_scale: Option<f64>,
+    /// Reruns of failing tests without their own number of retries.
+    pub retries: Option<usize>,
+    /// Only runs the tests of a shard of the suite.
+    pub shard: Option<Shard>,
+    /// Runs the tests in a random order.
+    pub shuffle: bool,
+    /// Seed of the random order of the tests, to reproduce an order.
+    pub shuffle_seed: Option<u64>,
+    /// Highlights the durations of tests running longer than this duration.
+    pub slow_threshold: Option<Duration>,
+    /// Lists the tests without running them (`cliche list`).
+    pub list: bool,
+    /// Reruns the tests whose script or companion files change, until interrupted.
+    pub watch: bool,
+    /// Prints the help and exits.
+    pub help: bool,
+    /// Prints the version and exits.
+    pub version: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CliError {
+    /// The option is not known.
+    UnknownOption(String),
+    /// The option requires a value but none has been given.
+    MissingValue(String),
+    /// The value of the option is not valid.
+    InvalidValue { option: String, reason: String },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "unknown option '{option}'"),
+            CliError::MissingValue(option) => write!(f, "option '{option}' requires a value"),
+            CliError::InvalidValue { option, reason } => {
+                write!(f, "invalid value for option '{option}': {reason}")
+            }
+        }
+    }
+}
+
+/// Commands of cliche, with their name, their usage and their description.
+const COMMANDS: [(&str, &str, &str); 10] = [
+    (
+        "run",
+        "cliche [run] [OPTIONS] [FILES]...",
+        "Runs the tests of FILES (the default command): test scripts, manifest files or \
+         directories. The tests of a directory are listed by its cliche.manifest file, or are the \
+         .sh scripts of the directory. Without FILES, runs the tests of the cliche.toml file of the \
+         current directory or of its nearest ancestor.",
+    ),
+    (
+        "update",
+        "cliche update [OPTIONS] [FILES]...",
+        "Runs the tests of FILES and updates their snapshots, like --update.",
+    ),
+    (
+        "list",
+        "cliche list [OPTIONS] [FILES]...",
+        "Prints the tests of FILES with their companion files (snapshots, stdin...) without running \
+         them.",
+    ),
+    (
+        "exec",
+        "cliche exec --name <NAME> [--update] -- <PROGRAM> [ARGS]...",
+        "Runs PROGRAM with ARGS in the current directory and verifies it against the snapshots \
+         named after NAME (NAME.out, NAME.err, NAME.exit...), or updates them with --update.",
+    ),
+    (
+        "review",
+        "cliche review [FILES]...",
+        "Shows the diff of each pending snapshot (.out.new, .err.new) written by failing tests \
+         under FILES, and asks whether to accept it over the snapshot, reject it or skip it.",
+    ),
+    (
+        "import",
+        "cliche import [-o <DIR>] [FILES]...",
+        "Converts trycmd (.trycmd, .md, .toml), shelltest (.test) and BATS (.bats) tests to \
+         cliche scripts and snapshots, written next to FILES or in DIR.",
+    ),
+    (
+        "bundle",
+        "cliche bundle [-o <FILE>] [--snapshot-root <SCRIPTS_DIR>=<SNAPSHOTS_DIR>] <SCRIPT>",
+        "Packages a test with its snapshots, fixtures and environment description in a tar \
+         archive (SCRIPT.bundle.tar by default).",
+    ),
+    (
+        "graph",
+        "cliche graph [--format dot|json] [--no-ignore] [--snapshot-root <SCRIPTS_DIR>=<SNAPSHOTS_DIR>] [FILES]...",
+        "Prints the tests with their post-hooks, shared snapshots, shared fixtures and locks as a \
+         DOT or JSON graph: tests sharing a lock are serialized.",
+    ),
+    (
+        "drift",
+        "cliche drift --against <REV> [--no-ignore] [--snapshot-root <SCRIPTS_DIR>=<SNAPSHOTS_DIR>] [FILES]...",
+        "Runs the tests and verifies their outputs against their snapshots and the snapshots of \
+         the git revision REV: tests matching both only changed expectations, tests matching the \
+         current snapshots only changed behavior.",
+    ),
+    (
+        "doctor",
+        "cliche doctor [--no-ignore] [--snapshot-root <SCRIPTS_DIR>=<SNAPSHOTS_DIR>] [FILES]...",
+        "Checks the tests without running them: scripts not executable or with a missing \
+         interpreter, invalid or whole-line empty patterns, CRLF snapshots, misspelled or orphaned \
+         expectation files, and an invalid configuration.",
+    ),
+];
+
+/// Help of a command line option: its short name, its long name (with its value, if any) and
+/// its description. Options are sorted by long name.
+const OPTIONS: [(Option<char>, &str, &str); 47] = [
+    (
+        None,
+        "bless-new-tests",
+        "Create snapshots of tests without any, verify other tests",
+    ),
+    (
+        None,
+        "capture-files",
+        "Capture test outputs in temporary files instead of pipes",
+    ),
+    (
+        None,
+        "color <WHEN>",
+        "Use colors: auto (default), always or never",
+    ),
+    (
+        None,
+        "coverage",
+        "Print a summary of the streams asserted by tests",
+    ),
+    (
+        None,
+        "dry-run",
+        "With --update, only preview snapshot changes",
+    ),
+    (
+        None,
+        "event-stream <FILE|FD>",
+        "Write progress events as JSON lines to FILE or file descriptor FD",
+    ),
+    (
+        None,
+        "exit-only",
+        "Verify exit codes only, skipping output snapshots",
+    ),
+    (
+        None,
+        "explain <CODE>",
+        "Explain an error code, like CHECK_STDOUT_PATTERN",
+    ),
+    (
+        None,
+        "filter <PATTERN>",
+        "Only run tests matching a glob or /regex/ PATTERN (can be repeated)",
+    ),
+    (
+        None,
+        "fix-perms",
+        "Make test scripts without executable bit executable",
+    ),
+    (
+        Some('f'),
+        "force",
+        "With --update, apply changes without confirmation",
+    ),
+    (Some('h'), "help", "Print help"),
+    (
+        None,
+        "history <FILE>",
+        "Record test durations in FILE, flagging unusually slow tests",
+    ),
+    (
+        None,
+        "list",
+        "Print the tests with their companion files, without running them",
+    ),
+    (
+        None,
+        "message-format <FMT>",
+        "Format of failure messages: human (default) or json",
+    ),
+    (
+        None,
+        "no-capture",
+        "Stream outputs to the terminal, checking exit codes only",
+    ),
+    (
+        None,
+        "no-fail-fast",
+        "Run all tests, grouping identical failures in a summary",
+    ),
+    (
+        None,
+        "no-ignore",
+        "Discover tests in paths ignored by .gitignore files",
+    ),
+    (
+        None,
+        "non-empty-patterns",
+        "Fail tests with patterns that can match an empty string",
+    ),
+    (
+        None,
+        "only <NAME>",
+        "Run only the test NAME, with maximum verbosity",
+    ),
+    (
+        None,
+        "prefix-output",
+        "With --no-capture, prefix each streamed line with a tag of its test",
+    ),
+    (
+        None,
+        "record-exec <DIR>",
+        "Record the outputs of each test in DIR",
+    ),
+    (
+        None,
+        "record-failed-only",
+        "With --record-exec, only keep the outputs of failing tests",
+    ),
+    (
+        None,
+        "record-keep <N>",
+        "With --record-exec, record each run in a new directory, keeping N runs",
+    ),
+    (
+        None,
+        "record-max-size <SIZE>",
+        "With --record-exec, remove the oldest recordings beyond SIZE, like 500M",
+    ),
+    (
+        None,
+        "replay <DIR>",
+        "Verify the outputs recorded in DIR instead of running tests",
+    ),
+    (
+        None,
+        "report-json <FILE>",
+        "Write a JSON report of the test results to FILE",
+    ),
+    (
+        None,
+        "retries <N>",
+        "Rerun failing tests up to N times, passing tests are reported as flaky",
+    ),
+    (
+        None,
+        "shard <INDEX>/<COUNT>",
+        "Run shard INDEX of COUNT stable parts of the tests, like 2/4",
+    ),
+    (
+        None,
+        "shuffle",
+        "Run tests in a random order, printing the seed of the order",
+    ),
+    (
+        None,
+        "shuffle-seed <N>",
+        "Run tests in the random order of seed N, printed by --shuffle",
+    ),
+    (
+        None,
+        "slow-threshold <DURATION>",
+        "Highlight tests running longer than DURATION, like 500ms or 2s",
+    ),
+    (
+        None,
+        "snapshot-root <SCRIPTS_DIR>=<SNAPSHOTS_DIR>",
+        "Store snapshots of SCRIPTS_DIR under SNAPSHOTS_DIR",
+    ),
+    (
+        None,
+        "snapshot-variant <SUFFIX>",
+        "Prefer foo.out.SUFFIX to foo.out etc... (can be repeated)",
+    ),
+    (
+        None,
+        "stderr-only <REGEX>",
+        "Fail if a stdout line matches REGEX (can be repeated)",
+    ),
+    (
+        None,
+        "strict",
+        "Fail tests with misspelled or conflicting files, or leftover processes",
+    ),
+    (
+        None,
+        "suggest-shared",
+        "Report identical snapshots that could be shared",
+    ),
+    (
+        None,
+        "tag <TAG>",
+        "Only run tests tagged with TAG (can be repeated)",
+    ),
+    (
+        None,
+        "timeout <DURATION>",
+        "Kill tests running longer than DURATION, like 500ms or 2s",
+    ),
+    (
+        None,
+        "timeout-scale <FACTOR>",
+        "Multiply every timeout by FACTOR, like 3 on slow machines",
+    ),
+    (Some('u'), "update", "Update snapshots from actual results"),
+    (
+        None,
+        "update=<GLOB>",
+        "Update snapshots of tests matching GLOB only",
+    ),
+    (
+        None,
+        "update-existing",
+        "Update existing snapshots only, without creating new ones",
+    ),
+    (
+        None,
+        "update-failed",
+        "Update snapshots of failing tests only",
+    ),
+    (
+        Some('v'),
+        "verbose",
+        "Print additional information for each test",
+    ),
+    (Some('V'), "version", "Print version"),
+    (
+        None,
+        "watch",
+        "Rerun tests when their script or companion files change",
+    ),
+];
+
+/// Column of the descriptions of the options in the help.
+const HELP_COLUMN: usize = 29;
+
+/// Column of the descriptions of the commands in the help.
+const COMMAND_COLUMN: usize = 10;
+
+/// Maximum width of the help.
+const HELP_WIDTH: usize = 100;
+
+/// Returns the help of cliche, generated from its commands and options.
+pub fn help() -> String {
+    let mut s = String::new();
+    s.push_str("cliche, snapshot tests for CLIs.\n\n");
+    for (_, usage, _) in COMMANDS {
+        s.push_str(&format!("{usage}\n"));
+    }
+    s.push_str("\nCommands:\n");
+    for (name, _, description) in COMMANDS {
+        let lines = wrap(description, HELP_WIDTH - COMMAND_COLUMN);
+        for (i, line) in lines.iter().enumerate() {
+            let name = if i == 0 { name } else { "" };
+            s.push_str(&format!("  {name:<w$}{line}\n", w = COMMAND_COLUMN - 2));
+        }
+    }
+    s.push_str("\nOptions:\n");
+    for (short, long, description) in OPTIONS {
+        let name = match short {
+            Some(short) => format!("  -{short}, --{long}"),
+            None => format!("      --{long}"),
+        };
+        // Long names are followed by their description on the next line.
+        if name.len() < HELP_COLUMN {
+            s.push_str(&format!("{name:<HELP_COLUMN$}{description}\n"));
+        } else {
+            s.push_str(&format!("{name}\n{:HELP_COLUMN$}{description}\n", ""));
+        }
+    }
+    s
+}
+
+/// Wraps a `text` on lines of at most `width` chars, breaking lines between words.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// Parses command line arguments `args` (without the program name).
+///
+/// The first argument can be a command: `run` (the default), `update` (like `--update`) or
+/// `list`. A test script named like a command is given with its path (`./run`).
+pub fn parse(args: &[String]) -> Result<CliOptions, CliError> {
+    let mut options = CliOptions::default();
+    let args = match args.first().map(String::as_str) {
+        Some("run") => &args[1..],
+        Some("update") => {
+            options.update = true;
+            &args[1..]
+        }
+        Some("list") => {
+            options.list = true;
+            &args[1..]
+        }
+        _ => args,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "-V" | "--version" => options.version = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "-u" | "--update" => options.update = true,
+            "--update-failed" => {
+                options.update = true;
+                options.update_failed = true;
+            }
+            "--update-existing" => {
+                options.update = true;
+                options.update_existing = true;
+            }
+            option if option.starts_with("--update=") => {
+                options.update = true;
+                options.update_glob = Some(Glob::new(&option["--update=".len()..]));
+            }
+            "--bless-new-tests" => options.bless_new_tests = true,
+            "--capture-files" => options.capture_files = true,
+            "--coverage" => options.coverage = true,
+            "--suggest-shared" => options.suggest_shared = true,
+            "--dry-run" => options.dry_run = true,
+            "--message-format" => {
+                let value = next_value(arg, &mut args)?;
+                options.message_format = match value {
+                    "human" => MessageFormat::Human,
+                    "json" => MessageFormat::Json,
+                    _ => {
+                        return Err(CliError::InvalidValue {
+                            option: arg.clone(),
+                            reason: format!("expecting human or json, found <{value}>"),
+                        });
+                    }
+                };
+            }
+            "--color" => {
+                let value = next_value(arg, &mut args)?;
+                let color = value.parse().map_err(|reason| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason,
+                })?;
+                options.color = Some(color);
+            }
+            "--event-stream" => {
+                let value = next_value(arg, &mut args)?;
+                options.event_stream = Some(value.to_string());
+            }
+            "--explain" => {
+                let value = next_value(arg, &mut args)?;
+                options.explain = Some(value.to_string());
+            }
+            "-f" | "--force" => options.force = true,
+            "--fix-perms" => options.fix_perms = true,
+            "--history" => {
+                let value = next_value(arg, &mut args)?;
+                options.history = Some(PathBuf::from(value));
+            }
+            "--no-fail-fast" => options.no_fail_fast = true,
+            "--report-json" => {
+                let value = next_value(arg, &mut args)?;
+                options.report_json = Some(PathBuf::from(value));
+            }
+            "--only" => {
+                let value = next_value(arg, &mut args)?;
+                options.only = Some(value.to_string());
+            }
+            "--record-exec" => {
+                let value = next_value(arg, &mut args)?;
+                options.record_exec = Some(PathBuf::from(value));
+            }
+            "--record-failed-only" => options.record_failed_only = true,
+            "--record-keep" => {
+                let value = next_value(arg, &mut args)?;
+                let keep = value.parse().ok().filter(|keep| *keep > 0).ok_or_else(|| {
+                    CliError::InvalidValue {
+                        option: arg.clone(),
+                        reason: format!("expecting a positive number of runs, got <{value}>"),
+                    }
+                })?;
+                options.record_keep = Some(keep);
+            }
+            "--record-max-size" => {
+                let value = next_value(arg, &mut args)?;
+                let size = job::parse_bytes(value).map_err(|reason| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason,
+                })?;
+                options.record_max_size = Some(size);
+            }
+            "--replay" => {
+                let value = next_value(arg, &mut args)?;
+                options.replay = Some(PathBuf::from(value));
+            }
+            "--snapshot-root" => {
+                let value = next_value(arg, &mut args)?;
+                let root = value.parse().map_err(|reason| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason,
+                })?;
+                options.snapshot_roots.push(root);
+            }
+            "--snapshot-variant" => {
+                let value = next_value(arg, &mut args)?;
+                options.snapshot_variants.push(value.to_string());
+            }
+            "--exit-only" => options.exit_only = true,
+            "--no-capture" => options.no_capture = true,
+            "--list" => options.list = true,
+            "--watch" => options.watch = true,
+            "--prefix-output" => options.prefix_output = true,
+            "--no-ignore" => options.no_ignore = true,
+            "--non-empty-patterns" => options.non_empty_patterns = true,
+            "--strict" => options.strict = true,
+            "--timeout" => {
+                let value = next_value(arg, &mut args)?;
+                let timeout =
+                    command::parse_duration(value).map_err(|reason| CliError::InvalidValue {
+                        option: arg.clone(),
+                        reason,
+                    })?;
+                options.timeout = Some(timeout);
+            }
+            "--timeout-scale" => {
+                let value = next_value(arg, &mut args)?;
+                let scale = command::parse_timeout_scale(value).map_err(|reason| {
+                    CliError::InvalidValue {
+                        option: arg.clone(),
+                        reason,
+                    }
+                })?;
+                options.timeout_scale = Some(scale);
+            }
+            "--shard" => {
+                let value = next_value(arg, &mut args)?;
+                let shard = value.parse().map_err(|reason| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason,
+                })?;
+                options.shard = Some(shard);
+            }
+            "--shuffle" => options.shuffle = true,
+            "--shuffle-seed" => {
+                let value = next_value(arg, &mut args)?;
+                let seed = value.parse().map_err(|_| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason: format!("expecting a seed, got <{value}>"),
+                })?;
+                options.shuffle = true;
+                options.shuffle_seed = Some(seed);
+            }
+            "--retries" => {
+                let value = next_value(arg, &mut args)?;
+                let retries = value.parse().map_err(|_| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason: format!("expecting a number of retries, got <{value}>"),
+                })?;
+                options.retries = Some(retries);
+            }
+            "--slow-threshold" => {
+                let value = next_value(arg, &mut args)?;
+                let threshold =
+                    command::parse_duration(value).map_err(|reason| CliError::InvalidValue {
+                        option: arg.clone(),
+                        reason,
+                    })?;
+                options.slow_threshold = Some(threshold);
+            }
+            "--filter" => {
+                let value = next_value(arg, &mut args)?;
+                let filter = value.parse().map_err(|reason| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason,
+                })?;
+                options.filters.push(filter);
+            }
+            "--tag" => {
+                let value = next_value(arg, &mut args)?;
+                options.tags.push(value.to_string());
+            }
+            "--stderr-only" => {
+                let value = next_value(arg, &mut args)?;
+                let regex = Regex::new(value).map_err(|err| CliError::InvalidValue {
+                    option: arg.clone(),
+                    reason: err.to_string(),
+                })?;
+                options.stderr_only.push(regex);
+            }
+            // Everything after `--` is a file, even if it starts with a dash.
+            "--" => {
+                options.files.extend(args.by_ref().map(PathBuf::from));
+            }
+            option if option.starts_with('-') && option != "-" => {
+                return Err(CliError::UnknownOption(option.to_string()));
+            }
+            file => options.files.push(PathBuf::from(file)),
+        }
+    }
+    Ok(options)
+}
+
+/// Returns the value of an `option`, consuming the next argument of `args`.
+fn next_value<'a>(
+    option: &str,
+    args: &mut impl Iterator<Item = &'a String>,
+) -> Result<&'a str, CliError> {
+    args.next()
+        .map(|value| value.as_str())
+        .ok_or_else(|| CliError::MissingValue(option.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_files() {
+        let options = parse(&args(&["a.sh", "-v", "b.sh"])).unwrap();
+        assert_eq!(
+            options,
+            CliOptions {
+                files: vec![PathBuf::from("a.sh"), PathBuf::from("b.sh")],
+                tags: vec![],
+                filters: vec![],
+                only: None,
+                update: false,
+                update_failed: false,
+                update_existing: false,
+                update_glob: None,
+                bless_new_tests: false,
+                suggest_shared: false,
+                dry_run: false,
+                force: false,
+                fix_perms: false,
+                coverage: false,
+                no_fail_fast: false,
+                snapshot_roots: vec![],
+                snapshot_variants: vec![],
+                strict: false,
+                exit_only: false,
+                no_capture: false,
+                prefix_output: false,
+                no_ignore: false,
+                non_empty_patterns: false,
+                event_stream: None,
+                history: None,
+                report_json: None,
+                message_format: MessageFormat::Human,
+                color: None,
+                explain: None,
+                verbose: true,
+                stderr_only: vec![],
+                capture_files: false,
+                record_exec: None,
+                record_keep: None,
+                record_max_size: None,
+                record_failed_only: false,
+                replay: None,
+                timeout: None,
+                timeout_scale: None,
+                retries: None,
+                shard: None,
+                shuffle: false,
+                shuffle_seed: None,
+                slow_threshold: None,
+                list: false,
+                watch: false,
+                help: false,
+                version: false,
+            }
+        );
+
+        let options = parse(&args(&["--tag", "slow", "tests", "--tag", "auth"])).unwrap();
+        assert_eq!(options.files, vec![PathBuf::from("tests")]);
+        assert_eq!(options.tags, vec!["slow".to_string(), "auth".to_string()]);
+
+        let options = parse(&args(&[
+            "tests", "--filter", "login*", "--filter", "/^auth/",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options.filters,
+            vec![
+                TestFilter::Glob(Glob::new("login*")),
+                TestFilter::Regex(Regex::new("^auth").unwrap()),
+            ]
+        );
+        assert!(parse(&args(&["--filter", "/(/", "tests"])).is_err());
+
+        let options = parse(&args(&[
+            "--snapshot-variant",
+            "ci",
+            "--snapshot-variant",
+            "linux",
+            "a.sh",
+        ]))
+        .unwrap();
+        assert_eq!(options.snapshot_variants, vec!["ci", "linux"]);
+
+        let options = parse(&args(&["--shuffle-seed", "42", "tests"])).unwrap();
+        assert!(options.shuffle);
+        assert_eq!(options.shuffle_seed, Some(42));
+
+        let options = parse(&args(&["--shard", "2/4", "tests"])).unwrap();
+        assert_eq!(options.shard, Some(Shard { index: 2, count: 4 }));
+        assert!(parse(&args(&["--shard", "5/4", "tests"])).is_err());
+
+        let options = parse(&args(&[
+            "--record-exec",
+            "recordings",
+            "--record-keep",
+            "5",
+            "--record-max-size",
+            "1M",
+            "tests",
+        ]))
+        .unwrap();
+        assert_eq!(options.record_keep, Some(5));
+        assert_eq!(options.record_max_size, Some(1024 * 1024));
+        assert!(parse(&args(&["--record-keep", "0", "tests"])).is_err());
+
+        let options = parse(&args(&["--timeout-scale", "1.5", "tests"])).unwrap();
+        assert_eq!(options.timeout_scale, Some(1.5));
+        assert!(parse(&args(&["--timeout-scale", "0", "tests"])).is_err());
+
+        let options = parse(&args(&["--retries", "2", "tests"])).unwrap();
+        assert_eq!(options.retries, Some(2));
+        assert!(parse(&args(&["--retries", "many", "tests"])).is_err());
+
+        let options = parse(&args(&["--slow-threshold", "500ms", "tests"])).unwrap();
+        assert_eq!(options.slow_threshold, Some(Duration::from_millis(500)));
+        assert!(parse(&args(&["--slow-threshold", "fast", "tests"])).is_err());
+
+        let options = parse(&args(&["--", "--verbose"])).unwrap();
+        assert_eq!(options.files, vec![PathBuf::from("--verbose")]);
+        assert!(!options.verbose);
+    }
+
+    #[test]
+    fn test_parse_stderr_only() {
+        let options = parse(&args(&["--stderr-only", "^error:", "a.sh"])).unwrap();
+        assert_eq!(options.stderr_only, vec![Regex::new("^error:").unwrap()]);
+
+        assert_eq!(
+            parse(&args(&["a.sh", "--stderr-only"])),
+            Err(CliError::MissingValue("--stderr-only".to_string()))
+        );
+        assert!(matches!(
+            parse(&args(&["--stderr-only", "*"])),
+            Err(CliError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let options = parse(&args(&["--update-failed", "--update=login*", "a.sh"])).unwrap();
+        assert!(options.update);
+        assert!(options.update_failed);
+        assert!(!options.update_existing);
+        assert_eq!(options.update_glob, Some(Glob::new("login*")));
+        assert_eq!(options.files, vec![PathBuf::from("a.sh")]);
+
+        let options = parse(&args(&["--bless-new-tests", "a.sh"])).unwrap();
+        assert!(!options.update);
+        assert!(options.bless_new_tests);
+    }
+
+    #[test]
+    fn test_parse_color() {
+        let options = parse(&args(&["--color", "never", "a.sh"])).unwrap();
+        assert_eq!(options.color, Some(ColorChoice::Never));
+        assert!(matches!(
+            parse(&args(&["--color", "sometimes", "a.sh"])),
+            Err(CliError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let options = parse(&args(&["update", "a.sh"])).unwrap();
+        assert!(options.update);
+        assert_eq!(options.files, vec![PathBuf::from("a.sh")]);
+
+        let options = parse(&args(&["list", "--tag", "slow", "tests"])).unwrap();
+        assert!(options.list);
+        assert_eq!(options.tags, vec!["slow".to_string()]);
+
+        let options = parse(&args(&["run", "-h"])).unwrap();
+        assert!(options.help);
+        assert!(options.files.is_empty());
+
+        let options = parse(&args(&["a.sh", "list"])).unwrap();
+        assert!(!options.list);
+        assert_eq!(options.files.len(), 2);
+
+        let options = parse(&args(&["--list", "tests"])).unwrap();
+        assert!(options.list);
+    }
+
+    #[test]
+    fn test_help() {
+        // Options are sorted and every documented option is parsed.
+        let names = OPTIONS
+            .iter()
+            .map(|(_, long, _)| long.split([' ', '=']).next().unwrap())
+            .collect::<Vec<_>>();
+        assert!(names.is_sorted());
+        for name in names {
+            let result = parse(&args(&[&format!("--{name}")]));
+            assert!(
+                !matches!(result, Err(CliError::UnknownOption(_))),
+                "--{name}"
+            );
+        }
+        let help = help();
+        assert!(
+            help.contains("\n  -u, --update               Update snapshots from actual results\n")
+        );
+        assert!(help.contains("\n  list    Prints the tests of FILES with their companion files"));
+        assert!(
+            help.lines()
+                .all(|line| line.len() <= HELP_WIDTH || line.starts_with("cliche "))
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        assert_eq!(
+            parse(&args(&["--foo", "a.sh"])),
+            Err(CliError::UnknownOption("--foo".to_string()))
+        );
+    }
+}
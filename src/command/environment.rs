@@ -0,0 +1,98 @@
+//! Environment of a command, given by its `foo.env` companion file.
+//!
+//! Each line is a `KEY=VALUE` variable added to the environment of the command. A `# clear-env`
+//! line starts from an empty environment instead of the environment of cliche:
+//!
+//! ```text
+//! # clear-env
+//! PATH=/usr/bin:/bin
+//! LANG=C
+//! ```
+use std::process::Command;
+
+/// Directive line starting the command from an empty environment.
+const CLEAR_ENV_DIRECTIVE: &str = "# clear-env";
+
+/// Variables set in the environment of a command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Env {
+    /// The environment of cliche is not inherited.
+    pub clear: bool,
+    /// Variables `(key, value)`, in order.
+    pub vars: Vec<(String, String)>,
+}
+
+impl Env {
+    /// Parses the `text` of an environment file. Values are not expanded, and can be written
+    /// between double quotes.
+    ///
+    /// Returns the reason and the 1-based line index of the error if a line is not valid.
+    pub fn parse(text: &str) -> Result<Env, (String, usize)> {
+        let mut env = Env::default();
+        for (index, line) in text.lines().enumerate() {
+            let row = index + 1;
+            let line = line.trim();
+            if line == CLEAR_ENV_DIRECTIVE {
+                env.clear = true;
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err((format!("expecting KEY=VALUE, found <{line}>"), row));
+            };
+            let key = key.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err((format!("invalid variable name <{key}>"), row));
+            }
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            env.vars.push((key.to_string(), value.to_string()));
+        }
+        Ok(env)
+    }
+
+    /// Sets this environment on `cmd`. It must be applied before any other variable of the
+    /// command, as clearing the environment also removes the variables already set.
+    pub fn apply(&self, cmd: &mut Command) {
+        if self.clear {
+            cmd.env_clear();
+        }
+        cmd.envs(self.vars.iter().map(|(k, v)| (k, v)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text =
+            "# clear-env\n# Some comment\nPATH=/usr/bin:/bin\n\nGREETING=\"Hello world\"\nEMPTY=\n";
+        assert_eq!(
+            Env::parse(text),
+            Ok(Env {
+                clear: true,
+                vars: vec![
+                    ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+                    ("GREETING".to_string(), "Hello world".to_string()),
+                    ("EMPTY".to_string(), "".to_string()),
+                ],
+            })
+        );
+        assert!(!Env::parse("A=1\n").unwrap().clear);
+        assert_eq!(
+            Env::parse("A=1\nexport B\n"),
+            Err(("expecting KEY=VALUE, found <export B>".to_string(), 2))
+        );
+        assert_eq!(
+            Env::parse("export B=1\n"),
+            Err(("invalid variable name <export B>".to_string(), 1))
+        );
+    }
+}
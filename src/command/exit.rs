@@ -0,0 +1,378 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::process::ExitStatus;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExitCode(i32);
+
+/// Exit codes of [sysexits.h](https://man.freebsd.org/cgi/man.cgi?sysexits).
+const SYSEXITS: [(&str, i32); 16] = [
+    ("EX_OK", 0),
+    ("EX_USAGE", 64),
+    ("EX_DATAERR", 65),
+    ("EX_NOINPUT", 66),
+    ("EX_NOUSER", 67),
+    ("EX_NOHOST", 68),
+    ("EX_UNAVAILABLE", 69),
+    ("EX_SOFTWARE", 70),
+    ("EX_OSERR", 71),
+    ("EX_OSFILE", 72),
+    ("EX_CANTCREAT", 73),
+    ("EX_IOERR", 74),
+    ("EX_TEMPFAIL", 75),
+    ("EX_PROTOCOL", 76),
+    ("EX_NOPERM", 77),
+    ("EX_CONFIG", 78),
+];
+
+/// Exit codes used by shells when a command can't be run.
+const SHELL_EXITS: [(&str, i32); 2] = [("not-executable", 126), ("not-found", 127)];
+
+/// Signals killing a process run by a shell: the shell exit code is 128 + the signal number, and
+/// signal numbers depend on the platform.
+#[cfg(unix)]
+const SIGNALS: [(&str, i32); 15] = [
+    ("SIGHUP", libc::SIGHUP),
+    ("SIGINT", libc::SIGINT),
+    ("SIGQUIT", libc::SIGQUIT),
+    ("SIGILL", libc::SIGILL),
+    ("SIGTRAP", libc::SIGTRAP),
+    ("SIGABRT", libc::SIGABRT),
+    ("SIGBUS", libc::SIGBUS),
+    ("SIGFPE", libc::SIGFPE),
+    ("SIGKILL", libc::SIGKILL),
+    ("SIGUSR1", libc::SIGUSR1),
+    ("SIGSEGV", libc::SIGSEGV),
+    ("SIGUSR2", libc::SIGUSR2),
+    ("SIGPIPE", libc::SIGPIPE),
+    ("SIGALRM", libc::SIGALRM),
+    ("SIGTERM", libc::SIGTERM),
+];
+#[cfg(not(unix))]
+const SIGNALS: [(&str, i32); 0] = [];
+
+/// Offset added to a signal number by shells to build the exit code of a killed command.
+const SIGNAL_OFFSET: i32 = 128;
+
+impl ExitCode {
+    pub fn as_i32(self) -> i32 {
+        self.0
+    }
+
+    /// Returns the exit code of a symbolic `name` like `EX_USAGE`, `SIGINT` or `not-found`.
+    pub fn from_name(name: &str) -> Option<ExitCode> {
+        let code = SYSEXITS
+            .iter()
+            .chain(SHELL_EXITS.iter())
+            .find(|(n, _)| *n == name)
+            .map(|(_, code)| *code);
+        let code = code.or_else(|| {
+            SIGNALS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, signal)| SIGNAL_OFFSET + signal)
+        });
+        code.map(ExitCode)
+    }
+
+    /// Returns the conventional name of this exit code, if any. A successful exit code has no
+    /// name.
+    pub fn name(self) -> Option<&'static str> {
+        if self.0 == 0 {
+            return None;
+        }
+        let name = SYSEXITS
+            .iter()
+            .chain(SHELL_EXITS.iter())
+            .find(|(_, code)| *code == self.0)
+            .map(|(name, _)| *name);
+        name.or_else(|| {
+            SIGNALS
+                .iter()
+                .find(|(_, signal)| SIGNAL_OFFSET + signal == self.0)
+                .map(|(name, _)| *name)
+        })
+    }
+}
+
+impl From<i32> for ExitCode {
+    fn from(value: i32) -> Self {
+        ExitCode(value)
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} ({name})", self.0),
+            None => fmt::Display::fmt(&self.0, f),
+        }
+    }
+}
+
+/// Prefix of a signal in an exit code file: `signal:11` or `signal:SIGSEGV`.
+const SIGNAL_PREFIX: &str = "signal:";
+
+/// Returns the name of a `signal` number, if known on this platform.
+fn signal_name(signal: i32) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, s)| *s == signal)
+        .map(|(name, _)| *name)
+}
+
+/// The termination of a command: an exit code, or the signal that has killed it (on Unix only).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Termination {
+    ExitCode(ExitCode),
+    Signal(i32),
+}
+
+impl Termination {
+    /// Returns the exit code of this termination: a command killed by a signal has the exit code
+    /// a shell would report, 128 + the signal number.
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            Termination::ExitCode(code) => code,
+            Termination::Signal(signal) => ExitCode(SIGNAL_OFFSET + signal),
+        }
+    }
+
+    /// Returns this termination as written in an exit code file: `3` or `signal:11`.
+    pub fn to_expectation(self) -> String {
+        match self {
+            Termination::ExitCode(code) => code.0.to_string(),
+            Termination::Signal(signal) => format!("{SIGNAL_PREFIX}{signal}"),
+        }
+    }
+}
+
+impl From<ExitCode> for Termination {
+    fn from(value: ExitCode) -> Self {
+        Termination::ExitCode(value)
+    }
+}
+
+impl From<ExitStatus> for Termination {
+    #[cfg(unix)]
+    fn from(status: ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Termination::ExitCode(ExitCode(code)),
+            (None, Some(signal)) => Termination::Signal(signal),
+            (None, None) => Termination::ExitCode(ExitCode(-1)),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(status: ExitStatus) -> Self {
+        Termination::ExitCode(ExitCode(status.code().unwrap_or(-1)))
+    }
+}
+
+impl FromStr for Termination {
+    type Err = String;
+
+    /// Parses an exit code (`64`, `EX_USAGE`) or a signal (`signal:11`, `signal:SIGSEGV`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some(signal) = s.strip_prefix(SIGNAL_PREFIX) else {
+            return Ok(Termination::ExitCode(parse_exit_code(s)?));
+        };
+        let signal = signal.trim();
+        if let Ok(signal) = signal.parse::<i32>() {
+            return Ok(Termination::Signal(signal));
+        }
+        SIGNALS
+            .iter()
+            .find(|(name, _)| *name == signal)
+            .map(|(_, signal)| Termination::Signal(*signal))
+            .ok_or_else(|| format!("<{signal}> is not a valid signal"))
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::ExitCode(code) => fmt::Display::fmt(code, f),
+            Termination::Signal(signal) => match signal_name(*signal) {
+                Some(name) => write!(f, "signal {signal} ({name})"),
+                None => write!(f, "signal {signal}"),
+            },
+        }
+    }
+}
+
+/// An expected exit code: an exact code `2` (or its name `EX_USAGE`), any code but one `!0`, an
+/// inclusive range of codes `1..5`, a signal killing the command `signal:11`, or any code `*`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExpectedExitCode {
+    Exact(ExitCode),
+    Not(ExitCode),
+    Range(ExitCode, ExitCode),
+    Signal(i32),
+    Any,
+}
+
+impl ExpectedExitCode {
+    /// Returns `true` if the `termination` of a command is expected. Only `!code` and `*` match
+    /// both exit codes and signals.
+    pub fn matches(self, termination: Termination) -> bool {
+        match (self, termination) {
+            (ExpectedExitCode::Any, _) => true,
+            (ExpectedExitCode::Not(unexpected), termination) => {
+                termination != Termination::ExitCode(unexpected)
+            }
+            (ExpectedExitCode::Exact(expected), Termination::ExitCode(code)) => code == expected,
+            (ExpectedExitCode::Range(min, max), Termination::ExitCode(code)) => {
+                (min.0..=max.0).contains(&code.0)
+            }
+            (ExpectedExitCode::Signal(expected), Termination::Signal(signal)) => signal == expected,
+            _ => false,
+        }
+    }
+
+    /// Returns the expected exit code if a single code is expected.
+    pub fn exact(self) -> Option<ExitCode> {
+        match self {
+            ExpectedExitCode::Exact(code) => Some(code),
+            _ => None,
+        }
+    }
+}
+
+impl From<ExitCode> for ExpectedExitCode {
+    fn from(value: ExitCode) -> Self {
+        ExpectedExitCode::Exact(value)
+    }
+}
+
+/// Parses an exit code given by its value (`64`) or its name (`EX_USAGE`).
+fn parse_exit_code(s: &str) -> Result<ExitCode, String> {
+    let s = s.trim();
+    if let Ok(code) = s.parse::<i32>() {
+        return Ok(ExitCode(code));
+    }
+    ExitCode::from_name(s).ok_or_else(|| format!("<{s}> is not a valid exit code"))
+}
+
+impl FromStr for ExpectedExitCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(ExpectedExitCode::Any);
+        }
+        if let Some(code) = s.strip_prefix('!') {
+            return Ok(ExpectedExitCode::Not(parse_exit_code(code)?));
+        }
+        if s.starts_with(SIGNAL_PREFIX) {
+            return match s.parse()? {
+                Termination::Signal(signal) => Ok(ExpectedExitCode::Signal(signal)),
+                Termination::ExitCode(code) => Ok(ExpectedExitCode::Exact(code)),
+            };
+        }
+        let Some((min, max)) = s.split_once("..") else {
+            return Ok(ExpectedExitCode::Exact(parse_exit_code(s)?));
+        };
+        let (min, max) = (parse_exit_code(min)?, parse_exit_code(max)?);
+        if max.0 < min.0 {
+            return Err(format!("<{s}> is an empty range"));
+        }
+        Ok(ExpectedExitCode::Range(min, max))
+    }
+}
+
+impl fmt::Display for ExpectedExitCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectedExitCode::Exact(code) => fmt::Display::fmt(code, f),
+            ExpectedExitCode::Not(code) => write!(f, "any but {code}"),
+            ExpectedExitCode::Range(min, max) => write!(f, "{}..{}", min.0, max.0),
+            ExpectedExitCode::Signal(signal) => fmt::Display::fmt(&Termination::Signal(*signal), f),
+            ExpectedExitCode::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(ExitCode::from_name("EX_OK"), Some(ExitCode(0)));
+        assert_eq!(ExitCode::from_name("EX_USAGE"), Some(ExitCode(64)));
+        assert_eq!(ExitCode::from_name("not-found"), Some(ExitCode(127)));
+        assert_eq!(ExitCode::from_name("ex_usage"), None);
+        assert_eq!(ExitCode::from_name("12"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_name() {
+        assert_eq!(ExitCode::from_name("SIGINT"), Some(ExitCode(130)));
+        assert_eq!(ExitCode(137).name(), Some("SIGKILL"));
+    }
+
+    #[test]
+    fn test_expected_exit_code() {
+        let expected = "EX_USAGE".parse::<ExpectedExitCode>().unwrap();
+        assert_eq!(expected, ExpectedExitCode::Exact(ExitCode(64)));
+        assert!(expected.matches(ExitCode(64).into()));
+        assert_eq!(expected.exact(), Some(ExitCode(64)));
+
+        let expected = "!0".parse::<ExpectedExitCode>().unwrap();
+        assert!(expected.matches(ExitCode(1).into()));
+        assert!(!expected.matches(ExitCode(0).into()));
+        assert_eq!(expected.to_string(), "any but 0");
+
+        let expected = " 1..5\n".parse::<ExpectedExitCode>().unwrap();
+        assert!(expected.matches(ExitCode(1).into()));
+        assert!(expected.matches(ExitCode(5).into()));
+        assert!(!expected.matches(ExitCode(6).into()));
+        assert_eq!(expected.to_string(), "1..5");
+        assert_eq!(expected.exact(), None);
+
+        assert!(
+            "*".parse::<ExpectedExitCode>()
+                .unwrap()
+                .matches(ExitCode(255).into())
+        );
+        assert!("5..1".parse::<ExpectedExitCode>().is_err());
+        assert!("1..".parse::<ExpectedExitCode>().is_err());
+        assert!("failure".parse::<ExpectedExitCode>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal() {
+        let segv = Termination::Signal(libc::SIGSEGV);
+        assert_eq!("signal:SIGSEGV".parse::<Termination>(), Ok(segv));
+        assert_eq!(segv.to_expectation().parse::<Termination>(), Ok(segv));
+        assert_eq!(segv.exit_code(), ExitCode(128 + libc::SIGSEGV));
+        assert_eq!(
+            segv.to_string(),
+            format!("signal {} (SIGSEGV)", libc::SIGSEGV)
+        );
+
+        let expected = format!("signal:{}", libc::SIGSEGV);
+        let expected = expected.parse::<ExpectedExitCode>().unwrap();
+        assert!(expected.matches(segv));
+        assert!(!expected.matches(Termination::Signal(libc::SIGKILL)));
+        assert!(!expected.matches(segv.exit_code().into()));
+        assert!(!ExpectedExitCode::Exact(segv.exit_code()).matches(segv));
+        assert!("!0".parse::<ExpectedExitCode>().unwrap().matches(segv));
+        assert!("signal:SIGFOO".parse::<ExpectedExitCode>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ExitCode(0).to_string(), "0");
+        assert_eq!(ExitCode(1).to_string(), "1");
+        assert_eq!(ExitCode(64).to_string(), "64 (EX_USAGE)");
+        assert_eq!(ExitCode(126).to_string(), "126 (not-executable)");
+    }
+}
@@ -0,0 +1,278 @@
+//! Front-matter of the stdout and stderr snapshots.
+//!
+//! A snapshot can start with options delimited by `---` lines, stripped before comparison:
+//!
+//! ```text
+//! ---
+//! normalize: [crlf, ansi]
+//! mode: unordered
+//! ---
+//! Hello
+//! World
+//! ```
+use std::str::FromStr;
+
+/// Delimiter line of a front-matter.
+const DELIMITER: &str = "---";
+
+/// A normalization applied to both the expected and the actual output before comparison.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Normalization {
+    /// Windows newlines `\r\n` are replaced by `\n`.
+    Crlf,
+    /// ANSI escape sequences (colors, cursor moves...) are removed.
+    Ansi,
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "crlf" => Ok(Normalization::Crlf),
+            "ansi" => Ok(Normalization::Ansi),
+            _ => Err(format!(
+                "unknown normalization <{s}>, expecting crlf or ansi"
+            )),
+        }
+    }
+}
+
+/// How the expected and actual outputs are compared.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CompareMode {
+    /// Outputs are equal.
+    #[default]
+    Exact,
+    /// Outputs have the same lines, in any order.
+    Unordered,
+}
+
+/// The options of a snapshot, declared in its front-matter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    pub normalize: Vec<Normalization>,
+    pub mode: CompareMode,
+    /// Length in bytes of the front-matter, delimiters included.
+    len: usize,
+    /// Number of lines of the front-matter, delimiters included.
+    rows: usize,
+}
+
+impl FrontMatter {
+    /// Parses the front-matter of a `snapshot`. A snapshot without front-matter has the default
+    /// options.
+    ///
+    /// Returns the reason and the 1-based line index of the error if the front-matter is not
+    /// valid.
+    pub fn parse(snapshot: &[u8]) -> Result<FrontMatter, (String, usize)> {
+        let mut front_matter = FrontMatter::default();
+        let mut lines = snapshot.split_inclusive(|b| *b == b'\n');
+        let mut len = match lines.next() {
+            Some(line) if trim_newline(line) == DELIMITER.as_bytes() => line.len(),
+            _ => return Ok(front_matter),
+        };
+        for (index, line) in lines.enumerate() {
+            let row = index + 2;
+            len += line.len();
+            let Ok(line) = str::from_utf8(trim_newline(line)) else {
+                return Err(("front-matter is not valid UTF-8".to_string(), row));
+            };
+            let line = line.trim();
+            if line == DELIMITER {
+                front_matter.len = len;
+                front_matter.rows = row;
+                return Ok(front_matter);
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            front_matter
+                .set_option(line)
+                .map_err(|reason| (reason, row))?;
+        }
+        Err((format!("missing closing <{DELIMITER}>"), 1))
+    }
+
+    /// Sets an option given by a `key: value` line.
+    fn set_option(&mut self, line: &str) -> Result<(), String> {
+        let Some((key, value)) = line.split_once(':') else {
+            return Err(format!("expecting key: value, found <{line}>"));
+        };
+        let value = value.trim();
+        match key.trim() {
+            "normalize" => {
+                let values = value
+                    .strip_prefix('[')
+                    .and_then(|v| v.strip_suffix(']'))
+                    .unwrap_or(value);
+                self.normalize = values
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?;
+            }
+            "mode" => {
+                self.mode = match value {
+                    "exact" => CompareMode::Exact,
+                    "unordered" => CompareMode::Unordered,
+                    _ => {
+                        return Err(format!(
+                            "unknown mode <{value}>, expecting exact or unordered"
+                        ));
+                    }
+                }
+            }
+            key => return Err(format!("unknown option <{key}>")),
+        }
+        Ok(())
+    }
+
+    /// Returns the front-matter of a `snapshot`, delimiters included.
+    pub fn header<'a>(&self, snapshot: &'a [u8]) -> &'a [u8] {
+        &snapshot[..self.len]
+    }
+
+    /// Returns the content of a `snapshot` following its front-matter.
+    pub fn body<'a>(&self, snapshot: &'a [u8]) -> &'a [u8] {
+        &snapshot[self.len..]
+    }
+
+    /// Returns the number of lines of the front-matter, to locate lines of the body in the
+    /// snapshot.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns an `output` with the normalizations of this front-matter applied.
+    pub fn normalize(&self, output: &[u8]) -> Vec<u8> {
+        let mut output = output.to_vec();
+        for normalization in &self.normalize {
+            output = match normalization {
+                Normalization::Crlf => replace_crlf(&output),
+                Normalization::Ansi => strip_ansi(&output),
+            };
+        }
+        output
+    }
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Replaces the `\r\n` newlines of `bytes` by `\n`.
+fn replace_crlf(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'\r' && bytes.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        out.push(*b);
+    }
+    out
+}
+
+/// Removes the ANSI escape sequences of `bytes`: CSI sequences (`ESC [ ... final byte`) like
+/// colors, OSC sequences (`ESC ] ... BEL` or `ESC ] ... ESC \`) like hyperlinks, and two bytes
+/// escape sequences.
+fn strip_ansi(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != 0x1b {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        match bytes.get(i) {
+            Some(b'[') => {
+                // Parameter and intermediate bytes, up to the final byte.
+                i += 1;
+                while i < bytes.len() && !(0x40..=0x7e).contains(&bytes[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            Some(b']') => {
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == 0x07 {
+                        i += 1;
+                        break;
+                    }
+                    if bytes[i] == 0x1b && bytes.get(i + 1) == Some(&b'\\') {
+                        i += 2;
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            Some(_) => i += 1,
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let snapshot =
+            b"---\nnormalize: [crlf, ansi]\n# Lines in any order\nmode: unordered\n---\nHello\n";
+        let front_matter = FrontMatter::parse(snapshot).unwrap();
+        assert_eq!(
+            front_matter.normalize,
+            vec![Normalization::Crlf, Normalization::Ansi]
+        );
+        assert_eq!(front_matter.mode, CompareMode::Unordered);
+        assert_eq!(front_matter.rows(), 5);
+        assert_eq!(front_matter.body(snapshot), b"Hello\n");
+        assert_eq!(
+            front_matter.header(snapshot),
+            b"---\nnormalize: [crlf, ansi]\n# Lines in any order\nmode: unordered\n---\n"
+        );
+
+        let snapshot = b"Hello\n---\n";
+        let front_matter = FrontMatter::parse(snapshot).unwrap();
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(front_matter.body(snapshot), snapshot);
+
+        assert_eq!(
+            FrontMatter::parse(b"---\nmode: sorted\n---\n"),
+            Err((
+                "unknown mode <sorted>, expecting exact or unordered".to_string(),
+                2
+            ))
+        );
+        assert_eq!(
+            FrontMatter::parse(b"---\nmode: exact\nHello\n"),
+            Err(("expecting key: value, found <Hello>".to_string(), 3))
+        );
+        assert_eq!(
+            FrontMatter::parse(b"---\nmode: exact\n"),
+            Err(("missing closing <--->".to_string(), 1))
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let front_matter = FrontMatter {
+            normalize: vec![Normalization::Crlf, Normalization::Ansi],
+            ..Default::default()
+        };
+        assert_eq!(
+            front_matter.normalize(
+                b"\x1b[1;31merror\x1b[0m: \x1b]8;;http://example.com\x1b\\link\x1b]8;;\x07\r\nok\r\n"
+            ),
+            b"error: link\nok\n"
+        );
+        assert_eq!(FrontMatter::default().normalize(b"a\r\n"), b"a\r\n");
+    }
+}
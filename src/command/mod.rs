@@ -0,0 +1,2651 @@
+use crate::chunk::{EmptyPattern, Regex, find_empty_patterns};
+use crate::count::CountRange;
+use crate::error::Error;
+use crate::job::{Job, ResourceUsage, Scheduling};
+use crate::json::JsonValue;
+use crate::screen::ScreenSize;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use std::{env, fs, io, thread};
+
+mod environment;
+mod exit;
+mod front_matter;
+
+pub use self::environment::Env;
+pub use self::exit::{ExitCode, ExpectedExitCode, Termination};
+pub use self::front_matter::{CompareMode, FrontMatter, Normalization};
+
+/// Extensions of the expectation files of a command, relative to the command script.
+pub const EXPECTATION_EXTENSIONS: [&str; 18] = [
+    "out",
+    "out.pattern",
+    "out.json",
+    "out.sha256",
+    "out.lines",
+    "out.count",
+    "out.first",
+    "out.last",
+    "screen",
+    "screen.sizes",
+    "err",
+    "err.pattern",
+    "err.json",
+    "err.sha256",
+    "err.only",
+    "err.count",
+    "exit",
+    "state.json",
+];
+
+/// Suffix of the alternatives of a stdout or stderr snapshot, followed by a number: an output
+/// matching `foo.out.alt1` or `foo.out.alt2` is accepted as well as an output matching `foo.out`.
+pub const ALTERNATIVE_SUFFIX: &str = "alt";
+
+/// Suffix of a pending snapshot, the actual output of a failing command written next to its
+/// snapshot (`foo.out.new` for `foo.out`).
+pub const PENDING_SUFFIX: &str = "new";
+
+/// Prefix of a snapshot referencing a shared snapshot, like `@usage.out`.
+pub const SHARED_SNAPSHOT_PREFIX: char = '@';
+
+/// Start and end of an include directive, a line of an expectation file replaced by the content
+/// of another file, like `<<<include:common/header.out>>>`.
+const INCLUDE_START: &str = "<<<include:";
+const INCLUDE_END: &str = ">>>";
+
+/// Maximum depth of nested includes, to stop include cycles.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Default maximum size in bytes of a companion file (expectation or input file) read by cliche.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 << 20;
+
+/// Extension of the post-hook script of a command, relative to the command script.
+pub const POST_HOOK_EXTENSION: &str = "post.sh";
+
+/// Extension of the file fed to the standard input of a command, relative to the command script.
+pub const STDIN_EXTENSION: &str = "stdin";
+
+/// Extension of the file giving environment variables of a command, relative to the command
+/// script.
+pub const ENV_EXTENSION: &str = "env";
+
+/// Extension of the file giving the arguments of a command, relative to the command script.
+pub const ARGS_EXTENSION: &str = "args";
+
+/// Extension of the file giving the working directory of a command, relative to the command
+/// script.
+pub const CWD_EXTENSION: &str = "cwd";
+
+/// Extension of the file giving the timeout of a command, relative to the command script.
+pub const TIMEOUT_EXTENSION: &str = "timeout";
+
+/// Environment variable giving the command a directory to write its state to.
+pub const WORK_DIR_ENV_VAR: &str = "CLICHE_WORK_DIR";
+
+/// Name of the state file written by the command in its work directory.
+pub const STATE_FILE_NAME: &str = "state.json";
+
+/// Maximum edit distance between an unknown companion file extension and an expectation file
+/// extension for the file to be considered misspelled.
+const MAX_MISSPELL_DISTANCE: usize = 2;
+
+/// An expected number of lines of an output matching a pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchCount {
+    /// 1-based line index of the count in its expectation file.
+    pub row: usize,
+    pub expected: CountRange,
+    pub pattern: Regex,
+}
+
+/// Represents a command specification
+pub struct CommandSpec {
+    cmd_path: PathBuf,
+    snapshot_base: PathBuf,
+    stdout_path: Option<PathBuf>,
+    stdout_pat_path: Option<PathBuf>,
+    stdout_lines_path: Option<PathBuf>,
+    stdout_count_path: Option<PathBuf>,
+    stdout_first_path: Option<PathBuf>,
+    stdout_last_path: Option<PathBuf>,
+    screen_path: Option<PathBuf>,
+    screen_sizes_path: Option<PathBuf>,
+    stderr_path: Option<PathBuf>,
+    stderr_pat_path: Option<PathBuf>,
+    stderr_only_path: Option<PathBuf>,
+    stderr_count_path: Option<PathBuf>,
+    exit_code_path: Option<PathBuf>,
+    state_path: Option<PathBuf>,
+    post_hook_path: Option<PathBuf>,
+    stdin_path: Option<PathBuf>,
+    env_path: Option<PathBuf>,
+    cwd_path: Option<PathBuf>,
+    args_path: Option<PathBuf>,
+    timeout_path: Option<PathBuf>,
+    /// Timeout of the command if it has no timeout file.
+    timeout: Option<Duration>,
+    /// Factor scaling the timeout of the command, whatever its origin.
+    timeout_scale: f64,
+    capture_files: bool,
+    scheduling: Scheduling,
+    /// Locales the command is also run under, each one with its own snapshots.
+    locales: Vec<String>,
+    /// Locale of this command, set with `LC_ALL`.
+    locale: Option<String>,
+    /// Suffixes of the preferred variants of the expectation files.
+    snapshot_variants: Vec<String>,
+    /// Comparators overriding the default comparator of expectation files, by extension.
+    comparators: Vec<(String, String)>,
+    /// Variables set before the ones of the environment file.
+    default_env: Vec<(String, String)>,
+    /// Normalizations of the snapshots without front-matter normalizations.
+    default_normalize: Vec<Normalization>,
+    /// Prefix of the lines of the echoed outputs.
+    echo_prefix: Option<String>,
+    /// Arguments and working directory of a program run without script.
+    program: Option<(Vec<String>, PathBuf)>,
+    /// Maximum size in bytes of the companion files read.
+    max_file_size: u64,
+}
+
+impl CommandSpec {
+    /// Creates a new expected command spec using script at `cmd_path`.
+    pub fn new(cmd_path: &Path) -> Result<Self, io::Error> {
+        let cmd_path = fs::canonicalize(cmd_path)?;
+        CommandSpec::with_snapshot_base(&cmd_path, &cmd_path)
+    }
+
+    /// Creates a new expected command spec using script at `cmd_path`, whose expectation files
+    /// are named after `snapshot_base` instead of the script (`foo.out` for `foo.sh` etc...).
+    pub fn with_snapshot_base(cmd_path: &Path, snapshot_base: &Path) -> Result<Self, io::Error> {
+        let cmd_path = fs::canonicalize(cmd_path)?;
+        let snapshot_base = snapshot_base.to_path_buf();
+        let post_hook_path = with_ext(&cmd_path, POST_HOOK_EXTENSION);
+        let stdin_path = with_ext(&cmd_path, STDIN_EXTENSION);
+        let env_path = with_ext(&cmd_path, ENV_EXTENSION);
+        let cwd_path = with_ext(&cmd_path, CWD_EXTENSION);
+        let args_path = with_ext(&cmd_path, ARGS_EXTENSION);
+        let timeout_path = with_ext(&cmd_path, TIMEOUT_EXTENSION);
+
+        let mut spec = CommandSpec {
+            cmd_path,
+            snapshot_base,
+            stdout_path: None,
+            stdout_pat_path: None,
+            stdout_lines_path: None,
+            stdout_count_path: None,
+            stdout_first_path: None,
+            stdout_last_path: None,
+            screen_path: None,
+            screen_sizes_path: None,
+            stderr_path: None,
+            stderr_pat_path: None,
+            stderr_only_path: None,
+            stderr_count_path: None,
+            exit_code_path: None,
+            state_path: None,
+            post_hook_path,
+            stdin_path,
+            env_path,
+            cwd_path,
+            args_path,
+            timeout_path,
+            timeout: None,
+            timeout_scale: 1.0,
+            capture_files: false,
+            scheduling: Scheduling::default(),
+            locales: vec![],
+            locale: None,
+            snapshot_variants: vec![],
+            comparators: vec![],
+            default_env: vec![],
+            default_normalize: vec![],
+            echo_prefix: None,
+            program: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        };
+        spec.find_expectations();
+        Ok(spec)
+    }
+
+    /// Creates a new expected command spec running `program` with `args` in the directory `cwd`,
+    /// instead of a script. Expectation and input files are named after `base` (`foo.out` and
+    /// `foo.stdin` for `foo` etc...), arguments and working directory files excepted.
+    pub fn with_program(
+        program: &Path,
+        args: Vec<String>,
+        cwd: &Path,
+        base: &Path,
+    ) -> Result<Self, io::Error> {
+        let mut spec = CommandSpec::with_snapshot_base(program, base)?;
+        spec.post_hook_path = with_ext(base, POST_HOOK_EXTENSION);
+        spec.stdin_path = with_ext(base, STDIN_EXTENSION);
+        spec.env_path = with_ext(base, ENV_EXTENSION);
+        spec.cwd_path = None;
+        spec.args_path = None;
+        spec.timeout_path = with_ext(base, TIMEOUT_EXTENSION);
+        spec.program = Some((args, cwd.to_path_buf()));
+        Ok(spec)
+    }
+
+    /// Finds the expectation files of this command spec, given its snapshot variants.
+    fn find_expectations(&mut self) {
+        self.stdout_path = self.find_snapshot("out");
+        self.stdout_pat_path = self.find_snapshot("out.pattern");
+        self.stdout_lines_path = self.find_snapshot("out.lines");
+        self.stdout_count_path = self.find_snapshot("out.count");
+        self.stdout_first_path = self.find_snapshot("out.first");
+        self.stdout_last_path = self.find_snapshot("out.last");
+        self.screen_path = self.find_snapshot("screen");
+        self.screen_sizes_path = self.find_snapshot("screen.sizes");
+        self.exit_code_path = self.find_snapshot("exit");
+        self.stderr_path = self.find_snapshot("err");
+        self.stderr_pat_path = self.find_snapshot("err.pattern");
+        self.stderr_only_path = self.find_snapshot("err.only");
+        self.stderr_count_path = self.find_snapshot("err.count");
+        self.state_path = self.find_snapshot("state.json");
+    }
+
+    /// Returns the path of the snapshot file with extension `ext`, if it exists.
+    fn find_snapshot(&self, ext: &str) -> Option<PathBuf> {
+        let path = self.snapshot_path(ext);
+        path.exists().then_some(path)
+    }
+
+    /// Returns `true` if the expectation file with extension `ext` (like `out.json`) exists.
+    pub fn has_expectation(&self, ext: &str) -> bool {
+        self.find_snapshot(ext).is_some()
+    }
+
+    /// Returns the content of the expectation file with extension `ext`.
+    pub fn read_expectation(&self, ext: &str) -> Result<Vec<u8>, Error> {
+        read_file(&self.snapshot_path(ext), self.max_file_size)
+    }
+
+    /// Returns the content of the expectation file with extension `ext`, as UTF-8 text.
+    pub fn read_expectation_text(&self, ext: &str) -> Result<String, Error> {
+        read_text_with_includes(&self.snapshot_path(ext), self.max_file_size)
+    }
+
+    /// Returns the snapshot with extension `ext` (`out` or `err`), the content of the shared
+    /// snapshot it references if it's a reference like `@usage.out`.
+    pub fn read_snapshot(&self, ext: &str) -> Result<Vec<u8>, Error> {
+        let path = self.snapshot_path(ext);
+        let snapshot = self.read_expectation(ext)?;
+        let snapshot = read_shared_snapshot(&path, snapshot, self.max_file_size)?;
+        expand_includes(&path, snapshot, self.max_file_size)
+    }
+
+    /// Returns `true` if the expectation file with extension `ext` has include directives, like
+    /// `<<<include:common/header.out>>>`.
+    pub fn has_includes(&self, ext: &str) -> bool {
+        self.read_expectation(ext).is_ok_and(|text| {
+            text.split(|b| *b == b'\n')
+                .any(|line| include_path(line).is_some())
+        })
+    }
+
+    /// Returns the path of the shared snapshot referenced by the snapshot with extension `ext`
+    /// (`out` or `err`), if the snapshot exists and is a reference like `@usage.out`.
+    pub fn shared_snapshot(&self, ext: &str) -> Result<Option<PathBuf>, Error> {
+        let Some(path) = self.find_snapshot(ext) else {
+            return Ok(None);
+        };
+        let snapshot = self.read_expectation(ext)?;
+        Ok(shared_snapshot_path(&path, &snapshot))
+    }
+
+    /// Returns the front-matter of the `snapshot` read from the expectation file with extension
+    /// `ext`.
+    pub fn snapshot_front_matter(&self, ext: &str, snapshot: &[u8]) -> Result<FrontMatter, Error> {
+        self.front_matter(Some(&self.snapshot_path(ext)), snapshot)
+    }
+
+    /// Compares the expectation files with extension `ext` with the comparator `name` instead of
+    /// their default comparator, for each `(ext, name)` of `comparators`.
+    pub fn with_comparators(mut self, comparators: Vec<(String, String)>) -> Self {
+        self.comparators = comparators;
+        self
+    }
+
+    /// Sets the variables `(key, value)` of the environment of the command, before the ones of its
+    /// environment file.
+    pub fn with_default_env(mut self, vars: Vec<(String, String)>) -> Self {
+        self.default_env = vars;
+        self
+    }
+
+    /// Sets the normalizations of the snapshots whose front-matter doesn't declare any.
+    pub fn with_default_normalize(mut self, normalize: Vec<Normalization>) -> Self {
+        self.default_normalize = normalize;
+        self
+    }
+
+    /// Prefixes each line of the outputs echoed by [`CommandSpec::execute_streamed`] with
+    /// `prefix`. Lines are then written at once, so that the outputs of commands run concurrently
+    /// are interleaved line by line.
+    pub fn with_echo_prefix(mut self, prefix: &str) -> Self {
+        self.echo_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Sets the maximum size in bytes of the companion files read, larger files being reported as
+    /// errors rather than read into memory.
+    pub fn with_max_file_size(mut self, max: u64) -> Self {
+        self.max_file_size = max;
+        self
+    }
+
+    /// Parses the front-matter of a `snapshot` read from the file at `path`.
+    fn front_matter(&self, path: Option<&Path>, snapshot: &[u8]) -> Result<FrontMatter, Error> {
+        let mut front_matter =
+            FrontMatter::parse(snapshot).map_err(|(reason, row)| Error::FrontMatterInvalid {
+                path: path.map(Path::to_path_buf).unwrap_or_default(),
+                reason,
+                row,
+            })?;
+        if front_matter.normalize.is_empty() {
+            front_matter.normalize = self.default_normalize.clone();
+        }
+        Ok(front_matter)
+    }
+
+    /// Returns the name of the comparator overriding the default comparator of the expectation
+    /// file with extension `ext`, if any.
+    pub fn comparator(&self, ext: &str) -> Option<&str> {
+        self.comparators
+            .iter()
+            .find(|(e, _)| e == ext)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// Prefers variants of the expectation files, in the order of `variants`: with a `ci` variant,
+    /// `foo.out.ci` is used instead of `foo.out` if it exists.
+    pub fn with_snapshot_variants(mut self, variants: Vec<String>) -> Self {
+        self.snapshot_variants = variants;
+        self.find_expectations();
+        self
+    }
+
+    /// Captures the outputs of the command in temporary files instead of pipes if `capture_files`
+    /// is `true` (see [`capture_to_files`]).
+    pub fn with_capture_files(mut self, capture_files: bool) -> Self {
+        self.capture_files = capture_files;
+        self
+    }
+
+    /// Kills the command after `timeout` if it has no timeout file.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Scales the timeout of the command by a `scale` factor, for slow machines.
+    pub fn with_timeout_scale(mut self, scale: f64) -> Self {
+        self.timeout_scale = scale;
+        self
+    }
+
+    /// Returns the timeout of the command: the duration given by its timeout file (like `2s` or
+    /// `500ms`), or its default timeout.
+    pub fn timeout(&self) -> Result<Option<Duration>, Error> {
+        let Some(timeout_path) = &self.timeout_path else {
+            return Ok(self.timeout.map(|t| t.mul_f64(self.timeout_scale)));
+        };
+        let text = read_text(timeout_path, self.max_file_size)?;
+        match parse_duration(&text) {
+            Ok(timeout) => Ok(Some(timeout.mul_f64(self.timeout_scale))),
+            Err(_) => Err(Error::FileNotDuration {
+                path: timeout_path.clone(),
+            }),
+        }
+    }
+
+    /// Returns the environment of the command given by its environment file, or an environment
+    /// inheriting the environment of cliche.
+    ///
+    /// The default variables of the command come first, so that the environment file can override
+    /// them.
+    pub fn env(&self) -> Result<Env, Error> {
+        let Some(env_path) = &self.env_path else {
+            return Ok(Env {
+                clear: false,
+                vars: self.default_env.clone(),
+            });
+        };
+        let text = read_text(env_path, self.max_file_size)?;
+        let mut env = Env::parse(&text).map_err(|(reason, row)| Error::EnvInvalid {
+            path: env_path.clone(),
+            reason,
+            row,
+        })?;
+        env.vars.splice(0..0, self.default_env.iter().cloned());
+        Ok(env)
+    }
+
+    /// Returns the working directory of the command: the directory given by its cwd file, relative
+    /// to the directory of the script, or the directory of the script.
+    pub fn cwd(&self) -> Result<PathBuf, Error> {
+        if let Some((_, cwd)) = &self.program {
+            return Ok(cwd.clone());
+        }
+        let script_dir = self.cmd_path.parent().unwrap_or(Path::new("/"));
+        let Some(cwd_path) = &self.cwd_path else {
+            return Ok(script_dir.to_path_buf());
+        };
+        let text = read_text(cwd_path, self.max_file_size)?;
+        let cwd = script_dir.join(text.trim());
+        if !cwd.is_dir() {
+            return Err(Error::CwdInvalid {
+                path: cwd_path.clone(),
+                cwd,
+            });
+        }
+        Ok(cwd)
+    }
+
+    /// Returns the arguments of the command given by its arguments file, one argument per line.
+    pub fn args(&self) -> Result<Vec<String>, Error> {
+        if let Some((args, _)) = &self.program {
+            return Ok(args.clone());
+        }
+        let Some(args_path) = &self.args_path else {
+            return Ok(vec![]);
+        };
+        let text = read_text(args_path, self.max_file_size)?;
+        parse_args(&text).map_err(|(reason, row)| Error::ArgsInvalid {
+            path: args_path.clone(),
+            reason,
+            row,
+        })
+    }
+
+    /// Checks that the companion files configuring the command (its timeout, arguments,
+    /// environment and working directory) are valid, so that it can be executed.
+    pub fn check_command_files(&self) -> Result<(), Error> {
+        self.timeout()?;
+        self.args()?;
+        self.env()?;
+        self.cwd()?;
+        Ok(())
+    }
+
+    /// Checks that the script of the command can be executed, i.e. that it has an executable bit
+    /// on Unix.
+    pub fn check_executable(&self) -> Result<(), Error> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&self.cmd_path)
+                .map_err(|err| Error::FileRead {
+                    path: self.cmd_path.clone(),
+                    cause: err.to_string(),
+                })?
+                .permissions()
+                .mode();
+            if mode & 0o111 == 0 {
+                return Err(Error::ScriptNotExecutable {
+                    path: self.cmd_path.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the script of the command executable by whoever can read it.
+    pub fn fix_permissions(&self) -> Result<(), io::Error> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&self.cmd_path)?.permissions().mode();
+            let mode = mode | ((mode & 0o444) >> 2);
+            fs::set_permissions(&self.cmd_path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the working directory of this command is given by a cwd file.
+    pub fn has_cwd(&self) -> bool {
+        self.cwd_path.is_some()
+    }
+
+    /// Runs the command with a given priority and CPU affinity.
+    pub fn with_scheduling(mut self, scheduling: Scheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Runs the command under each of `locales` too (see [`CommandSpec::for_locale`]).
+    pub fn with_locales(mut self, locales: Vec<String>) -> Self {
+        self.locales = locales;
+        self
+    }
+
+    /// Returns the locales the command is also run under.
+    pub fn locales(&self) -> &[String] {
+        &self.locales
+    }
+
+    /// Returns the spec of this command run under a `locale`: `LC_ALL` is set to `locale`, and
+    /// expectation files are suffixed by the locale (`foo.fr_FR.UTF-8.out` for instance).
+    pub fn for_locale(&self, locale: &str) -> Result<CommandSpec, io::Error> {
+        let stem = self.snapshot_base.file_stem().unwrap_or_default();
+        let ext = self.snapshot_base.extension().unwrap_or_default();
+        let name = format!(
+            "{}.{locale}.{}",
+            stem.to_string_lossy(),
+            ext.to_string_lossy()
+        );
+        let snapshot_base = self.snapshot_base.with_file_name(name);
+        let mut spec = CommandSpec::with_snapshot_base(&self.cmd_path, &snapshot_base)?;
+        spec.capture_files = self.capture_files;
+        spec.scheduling = self.scheduling.clone();
+        spec.timeout = self.timeout;
+        spec.timeout_scale = self.timeout_scale;
+        spec.comparators = self.comparators.clone();
+        spec.default_env = self.default_env.clone();
+        spec.default_normalize = self.default_normalize.clone();
+        spec.echo_prefix = self.echo_prefix.clone();
+        spec.max_file_size = self.max_file_size;
+        spec.post_hook_path = self.post_hook_path.clone();
+        spec.stdin_path = self.stdin_path.clone();
+        spec.env_path = self.env_path.clone();
+        spec.cwd_path = self.cwd_path.clone();
+        spec.args_path = self.args_path.clone();
+        spec.timeout_path = self.timeout_path.clone();
+        spec.program = self.program.clone();
+        spec.locale = Some(locale.to_string());
+        Ok(spec.with_snapshot_variants(self.snapshot_variants.clone()))
+    }
+
+    /// Returns the locale of this command, if it's set.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    /// Executes the command and returns the result.
+    ///
+    /// The command runs in its own [`Job`]: once the command has exited, every process it may have
+    /// left behind is killed.
+    pub fn execute(&self) -> Result<CommandResult, io::Error> {
+        self.run(false)
+    }
+
+    /// Executes the command and returns the result, echoing the command stdout and stderr while
+    /// they're being captured.
+    pub fn execute_streamed(&self) -> Result<CommandResult, io::Error> {
+        self.run(true)
+    }
+
+    fn run(&self, stream: bool) -> Result<CommandResult, io::Error> {
+        let mut cmd = self.command()?;
+        // Programs whose screen is checked are told the size of the terminal.
+        if self.has_screen() {
+            set_screen_size(&mut cmd, self.screen_size());
+        }
+        self.run_in_work_dir(cmd, stream)
+    }
+
+    /// Executes the command on a terminal of a given `size` and returns the result.
+    pub fn execute_with_screen_size(&self, size: ScreenSize) -> Result<CommandResult, io::Error> {
+        let mut cmd = self.command()?;
+        set_screen_size(&mut cmd, size);
+        self.run_in_work_dir(cmd, false)
+    }
+
+    /// Runs `cmd` with a new work directory, and captures its outputs, the state it has written
+    /// in the work directory and the size of the data left there.
+    ///
+    /// The stdin file of the command is fed to its standard input, if it exists, and the command
+    /// is killed once its timeout is exceeded.
+    fn run_in_work_dir(&self, mut cmd: Command, stream: bool) -> Result<CommandResult, io::Error> {
+        let timeout = self
+            .timeout()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.message()))?;
+        let work_dir = WorkDir::create()?;
+        cmd.env(WORK_DIR_ENV_VAR, &work_dir.path);
+        let stdin = match &self.stdin_path {
+            Some(path) => Stdin::File(path),
+            None => Stdin::Null,
+        };
+        // Echoing the outputs requires to read them while they're produced.
+        let capture = if self.capture_files && !stream {
+            Capture::Files
+        } else {
+            Capture::Pipes {
+                stream,
+                prefix: self.echo_prefix.clone(),
+            }
+        };
+        let result =
+            run_captured(cmd, stdin, capture, timeout).map_err(|err| self.spawn_error(err))?;
+        let state = fs::read(work_dir.path.join(STATE_FILE_NAME)).ok();
+        let disk_usage = work_dir.size();
+        Ok(result.with_state(state).with_disk_usage(disk_usage))
+    }
+
+    /// Returns a more helpful error than a spawn error `err` if the script exists but the
+    /// interpreter of its shebang doesn't.
+    fn spawn_error(&self, err: io::Error) -> io::Error {
+        if err.kind() != io::ErrorKind::NotFound || !self.cmd_path.exists() {
+            return err;
+        }
+        match self.check_interpreter() {
+            Ok(()) => err,
+            Err(err) => io::Error::new(io::ErrorKind::NotFound, err.message()),
+        }
+    }
+
+    /// Checks that the interpreter of the shebang line of the script exists, if the script has
+    /// one. An unreadable script is reported when the command is run.
+    pub fn check_interpreter(&self) -> Result<(), Error> {
+        let Some(interpreter) = fs::read(&self.cmd_path)
+            .ok()
+            .and_then(|script| shebang_interpreter(&script))
+        else {
+            return Ok(());
+        };
+        if Path::new(&interpreter).exists() {
+            return Ok(());
+        }
+        Err(Error::InterpreterNotFound {
+            path: self.cmd_path.clone(),
+            interpreter,
+        })
+    }
+
+    /// Returns the process command of the script, with its arguments, working directory,
+    /// environment, scheduling and locale.
+    fn command(&self) -> Result<Command, io::Error> {
+        self.check_executable()
+            .map_err(|err| io::Error::new(io::ErrorKind::PermissionDenied, err.message()))?;
+        let to_io_error = |err: Error| io::Error::new(io::ErrorKind::InvalidData, err.message());
+        let args = self.args().map_err(to_io_error)?;
+        let env = self.env().map_err(to_io_error)?;
+        let cwd = self.cwd().map_err(to_io_error)?;
+        let mut cmd = Command::new(self.cmd_path.as_os_str());
+        cmd.args(args).current_dir(cwd);
+        self.set_env(&mut cmd, &env);
+        Job::schedule(&mut cmd, &self.scheduling);
+        Ok(cmd)
+    }
+
+    /// Sets the environment `env` of the command on `cmd`, with its locale.
+    fn set_env(&self, cmd: &mut Command, env: &Env) {
+        env.apply(cmd);
+        if let Some(locale) = &self.locale {
+            cmd.env("LC_ALL", locale);
+        }
+    }
+
+    /// Returns the environment the command runs with, sorted by name: the environment of cliche
+    /// unless it's cleared, with the default variables, the variables of the environment file,
+    /// the locale and the size of the terminal. The path of the work directory, created for each
+    /// execution, is not included.
+    pub fn resolved_env(&self) -> Result<Vec<(String, String)>, Error> {
+        let env = self.env()?;
+        let mut cmd = Command::new(self.cmd_path.as_os_str());
+        self.set_env(&mut cmd, &env);
+        if self.has_screen() {
+            set_screen_size(&mut cmd, self.screen_size());
+        }
+        let mut vars = if env.clear {
+            BTreeMap::new()
+        } else {
+            env::vars_os().collect()
+        };
+        for (key, value) in cmd.get_envs() {
+            match value {
+                Some(value) => vars.insert(key.to_os_string(), value.to_os_string()),
+                None => vars.remove(key),
+            };
+        }
+        Ok(vars
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().to_string(),
+                    v.to_string_lossy().to_string(),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the expected code for this command spec.
+    pub fn exit_code(&self) -> Result<ExpectedExitCode, Error> {
+        let Some(exit_code_path) = &self.exit_code_path else {
+            return Ok(ExitCode::from(0).into());
+        };
+
+        let exit_code = read_file(exit_code_path, self.max_file_size)?;
+        let Ok(exit_code) = String::from_utf8(exit_code.clone()) else {
+            return Err(Error::FileNotUtf8 {
+                path: exit_code_path.clone(),
+            });
+        };
+        // Exit code can also be given by its name (`EX_USAGE`, `SIGINT` etc...), or be a range
+        // (`1..5`), a negation (`!0`) or any code (`*`).
+        exit_code.parse().map_err(|_| Error::FileNotInteger {
+            path: exit_code_path.clone(),
+        })
+    }
+
+    /// Returns `true` if this command has at least one expectation on stdout.
+    pub fn has_stdout_expectation(&self) -> bool {
+        self.has_stdout()
+            || self.has_stdout_pat()
+            || self.has_expectation("out.json")
+            || self.has_expectation("out.sha256")
+            || self.has_stdout_lines()
+            || self.has_stdout_count()
+            || self.has_stdout_first()
+            || self.has_stdout_last()
+            || self.has_screen()
+            || self.has_screen_sizes()
+    }
+
+    /// Returns `true` if the stderr of this command is compared with an expectation file
+    /// (`foo.err`, `foo.err.pattern`, `foo.err.json` or `foo.err.sha256`).
+    pub fn has_stderr_expectation(&self) -> bool {
+        self.has_stderr()
+            || self.has_stderr_pat()
+            || self.has_expectation("err.json")
+            || self.has_expectation("err.sha256")
+    }
+
+    /// Returns `true` if this command has an expected exit code, `false` otherwise.
+    pub fn has_exit_code(&self) -> bool {
+        self.exit_code_path.is_some()
+    }
+
+    /// Returns `true` if this command has expected stdout, `false` otherwise.
+    pub fn has_stdout(&self) -> bool {
+        self.stdout_path.is_some()
+    }
+
+    /// Returns the expected stdout buffer for this command spec.
+    pub fn stdout(&self) -> Result<Vec<u8>, Error> {
+        let Some(stdout_path) = &self.stdout_path else {
+            return Ok(vec![]);
+        };
+        let stdout = read_file(stdout_path, self.max_file_size)?;
+        let stdout = read_shared_snapshot(stdout_path, stdout, self.max_file_size)?;
+        expand_includes(stdout_path, stdout, self.max_file_size)
+    }
+
+    /// Returns the front-matter of the expected `stdout` of this command.
+    pub fn stdout_front_matter(&self, stdout: &[u8]) -> Result<FrontMatter, Error> {
+        self.front_matter(self.stdout_path.as_deref(), stdout)
+    }
+
+    /// Returns `true` if this command has expected stdout, `false` otherwise.
+    pub fn has_stdout_pat(&self) -> bool {
+        self.stdout_pat_path.is_some()
+    }
+
+    /// Returns the expected patterned stdout buffer for this command spec.
+    /// For the moment, we only deal with UTF-8 pattern stdout
+    pub fn stdout_pat(&self) -> Result<String, Error> {
+        let Some(stdout_pat_path) = &self.stdout_pat_path else {
+            return Ok("".to_string());
+        };
+        read_text_with_includes(stdout_pat_path, self.max_file_size)
+    }
+
+    /// Returns the patterns of the expected patterned stdout and stderr that can match an empty
+    /// string, with the path of their pattern file. An unreadable pattern file has no pattern, the
+    /// error being reported by its verification.
+    pub fn empty_patterns(&self) -> Vec<(PathBuf, EmptyPattern)> {
+        [&self.stdout_pat_path, &self.stderr_pat_path]
+            .into_iter()
+            .flatten()
+            .flat_map(|path| {
+                read_text_with_includes(path, self.max_file_size)
+                    .map(|pattern| find_empty_patterns(&pattern))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|p| (path.clone(), p))
+            })
+            .collect()
+    }
+
+    /// Returns `true` if this command has an expected stdout line count, `false` otherwise.
+    pub fn has_stdout_lines(&self) -> bool {
+        self.stdout_lines_path.is_some()
+    }
+
+    /// Returns the expected range of stdout line count for this command spec.
+    pub fn stdout_lines(&self) -> Result<CountRange, Error> {
+        let Some(stdout_lines_path) = &self.stdout_lines_path else {
+            return Ok(CountRange::exact(0));
+        };
+        let stdout_lines = read_text(stdout_lines_path, self.max_file_size)?;
+        let Ok(stdout_lines) = stdout_lines.parse::<CountRange>() else {
+            return Err(Error::FileNotCount {
+                path: stdout_lines_path.clone(),
+            });
+        };
+        Ok(stdout_lines)
+    }
+
+    /// Returns `true` if this command has expected counts of stdout matching lines, `false`
+    /// otherwise.
+    pub fn has_stdout_count(&self) -> bool {
+        self.stdout_count_path.is_some()
+    }
+
+    /// Returns the expected counts of stdout lines matching a pattern for this command spec.
+    pub fn stdout_count(&self) -> Result<Vec<MatchCount>, Error> {
+        match &self.stdout_count_path {
+            Some(path) => read_match_counts(path, self.max_file_size),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns `true` if this command has an expected stdout first line, `false` otherwise.
+    pub fn has_stdout_first(&self) -> bool {
+        self.stdout_first_path.is_some()
+    }
+
+    /// Returns the expected first line of stdout (that may contain patterns) for this command spec.
+    pub fn stdout_first(&self) -> Result<String, Error> {
+        let Some(stdout_first_path) = &self.stdout_first_path else {
+            return Ok("".to_string());
+        };
+        read_text(stdout_first_path, self.max_file_size)
+    }
+
+    /// Returns `true` if this command has an expected stdout last line, `false` otherwise.
+    pub fn has_stdout_last(&self) -> bool {
+        self.stdout_last_path.is_some()
+    }
+
+    /// Returns the expected last line of stdout (that may contain patterns) for this command spec.
+    pub fn stdout_last(&self) -> Result<String, Error> {
+        let Some(stdout_last_path) = &self.stdout_last_path else {
+            return Ok("".to_string());
+        };
+        read_text(stdout_last_path, self.max_file_size)
+    }
+
+    /// Returns `true` if this command has an expected terminal screen, `false` otherwise.
+    pub fn has_screen(&self) -> bool {
+        self.screen_path.is_some()
+    }
+
+    /// Returns the expected terminal screen, the text left on the screen after replaying stdout.
+    pub fn screen(&self) -> Result<String, Error> {
+        let Some(screen_path) = &self.screen_path else {
+            return Ok("".to_string());
+        };
+        read_text(screen_path, self.max_file_size)
+    }
+
+    /// Returns the size of the terminal on which stdout is replayed.
+    pub fn screen_size(&self) -> ScreenSize {
+        ScreenSize::default()
+    }
+
+    /// Returns `true` if this command has a list of terminal sizes to check, `false` otherwise.
+    pub fn has_screen_sizes(&self) -> bool {
+        self.screen_sizes_path.is_some()
+    }
+
+    /// Returns the terminal sizes on which the command is run, each one having its own screen
+    /// snapshot (`foo.120x40.screen` for instance). Sizes are separated by whitespaces.
+    pub fn screen_sizes(&self) -> Result<Vec<ScreenSize>, Error> {
+        let Some(screen_sizes_path) = &self.screen_sizes_path else {
+            return Ok(vec![]);
+        };
+        let text = read_text(screen_sizes_path, self.max_file_size)?;
+        let sizes = text
+            .split_whitespace()
+            .map(|size| size.parse::<ScreenSize>())
+            .collect::<Result<Vec<_>, _>>();
+        sizes.map_err(|_| Error::FileNotScreenSizes {
+            path: screen_sizes_path.clone(),
+        })
+    }
+
+    /// Returns the path of the screen snapshot for a terminal of a given `size`.
+    pub fn sized_screen_path(&self, size: ScreenSize) -> PathBuf {
+        self.snapshot_path(&format!("{size}.screen"))
+    }
+
+    /// Returns the expected terminal screen for a terminal of a given `size`.
+    pub fn sized_screen(&self, size: ScreenSize) -> Result<String, Error> {
+        read_text(&self.sized_screen_path(size), self.max_file_size)
+    }
+
+    pub fn has_stderr(&self) -> bool {
+        self.stderr_path.is_some()
+    }
+
+    /// Returns the expected stderr buffer for this command spec.
+    pub fn stderr(&self) -> Result<Vec<u8>, Error> {
+        let Some(stderr_path) = &self.stderr_path else {
+            return Ok(vec![]);
+        };
+        let stderr = read_file(stderr_path, self.max_file_size)?;
+        let stderr = read_shared_snapshot(stderr_path, stderr, self.max_file_size)?;
+        expand_includes(stderr_path, stderr, self.max_file_size)
+    }
+
+    /// Returns the front-matter of the expected `stderr` of this command.
+    pub fn stderr_front_matter(&self, stderr: &[u8]) -> Result<FrontMatter, Error> {
+        self.front_matter(self.stderr_path.as_deref(), stderr)
+    }
+
+    /// Returns the alternatives of the snapshot with extension `ext` (`out` or `err`), ordered by
+    /// number, with their extension (`out.alt1` for instance) and their front-matter.
+    ///
+    /// Alternatives are read next to the snapshot, whether it exists or not.
+    pub fn alternatives(&self, ext: &str) -> Result<Vec<(String, Vec<u8>, FrontMatter)>, Error> {
+        let snapshot_path = self.snapshot_path(ext);
+        let (Some(dir), Some(name)) = (snapshot_path.parent(), snapshot_path.file_name()) else {
+            return Ok(vec![]);
+        };
+        // A relative snapshot path like `foo.out` has an empty parent.
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(vec![]);
+        };
+        let prefix = format!("{}.{ALTERNATIVE_SUFFIX}", name.to_string_lossy());
+        let mut paths = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let name = path.file_name()?.to_string_lossy().to_string();
+                let number = name.strip_prefix(&prefix)?;
+                if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                Some((number.parse::<u64>().ok()?, path))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .map(|(number, path)| {
+                let snapshot = read_file(&path, self.max_file_size)?;
+                let front_matter = self.front_matter(Some(&path), &snapshot)?;
+                let ext = format!("{ext}.{ALTERNATIVE_SUFFIX}{number}");
+                Ok((ext, snapshot, front_matter))
+            })
+            .collect()
+    }
+
+    /// Returns `true` if this command has an expected patterned stderr, `false` otherwise.
+    pub fn has_stderr_pat(&self) -> bool {
+        self.stderr_pat_path.is_some()
+    }
+
+    /// Returns the expected patterned stderr for this command spec.
+    pub fn stderr_pat(&self) -> Result<String, Error> {
+        let Some(stderr_pat_path) = &self.stderr_pat_path else {
+            return Ok("".to_string());
+        };
+        read_text_with_includes(stderr_pat_path, self.max_file_size)
+    }
+
+    /// Returns the patterns of lines that must only appear on stderr for this command spec, one
+    /// regex per non-empty line.
+    pub fn stderr_only(&self) -> Result<Vec<Regex>, Error> {
+        let Some(stderr_only_path) = &self.stderr_only_path else {
+            return Ok(vec![]);
+        };
+        let text = read_text(stderr_only_path, self.max_file_size)?;
+        let mut patterns = vec![];
+        for (index, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let regex = match Regex::new(line) {
+                Ok(r) => r,
+                Err(err) => {
+                    return Err(Error::FileInvalidRegex {
+                        path: stderr_only_path.clone(),
+                        reason: err.to_string(),
+                        row: index + 1,
+                    });
+                }
+            };
+            patterns.push(regex);
+        }
+        Ok(patterns)
+    }
+
+    /// Returns `true` if this command has expected counts of stderr matching lines, `false`
+    /// otherwise.
+    pub fn has_stderr_count(&self) -> bool {
+        self.stderr_count_path.is_some()
+    }
+
+    /// Returns the expected counts of stderr lines matching a pattern for this command spec.
+    pub fn stderr_count(&self) -> Result<Vec<MatchCount>, Error> {
+        match &self.stderr_count_path {
+            Some(path) => read_match_counts(path, self.max_file_size),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns `true` if this command has an expected state, `false` otherwise.
+    pub fn has_state(&self) -> bool {
+        self.state_path.is_some()
+    }
+
+    /// Returns the expected state written by the command in its work directory.
+    pub fn state(&self) -> Result<JsonValue, Error> {
+        let Some(state_path) = &self.state_path else {
+            return Ok(JsonValue::Null);
+        };
+        let text = read_text(state_path, self.max_file_size)?;
+        JsonValue::parse(&text).map_err(|reason| Error::FileNotJson {
+            path: state_path.clone(),
+            reason,
+        })
+    }
+
+    /// Returns `true` if this command has a post-hook script, `false` otherwise.
+    pub fn has_post_hook(&self) -> bool {
+        self.post_hook_path.is_some()
+    }
+
+    /// Returns the path of the post-hook script, run after a successful verification for
+    /// secondary assertions.
+    pub fn post_hook(&self) -> Option<&Path> {
+        self.post_hook_path.as_deref()
+    }
+
+    /// Returns the path of the file fed to the standard input of the command, if any.
+    pub fn stdin(&self) -> Option<&Path> {
+        self.stdin_path.as_deref()
+    }
+
+    /// Returns the expectation files of this command spec, with a short description of each one.
+    pub fn expectation_files(&self) -> Vec<(&'static str, &Path)> {
+        let files = [
+            ("stdout", &self.stdout_path),
+            ("stdout pattern", &self.stdout_pat_path),
+            ("stdout line count", &self.stdout_lines_path),
+            ("stdout matching line counts", &self.stdout_count_path),
+            ("stdout first line", &self.stdout_first_path),
+            ("stdout last line", &self.stdout_last_path),
+            ("terminal screen", &self.screen_path),
+            ("terminal sizes", &self.screen_sizes_path),
+            ("stderr", &self.stderr_path),
+            ("stderr pattern", &self.stderr_pat_path),
+            ("stderr only patterns", &self.stderr_only_path),
+            ("stderr matching line counts", &self.stderr_count_path),
+            ("exit code", &self.exit_code_path),
+            ("state", &self.state_path),
+            ("post-hook", &self.post_hook_path),
+            ("stdin", &self.stdin_path),
+            ("environment", &self.env_path),
+            ("working directory", &self.cwd_path),
+            ("arguments", &self.args_path),
+            ("timeout", &self.timeout_path),
+        ];
+        files
+            .into_iter()
+            .filter_map(|(kind, path)| path.as_deref().map(|p| (kind, p)))
+            .collect()
+    }
+
+    /// Returns every expectation file looked up for this command spec, with a short description
+    /// of each one and whether it has been found.
+    pub fn looked_up_files(&self) -> Vec<(&'static str, PathBuf, bool)> {
+        let stdout_json_path = self.find_snapshot("out.json");
+        let stdout_sha256_path = self.find_snapshot("out.sha256");
+        let stderr_json_path = self.find_snapshot("err.json");
+        let stderr_sha256_path = self.find_snapshot("err.sha256");
+        let files = [
+            ("stdout", "out", &self.stdout_path),
+            ("stdout pattern", "out.pattern", &self.stdout_pat_path),
+            ("stdout JSON", "out.json", &stdout_json_path),
+            ("stdout checksum", "out.sha256", &stdout_sha256_path),
+            ("stdout line count", "out.lines", &self.stdout_lines_path),
+            (
+                "stdout matching line counts",
+                "out.count",
+                &self.stdout_count_path,
+            ),
+            ("stdout first line", "out.first", &self.stdout_first_path),
+            ("stdout last line", "out.last", &self.stdout_last_path),
+            ("terminal screen", "screen", &self.screen_path),
+            ("terminal sizes", "screen.sizes", &self.screen_sizes_path),
+            ("stderr", "err", &self.stderr_path),
+            ("stderr pattern", "err.pattern", &self.stderr_pat_path),
+            ("stderr JSON", "err.json", &stderr_json_path),
+            ("stderr checksum", "err.sha256", &stderr_sha256_path),
+            ("stderr only patterns", "err.only", &self.stderr_only_path),
+            (
+                "stderr matching line counts",
+                "err.count",
+                &self.stderr_count_path,
+            ),
+            ("exit code", "exit", &self.exit_code_path),
+            ("state", "state.json", &self.state_path),
+        ];
+        files
+            .into_iter()
+            .map(|(kind, ext, path)| match path {
+                Some(path) => (kind, path.clone(), true),
+                None => (kind, self.snapshot_path(ext), false),
+            })
+            .collect()
+    }
+
+    /// Returns the path of the snapshot file with extension `ext` for this command spec, whether
+    /// it exists or not. The first existing variant of the snapshot is preferred.
+    pub fn snapshot_path(&self, ext: &str) -> PathBuf {
+        self.snapshot_variants
+            .iter()
+            .map(|variant| {
+                self.snapshot_base
+                    .with_extension(format!("{ext}.{variant}"))
+            })
+            .find(|path| path.exists())
+            .unwrap_or_else(|| self.snapshot_base.with_extension(ext))
+    }
+
+    pub fn cmd_path(&self) -> &Path {
+        &self.cmd_path
+    }
+
+    /// Returns every companion file of this command that has been found: expectation files, then
+    /// input files (stdin, environment...) and post-hook, with a short description of each one.
+    pub fn companion_files(&self) -> Vec<(&'static str, PathBuf)> {
+        let mut files = self
+            .looked_up_files()
+            .into_iter()
+            .filter(|(_, _, found)| *found)
+            .map(|(kind, path, _)| (kind, path))
+            .collect::<Vec<_>>();
+        for (kind, path) in self.expectation_files() {
+            if !files.iter().any(|(_, p)| p == path) {
+                files.push((kind, path.to_path_buf()));
+            }
+        }
+        files
+    }
+
+    /// Returns the companion files of this command that look like misspelled expectation files
+    /// (`foo.ouy` or `foo.out.patern` for instance), with the expectation file they resemble.
+    ///
+    /// Such files are not read, their expectations would be silently ignored.
+    pub fn misspelled_files(&self) -> Vec<(PathBuf, PathBuf)> {
+        let base = &self.snapshot_base;
+        let (Some(dir), Some(stem)) = (base.parent(), base.file_stem()) else {
+            return vec![];
+        };
+        // The snapshot base of a script of the current directory has an empty parent.
+        let read_dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let Ok(entries) = fs::read_dir(read_dir) else {
+            return vec![];
+        };
+        let prefix = format!("{}.", stem.to_string_lossy());
+        let script_ext = base.extension().map(|e| e.to_string_lossy());
+        let mut files = entries
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name();
+                let name = name.to_string_lossy();
+                let ext = name.strip_prefix(&prefix)?;
+                // Expectation files of a locale are checked like the other expectation files.
+                let locale = self
+                    .locales
+                    .iter()
+                    .map(|locale| format!("{locale}."))
+                    .find(|locale| ext.starts_with(locale))
+                    .unwrap_or_default();
+                let ext = &ext[locale.len()..];
+                // Variants of expectation files are checked like the other expectation files.
+                let ext = self
+                    .snapshot_variants
+                    .iter()
+                    .find_map(|variant| ext.strip_suffix(&format!(".{variant}")))
+                    .unwrap_or(ext);
+                if script_ext.as_deref() == Some(ext)
+                    || ext == POST_HOOK_EXTENSION
+                    || ext == STDIN_EXTENSION
+                    || ext == ENV_EXTENSION
+                    || ext == CWD_EXTENSION
+                    || ext == ARGS_EXTENSION
+                    || ext == TIMEOUT_EXTENSION
+                    || EXPECTATION_EXTENSIONS.contains(&ext)
+                    || is_alternative(ext)
+                    || ext
+                        .strip_suffix(PENDING_SUFFIX)
+                        .is_some_and(|ext| ext.ends_with('.'))
+                {
+                    return None;
+                }
+                let known = EXPECTATION_EXTENSIONS
+                    .iter()
+                    .find(|known| edit_distance(ext, known) <= MAX_MISSPELL_DISTANCE)?;
+                let expected = self.snapshot_path(&format!("{locale}{known}"));
+                Some((dir.join(name.as_ref()), expected))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct CommandResult {
+    termination: Termination,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    usage: ResourceUsage,
+    elapsed: Duration,
+    /// Content of the state file written by the command, if any.
+    state: Option<Vec<u8>>,
+    /// Commands of the processes left running by the command after it has exited.
+    leftovers: Vec<String>,
+    /// Number of bytes left by the command in its work directory.
+    disk_usage: u64,
+    /// Timeout exceeded by the command, killed before it has exited.
+    timed_out: Option<Duration>,
+}
+
+impl CommandResult {
+    pub fn new(exit_code: ExitCode, stdout: &[u8], stderr: &[u8]) -> Self {
+        CommandResult {
+            termination: exit_code.into(),
+            stdout: stdout.to_vec(),
+            stderr: stderr.to_vec(),
+            usage: ResourceUsage::default(),
+            elapsed: Duration::ZERO,
+            state: None,
+            leftovers: vec![],
+            disk_usage: 0,
+            timed_out: None,
+        }
+    }
+
+    /// Sets the resources used to produce this result.
+    pub fn with_usage(mut self, usage: ResourceUsage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Sets how the command has terminated.
+    pub fn with_termination(mut self, termination: Termination) -> Self {
+        self.termination = termination;
+        self
+    }
+
+    /// Sets the wall-clock duration of the command.
+    pub fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    /// Sets the content of the state file written by the command.
+    pub fn with_state(mut self, state: Option<Vec<u8>>) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Sets the commands of the processes left running by the command.
+    pub fn with_leftovers(mut self, leftovers: Vec<String>) -> Self {
+        self.leftovers = leftovers;
+        self
+    }
+
+    /// Replaces the captured stdout, with a post-processed one for instance.
+    pub fn with_stdout(mut self, stdout: Vec<u8>) -> Self {
+        self.stdout = stdout;
+        self
+    }
+
+    /// Sets the number of bytes left by the command in its work directory.
+    pub fn with_disk_usage(mut self, disk_usage: u64) -> Self {
+        self.disk_usage = disk_usage;
+        self
+    }
+
+    /// Sets the timeout exceeded by the command, if it has been killed.
+    pub fn with_timed_out(mut self, timed_out: Option<Duration>) -> Self {
+        self.timed_out = timed_out;
+        self
+    }
+
+    /// Returns the exit code of the command, 128 + the signal number if it has been killed by a
+    /// signal.
+    pub fn exit_code(&self) -> ExitCode {
+        self.termination.exit_code()
+    }
+
+    /// Returns how the command has terminated, with an exit code or killed by a signal.
+    pub fn termination(&self) -> Termination {
+        self.termination
+    }
+
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    pub fn usage(&self) -> ResourceUsage {
+        self.usage
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn state(&self) -> Option<&[u8]> {
+        self.state.as_deref()
+    }
+
+    pub fn leftovers(&self) -> &[String] {
+        &self.leftovers
+    }
+
+    pub fn disk_usage(&self) -> u64 {
+        self.disk_usage
+    }
+
+    /// Returns the timeout exceeded by the command, if it has been killed. Outputs are then the
+    /// partial outputs written before the command has been killed.
+    pub fn timed_out(&self) -> Option<Duration> {
+        self.timed_out
+    }
+}
+
+/// The standard input of a command. The input is never decoded nor translated: binary input is fed
+/// as is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stdin<'input> {
+    /// No input, the standard input is null.
+    Null,
+    /// Bytes written to the standard input.
+    Bytes(&'input [u8]),
+    /// A file the standard input is redirected from, without being loaded in memory: large
+    /// fixtures are read directly by the command.
+    File(&'input Path),
+}
+
+/// Runs `cmd` in its own [`Job`] and captures its outputs. The command is fed with `stdin`. If
+/// `stream` is `true`, the outputs are echoed while being captured.
+pub fn capture(cmd: Command, stdin: Stdin, stream: bool) -> Result<CommandResult, io::Error> {
+    let capture = Capture::Pipes {
+        stream,
+        prefix: None,
+    };
+    run_captured(cmd, stdin, capture, None)
+}
+
+/// Runs `cmd` like [`capture`], its outputs being redirected to temporary files instead of pipes.
+///
+/// The outputs are read once the command has exited: very chatty commands never wait for a pipe
+/// to be drained, and there's no reader thread per output. The files are removed after reading.
+pub fn capture_to_files(cmd: Command, stdin: Stdin) -> Result<CommandResult, io::Error> {
+    run_captured(cmd, stdin, Capture::Files, None)
+}
+
+/// How the outputs of a command are captured.
+#[derive(Clone, Debug)]
+enum Capture {
+    /// Through pipes read in dedicated threads, optionally echoing the outputs, each line being
+    /// prefixed by `prefix` if any.
+    Pipes {
+        stream: bool,
+        prefix: Option<String>,
+    },
+    /// In temporary files.
+    Files,
+}
+
+/// The outputs of a running command, being captured.
+enum Outputs {
+    Pipes(
+        JoinHandle<Result<Vec<u8>, io::Error>>,
+        JoinHandle<Result<Vec<u8>, io::Error>>,
+    ),
+    Files(TempFile, TempFile),
+}
+
+/// Runs `cmd` in its own [`Job`] and captures its outputs. Once `timeout` is exceeded, every
+/// process of the job is killed.
+fn run_captured(
+    mut cmd: Command,
+    stdin: Stdin,
+    capture: Capture,
+    timeout: Option<Duration>,
+) -> Result<CommandResult, io::Error> {
+    let (stdin_cfg, input) = match stdin {
+        Stdin::Null => (Stdio::null(), None),
+        Stdin::Bytes(bytes) => (Stdio::piped(), Some(bytes.to_vec())),
+        Stdin::File(path) => (Stdio::from(fs::File::open(path)?), None),
+    };
+    cmd.stdin(stdin_cfg);
+    let files = match &capture {
+        Capture::Pipes { .. } => {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+            None
+        }
+        Capture::Files => {
+            let stdout = TempFile::create("stdout")?;
+            let stderr = TempFile::create("stderr")?;
+            cmd.stdout(stdout.stdio()?).stderr(stderr.stdio()?);
+            Some((stdout, stderr))
+        }
+    };
+    Job::prepare(&mut cmd);
+
+    let start = Instant::now();
+    let mut child = cmd.spawn()?;
+    let job = Job::attach(&child)?;
+    let writer = write_in_background(child.stdin.take(), input);
+    let outputs = match (capture, files) {
+        (_, Some((stdout, stderr))) => Outputs::Files(stdout, stderr),
+        (
+            Capture::Pipes {
+                stream: true,
+                prefix,
+            },
+            None,
+        ) => Outputs::Pipes(
+            read_in_background(child.stdout.take(), Some(Echo::Stdout), prefix.clone()),
+            read_in_background(child.stderr.take(), Some(Echo::Stderr), prefix),
+        ),
+        (_, None) => Outputs::Pipes(
+            read_in_background(child.stdout.take(), None, None),
+            read_in_background(child.stderr.take(), None, None),
+        ),
+    };
+    let timed_out = AtomicBool::new(false);
+    let (status, usage) = thread::scope(|scope| {
+        // The watchdog is notified (or rather disconnected) as soon as the child has exited.
+        let (exited, exit) = mpsc::channel::<()>();
+        if let Some(timeout) = timeout {
+            let (job, timed_out) = (&job, &timed_out);
+            scope.spawn(move || {
+                if exit.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    let _ = job.kill();
+                }
+            });
+        }
+        let waited = job.wait(&mut child);
+        drop(exited);
+        waited
+    })?;
+    let elapsed = start.elapsed();
+    let timed_out = timed_out.into_inner().then_some(timeout).flatten();
+    // Processes left behind may still hold the pipes open, we kill them before reading the
+    // outputs to the end. They're reported, listing them being best effort.
+    let leftovers = job.leftovers().unwrap_or_default();
+    job.kill()?;
+    let (stdout, stderr) = match outputs {
+        Outputs::Pipes(stdout, stderr) => (
+            stdout.join().expect("stdout reader panicked")?,
+            stderr.join().expect("stderr reader panicked")?,
+        ),
+        Outputs::Files(stdout, stderr) => (fs::read(&stdout.path)?, fs::read(&stderr.path)?),
+    };
+    writer.join().expect("stdin writer panicked")?;
+
+    // A command killed by its timeout has no exit code.
+    let termination = match timed_out {
+        Some(_) => ExitCode::from(status.code().unwrap_or(-1)).into(),
+        None => Termination::from(status),
+    };
+    let result = CommandResult::new(ExitCode::from(0), &stdout, &stderr)
+        .with_termination(termination)
+        .with_usage(usage)
+        .with_elapsed(elapsed)
+        .with_leftovers(leftovers)
+        .with_timed_out(timed_out);
+    Ok(result)
+}
+
+/// A temporary file, removed when dropped.
+struct TempFile {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl TempFile {
+    /// Creates a new empty temporary file, whose name ends with `suffix`.
+    fn create(suffix: &str) -> Result<Self, io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("cliche-{}-{id}.{suffix}", std::process::id());
+        let path = env::temp_dir().join(name);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(TempFile { path, file })
+    }
+
+    /// Returns a handle to this file, to redirect a child output.
+    fn stdio(&self) -> Result<Stdio, io::Error> {
+        Ok(Stdio::from(self.file.try_clone()?))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A temporary work directory of a command, removed with its content when dropped.
+struct WorkDir {
+    path: PathBuf,
+}
+
+impl WorkDir {
+    /// Creates a new empty temporary directory.
+    fn create() -> Result<Self, io::Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("cliche-{}-{id}.work", std::process::id());
+        let path = env::temp_dir().join(name);
+        fs::create_dir(&path)?;
+        Ok(WorkDir { path })
+    }
+
+    /// Returns the total size in bytes of the files of this directory.
+    fn size(&self) -> u64 {
+        dir_size(&self.path)
+    }
+}
+
+/// Returns the total size in bytes of the files under the directory at `path`. Symbolic links are
+/// not followed, and entries that can't be read are ignored.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+        .map(|(path, metadata)| {
+            if metadata.is_dir() {
+                dir_size(&path)
+            } else {
+                metadata.len()
+            }
+        })
+        .sum()
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Returns the path of the shared snapshot referenced by a `snapshot` read at `path`, if the
+/// snapshot is made of a single reference line like `@usage.out`. The shared snapshot is relative
+/// to the directory of the snapshot, and is a stdout (`.out`) or stderr (`.err`) snapshot.
+pub fn shared_snapshot_path(path: &Path, snapshot: &[u8]) -> Option<PathBuf> {
+    let snapshot = str::from_utf8(snapshot).ok()?;
+    let snapshot = snapshot.strip_suffix('\n').unwrap_or(snapshot);
+    let reference = snapshot.strip_prefix(SHARED_SNAPSHOT_PREFIX)?;
+    if reference.contains('\n') || !(reference.ends_with(".out") || reference.ends_with(".err")) {
+        return None;
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Some(dir.join(reference))
+}
+
+/// Returns the content of the shared snapshot referenced by a `snapshot` read at `path`, or the
+/// snapshot itself if it's not a reference.
+fn read_shared_snapshot(path: &Path, snapshot: Vec<u8>, max: u64) -> Result<Vec<u8>, Error> {
+    let Some(shared_path) = shared_snapshot_path(path, &snapshot) else {
+        return Ok(snapshot);
+    };
+    read_file(&shared_path, max)
+}
+
+/// Returns the path of the file included by a `line` of an expectation file, if the line is an
+/// include directive like `<<<include:common/header.out>>>`.
+fn include_path(line: &[u8]) -> Option<&str> {
+    let line = str::from_utf8(line).ok()?;
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    line.strip_prefix(INCLUDE_START)?.strip_suffix(INCLUDE_END)
+}
+
+/// Returns the content of an expectation file read at `path`, with each include directive line
+/// replaced by the content of the included file (relative to the directory of `path`).
+/// Included files can include other files.
+fn expand_includes(path: &Path, content: Vec<u8>, max: u64) -> Result<Vec<u8>, Error> {
+    expand_includes_at(path, content, max, 0)
+}
+
+fn expand_includes_at(
+    path: &Path,
+    content: Vec<u8>,
+    max: u64,
+    depth: usize,
+) -> Result<Vec<u8>, Error> {
+    let lines = content.split_inclusive(|b| *b == b'\n');
+    if !lines.clone().any(|line| include_path(line).is_some()) {
+        return Ok(content);
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut expanded = vec![];
+    for (index, line) in lines.enumerate() {
+        let Some(include) = include_path(line) else {
+            expanded.extend_from_slice(line);
+            continue;
+        };
+        let error = |reason: String| Error::IncludeInvalid {
+            path: path.to_path_buf(),
+            reason,
+            row: index + 1,
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(error(format!(
+                "includes nested more than {MAX_INCLUDE_DEPTH} levels deep"
+            )));
+        }
+        let include_path = dir.join(include);
+        let fragment = match read_file(&include_path, max) {
+            Ok(fragment) => fragment,
+            Err(Error::FileRead { cause, .. }) => {
+                return Err(error(format!(
+                    "can't read {}: {cause}",
+                    include_path.display()
+                )));
+            }
+            Err(err) => return Err(err),
+        };
+        expanded.extend(expand_includes_at(&include_path, fragment, max, depth + 1)?);
+    }
+    Ok(expanded)
+}
+
+/// Reads the text file at `path`, expanding its include directives.
+fn read_text_with_includes(path: &Path, max: u64) -> Result<String, Error> {
+    let text = read_text(path, max)?;
+    let text = expand_includes(path, text.into_bytes(), max)?;
+    String::from_utf8(text).map_err(|_| Error::FileNotUtf8 {
+        path: path.to_path_buf(),
+    })
+}
+
+/// Returns `true` if `ext` is the extension of an alternative stdout or stderr snapshot, like
+/// `out.alt1`.
+fn is_alternative(ext: &str) -> bool {
+    ["out", "err"].iter().any(|snapshot| {
+        ext.strip_prefix(snapshot)
+            .and_then(|ext| ext.strip_prefix('.'))
+            .and_then(|ext| ext.strip_prefix(ALTERNATIVE_SUFFIX))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+/// Parses a duration like `2`, `1.5s`, `500ms` or `1m`. A number without unit is a number of
+/// seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let err = || format!("expecting a duration like 2s, 500ms or 1m, found <{s}>");
+    let text = s.trim();
+    let (value, unit) = if let Some(value) = text.strip_suffix("ms") {
+        (value, 0.001)
+    } else if let Some(value) = text.strip_suffix('s') {
+        (value, 1.0)
+    } else if let Some(value) = text.strip_suffix('m') {
+        (value, 60.0)
+    } else {
+        (text, 1.0)
+    };
+    let value = value.trim().parse::<f64>().map_err(|_| err())?;
+    Duration::try_from_secs_f64(value * unit).map_err(|_| err())
+}
+
+/// Parses a factor scaling timeouts, a positive number like `3` or `1.5`.
+pub fn parse_timeout_scale(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(scale) if scale.is_finite() && scale > 0.0 => Ok(scale),
+        _ => Err(format!(
+            "expecting a positive factor like 3 or 1.5, found <{s}>"
+        )),
+    }
+}
+
+/// Returns the interpreter of the shebang line of a `script`, `/usr/bin/python` for
+/// `#!/usr/bin/python -u` for instance.
+fn shebang_interpreter(script: &[u8]) -> Option<String> {
+    let line = script.strip_prefix(b"#!")?;
+    let line = line.split(|b| *b == b'\n').next()?;
+    let line = str::from_utf8(line).ok()?;
+    line.split_whitespace().next().map(str::to_string)
+}
+
+/// Parses the `text` of an arguments file, one argument per line. Lines are trimmed and empty
+/// lines are skipped, unless the argument is between double quotes: `"  two spaces"` keeps its
+/// leading spaces and `""` is an empty argument. In a quoted argument, `\"` is a double quote and
+/// `\\` a backslash.
+///
+/// Returns the reason and the 1-based line index of the error if a line is not valid.
+fn parse_args(text: &str) -> Result<Vec<String>, (String, usize)> {
+    let mut args = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let row = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some(quoted) = line.strip_prefix('"') else {
+            args.push(line.to_string());
+            continue;
+        };
+        let mut arg = String::new();
+        let mut chars = quoted.chars();
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c @ ('"' | '\\')) => arg.push(c),
+                    Some(c) => return Err((format!("invalid escape <\\{c}>"), row)),
+                    None => return Err(("missing closing quote".to_string(), row)),
+                },
+                '"' => {
+                    closed = true;
+                    break;
+                }
+                c => arg.push(c),
+            }
+        }
+        if !closed {
+            return Err(("missing closing quote".to_string(), row));
+        }
+        if chars.next().is_some() {
+            return Err(("unexpected chars after closing quote".to_string(), row));
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+/// Reads the file at `path`, failing without reading it into memory if it's larger than `max`
+/// bytes.
+pub fn read_file(path: &Path, max: u64) -> Result<Vec<u8>, Error> {
+    let read_error = |err: io::Error| Error::FileRead {
+        path: path.to_path_buf(),
+        cause: err.to_string(),
+    };
+    let file = fs::File::open(path).map_err(read_error)?;
+    let size = file.metadata().map_err(read_error)?.len();
+    if size > max {
+        return Err(Error::FileTooLarge {
+            path: path.to_path_buf(),
+            size,
+            max,
+        });
+    }
+    // The file may have grown since, or be a device without size.
+    let mut content = vec![];
+    file.take(max + 1)
+        .read_to_end(&mut content)
+        .map_err(read_error)?;
+    if content.len() as u64 > max {
+        return Err(Error::FileTooLarge {
+            path: path.to_path_buf(),
+            size: content.len() as u64,
+            max,
+        });
+    }
+    Ok(content)
+}
+
+/// Reads the file at `path` as a UTF-8 string, failing if it's larger than `max` bytes.
+pub fn read_text(path: &Path, max: u64) -> Result<String, Error> {
+    let text = read_file(path, max)?;
+    let Ok(text) = String::from_utf8(text) else {
+        return Err(Error::FileNotUtf8 {
+            path: path.to_path_buf(),
+        });
+    };
+    Ok(text)
+}
+
+/// Where a child output is echoed while being captured.
+#[derive(Copy, Clone, Debug)]
+enum Echo {
+    Stdout,
+    Stderr,
+}
+
+impl Echo {
+    /// Writes `bytes` at once, and flushes them.
+    fn write(self, bytes: &[u8]) -> Result<(), io::Error> {
+        match self {
+            Echo::Stdout => {
+                let mut out = io::stdout().lock();
+                out.write_all(bytes)?;
+                out.flush()
+            }
+            Echo::Stderr => {
+                let mut err = io::stderr().lock();
+                err.write_all(bytes)?;
+                err.flush()
+            }
+        }
+    }
+}
+
+/// Removes the complete lines of `pending` and returns them, each one prefixed by `prefix`.
+/// The last line, if not terminated, stays in `pending`.
+fn take_prefixed_lines(pending: &mut Vec<u8>, prefix: &[u8]) -> Vec<u8> {
+    let Some(end) = pending.iter().rposition(|b| *b == b'\n') else {
+        return vec![];
+    };
+    let mut lines = vec![];
+    for line in pending
+        .drain(..=end)
+        .collect::<Vec<_>>()
+        .split_inclusive(|b| *b == b'\n')
+    {
+        lines.extend_from_slice(prefix);
+        lines.extend_from_slice(line);
+    }
+    lines
+}
+
+/// Reads a child `pipe` to the end in a dedicated thread, optionally echoing what's read. Echoed
+/// lines are prefixed by `prefix`, if any.
+fn read_in_background<R>(
+    pipe: Option<R>,
+    echo: Option<Echo>,
+    prefix: Option<String>,
+) -> JoinHandle<Result<Vec<u8>, io::Error>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = vec![];
+        let Some(mut pipe) = pipe else {
+            return Ok(buf);
+        };
+        let Some(echo) = echo else {
+            pipe.read_to_end(&mut buf)?;
+            return Ok(buf);
+        };
+        let mut chunk = [0; 8192];
+        let mut pending = vec![];
+        loop {
+            let n = match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            buf.extend_from_slice(&chunk[..n]);
+            let Some(prefix) = &prefix else {
+                echo.write(&chunk[..n])?;
+                continue;
+            };
+            // Only complete lines are echoed, a partial line waiting for the next read.
+            pending.extend_from_slice(&chunk[..n]);
+            let lines = take_prefixed_lines(&mut pending, prefix.as_bytes());
+            if !lines.is_empty() {
+                echo.write(&lines)?;
+            }
+        }
+        if let Some(prefix) = &prefix
+            && !pending.is_empty()
+        {
+            pending.push(b'\n');
+            echo.write(&take_prefixed_lines(&mut pending, prefix.as_bytes()))?;
+        }
+        Ok(buf)
+    })
+}
+
+/// Writes `input` to a child `pipe` in a dedicated thread, then closes the pipe.
+fn write_in_background<W>(
+    pipe: Option<W>,
+    input: Option<Vec<u8>>,
+) -> JoinHandle<Result<(), io::Error>>
+where
+    W: Write + Send + 'static,
+{
+    thread::spawn(move || {
+        let (Some(mut pipe), Some(input)) = (pipe, input) else {
+            return Ok(());
+        };
+        // The command may exit without reading all its input, this is not an error.
+        match pipe.write_all(&input) {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err),
+            _ => Ok(()),
+        }
+    })
+}
+
+/// Returns the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+/// Tells the program run by `cmd` the `size` of its terminal.
+fn set_screen_size(cmd: &mut Command, size: ScreenSize) {
+    cmd.env("COLUMNS", size.cols.to_string());
+    cmd.env("LINES", size.rows.to_string());
+}
+
+/// Reads the expected counts of matching lines of the file at `path`. Each non-empty line is a
+/// count or a range of counts, followed by a regex: `0 warning:` or `..3 deprecated` for
+/// instance.
+fn read_match_counts(path: &Path, max: u64) -> Result<Vec<MatchCount>, Error> {
+    let text = read_text(path, max)?;
+    let mut counts = vec![];
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line = line.trim_start();
+        let (expected, pattern) = line.split_once(' ').unwrap_or((line.trim_end(), ""));
+        let Ok(expected) = expected.parse::<CountRange>() else {
+            return Err(Error::FileNotCount {
+                path: path.to_path_buf(),
+            });
+        };
+        let pattern = match Regex::new(pattern.trim_start()) {
+            Ok(r) => r,
+            Err(err) => {
+                return Err(Error::FileInvalidRegex {
+                    path: path.to_path_buf(),
+                    reason: err.to_string(),
+                    row: index + 1,
+                });
+            }
+        };
+        counts.push(MatchCount {
+            row: index + 1,
+            expected,
+            pattern,
+        });
+    }
+    Ok(counts)
+}
+
+fn with_ext(path: &Path, ext: &str) -> Option<PathBuf> {
+    let mut path = path.to_path_buf();
+    path.set_extension(ext);
+    if path.exists() { Some(path) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("out", "out"), 0);
+        assert_eq!(edit_distance("ouy", "out"), 1);
+        assert_eq!(edit_distance("out.patern", "out.pattern"), 1);
+        assert_eq!(edit_distance("md", "out"), 3);
+        assert_eq!(edit_distance("", "err"), 3);
+    }
+
+    #[test]
+    fn test_misspelled_files() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for name in [
+            "foo.sh",
+            "foo.out",
+            "foo.ouy",
+            "foo.out.patern",
+            "foo.md",
+            "foobar.ouy",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        let dir = cmd.cmd_path().parent().unwrap();
+        assert_eq!(
+            cmd.misspelled_files(),
+            vec![
+                (dir.join("foo.out.patern"), dir.join("foo.out.pattern")),
+                (dir.join("foo.ouy"), dir.join("foo.out")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_companion_files() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for name in [
+            "foo.sh",
+            "foo.out",
+            "foo.out.json",
+            "foo.exit",
+            "foo.stdin",
+            "foo.md",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        let dir = cmd.cmd_path().parent().unwrap();
+        assert_eq!(
+            cmd.companion_files(),
+            vec![
+                ("stdout", dir.join("foo.out")),
+                ("stdout JSON", dir.join("foo.out.json")),
+                ("exit code", dir.join("foo.exit")),
+                ("stdin", dir.join("foo.stdin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_patterns() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(dir.join("foo.sh"), "").unwrap();
+        fs::write(dir.join("foo.out.pattern"), "Hello <<<.*>>>\n").unwrap();
+        fs::write(dir.join("foo.err.pattern"), "Error\n<<<.*>>>\n").unwrap();
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        let dir = cmd.cmd_path().parent().unwrap();
+        let empty_patterns = cmd
+            .empty_patterns()
+            .into_iter()
+            .map(|(path, p)| (path, p.row, p.whole_line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            empty_patterns,
+            vec![
+                (dir.join("foo.out.pattern"), 1, false),
+                (dir.join("foo.err.pattern"), 2, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_match_counts() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("foo.out.count");
+        fs::write(&path, "  2 ^foo\n\n  3\t\n").unwrap();
+        let counts = read_match_counts(&path, DEFAULT_MAX_FILE_SIZE)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.row, c.expected, c.pattern))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            vec![
+                (1, "2".parse().unwrap(), Regex::new("^foo").unwrap()),
+                (3, "3".parse().unwrap(), Regex::new("").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_variants() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for name in ["foo.sh", "foo.out", "foo.out.ci", "foo.err", "foo.exit.w"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        let dir = cmd.cmd_path().parent().unwrap().to_path_buf();
+        assert_eq!(cmd.stdout_path, Some(dir.join("foo.out")));
+        assert!(!cmd.has_exit_code());
+
+        let cmd = cmd.with_snapshot_variants(vec!["w".to_string(), "ci".to_string()]);
+        assert_eq!(cmd.stdout_path, Some(dir.join("foo.out.ci")));
+        assert_eq!(cmd.stderr_path, Some(dir.join("foo.err")));
+        assert_eq!(cmd.exit_code_path, Some(dir.join("foo.exit.w")));
+        assert_eq!(
+            cmd.snapshot_path("out.pattern"),
+            dir.join("foo.out.pattern")
+        );
+        assert!(cmd.misspelled_files().is_empty());
+    }
+
+    #[test]
+    fn test_screen_sizes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(dir.join("foo.sh"), "").unwrap();
+        fs::write(dir.join("foo.screen.sizes"), "80x24\n120x40\n").unwrap();
+        fs::write(dir.join("foo.120x40.screen"), "").unwrap();
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        let size = ScreenSize::new(120, 40);
+        assert_eq!(
+            cmd.screen_sizes().unwrap(),
+            vec![ScreenSize::new(80, 24), size]
+        );
+        assert_eq!(
+            cmd.sized_screen_path(size),
+            cmd.cmd_path().with_extension("120x40.screen")
+        );
+        assert!(cmd.misspelled_files().is_empty());
+
+        fs::write(dir.join("foo.screen.sizes"), "80x24 wide\n").unwrap();
+        let cmd = CommandSpec::new(&dir.join("foo.sh")).unwrap();
+        assert!(matches!(
+            cmd.screen_sizes(),
+            Err(Error::FileNotScreenSizes { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_to_files() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "head -c 1000000 /dev/zero; echo error >&2; exit 3"]);
+        let result = capture_to_files(cmd, Stdin::Bytes(b"")).unwrap();
+        assert_eq!(result.stdout().len(), 1_000_000);
+        assert_eq!(result.stderr(), b"error\n");
+        assert_eq!(result.exit_code(), ExitCode::from(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_killed_by_signal() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "kill -SEGV $$"]);
+        let result = capture(cmd, Stdin::Bytes(b""), false).unwrap();
+        assert_eq!(result.termination(), Termination::Signal(libc::SIGSEGV));
+        assert_eq!(result.exit_code(), ExitCode::from(128 + libc::SIGSEGV));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_binary_stdin() {
+        let input = (0..=255).cycle().take(100_000).collect::<Vec<u8>>();
+        let result = capture(Command::new("cat"), Stdin::Bytes(&input), false).unwrap();
+        assert_eq!(result.stdout(), input);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixture.bin");
+        fs::write(&path, &input).unwrap();
+        let result = capture(Command::new("cat"), Stdin::File(&path), false).unwrap();
+        assert_eq!(result.stdout(), input);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_disk_usage() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(
+            &cmd_path,
+            "#!/bin/sh\n\
+             head -c 1000 /dev/zero > \"$CLICHE_WORK_DIR/a.bin\"\n\
+             mkdir \"$CLICHE_WORK_DIR/sub\"\n\
+             head -c 2000 /dev/zero > \"$CLICHE_WORK_DIR/sub/b.bin\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let result = cmd.execute().unwrap();
+        assert_eq!(result.disk_usage(), 3000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_stdin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\ncat\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(cmd.stdin(), None);
+        assert_eq!(cmd.execute().unwrap().stdout(), b"");
+
+        let input = (0..=255).collect::<Vec<u8>>();
+        fs::write(tmp_dir.path().join("foo.stdin"), &input).unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert!(cmd.stdin().is_some());
+        assert_eq!(cmd.execute().unwrap().stdout(), input);
+        assert!(cmd.misspelled_files().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\necho before\nsleep 5\necho after\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(tmp_dir.path().join("foo.timeout"), "200ms\n").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path)
+            .unwrap()
+            .with_timeout(Some(Duration::from_secs(60)));
+        assert_eq!(cmd.timeout(), Ok(Some(Duration::from_millis(200))));
+        let result = cmd.execute().unwrap();
+        assert_eq!(result.timed_out(), Some(Duration::from_millis(200)));
+        assert_eq!(result.stdout(), b"before\n");
+        assert!(result.elapsed() < Duration::from_secs(5));
+        assert!(cmd.misspelled_files().is_empty());
+
+        // Timeouts are scaled, whether they're given by a file or not.
+        let cmd = cmd.with_timeout_scale(2.5);
+        assert_eq!(cmd.timeout(), Ok(Some(Duration::from_millis(500))));
+        fs::remove_file(tmp_dir.path().join("foo.timeout")).unwrap();
+        let cmd = CommandSpec::new(&cmd_path)
+            .unwrap()
+            .with_timeout(Some(Duration::from_secs(60)))
+            .with_timeout_scale(2.5);
+        assert_eq!(cmd.timeout(), Ok(Some(Duration::from_secs(150))));
+        assert_eq!(parse_timeout_scale("3"), Ok(3.0));
+        assert!(parse_timeout_scale("0").is_err());
+        assert!(parse_timeout_scale("-1").is_err());
+        assert!(parse_timeout_scale("NaN").is_err());
+
+        fs::write(tmp_dir.path().join("foo.timeout"), "soon\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(
+            cmd.timeout(),
+            Err(Error::FileNotDuration {
+                path: tmp_dir.path().join("foo.timeout")
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_env() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(
+            &cmd_path,
+            "#!/bin/sh\necho \"$GREETING ${HOME:-no home}\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(tmp_dir.path().join("foo.env"), "GREETING=\"Hello world\"\n").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let home = env::var("HOME").unwrap_or("no home".to_string());
+        assert_eq!(
+            cmd.execute().unwrap().stdout(),
+            format!("Hello world {home}\n").as_bytes()
+        );
+        assert!(cmd.misspelled_files().is_empty());
+
+        fs::write(tmp_dir.path().join("foo.env"), "# clear-env\nGREETING=Hi\n").unwrap();
+        assert_eq!(cmd.execute().unwrap().stdout(), b"Hi no home\n");
+
+        fs::write(tmp_dir.path().join("foo.env"), "GREETING\n").unwrap();
+        assert!(cmd.execute().is_err());
+    }
+
+    #[test]
+    fn test_resolved_env() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\n").unwrap();
+        fs::write(tmp_dir.path().join("foo.env"), "GREETING=Hi\nTZ=UTC\n").unwrap();
+        let default_env = vec![
+            ("TZ".to_string(), "CET".to_string()),
+            ("LANG".to_string(), "C".to_string()),
+        ];
+
+        let cmd = CommandSpec::new(&cmd_path)
+            .unwrap()
+            .with_default_env(default_env.clone());
+        let vars = cmd.resolved_env().unwrap();
+        let var = |key: &str| vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(var("GREETING"), Some("Hi"));
+        assert_eq!(var("TZ"), Some("UTC"));
+        assert_eq!(var("LANG"), Some("C"));
+        assert_eq!(var("PATH"), env::var("PATH").ok().as_deref());
+
+        fs::write(tmp_dir.path().join("foo.env"), "# clear-env\nGREETING=Hi\n").unwrap();
+        let cmd = cmd.for_locale("fr_FR.UTF-8").unwrap();
+        assert_eq!(
+            cmd.resolved_env().unwrap(),
+            vec![
+                ("GREETING".to_string(), "Hi".to_string()),
+                ("LANG".to_string(), "C".to_string()),
+                ("LC_ALL".to_string(), "fr_FR.UTF-8".to_string()),
+                ("TZ".to_string(), "CET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_cwd() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = fs::canonicalize(tmp_dir.path()).unwrap();
+        fs::create_dir(dir.join("data")).unwrap();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\npwd\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(cmd.cwd(), Ok(dir.clone()));
+        let stdout = cmd.execute().unwrap().stdout().to_vec();
+        assert_eq!(stdout, format!("{}\n", dir.display()).as_bytes());
+
+        fs::write(dir.join("foo.cwd"), "data\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert!(cmd.has_cwd());
+        let stdout = cmd.execute().unwrap().stdout().to_vec();
+        assert_eq!(
+            stdout,
+            format!("{}\n", dir.join("data").display()).as_bytes()
+        );
+        assert!(cmd.misspelled_files().is_empty());
+
+        fs::write(dir.join("foo.cwd"), "missing\n").unwrap();
+        assert_eq!(
+            cmd.cwd(),
+            Err(Error::CwdInvalid {
+                path: dir.join("foo.cwd"),
+                cwd: dir.join("missing"),
+            })
+        );
+        assert!(cmd.execute().is_err());
+    }
+
+    #[test]
+    fn test_check_command_files() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = fs::canonicalize(tmp_dir.path()).unwrap();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\n").unwrap();
+        fs::write(dir.join("foo.timeout"), "2s\n").unwrap();
+        fs::write(dir.join("foo.env"), "GREETING=Hi\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(cmd.check_command_files(), Ok(()));
+
+        fs::write(dir.join("foo.timeout"), "soon\n").unwrap();
+        assert_eq!(
+            cmd.check_command_files(),
+            Err(Error::FileNotDuration {
+                path: dir.join("foo.timeout"),
+            })
+        );
+
+        fs::write(dir.join("foo.timeout"), "2s\n").unwrap();
+        fs::write(dir.join("foo.cwd"), "missing\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert!(matches!(
+            cmd.check_command_files(),
+            Err(Error::CwdInvalid { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let text = "--name\nJohn Doe\n\n\"  indented\"\n\"\"\n\"say \\\"hi\\\"\"\n";
+        assert_eq!(
+            parse_args(text),
+            Ok(vec![
+                "--name".to_string(),
+                "John Doe".to_string(),
+                "  indented".to_string(),
+                "".to_string(),
+                "say \"hi\"".to_string(),
+            ])
+        );
+        assert_eq!(
+            parse_args("a\n\"b\n"),
+            Err(("missing closing quote".to_string(), 2))
+        );
+        assert_eq!(
+            parse_args("\"b\" c\n"),
+            Err(("unexpected chars after closing quote".to_string(), 1))
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_args() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\nfor arg; do echo \"<$arg>\"; done\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(tmp_dir.path().join("foo.args"), "--name\nJohn Doe\n\"\"\n").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(
+            cmd.execute().unwrap().stdout(),
+            b"<--name>\n<John Doe>\n<>\n"
+        );
+        assert!(cmd.misspelled_files().is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_execute_program() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = fs::canonicalize(tmp_dir.path()).unwrap();
+        let base = dir.join("greet");
+        fs::write(dir.join("greet.stdin"), "Hello\n").unwrap();
+        fs::write(dir.join("greet.out"), "").unwrap();
+        // Arguments and working directory files of the base are not read.
+        fs::write(dir.join("greet.args"), "--unused\n").unwrap();
+
+        let args = vec!["-c".to_string(), "cat; pwd".to_string()];
+        let cmd = CommandSpec::with_program(Path::new("/bin/sh"), args, &dir, &base).unwrap();
+        assert!(cmd.has_stdout());
+        assert_eq!(
+            cmd.execute().unwrap().stdout(),
+            format!("Hello\n{}\n", dir.display()).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_shared_snapshot() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "").unwrap();
+        fs::create_dir(dir.join("shared")).unwrap();
+        fs::write(dir.join("shared/usage.out"), "Usage: foo\n").unwrap();
+        fs::write(dir.join("foo.out"), "@shared/usage.out\n").unwrap();
+        fs::write(dir.join("foo.err"), "@user\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(cmd.stdout().unwrap(), b"Usage: foo\n");
+        assert_eq!(
+            cmd.shared_snapshot("out").unwrap(),
+            Some(dir.join("shared/usage.out"))
+        );
+        // Only stdout and stderr snapshots can be shared.
+        assert_eq!(cmd.stderr().unwrap(), b"@user\n");
+        assert_eq!(cmd.shared_snapshot("err").unwrap(), None);
+
+        fs::write(dir.join("foo.out"), "@missing.out").unwrap();
+        assert!(matches!(cmd.stdout(), Err(Error::FileRead { .. })));
+    }
+
+    #[test]
+    fn test_includes() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "").unwrap();
+        fs::create_dir(dir.join("common")).unwrap();
+        fs::write(
+            dir.join("common/header.out"),
+            "tool v1.2\n<<<include:year.out>>>\n",
+        )
+        .unwrap();
+        fs::write(dir.join("common/year.out"), "(c) 2024\n").unwrap();
+        fs::write(
+            dir.join("foo.out"),
+            "<<<include:common/header.out>>>\nHello\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("foo.out.pattern"),
+            "<<<include:common/header.out>>>\n<<<\\w+>>>\n",
+        )
+        .unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(cmd.stdout().unwrap(), b"tool v1.2\n(c) 2024\nHello\n");
+        assert_eq!(
+            cmd.stdout_pat().unwrap(),
+            "tool v1.2\n(c) 2024\n<<<\\w+>>>\n"
+        );
+        assert!(cmd.has_includes("out"));
+
+        // Include cycles are stopped.
+        fs::write(dir.join("common/year.out"), "<<<include:header.out>>>\n").unwrap();
+        assert!(matches!(cmd.stdout(), Err(Error::IncludeInvalid { .. })));
+
+        fs::write(dir.join("foo.out"), "Hello\n<<<include:missing.out>>>\n").unwrap();
+        let err = cmd.stdout().unwrap_err();
+        assert_eq!(err.location(), (dir.join("foo.out"), Some(2)));
+        assert!(!CommandSpec::new(&cmd_path).unwrap().has_includes("err"));
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "").unwrap();
+        fs::write(dir.join("foo.out"), "Hello\n<<<include:big.out>>>\n").unwrap();
+        fs::write(dir.join("big.out"), "x".repeat(100)).unwrap();
+        fs::write(dir.join("foo.exit"), "0\n").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap().with_max_file_size(64);
+        assert_eq!(
+            cmd.stdout(),
+            Err(Error::FileTooLarge {
+                path: dir.join("big.out"),
+                size: 100,
+                max: 64,
+            })
+        );
+        assert!(cmd.exit_code().is_ok());
+        let cmd = cmd.with_max_file_size(1);
+        assert!(matches!(cmd.exit_code(), Err(Error::FileTooLarge { .. })));
+        assert!(CommandSpec::new(&cmd_path).unwrap().stdout().is_ok());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_not_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "#!/bin/sh\necho hello\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(
+            cmd.check_executable(),
+            Err(Error::ScriptNotExecutable {
+                path: cmd_path.clone()
+            })
+        );
+        let err = cmd.execute().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+        cmd.fix_permissions().unwrap();
+        let mode = fs::metadata(&cmd_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(cmd.execute().unwrap().stdout(), b"hello\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_missing_interpreter() {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(
+            shebang_interpreter(b"#! /usr/bin/python -u\nprint('hi')\n"),
+            Some("/usr/bin/python".to_string())
+        );
+        assert_eq!(shebang_interpreter(b"echo hi\n"), None);
+
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.py");
+        fs::write(&cmd_path, "#!/usr/bin/cliche-missing\nprint('hi')\n").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let err = cmd.execute().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Interpreter /usr/bin/cliche-missing of script {} is not found",
+                cmd.cmd_path().display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration(" 1m\n"), Ok(Duration::from_secs(60)));
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn test_take_prefixed_lines() {
+        let mut pending = b"Hello\nWorld\nHow ar".to_vec();
+        let lines = take_prefixed_lines(&mut pending, b"[a] ");
+        assert_eq!(lines, b"[a] Hello\n[a] World\n");
+        assert_eq!(pending, b"How ar");
+
+        let lines = take_prefixed_lines(&mut pending, b"[a] ");
+        assert!(lines.is_empty());
+        pending.extend_from_slice(b"e you?\n");
+        let lines = take_prefixed_lines(&mut pending, b"[a] ");
+        assert_eq!(lines, b"[a] How are you?\n");
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Project configuration, read from the `cliche.toml` file of the current directory or of its
+//! nearest ancestor.
+//!
+//! ```toml
+//! tests = ["tests"]
+//! timeout = "10s"
+//! timeout_scale = 3
+//! color = "never"
+//! normalize = ["crlf"]
+//! max_file_size = "16M"
+//! locales = ["C", "fr_FR.UTF-8"]
+//! snapshot_variants = ["ci"]
+//!
+//! [env]
+//! LANG = "C"
+//!
+//! [invariants]
+//! stderr = ["panicked at", "AddressSanitizer"]
+//! stdout = ["^DEBUG"]
+//! ```
+//!
+//! Values are defaults: the options given on the command line take precedence over them.
+use crate::chunk::Regex;
+use crate::cli::ColorChoice;
+use crate::command;
+use crate::command::Normalization;
+use crate::error::Error;
+use crate::job;
+use crate::suite;
+use crate::toml;
+use crate::toml::Value;
+use crate::verify::Invariant;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Name of the configuration file.
+pub const CONFIG_FILE_NAME: &str = "cliche.toml";
+
+/// Configuration of a project.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// Assertions on the outputs of every test of the suite.
+    pub invariants: Vec<Invariant>,
+    /// Locales tests are also run under, if they don't declare their own locales.
+    pub locales: Vec<String>,
+    /// Suffixes of the preferred variants of the expectation files, in order.
+    pub snapshot_variants: Vec<String>,
+    /// Tests run if no test is given on the command line, relative to the configuration file.
+    pub tests: Vec<PathBuf>,
+    /// Timeout of the tests without timeout file.
+    pub timeout: Option<Duration>,
+    /// Factor scaling the timeouts of the tests.
+    pub timeout_scale: Option<f64>,
+    /// When to use colors in messages.
+    pub color: Option<ColorChoice>,
+    /// Variables `(key, value)` set in the environment of every test, before the ones of its
+    /// environment file.
+    pub env: Vec<(String, String)>,
+    /// Normalizations of the snapshots whose front-matter doesn't declare any.
+    pub normalize: Vec<Normalization>,
+    /// Maximum size in bytes of the companion files of the tests.
+    pub max_file_size: Option<u64>,
+}
+
+/// Returns the path of the configuration file of the directory `cwd` or of its nearest ancestor,
+/// relative to `cwd`.
+pub fn find(cwd: &Path) -> Option<PathBuf> {
+    let depth = cwd
+        .ancestors()
+        .position(|dir| dir.join(CONFIG_FILE_NAME).is_file())?;
+    let mut path = PathBuf::new();
+    for _ in 0..depth {
+        path.push("..");
+    }
+    path.push(CONFIG_FILE_NAME);
+    Some(path)
+}
+
+/// Loads the configuration of the file at `path`. A missing file gives the default
+/// configuration.
+pub fn load(path: &Path) -> Result<Config, Error> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let text = command::read_text(path, command::DEFAULT_MAX_FILE_SIZE)?;
+    let mut config = parse(&text).map_err(|reason| Error::ConfigInvalid {
+        path: path.to_path_buf(),
+        reason,
+    })?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for test in &mut config.tests {
+        *test = dir.join(&*test);
+    }
+    Ok(config)
+}
+
+/// Parses the configuration `text`.
+fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (key, value) in toml::parse(text)? {
+        if key == "locales" {
+            let Value::Array(locales) = value else {
+                return Err(format!("expecting an array of locales for key <{key}>"));
+            };
+            config.locales = locales
+                .iter()
+                .map(|l| suite::parse_locale(l))
+                .collect::<Result<_, _>>()?;
+            continue;
+        }
+        if key == "snapshot_variants" {
+            let Value::Array(variants) = value else {
+                return Err(format!("expecting an array of suffixes for key <{key}>"));
+            };
+            config.snapshot_variants = variants;
+            continue;
+        }
+        if key == "tests" {
+            let Value::Array(tests) = value else {
+                return Err(format!("expecting an array of paths for key <{key}>"));
+            };
+            config.tests = tests.into_iter().map(PathBuf::from).collect();
+            continue;
+        }
+        if key == "timeout" {
+            let timeout = match value {
+                Value::String(s) => command::parse_duration(&s)?,
+                Value::Integer(n) if n >= 0 => Duration::from_secs(n as u64),
+                _ => return Err(format!("expecting a duration for key <{key}>")),
+            };
+            config.timeout = Some(timeout);
+            continue;
+        }
+        if key == "timeout_scale" {
+            let scale = match value {
+                Value::String(s) => command::parse_timeout_scale(&s)?,
+                Value::Integer(n) if n > 0 => n as f64,
+                _ => return Err(format!("expecting a positive factor for key <{key}>")),
+            };
+            config.timeout_scale = Some(scale);
+            continue;
+        }
+        if key == "color" {
+            let Value::String(color) = value else {
+                return Err(format!("expecting auto, always or never for key <{key}>"));
+            };
+            config.color = Some(color.parse()?);
+            continue;
+        }
+        if key == "normalize" {
+            let Value::Array(normalize) = value else {
+                return Err(format!(
+                    "expecting an array of normalizations for key <{key}>"
+                ));
+            };
+            config.normalize = normalize
+                .iter()
+                .map(|n| n.parse())
+                .collect::<Result<_, _>>()?;
+            continue;
+        }
+        if key == "max_file_size" {
+            let max = match value {
+                Value::String(s) => job::parse_bytes(&s)?,
+                Value::Integer(n) if n >= 0 => n as u64,
+                _ => return Err(format!("expecting a size for key <{key}>")),
+            };
+            config.max_file_size = Some(max);
+            continue;
+        }
+        if let Some(name) = key.strip_prefix("env.") {
+            let Value::String(var) = value else {
+                return Err(format!("expecting a string for key <{key}>"));
+            };
+            config.env.push((name.to_string(), var));
+            continue;
+        }
+        let stderr = match key.as_str() {
+            "invariants.stdout" => false,
+            "invariants.stderr" => true,
+            _ => return Err(format!("unknown key <{key}>")),
+        };
+        let Value::Array(patterns) = value else {
+            return Err(format!("expecting an array of regexes for key <{key}>"));
+        };
+        for pattern in patterns {
+            let pattern = Regex::new(&pattern)
+                .map_err(|_| format!("invalid regex <{pattern}> for key <{key}>"))?;
+            config.invariants.push(Invariant { stderr, pattern });
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+locales = ["C", "fr_FR.UTF-8"]
+snapshot_variants = ["ci", "linux"]
+
+[invariants]
+stderr = ["panicked at", "AddressSanitizer"]
+stdout = ["^DEBUG"]
+"#;
+        let config = parse(text).unwrap();
+        assert_eq!(config.locales, vec!["C", "fr_FR.UTF-8"]);
+        assert_eq!(config.snapshot_variants, vec!["ci", "linux"]);
+        assert_eq!(
+            config.invariants,
+            vec![
+                Invariant {
+                    stderr: true,
+                    pattern: Regex::new("panicked at").unwrap(),
+                },
+                Invariant {
+                    stderr: true,
+                    pattern: Regex::new("AddressSanitizer").unwrap(),
+                },
+                Invariant {
+                    stderr: false,
+                    pattern: Regex::new("^DEBUG").unwrap(),
+                },
+            ]
+        );
+
+        assert_eq!(parse(""), Ok(Config::default()));
+        assert!(parse("[invariants]\nstderr = \"panicked at\"").is_err());
+        assert!(parse("[invariants]\nstderr = [\"(\"]").is_err());
+        assert!(parse("jobs = 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let text = r#"
+tests = ["tests", "examples/cli"]
+timeout = "1.5s"
+timeout_scale = "2.5"
+color = "never"
+normalize = ["crlf", "ansi"]
+max_file_size = "1M"
+
+[env]
+LANG = "C"
+TZ = "UTC"
+"#;
+        let config = parse(text).unwrap();
+        assert_eq!(
+            config.tests,
+            vec![PathBuf::from("tests"), PathBuf::from("examples/cli")]
+        );
+        assert_eq!(config.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(config.timeout_scale, Some(2.5));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert_eq!(config.max_file_size, Some(1 << 20));
+        assert_eq!(
+            config.normalize,
+            vec![Normalization::Crlf, Normalization::Ansi]
+        );
+        assert_eq!(
+            config.env,
+            vec![
+                ("LANG".to_string(), "C".to_string()),
+                ("TZ".to_string(), "UTC".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            parse("timeout = 2").unwrap().timeout,
+            Some(Duration::from_secs(2))
+        );
+        assert!(parse("timeout = \"soon\"").is_err());
+        assert_eq!(parse("timeout_scale = 3").unwrap().timeout_scale, Some(3.0));
+        assert!(parse("timeout_scale = 0").is_err());
+        assert!(parse("color = \"sometimes\"").is_err());
+        assert!(parse("normalize = [\"digits\"]").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let sub_dir = dir.join("tests/auth");
+        fs::create_dir_all(&sub_dir).unwrap();
+        assert_eq!(find(&sub_dir), None);
+
+        fs::write(dir.join(CONFIG_FILE_NAME), "tests = [\"tests\"]\n").unwrap();
+        assert_eq!(find(dir), Some(PathBuf::from("cliche.toml")));
+        let path = find(&sub_dir).unwrap();
+        assert_eq!(path, PathBuf::from("../../cliche.toml"));
+
+        let config = load(&sub_dir.join(&path)).unwrap();
+        assert_eq!(config.tests, vec![sub_dir.join("../../tests")]);
+    }
+}
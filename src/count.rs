@@ -0,0 +1,118 @@
+//! Inclusive ranges of counts, used by expectations on a number of items (lines for instance).
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// An inclusive range of counts: an exact count `42`, a bounded range `10..20`, or a half-open
+/// range `10..` or `..20`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CountRange {
+    min: usize,
+    max: Option<usize>,
+}
+
+impl CountRange {
+    /// Creates a range of exactly `count` items.
+    pub fn exact(count: usize) -> CountRange {
+        CountRange {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    /// Returns `true` if `count` is in this range.
+    pub fn contains(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl FromStr for CountRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("<{s}> is not a valid count"))
+        };
+        let Some((min, max)) = s.split_once("..") else {
+            let count = parse(s)?;
+            return Ok(CountRange {
+                min: count,
+                max: Some(count),
+            });
+        };
+        let min = if min.trim().is_empty() {
+            0
+        } else {
+            parse(min)?
+        };
+        let max = if max.trim().is_empty() {
+            None
+        } else {
+            Some(parse(max)?)
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("<{s}> is an empty range"));
+        }
+        Ok(CountRange { min, max })
+    }
+}
+
+impl fmt::Display for CountRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{max}"),
+            Some(max) if self.min == 0 => write!(f, "..{max}"),
+            Some(max) => write!(f, "{}..{max}", self.min),
+            None => write!(f, "{}..", self.min),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!("42".parse(), Ok(CountRange::exact(42)));
+        assert_eq!(
+            " 10..20\n".parse(),
+            Ok(CountRange {
+                min: 10,
+                max: Some(20)
+            })
+        );
+        assert_eq!("10..".parse(), Ok(CountRange { min: 10, max: None }));
+        assert_eq!(
+            "..20".parse(),
+            Ok(CountRange {
+                min: 0,
+                max: Some(20)
+            })
+        );
+        assert!("abc".parse::<CountRange>().is_err());
+        assert!("20..10".parse::<CountRange>().is_err());
+        assert!("-1".parse::<CountRange>().is_err());
+    }
+
+    #[test]
+    fn test_contains() {
+        let range = "10..20".parse::<CountRange>().unwrap();
+        assert!(!range.contains(9));
+        assert!(range.contains(10));
+        assert!(range.contains(20));
+        assert!(!range.contains(21));
+        assert!(CountRange::exact(0).contains(0));
+        assert!("3..".parse::<CountRange>().unwrap().contains(1000));
+    }
+
+    #[test]
+    fn test_display_range() {
+        for s in ["42", "10..20", "10..", "..20"] {
+            assert_eq!(s.parse::<CountRange>().unwrap().to_string(), s);
+        }
+    }
+}
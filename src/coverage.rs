@@ -0,0 +1,159 @@
+//! Coverage of the streams asserted by tests.
+use crate::command::CommandSpec;
+use crate::text::{Style, StyledString};
+
+/// The assertions made by a test on each stream of its command.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// Stdout is checked against an exact snapshot.
+    pub stdout_exact: bool,
+    /// Stdout is checked against a pattern snapshot.
+    pub stdout_pattern: bool,
+    /// Stdout is partially checked (line count, matching line count, first or last line).
+    pub stdout_partial: bool,
+    /// Stderr is checked against an exact snapshot.
+    pub stderr: bool,
+    /// Stderr is checked against a pattern snapshot.
+    pub stderr_pattern: bool,
+    /// The exit code is explicitly checked (otherwise, a zero exit code is expected).
+    pub exit_code: bool,
+}
+
+impl Coverage {
+    /// Returns the coverage of a command `spec`.
+    pub fn new(spec: &CommandSpec) -> Self {
+        Coverage {
+            stdout_exact: spec.has_stdout(),
+            stdout_pattern: spec.has_stdout_pat(),
+            stdout_partial: spec.has_stdout_lines()
+                || spec.has_stdout_count()
+                || spec.has_stdout_first()
+                || spec.has_stdout_last(),
+            stderr: spec.has_stderr(),
+            stderr_pattern: spec.has_stderr_pat(),
+            exit_code: spec.has_exit_code(),
+        }
+    }
+
+    /// Returns each kind of assertion with a flag indicating if it's made.
+    fn items(&self) -> [(&'static str, bool); 6] {
+        [
+            ("stdout exact", self.stdout_exact),
+            ("stdout pattern", self.stdout_pattern),
+            ("stdout partial", self.stdout_partial),
+            ("stderr", self.stderr),
+            ("stderr pattern", self.stderr_pattern),
+            ("exit code", self.exit_code),
+        ]
+    }
+
+    /// Renders the list of assertions made and not made.
+    pub fn render(&self) -> StyledString {
+        let mut s = StyledString::new();
+        for (i, (kind, covered)) in self.items().iter().enumerate() {
+            if i > 0 {
+                s.push(", ");
+            }
+            if *covered {
+                s.push_with(kind, Style::new().green());
+            } else {
+                s.push_with(&format!("no {kind}"), Style::new().bright_black());
+            }
+        }
+        s
+    }
+}
+
+/// Coverage of a whole suite.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageSummary {
+    tests: usize,
+    covered: [usize; 6],
+}
+
+impl CoverageSummary {
+    pub fn new() -> Self {
+        CoverageSummary::default()
+    }
+
+    /// Adds the `coverage` of a test to this summary.
+    pub fn add(&mut self, coverage: &Coverage) {
+        self.tests += 1;
+        for (i, (_, covered)) in coverage.items().iter().enumerate() {
+            if *covered {
+                self.covered[i] += 1;
+            }
+        }
+    }
+
+    /// Renders the summary: for each kind of assertion, the number of tests making it.
+    pub fn render(&self) -> StyledString {
+        let mut s = StyledString::new();
+        s.push_with("Coverage", Style::new().bold());
+        s.push(&format!(" of {} tests:\n", self.tests));
+        let kinds = Coverage::default().items().map(|(kind, _)| kind);
+        for (kind, covered) in kinds.iter().zip(self.covered.iter()) {
+            let uncovered = self.tests - covered;
+            let percent = (uncovered * 100).checked_div(self.tests).unwrap_or(0);
+            s.push_with(&format!("  {kind:<15}"), Style::new().blue().bold());
+            s.push(&format!("{covered}/{} tests", self.tests));
+            if uncovered > 0 {
+                s.push_with(
+                    &format!(" ({percent}% of tests don't assert {kind})"),
+                    Style::new().yellow(),
+                );
+            }
+            s.push("\n");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Format;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = CoverageSummary::new();
+        summary.add(&Coverage {
+            stdout_exact: true,
+            exit_code: true,
+            ..Default::default()
+        });
+        summary.add(&Coverage {
+            stdout_pattern: true,
+            ..Default::default()
+        });
+        summary.add(&Coverage {
+            stdout_exact: true,
+            stderr: true,
+            stderr_pattern: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            summary.render().to_string(Format::Plain),
+            "Coverage of 3 tests:
+  stdout exact   2/3 tests (33% of tests don't assert stdout exact)
+  stdout pattern 1/3 tests (66% of tests don't assert stdout pattern)
+  stdout partial 0/3 tests (100% of tests don't assert stdout partial)
+  stderr         1/3 tests (66% of tests don't assert stderr)
+  stderr pattern 1/3 tests (66% of tests don't assert stderr pattern)
+  exit code      1/3 tests (66% of tests don't assert exit code)
+"
+        );
+    }
+
+    #[test]
+    fn test_render_coverage() {
+        let coverage = Coverage {
+            stdout_exact: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            coverage.render().to_string(Format::Plain),
+            "stdout exact, no stdout pattern, no stdout partial, no stderr, no stderr pattern, no exit code"
+        );
+    }
+}
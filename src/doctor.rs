@@ -0,0 +1,293 @@
+//! Checks of the hygiene of a test suite, without running its tests.
+//!
+//! `cliche doctor [--no-ignore] [--snapshot-root SCRIPTS_DIR=SNAPSHOTS_DIR]... [FILES]...`
+//! reports the problems of the tests of `FILES` (the current directory by default) that would
+//! make them fail or silently pass: scripts that are not executable or whose interpreter is
+//! missing, invalid or whole-line permissive patterns, snapshots with CRLF line endings,
+//! misspelled expectation files, snapshots of no test, and an invalid configuration.
+use crate::chunk::PatternLines;
+use crate::command::{CommandSpec, EXPECTATION_EXTENSIONS};
+use crate::config;
+use crate::error::Error;
+use crate::print_warning;
+use crate::suite::{self, SnapshotRoot};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Runs `cliche doctor`, given the arguments after `doctor`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut roots: Vec<SnapshotRoot> = vec![];
+    let mut files = vec![];
+    let mut ignore = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snapshot-root" => match args.next() {
+                Some(root) => roots.push(root.parse()?),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--no-ignore" => ignore = false,
+            file => files.push(PathBuf::from(file)),
+        }
+    }
+    if files.is_empty() {
+        files.push(PathBuf::from("."));
+    }
+    let cwd = env::current_dir().map_err(|err| err.to_string())?;
+    let problems = diagnose(&files, &roots, ignore, &cwd);
+    problems.iter().for_each(print_warning);
+    match problems.len() {
+        0 => {
+            eprintln!("No problem found");
+            Ok(())
+        }
+        1 => Err("1 problem found".to_string()),
+        n => Err(format!("{n} problems found")),
+    }
+}
+
+/// Returns the problems of the configuration of the directory `cwd` and of the tests of `files`.
+fn diagnose(files: &[PathBuf], roots: &[SnapshotRoot], ignore: bool, cwd: &Path) -> Vec<Error> {
+    let mut problems = vec![];
+    if let Some(path) = config::find(cwd)
+        && let Err(err) = config::load(&cwd.join(path))
+    {
+        problems.push(err);
+    }
+    let tests = match suite::collect(files, ignore) {
+        Ok(tests) => tests,
+        Err(err) => {
+            problems.push(err);
+            return problems;
+        }
+    };
+    let mut specs = vec![];
+    for test in &tests {
+        let snapshot_base = suite::snapshot_base(roots, &test.path);
+        match CommandSpec::with_snapshot_base(&test.path, &snapshot_base) {
+            Ok(spec) => specs.push(spec),
+            Err(err) => problems.push(Error::FileRead {
+                path: test.path.clone(),
+                cause: err.to_string(),
+            }),
+        }
+    }
+    for spec in &specs {
+        problems.extend(check_spec(spec));
+    }
+    problems.extend(orphaned_snapshots(&specs));
+    problems
+}
+
+/// Returns the problems of the command `spec` of a test.
+fn check_spec(spec: &CommandSpec) -> Vec<Error> {
+    let mut problems = vec![];
+    if !spec.cmd_path().exists() {
+        problems.push(Error::FileRead {
+            path: spec.cmd_path().to_path_buf(),
+            cause: "No such file or directory".to_string(),
+        });
+        return problems;
+    }
+    if let Err(err) = spec.check_executable() {
+        problems.push(err);
+    }
+    if let Err(err) = spec.check_interpreter() {
+        problems.push(err);
+    }
+    let cmd_path = spec.cmd_path().to_path_buf();
+    match spec.stdout_pat() {
+        Ok(pattern) => {
+            if let Some((reason, row)) = invalid_pattern(&pattern) {
+                problems.push(Error::StdoutPatternFileInvalid {
+                    cmd_path: cmd_path.clone(),
+                    reason,
+                    row,
+                });
+            }
+        }
+        Err(err) => problems.push(err),
+    }
+    match spec.stderr_pat() {
+        Ok(pattern) => {
+            if let Some((reason, row)) = invalid_pattern(&pattern) {
+                problems.push(Error::StderrPatternFileInvalid {
+                    cmd_path,
+                    reason,
+                    row,
+                });
+            }
+        }
+        Err(err) => problems.push(err),
+    }
+    // Only patterns making a whole line match anything are suspicious: an empty pattern in a
+    // line is often an optional part of it.
+    problems.extend(
+        spec.empty_patterns()
+            .into_iter()
+            .filter(|(_, p)| p.whole_line)
+            .map(|(path, p)| Error::PatternMatchesEmpty {
+                path,
+                pattern: p.pattern,
+                row: p.row,
+            }),
+    );
+    for ext in ["out", "err"] {
+        let path = spec.snapshot_path(ext);
+        if let Ok(snapshot) = fs::read(&path)
+            && let Some(row) = crlf_row(&snapshot)
+        {
+            problems.push(Error::SnapshotCrlf { path, row });
+        }
+    }
+    problems.extend(
+        spec.misspelled_files()
+            .into_iter()
+            .map(|(path, expected)| Error::FileMisspelled { path, expected }),
+    );
+    problems
+}
+
+/// Returns the reason and the 1-based line index of the first invalid line of a `pattern` text,
+/// if any.
+fn invalid_pattern(pattern: &str) -> Option<(String, usize)> {
+    let lines = PatternLines::new(pattern);
+    let header_rows = lines.header_rows();
+    lines
+        .enumerate()
+        .find_map(|(i, line)| line.err().map(|reason| (reason, i + 1 + header_rows)))
+}
+
+/// Returns the 1-based line index of the first line of a `snapshot` ending with CRLF, if any.
+fn crlf_row(snapshot: &[u8]) -> Option<usize> {
+    snapshot
+        .split_inclusive(|b| *b == b'\n')
+        .position(|line| line.ends_with(b"\r\n"))
+        .map(|i| i + 1)
+}
+
+/// Returns the expectation files of the snapshot directories of the tests that belong to no test:
+/// their name doesn't start with the name of a test script, and no test references them as a
+/// shared snapshot.
+fn orphaned_snapshots(specs: &[CommandSpec]) -> Vec<Error> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut shared = BTreeSet::new();
+    for spec in specs {
+        for ext in ["out", "err"] {
+            if let Ok(Some(path)) = spec.shared_snapshot(ext) {
+                shared.insert(canonical(&path));
+            }
+        }
+    }
+    // Snapshot directories with the stems of the scripts of their tests.
+    let mut dirs: Vec<(PathBuf, PathBuf, Vec<String>)> = vec![];
+    for spec in specs {
+        let base = spec.snapshot_path("");
+        let dir = match base.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let stem = spec
+            .cmd_path()
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let key = canonical(&dir);
+        match dirs.iter_mut().find(|(k, _, _)| *k == key) {
+            Some((_, _, stems)) => stems.push(stem),
+            None => dirs.push((key, dir, vec![stem])),
+        }
+    }
+    let mut orphans = vec![];
+    for (_, dir, stems) in &dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_expectation = EXPECTATION_EXTENSIONS.iter().any(|ext| {
+                name.strip_suffix(ext)
+                    .and_then(|stem| stem.strip_suffix('.'))
+                    .is_some_and(|stem| !stem.is_empty())
+            });
+            let path = dir.join(&name);
+            if is_expectation
+                && !stems
+                    .iter()
+                    .any(|stem| name.starts_with(&format!("{stem}.")))
+                && !shared.contains(&canonical(&path))
+            {
+                orphans.push(path);
+            }
+        }
+    }
+    orphans.sort();
+    orphans
+        .into_iter()
+        .map(|path| Error::SnapshotOrphaned { path })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    fn write_script(path: &Path, text: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, text).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_diagnose() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        write_script(&dir.join("a.sh"), "#!/bin/sh\necho Hello\n");
+        fs::write(dir.join("a.out"), "Hello\r\n").unwrap();
+        write_script(&dir.join("b.sh"), "#!/nonexistent/sh\necho Hello\n");
+        fs::write(dir.join("b.out.pattern"), "<<<%{NONE}>>>\n<<<.*>>>\n").unwrap();
+        fs::write(dir.join("b.err"), "@usage.err\n").unwrap();
+        fs::write(dir.join("usage.err"), "Usage: b\n").unwrap();
+        fs::write(dir.join("c.sh"), "#!/bin/sh\necho Hello\n").unwrap();
+        fs::write(dir.join("c.ouy"), "Hello\n").unwrap();
+        fs::write(dir.join("d.out"), "Hello\n").unwrap();
+        fs::write(dir.join("cliche.toml"), "jobs = \"many\"\n").unwrap();
+
+        let problems = diagnose(&[dir.to_path_buf()], &[], true, dir)
+            .iter()
+            .map(Error::code)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            problems,
+            vec![
+                "CONFIG_INVALID",
+                "SNAPSHOT_CRLF",
+                "INTERPRETER_NOT_FOUND",
+                "STDOUT_PATTERN_FILE_INVALID",
+                "PATTERN_MATCHES_EMPTY",
+                "SCRIPT_NOT_EXECUTABLE",
+                "FILE_MISSPELLED",
+                "SNAPSHOT_ORPHANED",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert_eq!(invalid_pattern("Hello\n<<<.*>>>\n"), None);
+        assert!(matches!(invalid_pattern("Hello\n<<<(>>>\n"), Some((_, 2))));
+    }
+
+    #[test]
+    fn test_crlf_row() {
+        assert_eq!(crlf_row(b"a\nb\n"), None);
+        assert_eq!(crlf_row(b"a\nb\r\nc\r\n"), Some(2));
+        assert_eq!(crlf_row(b"a\r"), None);
+    }
+}
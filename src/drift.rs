@@ -0,0 +1,325 @@
+//! Drift of the snapshots of a suite since a git revision, to review commits updating many
+//! snapshots.
+//!
+//! `cliche drift --against <REV> [--no-ignore] [--snapshot-root SCRIPTS_DIR=SNAPSHOTS_DIR]...
+//! FILES...`
+//! runs each test once and verifies its outputs against both its current snapshots and the
+//! snapshots of the revision `REV`, read with `git show`. A test whose outputs match both versions
+//! only changed its expectations (a snapshot turned into a pattern, for instance), while a test
+//! whose outputs only match the current snapshots changed its behavior.
+//!
+//! The snapshots of the revision are written to a temporary directory mirroring the repository,
+//! so that they are verified like any snapshot. Shared snapshots they reference are read from the
+//! revision too; included files are not.
+use crate::suite::{self, SnapshotRoot, Test};
+use cliche::command::{self, CommandSpec, EXPECTATION_EXTENSIONS, PENDING_SUFFIX};
+use cliche::verify::{self, VerifyOptions};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Command};
+
+/// Drift of a test between a revision and the working tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Drift {
+    /// The snapshots are the same in the revision and the working tree.
+    Unchanged,
+    /// The test has no snapshots in the revision.
+    New,
+    /// The outputs match the current snapshots only.
+    Behavior,
+    /// The outputs match both the current snapshots and the snapshots of the revision.
+    Expectation,
+    /// The outputs don't match the current snapshots.
+    Failing,
+}
+
+impl Drift {
+    fn name(self) -> &'static str {
+        match self {
+            Drift::Unchanged => "unchanged",
+            Drift::New => "new",
+            Drift::Behavior => "behavior",
+            Drift::Expectation => "expectation",
+            Drift::Failing => "failing",
+        }
+    }
+}
+
+/// Runs `cliche drift`, given the arguments after `drift`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut rev = None;
+    let mut roots: Vec<SnapshotRoot> = vec![];
+    let mut files = vec![];
+    let mut ignore = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--against" => match args.next() {
+                Some(value) => rev = Some(value.clone()),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--snapshot-root" => match args.next() {
+                Some(root) => roots.push(root.parse()?),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--no-ignore" => ignore = false,
+            file => files.push(PathBuf::from(file)),
+        }
+    }
+    let Some(rev) = rev else {
+        return Err("option '--against' is required".to_string());
+    };
+    if files.is_empty() {
+        return Err("no tests to compare".to_string());
+    }
+    let tests = suite::collect(&files, ignore).map_err(|err| err.message())?;
+    let old_dir = env::temp_dir().join(format!("cliche-drift-{}", process::id()));
+    let drifts = compare(&tests, &roots, &rev, &old_dir);
+    let _ = fs::remove_dir_all(&old_dir);
+    let drifts = drifts?;
+
+    let width = drifts
+        .iter()
+        .map(|(_, d)| d.name().len())
+        .max()
+        .unwrap_or(0);
+    for (test, drift) in &drifts {
+        println!("{:width$}  {}", drift.name(), test.display_name());
+    }
+    let count = |drift| drifts.iter().filter(|(_, d)| *d == drift).count();
+    println!(
+        "Drift against {rev}: {} changed behavior, {} changed expectations only, {} unchanged, \
+         {} new, {} failing",
+        count(Drift::Behavior),
+        count(Drift::Expectation),
+        count(Drift::Unchanged),
+        count(Drift::New),
+        count(Drift::Failing),
+    );
+    Ok(())
+}
+
+/// Returns the drift of each of the `tests` since the revision `rev`, whose snapshots are written
+/// under `old_dir`.
+fn compare(
+    tests: &[Test],
+    roots: &[SnapshotRoot],
+    rev: &str,
+    old_dir: &Path,
+) -> Result<Vec<(Test, Drift)>, String> {
+    let mut drifts = vec![];
+    for test in tests {
+        let snapshot_base = suite::snapshot_base(roots, &test.path);
+        let dir = match snapshot_base.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = snapshot_base.file_name().unwrap_or_default();
+        let stem = Path::new(name).file_stem().unwrap_or_default();
+        let prefix = format!("{}.", stem.to_string_lossy());
+
+        let repo_dir = git(&dir, &["rev-parse", "--show-prefix"])
+            .map(|prefix| String::from_utf8_lossy(&prefix).trim().to_string())?;
+        git(
+            &dir,
+            &["rev-parse", "--verify", &format!("{rev}^{{commit}}")],
+        )
+        .map_err(|_| format!("unknown revision <{rev}>"))?;
+        let mut old = vec![];
+        for path in git_files(&dir, rev)? {
+            let file_name = path.rsplit('/').next().unwrap_or(&path).to_string();
+            if !file_name.starts_with(&prefix) {
+                continue;
+            }
+            let content = git(&dir, &["show", &format!("{rev}:{path}")])?;
+            let old_path = old_dir.join(&path);
+            write(&old_path, &content)?;
+            // A shared snapshot is resolved next to the snapshot in the revision.
+            if let Some(shared) = command::shared_snapshot_path(Path::new(&path), &content) {
+                let shared = normalize(&shared);
+                if let Ok(content) = git(&dir, &["show", &format!("{rev}:{}", shared.display())]) {
+                    write(&old_dir.join(&shared), &content)?;
+                }
+            }
+            if is_expectation(&file_name[prefix.len()..]) {
+                old.push((file_name, content));
+            }
+        }
+
+        let drift = if old.is_empty() {
+            Drift::New
+        } else if current_expectations(&dir, &prefix) == sorted(old) {
+            Drift::Unchanged
+        } else {
+            let read_error = |err| format!("cannot read test {}: {err}", test.path.display());
+            let spec =
+                CommandSpec::with_snapshot_base(&test.path, &snapshot_base).map_err(read_error)?;
+            let old_base = old_dir.join(&repo_dir).join(name);
+            let old_spec =
+                CommandSpec::with_snapshot_base(&test.path, &old_base).map_err(read_error)?;
+            let result = spec
+                .execute()
+                .map_err(|err| format!("cannot run test {}: {err}", test.path.display()))?;
+            let passes = |spec: &CommandSpec| match &test.formatter {
+                Some(f) => verify::format_stdout(spec, f, result.stdout()).is_ok_and(|stdout| {
+                    let formatted = result.clone().with_stdout(stdout);
+                    verify::check_result(spec, &formatted, &VerifyOptions::default()).is_ok()
+                }),
+                None => verify::check_result(spec, &result, &VerifyOptions::default()).is_ok(),
+            };
+            match (passes(&spec), passes(&old_spec)) {
+                (false, _) => Drift::Failing,
+                (true, false) => Drift::Behavior,
+                (true, true) => Drift::Expectation,
+            }
+        };
+        drifts.push((test.clone(), drift));
+    }
+    Ok(drifts)
+}
+
+/// Returns `true` if a file named after a test, followed by `ext`, is an expectation file,
+/// possibly localized (`fr.out`) or an alternative (`out.alt1`).
+fn is_expectation(ext: &str) -> bool {
+    if ext.ends_with(&format!(".{PENDING_SUFFIX}")) {
+        return false;
+    }
+    EXPECTATION_EXTENSIONS.iter().any(|known| {
+        ext == *known
+            || ext.ends_with(&format!(".{known}"))
+            || ext.starts_with(&format!("{known}."))
+    })
+}
+
+/// Returns the name and content of the expectation files of `dir` starting with `prefix`.
+fn current_expectations(dir: &Path, prefix: &str) -> Vec<(String, Vec<u8>)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let files = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let ext = name.strip_prefix(prefix)?;
+            if !is_expectation(ext) {
+                return None;
+            }
+            Some((name.clone(), fs::read(entry.path()).ok()?))
+        })
+        .collect();
+    sorted(files)
+}
+
+fn sorted(mut files: Vec<(String, Vec<u8>)>) -> Vec<(String, Vec<u8>)> {
+    files.sort();
+    files
+}
+
+/// Returns the paths, relative to the repository, of the files of `dir` in the revision `rev`.
+fn git_files(dir: &Path, rev: &str) -> Result<Vec<String>, String> {
+    let output = git(dir, &["ls-tree", "--full-name", "--name-only", rev, "./"])?;
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Runs git with `args` in `dir` and returns its stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| format!("cannot run git: {err}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args[0], stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Removes the `.` and `..` components of a relative `path`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+fn write(path: &Path, content: &[u8]) -> Result<(), String> {
+    let error = |err: std::io::Error| format!("cannot write {}: {err}", path.display());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(error)?;
+    }
+    fs::write(path, content).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git_in(dir: &Path, args: &[&str]) {
+        let mut cmd = vec!["-c", "user.name=test", "-c", "user.email=test@example.com"];
+        cmd.extend(args);
+        git(dir, &cmd).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn write_script(path: &Path, text: &str) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::write(path, format!("#!/bin/sh\n{text}")).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_compare() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        write_script(&dir.join("a.sh"), "echo Hello\n");
+        fs::write(dir.join("a.out"), "Hello\n").unwrap();
+        write_script(&dir.join("b.sh"), "echo 'Hello 42'\n");
+        fs::write(dir.join("b.out"), "Hello 42\n").unwrap();
+        write_script(&dir.join("c.sh"), "echo Bye\n");
+        fs::write(dir.join("c.out"), "Bye\n").unwrap();
+        git_in(dir, &["init", "-q"]);
+        git_in(dir, &["add", "."]);
+        git_in(dir, &["commit", "-q", "-m", "Add tests"]);
+
+        // `a` changes behavior, `b` only relaxes its expectation, `c` is unchanged, `d` is new.
+        write_script(&dir.join("a.sh"), "echo Hi\n");
+        fs::write(dir.join("a.out"), "Hi\n").unwrap();
+        fs::remove_file(dir.join("b.out")).unwrap();
+        fs::write(dir.join("b.out.pattern"), "Hello <<<%{NUMBER}>>>\n").unwrap();
+        write_script(&dir.join("d.sh"), "echo New\n");
+        fs::write(dir.join("d.out"), "New\n").unwrap();
+
+        let tests = ["a.sh", "b.sh", "c.sh", "d.sh"]
+            .iter()
+            .map(|name| Test::new(&dir.join(name)))
+            .collect::<Vec<_>>();
+        let old_dir = TempDir::new().unwrap();
+        let drifts = compare(&tests, &[], "HEAD", old_dir.path()).unwrap();
+        let drifts = drifts.into_iter().map(|(_, d)| d).collect::<Vec<_>>();
+        assert_eq!(
+            drifts,
+            vec![
+                Drift::Behavior,
+                Drift::Expectation,
+                Drift::Unchanged,
+                Drift::New
+            ]
+        );
+
+        assert!(compare(&tests, &[], "unknown", old_dir.path()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Detection of identical snapshots across tests, that could reference a shared snapshot instead
+//! (a snapshot made of a single `@usage.out` line).
+use crate::command::{CommandSpec, shared_snapshot_path};
+use crate::error::Error;
+use std::path::PathBuf;
+
+/// Minimum number of identical snapshots suggested to be shared.
+const MIN_DUPLICATES: usize = 3;
+
+/// Minimum number of lines of a snapshot suggested to be shared: sharing a one-line snapshot like
+/// `ok` is not worth it.
+const MIN_LINES: usize = 3;
+
+/// The stdout and stderr snapshots of the tests of a suite.
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    snapshots: Vec<(PathBuf, Vec<u8>)>,
+}
+
+impl Duplicates {
+    pub fn new() -> Self {
+        Duplicates::default()
+    }
+
+    /// Adds the stdout and stderr snapshots of a command `spec`, unless they already reference a
+    /// shared snapshot. Unreadable snapshots are reported by the verification of the test.
+    pub fn add(&mut self, spec: &CommandSpec) {
+        for ext in ["out", "err"] {
+            if !spec.has_expectation(ext) {
+                continue;
+            }
+            let path = spec.snapshot_path(ext);
+            let Ok(snapshot) = spec.read_expectation(ext) else {
+                continue;
+            };
+            if shared_snapshot_path(&path, &snapshot).is_some() {
+                continue;
+            }
+            self.snapshots.push((path, snapshot));
+        }
+    }
+
+    /// Returns a warning for each group of identical snapshots worth sharing.
+    pub fn warnings(&self) -> Vec<Error> {
+        let mut groups: Vec<(&[u8], Vec<PathBuf>)> = vec![];
+        for (path, snapshot) in &self.snapshots {
+            if snapshot.split_inclusive(|b| *b == b'\n').count() < MIN_LINES {
+                continue;
+            }
+            match groups.iter_mut().find(|(s, _)| s == snapshot) {
+                Some((_, paths)) => paths.push(path.clone()),
+                None => groups.push((snapshot, vec![path.clone()])),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|(_, paths)| paths.len() >= MIN_DUPLICATES)
+            .map(|(_, paths)| Error::SnapshotDuplicated { paths })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_warnings() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let usage = "Usage: tool [OPTIONS]\n\nOptions:\n  -h  Print help\n";
+        let mut duplicates = Duplicates::new();
+        for (name, stdout) in [
+            ("a", usage),
+            ("b", usage),
+            ("c", "@usage.out\n"),
+            ("d", usage),
+            ("e", "ok\n"),
+            ("f", "ok\n"),
+            ("g", "ok\n"),
+        ] {
+            let cmd_path = dir.join(format!("{name}.sh"));
+            fs::write(&cmd_path, "").unwrap();
+            fs::write(dir.join(format!("{name}.out")), stdout).unwrap();
+            duplicates.add(&CommandSpec::new(&cmd_path).unwrap());
+        }
+        assert_eq!(
+            duplicates.warnings(),
+            vec![Error::SnapshotDuplicated {
+                paths: vec![dir.join("a.out"), dir.join("b.out"), dir.join("d.out")],
+            }]
+        );
+    }
+}
@@ -1,6 +1,12 @@
-use crate::command::ExitCode;
+use crate::chunk::SegmentMismatch;
+use crate::chunk::line::CHUNK_SIZE;
+use crate::command::{ExitCode, ExpectedExitCode, STATE_FILE_NAME, Termination};
+use crate::count::CountRange;
+use crate::job::format_bytes;
+use crate::screen::ScreenSize;
 use crate::text::{Format, Style, StyledString};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
@@ -8,13 +14,129 @@ pub enum Error {
     FileRead { path: PathBuf, cause: String },
     /// The file is not a valid UTF-8 string.
     FileNotUtf8 { path: PathBuf },
-    /// The file can't be read as an integer (used for expected exit code).
+    /// The file is larger than the maximum size of the files read by cliche.
+    FileTooLarge { path: PathBuf, size: u64, max: u64 },
+    /// The file can't be read as an integer or an exit code name (used for expected exit code).
     FileNotInteger { path: PathBuf },
+    /// The file can't be read as a duration (used for timeouts).
+    FileNotDuration { path: PathBuf },
+    /// The file can't be read as a count or a range of counts (used for expected line count).
+    FileNotCount { path: PathBuf },
+    /// The terminal sizes file doesn't contain a list of `COLSxROWS` sizes.
+    FileNotScreenSizes { path: PathBuf },
+    /// A companion file of a command looks like a misspelled expectation file.
+    FileMisspelled { path: PathBuf, expected: PathBuf },
+    /// Identical stdout or stderr snapshots of several tests, that could share a snapshot.
+    SnapshotDuplicated { paths: Vec<PathBuf> },
+    /// A line of a stdout or stderr snapshot ends with CRLF.
+    SnapshotCrlf { path: PathBuf, row: usize },
+    /// An expectation file belongs to no test and is not a shared snapshot.
+    SnapshotOrphaned { path: PathBuf },
+    /// The configuration file is not valid.
+    ConfigInvalid { path: PathBuf, reason: String },
+    /// The file is not a valid JSON document.
+    FileNotJson { path: PathBuf, reason: String },
+    /// A pattern of the file can match an empty string, and probably more than intended.
+    PatternMatchesEmpty {
+        path: PathBuf,
+        pattern: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// A pattern file doesn't match the exact snapshot of the same stream: the test can't pass.
+    PatternConflict {
+        path: PathBuf,
+        snapshot: PathBuf,
+        /// 1-based line index in the pattern file.
+        row: usize,
+    },
+    /// Processes spawned by a command were still running after it has exited.
+    LeftoverProcesses {
+        cmd_path: PathBuf,
+        /// Commands of the leftover processes.
+        commands: Vec<String>,
+    },
+    /// A command has been killed as it has exceeded its timeout.
+    Timeout {
+        cmd_path: PathBuf,
+        timeout: Duration,
+        /// Stdout written before the command has been killed.
+        stdout: Vec<u8>,
+        /// Stderr written before the command has been killed.
+        stderr: Vec<u8>,
+    },
+    /// A command has left more data in its work directory than its disk budget.
+    CheckDiskUsage {
+        cmd_path: PathBuf,
+        /// Disk budget of the command, in bytes.
+        max: u64,
+        /// Number of bytes left in the work directory.
+        actual: u64,
+    },
+    /// A manifest file is not valid.
+    ManifestInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// A directive of a test script is not valid.
+    DirectiveInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// The interpreter given by the shebang of a script doesn't exist.
+    InterpreterNotFound { path: PathBuf, interpreter: String },
+    /// The script of a test has no executable bit.
+    ScriptNotExecutable { path: PathBuf },
+    /// A line of an arguments file is not valid.
+    ArgsInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// The working directory given by a cwd file is not a directory.
+    CwdInvalid {
+        path: PathBuf,
+        /// Working directory, relative to the directory of the script.
+        cwd: PathBuf,
+    },
+    /// A line of an environment file is not valid.
+    EnvInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// The front-matter of a snapshot is not valid.
+    FrontMatterInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// An include directive of an expectation file can't be expanded.
+    IncludeInvalid {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// A line of the file is not a valid regex.
+    FileInvalidRegex {
+        path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
     /// The expected exit code and the actual exit code are not equals.
     CheckExitCode {
         cmd_path: PathBuf,
-        expected: ExitCode,
-        actual: ExitCode,
+        expected: ExpectedExitCode,
+        actual: Termination,
         stderr: Vec<u8>,
     },
     /// A line in actual stdout doesn't equal the expected stdout line.
@@ -24,6 +146,8 @@ pub enum Error {
         actual: Option<String>,
         /// 1-based line index.
         row: usize,
+        /// 0-based index of the 64-char chunk of the line.
+        chunk: usize,
     },
     /// A line in actual stdout doesn't match the expected stdout pattern.
     CheckStdoutPattern {
@@ -32,6 +156,87 @@ pub enum Error {
         actual: Option<String>,
         /// 1-based line index.
         row: usize,
+        /// The first mismatching segment of a composite pattern line.
+        mismatch: Option<Box<SegmentMismatch>>,
+        /// The last actual line matched before the mismatch, if any.
+        matched: Option<String>,
+    },
+    /// The number of lines in actual stdout is not in the expected range.
+    CheckStdoutLineCount {
+        cmd_path: PathBuf,
+        expected: CountRange,
+        actual: usize,
+    },
+    /// The number of lines of an actual output matching a pattern is not in the expected range.
+    CheckMatchCount {
+        cmd_path: PathBuf,
+        /// `true` if lines are counted in stderr, `false` in stdout.
+        stderr: bool,
+        pattern: String,
+        expected: CountRange,
+        actual: usize,
+        /// 1-based line index of the count in its expectation file.
+        row: usize,
+    },
+    /// A line of an actual output matches a pattern forbidden by a suite invariant.
+    CheckInvariant {
+        cmd_path: PathBuf,
+        /// `true` if the line is in stderr, `false` in stdout.
+        stderr: bool,
+        pattern: String,
+        actual: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// A verification has failed for the command run under a locale.
+    CheckLocale {
+        cmd_path: PathBuf,
+        locale: String,
+        cause: Box<Error>,
+    },
+    /// An output matches neither its snapshot nor any of the alternatives of its snapshot.
+    CheckAlternative {
+        cmd_path: PathBuf,
+        /// Extension of the closest snapshot, `out.alt2` for instance.
+        ext: String,
+        /// Number of alternatives of the snapshot.
+        alternatives: usize,
+        /// Error of the closest snapshot.
+        cause: Box<Error>,
+    },
+    /// The actual state written by the command doesn't match the expected state.
+    CheckState {
+        cmd_path: PathBuf,
+        /// Path of the difference in the JSON state, `$.items[2]` for instance.
+        pointer: String,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    /// An actual output doesn't match the JSON value of an expectation file.
+    CheckOutputJson {
+        cmd_path: PathBuf,
+        /// Extension of the expectation file, `out.json` for instance.
+        ext: String,
+        /// Path of the difference in the JSON value, `$.items[2]` for instance.
+        pointer: String,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    /// The SHA-256 digest of an actual output doesn't match the digest of an expectation file.
+    CheckChecksum {
+        cmd_path: PathBuf,
+        /// Extension of the expectation file, `out.sha256` for instance.
+        ext: String,
+        expected: String,
+        actual: String,
+    },
+    /// A line in actual stdout matches a pattern that must only appear on stderr.
+    CheckStdoutStderrOnly {
+        cmd_path: PathBuf,
+        pattern: String,
+        actual: String,
+        /// 1-based line index.
+        row: usize,
     },
     /// A pattern stdout file is not valid
     StdoutPatternFileInvalid {
@@ -40,6 +245,52 @@ pub enum Error {
         /// 1-based line index.
         row: usize,
     },
+    /// A line in actual stderr doesn't match the expected stderr pattern.
+    CheckStderrPattern {
+        cmd_path: PathBuf,
+        expected: Option<String>,
+        actual: Option<String>,
+        /// 1-based line index.
+        row: usize,
+        /// The first mismatching segment of a composite pattern line.
+        mismatch: Option<Box<SegmentMismatch>>,
+        /// The last actual line matched before the mismatch, if any.
+        matched: Option<String>,
+    },
+    /// A pattern stderr file is not valid
+    StderrPatternFileInvalid {
+        cmd_path: PathBuf,
+        reason: String,
+        /// 1-based line index.
+        row: usize,
+    },
+    /// The post-hook script of a command has failed.
+    CheckPostHook {
+        cmd_path: PathBuf,
+        hook_path: PathBuf,
+        exit_code: ExitCode,
+        stderr: Vec<u8>,
+    },
+    /// The formatter of a command stdout has failed.
+    CheckFormatter {
+        cmd_path: PathBuf,
+        /// Shell command line of the formatter.
+        formatter: String,
+        exit_code: ExitCode,
+        stderr: Vec<u8>,
+    },
+    /// A line of the terminal screen rendered from stdout doesn't equal the expected line.
+    CheckScreenLine {
+        cmd_path: PathBuf,
+        /// Size of the terminal, if not the default one.
+        size: Option<ScreenSize>,
+        expected: Option<String>,
+        actual: Option<String>,
+        /// 1-based line index.
+        row: usize,
+        /// 0-based index of the 64-char chunk of the line.
+        chunk: usize,
+    },
     /// A line in actual stderr doesn't equal the expected stderr line.
     CheckStderrLine {
         cmd_path: PathBuf,
@@ -47,27 +298,137 @@ pub enum Error {
         actual: Option<String>,
         /// 1-based line index.
         row: usize,
+        /// 0-based index of the 64-char chunk of the line.
+        chunk: usize,
+    },
+    /// Actual stdout doesn't equal the expected stdout, compared byte to byte as one of them is
+    /// not valid UTF-8.
+    CheckStdoutBytes {
+        cmd_path: PathBuf,
+        /// 0-based offset of the first differing byte.
+        offset: usize,
+        /// 0-based offset of the first byte of the `expected` and `actual` windows.
+        start: usize,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// Actual stderr doesn't equal the expected stderr, compared byte to byte as one of them is
+    /// not valid UTF-8.
+    CheckStderrBytes {
+        cmd_path: PathBuf,
+        /// 0-based offset of the first differing byte.
+        offset: usize,
+        /// 0-based offset of the first byte of the `expected` and `actual` windows.
+        start: usize,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
     },
 }
 
 impl Error {
-    pub fn render(&self) -> String {
-        match self {
+    /// Renders this error for the console, given a `format`.
+    pub fn render(&self, format: Format) -> String {
+        let rendered = match self {
             Error::FileRead { .. } => "--> error FileRead".to_string(),
             Error::FileNotUtf8 { .. } => "--> error FileNotUtf8".to_string(),
             Error::FileNotInteger { .. } => "--> error FileNotInteger".to_string(),
+            Error::FileNotCount { .. } => "--> error FileNotCount".to_string(),
+            Error::FileNotDuration { .. } => format!("--> error: {}", self.message()),
+            Error::FileTooLarge { .. } => format!("--> error: {}", self.message()),
+            Error::CwdInvalid { .. } => format!("--> error: {}", self.message()),
+            Error::Timeout {
+                cmd_path,
+                timeout,
+                stdout,
+                stderr,
+            } => diff_timeout(&self.message(), cmd_path, *timeout, stdout, stderr, format),
+            Error::FileNotScreenSizes { .. } => format!("--> error: {}", self.message()),
+            Error::FileMisspelled { .. } => format!("--> error: {}", self.message()),
+            Error::SnapshotDuplicated { .. } => format!("--> error: {}", self.message()),
+            Error::SnapshotCrlf { .. } => format!("--> error: {}", self.message()),
+            Error::SnapshotOrphaned { .. } => format!("--> error: {}", self.message()),
+            Error::ScriptNotExecutable { .. } => format!("--> error: {}", self.message()),
+            Error::InterpreterNotFound { .. } => format!("--> error: {}", self.message()),
+            Error::PatternMatchesEmpty { .. } => format!("--> error: {}", self.message()),
+            Error::PatternConflict { .. } => format!("--> error: {}", self.message()),
+            Error::LeftoverProcesses { .. } => format!("--> error: {}", self.message()),
+            Error::CheckDiskUsage {
+                cmd_path,
+                max,
+                actual,
+            } => diff_value(
+                &self.message(),
+                "  script  :",
+                cmd_path,
+                "  max disk:",
+                &format_bytes(*max),
+                "  actual  :",
+                &format_bytes(*actual),
+                format,
+            ),
+            Error::FileNotJson { path, reason } => {
+                format!("--> error: invalid JSON in {}: {reason}", path.display())
+            }
+            Error::ConfigInvalid { path, reason } => {
+                format!(
+                    "--> error: invalid configuration {}: {reason}",
+                    path.display()
+                )
+            }
+            Error::ManifestInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid manifest {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::DirectiveInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid directive in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::FileInvalidRegex { path, reason, row } => {
+                format!(
+                    "--> error: invalid regex in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::ArgsInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid arguments in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::EnvInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid environment in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::FrontMatterInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid front-matter in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
+            Error::IncludeInvalid { path, reason, row } => {
+                format!(
+                    "--> error: invalid include in {} at line {row}: {reason}",
+                    path.display()
+                )
+            }
             Error::CheckExitCode {
                 cmd_path,
                 expected,
                 actual,
                 stderr,
             } => {
-                let title = "Exit code doesn't match";
+                let title = self.message();
                 let script_title = "  script  :";
                 let expected_title = "  expected:";
                 let actual_title = "  actual  :";
                 diff_exit(
-                    title,
+                    &title,
                     script_title,
                     cmd_path,
                     expected_title,
@@ -75,16 +436,16 @@ impl Error {
                     actual_title,
                     *actual,
                     stderr,
-                    Format::Ansi,
+                    format,
                 )
             }
             Error::CheckStdoutLine {
                 cmd_path,
                 expected,
                 actual,
-                row,
+                ..
             } => {
-                let title = format!("Stdout doesn't match at line {}", row);
+                let title = self.message();
                 let script_title = "  script       :";
                 let expected_title = "  expected line:";
                 let actual_title = "  actual line  :";
@@ -96,7 +457,7 @@ impl Error {
                     expected.as_deref(),
                     actual_title,
                     actual.as_deref(),
-                    Format::Ansi,
+                    format,
                 )
             }
             Error::CheckStdoutPattern {
@@ -104,11 +465,122 @@ impl Error {
                 expected,
                 actual,
                 row,
+                matched,
+                ..
+            }
+            | Error::CheckStderrPattern {
+                cmd_path,
+                expected,
+                actual,
+                row,
+                matched,
+                ..
             } => {
-                let title = format!("Stdout doesn't match at line {}", row);
+                let title = self.message();
                 let script_title = "  script          :";
                 let expected_title = "  expected pattern:";
                 let actual_title = "  actual line     :";
+                let mut text = diff_text(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    expected.as_deref(),
+                    actual_title,
+                    actual.as_deref(),
+                    format,
+                );
+                // Lines matched before the mismatch give the context of the divergence.
+                if let Some(matched) = matched {
+                    let mut s = StyledString::new();
+                    s.push_with("  matched lines   :", Style::new().blue().bold());
+                    s.push(&format!(" {}, last ", row - 1));
+                    s.push_with("<", Style::new().yellow());
+                    s.append(visible(matched).truncate(MAX_VALUE_WIDTH));
+                    s.push_with(">", Style::new().yellow());
+                    s.push("\n");
+                    text.push_str(&s.to_string(format));
+                }
+                text
+            }
+            Error::CheckStdoutLineCount {
+                cmd_path,
+                expected,
+                actual,
+            } => {
+                let title = self.message();
+                let script_title = "  script  :";
+                let expected_title = "  expected:";
+                let actual_title = "  actual  :";
+                diff_value(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    &expected.to_string(),
+                    actual_title,
+                    &actual.to_string(),
+                    format,
+                )
+            }
+            Error::CheckMatchCount {
+                cmd_path,
+                expected,
+                actual,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  script  :";
+                let expected_title = "  expected:";
+                let actual_title = "  actual  :";
+                diff_value(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    &expected.to_string(),
+                    actual_title,
+                    &actual.to_string(),
+                    format,
+                )
+            }
+            Error::CheckInvariant {
+                cmd_path,
+                pattern,
+                actual,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  script     :";
+                let expected_title = "  forbidden  :";
+                let actual_title = "  actual line:";
+                diff_text(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    Some(pattern),
+                    actual_title,
+                    Some(actual),
+                    format,
+                )
+            }
+            Error::CheckState {
+                cmd_path,
+                expected,
+                actual,
+                ..
+            }
+            | Error::CheckOutputJson {
+                cmd_path,
+                expected,
+                actual,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  script  :";
+                let expected_title = "  expected:";
+                let actual_title = "  actual  :";
                 diff_text(
                     &title,
                     script_title,
@@ -117,16 +589,92 @@ impl Error {
                     expected.as_deref(),
                     actual_title,
                     actual.as_deref(),
-                    Format::Ansi,
+                    format,
                 )
             }
-            Error::CheckStderrLine {
+            Error::CheckChecksum {
                 cmd_path,
                 expected,
                 actual,
-                row,
+                ..
             } => {
-                let title = format!("Stderr doesn't match at line {}", row);
+                let title = self.message();
+                let script_title = "  script  :";
+                let expected_title = "  expected:";
+                let actual_title = "  actual  :";
+                diff_text(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    Some(expected),
+                    actual_title,
+                    Some(actual),
+                    format,
+                )
+            }
+            Error::CheckLocale { locale, cause, .. } => {
+                let mut s = StyledString::new();
+                s.push_with("-->", Style::new().blue().bold());
+                s.push(" locale ");
+                s.push_with(locale, Style::new().bold());
+                s.push(" (LC_ALL)\n");
+                // The cause has already its legend.
+                return s.to_string(format) + &cause.render(format);
+            }
+            Error::CheckAlternative {
+                cmd_path,
+                ext,
+                alternatives,
+                cause,
+            } => {
+                let mut s = StyledString::new();
+                s.push_with("-->", Style::new().blue().bold());
+                s.push(&format!(
+                    " no match among {} snapshots, closest is ",
+                    alternatives + 1
+                ));
+                s.push_with(
+                    &cmd_path.with_extension(ext).display().to_string(),
+                    Style::new().bold(),
+                );
+                s.push("\n");
+                return s.to_string(format) + &cause.render(format);
+            }
+            Error::CheckStdoutStderrOnly {
+                cmd_path,
+                pattern,
+                actual,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  script     :";
+                let expected_title = "  stderr only:";
+                let actual_title = "  actual line:";
+                diff_text(
+                    &title,
+                    script_title,
+                    cmd_path,
+                    expected_title,
+                    Some(pattern),
+                    actual_title,
+                    Some(actual),
+                    format,
+                )
+            }
+            Error::CheckScreenLine {
+                cmd_path,
+                expected,
+                actual,
+                ..
+            }
+            | Error::CheckStderrLine {
+                cmd_path,
+                expected,
+                actual,
+                ..
+            } => {
+                let title = self.message();
                 let script_title = "  script       :";
                 let expected_title = "  expected line:";
                 let actual_title = "  actual line  :";
@@ -138,34 +686,573 @@ impl Error {
                     expected.as_deref(),
                     actual_title,
                     actual.as_deref(),
-                    Format::Ansi,
+                    format,
                 )
             }
+            Error::CheckStdoutBytes {
+                cmd_path,
+                offset,
+                start,
+                expected,
+                actual,
+            }
+            | Error::CheckStderrBytes {
+                cmd_path,
+                offset,
+                start,
+                expected,
+                actual,
+            } => diff_bytes(
+                &self.message(),
+                cmd_path,
+                *offset,
+                *start,
+                expected,
+                actual,
+                format,
+            ),
             Error::StdoutPatternFileInvalid { .. } => {
                 "--> error StdoutPatternFileInvalid".to_string()
             }
+            Error::StderrPatternFileInvalid { .. } => format!("--> error: {}", self.message()),
+            Error::CheckPostHook {
+                hook_path,
+                exit_code,
+                stderr,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  post-hook:";
+                let expected_title = "  expected :";
+                let actual_title = "  actual   :";
+                diff_exit(
+                    &title,
+                    script_title,
+                    hook_path,
+                    expected_title,
+                    ExitCode::from(0).into(),
+                    actual_title,
+                    (*exit_code).into(),
+                    stderr,
+                    format,
+                )
+            }
+            Error::CheckFormatter {
+                formatter,
+                exit_code,
+                stderr,
+                ..
+            } => {
+                let title = self.message();
+                let script_title = "  formatter:";
+                let expected_title = "  expected :";
+                let actual_title = "  actual   :";
+                diff_exit(
+                    &title,
+                    script_title,
+                    Path::new(formatter),
+                    expected_title,
+                    ExitCode::from(0).into(),
+                    actual_title,
+                    (*exit_code).into(),
+                    stderr,
+                    format,
+                )
+            }
+        };
+        // Values with visible markers are explained under the diff.
+        match self {
+            Error::CheckStdoutLine { .. }
+            | Error::CheckStdoutPattern { .. }
+            | Error::CheckStderrPattern { .. }
+            | Error::CheckStdoutStderrOnly { .. }
+            | Error::CheckScreenLine { .. }
+            | Error::CheckStderrLine { .. } => rendered + &legend(self.code(), format),
+            _ => rendered,
+        }
+    }
+
+    /// Returns the code of this error, explained by `cliche --explain CODE`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::FileRead { .. } => "FILE_READ",
+            Error::FileNotUtf8 { .. } => "FILE_NOT_UTF8",
+            Error::FileTooLarge { .. } => "FILE_TOO_LARGE",
+            Error::FileNotInteger { .. } => "FILE_NOT_INTEGER",
+            Error::FileNotDuration { .. } => "FILE_NOT_DURATION",
+            Error::Timeout { .. } => "TIMEOUT",
+            Error::FileNotCount { .. } => "FILE_NOT_COUNT",
+            Error::FileNotScreenSizes { .. } => "FILE_NOT_SCREEN_SIZES",
+            Error::FileMisspelled { .. } => "FILE_MISSPELLED",
+            Error::SnapshotDuplicated { .. } => "SNAPSHOT_DUPLICATED",
+            Error::SnapshotCrlf { .. } => "SNAPSHOT_CRLF",
+            Error::SnapshotOrphaned { .. } => "SNAPSHOT_ORPHANED",
+            Error::ScriptNotExecutable { .. } => "SCRIPT_NOT_EXECUTABLE",
+            Error::InterpreterNotFound { .. } => "INTERPRETER_NOT_FOUND",
+            Error::PatternMatchesEmpty { .. } => "PATTERN_MATCHES_EMPTY",
+            Error::PatternConflict { .. } => "PATTERN_CONFLICT",
+            Error::LeftoverProcesses { .. } => "LEFTOVER_PROCESSES",
+            Error::CheckDiskUsage { .. } => "CHECK_DISK_USAGE",
+            Error::FileNotJson { .. } => "FILE_NOT_JSON",
+            Error::ConfigInvalid { .. } => "CONFIG_INVALID",
+            Error::ManifestInvalid { .. } => "MANIFEST_INVALID",
+            Error::DirectiveInvalid { .. } => "DIRECTIVE_INVALID",
+            Error::FileInvalidRegex { .. } => "FILE_INVALID_REGEX",
+            Error::FrontMatterInvalid { .. } => "FRONT_MATTER_INVALID",
+            Error::IncludeInvalid { .. } => "INCLUDE_INVALID",
+            Error::EnvInvalid { .. } => "ENV_INVALID",
+            Error::ArgsInvalid { .. } => "ARGS_INVALID",
+            Error::CwdInvalid { .. } => "CWD_INVALID",
+            Error::CheckExitCode { .. } => "CHECK_EXIT_CODE",
+            Error::CheckStdoutLine { .. } => "CHECK_STDOUT_LINE",
+            Error::CheckStdoutPattern { .. } => "CHECK_STDOUT_PATTERN",
+            Error::CheckStdoutLineCount { .. } => "CHECK_STDOUT_LINE_COUNT",
+            Error::CheckMatchCount { .. } => "CHECK_MATCH_COUNT",
+            Error::CheckInvariant { .. } => "CHECK_INVARIANT",
+            Error::CheckState { .. } => "CHECK_STATE",
+            Error::CheckOutputJson { .. } => "CHECK_OUTPUT_JSON",
+            Error::CheckChecksum { .. } => "CHECK_CHECKSUM",
+            Error::CheckLocale { cause, .. } | Error::CheckAlternative { cause, .. } => {
+                cause.code()
+            }
+            Error::CheckStdoutStderrOnly { .. } => "CHECK_STDOUT_STDERR_ONLY",
+            Error::StdoutPatternFileInvalid { .. } => "STDOUT_PATTERN_FILE_INVALID",
+            Error::CheckStderrPattern { .. } => "CHECK_STDERR_PATTERN",
+            Error::StderrPatternFileInvalid { .. } => "STDERR_PATTERN_FILE_INVALID",
+            Error::CheckPostHook { .. } => "CHECK_POST_HOOK",
+            Error::CheckFormatter { .. } => "CHECK_FORMATTER",
+            Error::CheckScreenLine { .. } => "CHECK_SCREEN_LINE",
+            Error::CheckStderrLine { .. } => "CHECK_STDERR_LINE",
+            Error::CheckStdoutBytes { .. } => "CHECK_STDOUT_BYTES",
+            Error::CheckStderrBytes { .. } => "CHECK_STDERR_BYTES",
+        }
+    }
+
+    /// Returns a one-line description of this error.
+    pub fn message(&self) -> String {
+        match self {
+            Error::FileRead { path, cause } => {
+                format!("Cannot read file {}: {cause}", path.display())
+            }
+            Error::FileNotUtf8 { path } => format!("File {} is not valid UTF-8", path.display()),
+            Error::FileTooLarge { path, size, max } => format!(
+                "File {} is too large ({}, the maximum is {})",
+                path.display(),
+                format_bytes(*size),
+                format_bytes(*max)
+            ),
+            Error::FileNotDuration { path } => {
+                format!("File {} is not a valid duration", path.display())
+            }
+            Error::Timeout { timeout, .. } => {
+                format!("Command has timed out after {}", format_duration(*timeout))
+            }
+            Error::FileNotInteger { path } => {
+                format!("File {} is not a valid exit code", path.display())
+            }
+            Error::FileNotScreenSizes { path } => {
+                format!(
+                    "File {} is not a valid list of terminal sizes",
+                    path.display()
+                )
+            }
+            Error::FileNotCount { path } => {
+                format!("File {} is not a valid count or range", path.display())
+            }
+            Error::CheckDiskUsage { .. } => "Work directory is over its disk budget".to_string(),
+            Error::LeftoverProcesses { cmd_path, commands } => format!(
+                "{} left {} process(es) running after exiting: {}",
+                cmd_path.display(),
+                commands.len(),
+                commands.join(", ")
+            ),
+            Error::PatternMatchesEmpty { path, pattern, row } => format!(
+                "Pattern <{pattern}> of {} at line {row} can match an empty string",
+                path.display()
+            ),
+            Error::PatternConflict {
+                path,
+                snapshot,
+                row,
+            } => format!(
+                "Pattern file {} doesn't match snapshot {} at line {row}, the test can't pass",
+                path.display(),
+                snapshot.display()
+            ),
+            Error::FileMisspelled { path, expected } => format!(
+                "File {} looks like a misspelled expectation file (did you mean {}?)",
+                path.display(),
+                expected.display()
+            ),
+            Error::SnapshotDuplicated { paths } => format!(
+                "Snapshots {} are identical (move their content to a shared snapshot, referenced \
+                 by a single line like @shared.{})",
+                paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                paths[0].extension().unwrap_or_default().to_string_lossy()
+            ),
+            Error::SnapshotCrlf { path, row } => format!(
+                "Snapshot {} has a CRLF line ending at line {row}",
+                path.display()
+            ),
+            Error::SnapshotOrphaned { path } => {
+                format!("Snapshot {} belongs to no test script", path.display())
+            }
+            Error::ScriptNotExecutable { path } => format!(
+                "Script {} is not executable (run chmod +x {}, or cliche with --fix-perms)",
+                path.display(),
+                path.display()
+            ),
+            Error::InterpreterNotFound { path, interpreter } => format!(
+                "Interpreter {interpreter} of script {} is not found",
+                path.display()
+            ),
+            Error::FileNotJson { reason, .. } => format!("Invalid JSON: {reason}"),
+            Error::ConfigInvalid { reason, .. } => format!("Invalid configuration: {reason}"),
+            Error::ManifestInvalid { reason, row, .. } => {
+                format!("Invalid manifest at line {row}: {reason}")
+            }
+            Error::DirectiveInvalid { reason, row, .. } => {
+                format!("Invalid directive at line {row}: {reason}")
+            }
+            Error::FrontMatterInvalid { reason, row, .. } => {
+                format!("Invalid front-matter at line {row}: {reason}")
+            }
+            Error::IncludeInvalid { reason, row, .. } => {
+                format!("Invalid include at line {row}: {reason}")
+            }
+            Error::EnvInvalid { reason, row, .. } => {
+                format!("Invalid environment at line {row}: {reason}")
+            }
+            Error::ArgsInvalid { reason, row, .. } => {
+                format!("Invalid arguments at line {row}: {reason}")
+            }
+            Error::CwdInvalid { path, cwd } => {
+                format!(
+                    "Working directory {} given by {} is not a directory",
+                    cwd.display(),
+                    path.display()
+                )
+            }
+            Error::FileInvalidRegex { reason, row, .. } => {
+                format!("Invalid regex at line {row}: {reason}")
+            }
+            Error::CheckExitCode { .. } => "Exit code doesn't match".to_string(),
+            Error::CheckStdoutLine { row, chunk, .. } => {
+                format!("Stdout doesn't match at {}", position(*row, *chunk))
+            }
+            Error::CheckStdoutPattern { row, mismatch, .. } => {
+                pattern_message("Stdout", *row, mismatch.as_deref())
+            }
+            Error::CheckStderrPattern { row, mismatch, .. } => {
+                pattern_message("Stderr", *row, mismatch.as_deref())
+            }
+            Error::CheckStdoutLineCount { .. } => "Stdout line count doesn't match".to_string(),
+            Error::CheckMatchCount {
+                stderr, pattern, ..
+            } => {
+                let stream = if *stderr { "Stderr" } else { "Stdout" };
+                format!("{stream} count of lines matching <{pattern}> doesn't match")
+            }
+            Error::CheckInvariant { stderr, row, .. } => {
+                let stream = if *stderr { "stderr" } else { "stdout" };
+                format!("Suite invariant violated by {stream} at line {row}")
+            }
+            Error::CheckState {
+                pointer, actual, ..
+            } => match actual {
+                None if pointer == "$" => {
+                    format!("State file {STATE_FILE_NAME} has not been written")
+                }
+                _ => format!("State doesn't match at {pointer}"),
+            },
+            Error::CheckOutputJson { ext, pointer, .. } => {
+                format!("{} doesn't match at {pointer}", stream_name(ext))
+            }
+            Error::CheckChecksum { ext, .. } => {
+                format!("{} checksum doesn't match", stream_name(ext))
+            }
+            Error::CheckLocale { locale, cause, .. } => {
+                format!("{} (locale {locale})", cause.message())
+            }
+            Error::CheckAlternative { ext, cause, .. } => {
+                format!("{} (closest snapshot {ext})", cause.message())
+            }
+            Error::CheckStdoutStderrOnly { row, .. } => {
+                format!("Stdout has a line reserved to stderr at line {row}")
+            }
+            Error::StdoutPatternFileInvalid { reason, row, .. } => {
+                format!("Invalid stdout pattern at line {row}: {reason}")
+            }
+            Error::StderrPatternFileInvalid { reason, row, .. } => {
+                format!("Invalid stderr pattern at line {row}: {reason}")
+            }
+            Error::CheckScreenLine { row, chunk, .. } => {
+                format!(
+                    "Terminal screen doesn't match at {}",
+                    position(*row, *chunk)
+                )
+            }
+            Error::CheckStderrLine { row, chunk, .. } => {
+                format!("Stderr doesn't match at {}", position(*row, *chunk))
+            }
+            Error::CheckStdoutBytes { offset, .. } => {
+                format!("Stdout doesn't match at byte {offset} ({offset:#010x})")
+            }
+            Error::CheckStderrBytes { offset, .. } => {
+                format!("Stderr doesn't match at byte {offset} ({offset:#010x})")
+            }
+            Error::CheckPostHook { .. } => "Post-hook has failed".to_string(),
+            Error::CheckFormatter { .. } => "Formatter of stdout has failed".to_string(),
+        }
+    }
+
+    /// Returns the file where this error is located, with an optional 1-based line index.
+    ///
+    /// For a verification error, the file is the expectation file that has not been satisfied,
+    /// next to the command script.
+    pub fn location(&self) -> (PathBuf, Option<usize>) {
+        match self {
+            Error::FileRead { path, .. }
+            | Error::FileNotUtf8 { path }
+            | Error::FileTooLarge { path, .. }
+            | Error::FileNotInteger { path }
+            | Error::FileNotDuration { path }
+            | Error::CwdInvalid { path, .. }
+            | Error::ScriptNotExecutable { path }
+            | Error::InterpreterNotFound { path, .. }
+            | Error::FileNotCount { path }
+            | Error::FileNotScreenSizes { path }
+            | Error::FileMisspelled { path, .. }
+            | Error::SnapshotOrphaned { path }
+            | Error::ConfigInvalid { path, .. }
+            | Error::FileNotJson { path, .. } => (path.clone(), None),
+            Error::SnapshotDuplicated { paths } => (paths[0].clone(), None),
+            Error::ManifestInvalid { path, row, .. }
+            | Error::DirectiveInvalid { path, row, .. }
+            | Error::PatternMatchesEmpty { path, row, .. }
+            | Error::SnapshotCrlf { path, row }
+            | Error::PatternConflict { path, row, .. }
+            | Error::FrontMatterInvalid { path, row, .. }
+            | Error::IncludeInvalid { path, row, .. }
+            | Error::EnvInvalid { path, row, .. }
+            | Error::ArgsInvalid { path, row, .. }
+            | Error::FileInvalidRegex { path, row, .. } => (path.clone(), Some(*row)),
+            Error::CheckStdoutStderrOnly { cmd_path, .. }
+            | Error::CheckInvariant { cmd_path, .. }
+            | Error::LeftoverProcesses { cmd_path, .. }
+            | Error::CheckDiskUsage { cmd_path, .. }
+            | Error::Timeout { cmd_path, .. } => (cmd_path.clone(), None),
+            Error::CheckPostHook { hook_path, .. } => (hook_path.clone(), None),
+            Error::CheckFormatter { cmd_path, .. } => (cmd_path.clone(), None),
+            Error::CheckLocale {
+                cmd_path, cause, ..
+            }
+            | Error::CheckAlternative {
+                cmd_path, cause, ..
+            } => match self.expectation() {
+                Some((ext, row)) => (cmd_path.with_extension(&ext), row),
+                None => cause.location(),
+            },
+            Error::CheckExitCode { cmd_path, .. }
+            | Error::CheckStdoutLine { cmd_path, .. }
+            | Error::CheckStdoutPattern { cmd_path, .. }
+            | Error::StdoutPatternFileInvalid { cmd_path, .. }
+            | Error::CheckStderrPattern { cmd_path, .. }
+            | Error::StderrPatternFileInvalid { cmd_path, .. }
+            | Error::CheckStdoutLineCount { cmd_path, .. }
+            | Error::CheckMatchCount { cmd_path, .. }
+            | Error::CheckState { cmd_path, .. }
+            | Error::CheckOutputJson { cmd_path, .. }
+            | Error::CheckChecksum { cmd_path, .. }
+            | Error::CheckScreenLine { cmd_path, .. }
+            | Error::CheckStderrLine { cmd_path, .. }
+            | Error::CheckStdoutBytes { cmd_path, .. }
+            | Error::CheckStderrBytes { cmd_path, .. } => {
+                let (ext, row) = self.expectation().unwrap();
+                (cmd_path.with_extension(&ext), row)
+            }
+        }
+    }
+
+    /// Returns the extension of the expectation file not satisfied by a verification error, with
+    /// an optional 1-based line index.
+    pub fn expectation(&self) -> Option<(String, Option<usize>)> {
+        match self {
+            Error::CheckExitCode { .. } => Some(("exit".to_string(), None)),
+            Error::CheckStdoutLine { row, .. } => Some(("out".to_string(), Some(*row))),
+            Error::CheckStdoutPattern { row, .. } | Error::StdoutPatternFileInvalid { row, .. } => {
+                Some(("out.pattern".to_string(), Some(*row)))
+            }
+            Error::CheckStdoutLineCount { .. } => Some(("out.lines".to_string(), None)),
+            Error::CheckMatchCount { stderr, row, .. } => {
+                let ext = if *stderr { "err.count" } else { "out.count" };
+                Some((ext.to_string(), Some(*row)))
+            }
+            Error::CheckScreenLine { size, row, .. } => {
+                let ext = match size {
+                    Some(size) => format!("{size}.screen"),
+                    None => "screen".to_string(),
+                };
+                Some((ext, Some(*row)))
+            }
+            Error::CheckStderrLine { row, .. } => Some(("err".to_string(), Some(*row))),
+            Error::CheckStdoutBytes { .. } => Some(("out".to_string(), None)),
+            Error::CheckStderrBytes { .. } => Some(("err".to_string(), None)),
+            Error::CheckStderrPattern { row, .. } | Error::StderrPatternFileInvalid { row, .. } => {
+                Some(("err.pattern".to_string(), Some(*row)))
+            }
+            Error::CheckState { .. } => Some(("state.json".to_string(), None)),
+            Error::CheckOutputJson { ext, .. } | Error::CheckChecksum { ext, .. } => {
+                Some((ext.clone(), None))
+            }
+            Error::CheckLocale { locale, cause, .. } => {
+                let (ext, row) = cause.expectation()?;
+                Some((format!("{locale}.{ext}"), row))
+            }
+            Error::CheckAlternative { ext, cause, .. } => {
+                let (_, row) = cause.expectation()?;
+                Some((ext.clone(), row))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the expected and actual values of a verification error.
+    pub fn expected_actual(&self) -> (Option<String>, Option<String>) {
+        match self {
+            Error::CheckExitCode {
+                expected, actual, ..
+            } => (Some(expected.to_string()), Some(actual.to_string())),
+            Error::CheckStdoutLine {
+                expected, actual, ..
+            }
+            | Error::CheckStdoutPattern {
+                expected, actual, ..
+            }
+            | Error::CheckStderrPattern {
+                expected, actual, ..
+            }
+            | Error::CheckScreenLine {
+                expected, actual, ..
+            }
+            | Error::CheckState {
+                expected, actual, ..
+            }
+            | Error::CheckOutputJson {
+                expected, actual, ..
+            }
+            | Error::CheckStderrLine {
+                expected, actual, ..
+            } => (expected.clone(), actual.clone()),
+            Error::CheckStdoutLineCount {
+                expected, actual, ..
+            }
+            | Error::CheckMatchCount {
+                expected, actual, ..
+            } => (Some(expected.to_string()), Some(actual.to_string())),
+            Error::CheckStdoutStderrOnly {
+                pattern, actual, ..
+            }
+            | Error::CheckInvariant {
+                pattern, actual, ..
+            } => (Some(pattern.clone()), Some(actual.clone())),
+            Error::CheckStdoutBytes {
+                expected, actual, ..
+            }
+            | Error::CheckStderrBytes {
+                expected, actual, ..
+            } => (Some(hex(expected)), Some(hex(actual))),
+            Error::CheckLocale { cause, .. } | Error::CheckAlternative { cause, .. } => {
+                cause.expected_actual()
+            }
+            Error::CheckDiskUsage { max, actual, .. } => {
+                (Some(max.to_string()), Some(actual.to_string()))
+            }
+            Error::CheckPostHook { exit_code, .. } | Error::CheckFormatter { exit_code, .. } => (
+                Some(ExitCode::from(0).to_string()),
+                Some(exit_code.to_string()),
+            ),
+            _ => (None, None),
         }
     }
 }
 
-fn replace_visible(str: &str) -> String {
-    let yellow = Style::new().yellow();
+/// Returns the position of a line `chunk`, with its column if it's not the first chunk.
+fn position(row: usize, chunk: usize) -> String {
+    if chunk == 0 {
+        format!("line {row}")
+    } else {
+        format!("line {row}, column {}", chunk * CHUNK_SIZE + 1)
+    }
+}
 
-    let mut lf = StyledString::new();
-    lf.push_with("[\\n]", yellow);
-    let lf = lf.to_string(Format::Ansi);
+/// Returns a one-line legend of the markers used in values, with a pointer to the explanation of
+/// the error `code`.
+fn legend(code: &str, format: Format) -> String {
+    let mut s = StyledString::new();
+    s.push_with(
+        &format!(
+            "  < > delimit values, [\\n] is a newline, [\\r] a carriage return and [\\tab] a tab \
+             (see cliche --explain {code})\n"
+        ),
+        Style::new().bright_black(),
+    );
+    s.to_string(format)
+}
 
-    let mut cr = StyledString::new();
-    cr.push_with("[\\r]", yellow);
-    let cr = cr.to_string(Format::Ansi);
+/// Maximum display width of an expected or actual value in a diff, longer values are truncated
+/// so they don't break the terminal layout.
+const MAX_VALUE_WIDTH: usize = 120;
+
+/// Returns `str` with newlines, carriage returns and tabs replaced by visible markers.
+fn visible(str: &str) -> StyledString {
+    let yellow = Style::new().yellow();
+    let mut s = StyledString::new();
+    let mut text = String::new();
+    for c in str.chars() {
+        let marker = match c {
+            '\n' => "[\\n]",
+            '\r' => "[\\r]",
+            '\t' => "[\\tab]",
+            _ => {
+                text.push(c);
+                continue;
+            }
+        };
+        if !text.is_empty() {
+            s.push(&text);
+            text.clear();
+        }
+        s.push_with(marker, yellow);
+    }
+    if !text.is_empty() {
+        s.push(&text);
+    }
+    s
+}
 
-    let mut tab = StyledString::new();
-    tab.push_with("[\\tab]", yellow);
-    let tab = tab.to_string(Format::Ansi);
+/// Returns the message of a pattern mismatch of a `stream` at `row`, locating the mismatching
+/// segment of the line if any.
+fn pattern_message(stream: &str, row: usize, mismatch: Option<&SegmentMismatch>) -> String {
+    match mismatch {
+        Some(SegmentMismatch { segment, column }) => {
+            format!("{stream} doesn't match at line {row}, column {column} (segment \"{segment}\")")
+        }
+        None => format!("{stream} doesn't match at line {row}"),
+    }
+}
 
-    str.replace('\n', &lf)
-        .replace('\r', &cr)
-        .replace('\t', &tab)
+/// Returns the name of the stream checked by an expectation file with extension `ext`.
+fn stream_name(ext: &str) -> &'static str {
+    if ext.starts_with("err") {
+        "Stderr"
+    } else {
+        "Stdout"
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -196,34 +1283,205 @@ fn diff_text(
     s.push("\n");
 
     let expected = expected.unwrap_or("");
-    let expected = replace_visible(expected);
+    let expected = visible(expected).truncate(MAX_VALUE_WIDTH);
     s.push_with(expected_title, blue_bold);
     s.push(" ");
     s.push_with("<", yellow);
-    s.push(&expected);
+    s.append(expected);
     s.push_with(">", yellow);
     s.push("\n");
 
     let actual = actual.unwrap_or("");
-    let actual = replace_visible(actual);
+    let actual = visible(actual).truncate(MAX_VALUE_WIDTH);
     s.push_with(actual_title, blue_bold);
     s.push(" ");
     s.push_with("<", yellow);
-    s.push(&actual);
+    s.append(actual);
     s.push_with(">", yellow);
     s.push("\n");
     s.to_string(format)
 }
 
+/// Number of bytes of a row of hex dump.
+const HEX_ROW_WIDTH: usize = 16;
+
+/// Renders a byte difference at `offset`, with hex dumps of the `expected` and `actual` windows
+/// of bytes starting at `start`.
+fn diff_bytes(
+    title: &str,
+    script: &Path,
+    offset: usize,
+    start: usize,
+    expected: &[u8],
+    actual: &[u8],
+    format: Format,
+) -> String {
+    let red_bold = Style::new().red().bold();
+    let bold = Style::new().bold();
+    let blue_bold = Style::new().blue().bold();
+
+    let mut s = StyledString::new();
+    s.push_with("error", red_bold);
+    s.push_with(":", bold);
+    s.push(" ");
+    s.push_with(title, bold);
+    s.push("\n");
+    s.push_with("  script  :", blue_bold);
+    s.push(" ");
+    s.push(&script.display().to_string());
+    s.push("\n");
+    for (title, bytes) in [("  expected:", expected), ("  actual  :", actual)] {
+        s.push_with(title, blue_bold);
+        s.push("\n");
+        s.append(hex_dump(bytes, start, offset));
+    }
+    s.to_string(format)
+}
+
+/// Returns a hex dump of `bytes` starting at `start`, the byte at `offset` being highlighted.
+fn hex_dump(bytes: &[u8], start: usize, offset: usize) -> StyledString {
+    let mut s = StyledString::new();
+    if bytes.is_empty() {
+        s.push_with("    <no bytes>\n", Style::new().bright_black());
+        return s;
+    }
+    for (index, row) in bytes.chunks(HEX_ROW_WIDTH).enumerate() {
+        let row_start = start + index * HEX_ROW_WIDTH;
+        s.push(&format!("    {row_start:08x} "));
+        for (i, byte) in row.iter().enumerate() {
+            s.push(" ");
+            let text = format!("{byte:02x}");
+            if row_start + i == offset {
+                s.push_with(&text, Style::new().red().bold());
+            } else {
+                s.push(&text);
+            }
+        }
+        // Aligns the text column of an incomplete row.
+        s.push(&" ".repeat(3 * (HEX_ROW_WIDTH - row.len())));
+        let text = row
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        s.push(&format!("  |{text}|\n"));
+    }
+    s
+}
+
+/// Returns `bytes` as space-separated hex values.
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[allow(clippy::too_many_arguments)]
+fn diff_value(
+    title: &str,
+    script_title: &str,
+    script: &Path,
+    expected_title: &str,
+    expected: &str,
+    actual_title: &str,
+    actual: &str,
+    format: Format,
+) -> String {
+    let red_bold = Style::new().red().bold();
+    let bold = Style::new().bold();
+    let blue_bold = Style::new().blue().bold();
+
+    let mut s = StyledString::new();
+    s.push_with("error", red_bold);
+    s.push_with(":", bold);
+    s.push(" ");
+    s.push_with(title, bold);
+    s.push("\n");
+    s.push_with(script_title, blue_bold);
+    s.push(" ");
+    s.push(&script.display().to_string());
+    s.push("\n");
+
+    s.push_with(expected_title, blue_bold);
+    s.push(" ");
+    s.push(expected);
+    s.push("\n");
+
+    s.push_with(actual_title, blue_bold);
+    s.push(" ");
+    s.push(actual);
+    s.push("\n");
+    s.to_string(format)
+}
+
+/// Renders a command killed after `timeout`, with the `stdout` and `stderr` it has written so far.
+fn diff_timeout(
+    title: &str,
+    script: &Path,
+    timeout: Duration,
+    stdout: &[u8],
+    stderr: &[u8],
+    format: Format,
+) -> String {
+    let red_bold = Style::new().red().bold();
+    let bold = Style::new().bold();
+    let blue_bold = Style::new().blue().bold();
+
+    let mut s = StyledString::new();
+    s.push_with("error", red_bold);
+    s.push_with(":", bold);
+    s.push(" ");
+    s.push_with(title, bold);
+    s.push("\n");
+    s.push_with("  script :", blue_bold);
+    s.push(" ");
+    s.push(&script.display().to_string());
+    s.push("\n");
+    s.push_with("  timeout:", blue_bold);
+    s.push(" ");
+    s.push(&format_duration(timeout));
+    s.push("\n");
+    for (title, output) in [("  partial stdout:", stdout), ("  partial stderr:", stderr)] {
+        if output.is_empty() {
+            continue;
+        }
+        s.push_with(title, blue_bold);
+        s.push("\n");
+        for line in String::from_utf8_lossy(output).lines() {
+            s.push_with("|", blue_bold);
+            s.push(" ");
+            s.push(line);
+            s.push("\n");
+        }
+    }
+    s.to_string(format)
+}
+
+/// Formats a `duration` in seconds, or in milliseconds below one second.
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{}s", duration.as_secs_f64())
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn diff_exit(
     title: &str,
     script_title: &str,
     script: &Path,
     expected_title: &str,
-    expected: ExitCode,
+    expected: ExpectedExitCode,
     actual_title: &str,
-    actual: ExitCode,
+    actual: Termination,
     stderr: &[u8],
     format: Format,
 ) -> String {
@@ -0,0 +1,141 @@
+//! Machine-parsable progress events, written as newline-delimited JSON in real time.
+use crate::json::JsonValue;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A stream of progress events, to a file or to an inherited file descriptor.
+pub struct EventStream {
+    out: Box<dyn Write>,
+}
+
+impl EventStream {
+    /// Opens an event stream to `target`: a number is an already opened file descriptor (on
+    /// Unix), anything else is the path of a file to create.
+    pub fn open(target: &str) -> Result<EventStream, io::Error> {
+        #[cfg(unix)]
+        if let Ok(fd) = target.parse::<i32>() {
+            use std::os::fd::FromRawFd;
+            if fd < 0 {
+                return Err(io::Error::from(io::ErrorKind::InvalidInput));
+            }
+            // SAFETY: the file descriptor is given by the user, who has opened it for us
+            // (`cliche --event-stream 3 3>events.log`), and it is not used anywhere else.
+            let file = unsafe { File::from_raw_fd(fd) };
+            return Ok(EventStream {
+                out: Box::new(file),
+            });
+        }
+        let file = File::create(target)?;
+        Ok(EventStream {
+            out: Box::new(file),
+        })
+    }
+
+    /// Emits the start of a suite of `tests` tests.
+    pub fn suite_started(&mut self, tests: usize) {
+        self.emit(vec![
+            ("event", "suite_started".into()),
+            ("tests", tests.into()),
+        ]);
+    }
+
+    /// Emits the start of the test `name`, with its stable `id`.
+    pub fn test_started(&mut self, name: &str, id: &str, path: &str) {
+        self.emit(vec![
+            ("event", "test_started".into()),
+            ("test", name.into()),
+            ("id", id.into()),
+            ("path", path.into()),
+        ]);
+    }
+
+    /// Emits the expectation files looked up for the test `name`, found or absent.
+    pub fn files(&mut self, name: &str, files: &[(&str, PathBuf, bool)]) {
+        let files = files
+            .iter()
+            .map(|(kind, path, found)| {
+                JsonValue::object(vec![
+                    ("kind", (*kind).into()),
+                    ("path", path.display().to_string().into()),
+                    ("found", (*found).into()),
+                ])
+            })
+            .collect();
+        self.emit(vec![
+            ("event", "files".into()),
+            ("test", name.into()),
+            ("files", JsonValue::Array(files)),
+        ]);
+    }
+
+    /// Emits a diff produced by the test `name`, described by `diagnostic` fields.
+    pub fn diff(&mut self, name: &str, diagnostic: Vec<(&str, JsonValue)>) {
+        let mut members = vec![("event", "diff".into()), ("test", name.into())];
+        members.extend(diagnostic);
+        self.emit(members);
+    }
+
+    /// Emits the end of the test `name`.
+    pub fn test_finished(&mut self, name: &str, passed: bool, duration: Duration) {
+        let status = if passed { "passed" } else { "failed" };
+        self.emit(vec![
+            ("event", "test_finished".into()),
+            ("test", name.into()),
+            ("status", status.into()),
+            ("duration", duration.as_secs_f64().into()),
+        ]);
+    }
+
+    /// Emits the end of the suite.
+    pub fn suite_finished(&mut self, passed: usize, failed: usize) {
+        self.emit(vec![
+            ("event", "suite_finished".into()),
+            ("passed", passed.into()),
+            ("failed", failed.into()),
+        ]);
+    }
+
+    fn emit(&mut self, members: Vec<(&str, JsonValue)>) {
+        let event = JsonValue::object(members);
+        // Events are informative: a reader that has gone away must not fail the run.
+        let _ = writeln!(self.out, "{event}").and_then(|_| self.out.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_events() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("events.log");
+        let mut events = EventStream::open(path.to_str().unwrap()).unwrap();
+        events.suite_started(1);
+        events.test_started("foo", "0123456789abcdef", "foo.sh");
+        events.files(
+            "foo",
+            &[
+                ("stdout", PathBuf::from("foo.out"), true),
+                ("stderr", PathBuf::from("foo.err"), false),
+            ],
+        );
+        events.test_finished("foo", true, Duration::from_millis(250));
+        events.suite_finished(1, 0);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"event\":\"suite_started\",\"tests\":1}\n\
+             {\"event\":\"test_started\",\"test\":\"foo\",\"id\":\"0123456789abcdef\",\"path\":\"foo.sh\"}\n\
+             {\"event\":\"files\",\"test\":\"foo\",\"files\":[\
+             {\"kind\":\"stdout\",\"path\":\"foo.out\",\"found\":true},\
+             {\"kind\":\"stderr\",\"path\":\"foo.err\",\"found\":false}]}\n\
+             {\"event\":\"test_finished\",\"test\":\"foo\",\"status\":\"passed\",\"duration\":0.25}\n\
+             {\"event\":\"suite_finished\",\"passed\":1,\"failed\":0}\n"
+        );
+    }
+}
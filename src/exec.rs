@@ -0,0 +1,116 @@
+//! One-off check of a command given on the command line, without test script.
+//!
+//! `cliche exec --name <NAME> [--update] -- <PROGRAM> [ARGS]...`
+//! runs `PROGRAM` with `ARGS` in the current directory and verifies it against the expectation
+//! files named after `NAME` (`NAME.out`, `NAME.err`, `NAME.exit`...). Input files named after
+//! `NAME` (`NAME.stdin`, `NAME.env`...) are used too. With `--update`, the snapshots are written
+//! from the actual results instead.
+use crate::command::CommandSpec;
+use crate::verify::{self, VerifyOptions};
+use crate::{RunError, RunSummary, print_success, term_format, update, usage};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Runs `cliche exec`, given the arguments after `exec`.
+pub fn run(args: &[String]) -> Result<RunSummary, RunError> {
+    let mut name = None;
+    let mut updating = false;
+    let mut args = args.iter();
+    let mut command = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => match args.next() {
+                Some(value) => name = Some(value.clone()),
+                None => return Err(RunError::Cli(format!("option '{arg}' requires a value"))),
+            },
+            "--update" => updating = true,
+            "-h" | "--help" => {
+                usage();
+                return Ok(RunSummary::default());
+            }
+            "--" => {
+                command.extend(args.by_ref().cloned());
+            }
+            _ => {
+                command.push(arg.clone());
+                command.extend(args.by_ref().cloned());
+            }
+        }
+    }
+    let Some(name) = name else {
+        return Err(RunError::Cli("option '--name' is required".to_string()));
+    };
+    if command.is_empty() {
+        return Err(RunError::Cli("no command to run".to_string()));
+    }
+    let program = command.remove(0);
+    let Some(program_path) = find_program(&program, env::var_os("PATH")) else {
+        return Err(RunError::Command(format!("command not found: {program}")));
+    };
+    let cwd = env::current_dir().map_err(RunError::Io)?;
+    let spec = CommandSpec::with_program(&program_path, command, &cwd, Path::new(&name))
+        .map_err(RunError::Io)?;
+    let result = spec.execute().map_err(RunError::Io)?;
+
+    if updating {
+        let changes = update::plan(&spec, &result).map_err(RunError::Error)?;
+        eprint!("{}", update::preview(&changes).to_string(term_format()));
+        update::apply(&changes).map_err(RunError::Io)?;
+        return Ok(RunSummary::default());
+    }
+    match verify::check_result(&spec, &result, &VerifyOptions::default()) {
+        Ok(()) => {
+            print_success(&name, Some(result.elapsed()), None);
+            Ok(RunSummary {
+                passed: 1,
+                failed: 0,
+            })
+        }
+        Err(err) => Err(RunError::Verify(err).for_test(&name)),
+    }
+}
+
+/// Returns the path of `program`: the program itself if it's a path, or the first executable
+/// file named `program` in the directories of `path` (the `PATH` environment variable).
+fn find_program(program: &str, path: Option<std::ffi::OsString>) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) || program.contains('/') {
+        let program = PathBuf::from(program);
+        return program.is_file().then_some(program);
+    }
+    env::split_paths(&path?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_find_program() {
+        let path = Some("/nonexistent:/bin".into());
+        assert_eq!(
+            find_program("sh", path.clone()),
+            Some(PathBuf::from("/bin/sh"))
+        );
+        assert_eq!(find_program("cliche-nonexistent", path.clone()), None);
+        assert_eq!(
+            find_program("/bin/sh", None),
+            Some(PathBuf::from("/bin/sh"))
+        );
+        assert_eq!(find_program("sh", None), None);
+    }
+}
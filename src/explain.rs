@@ -0,0 +1,347 @@
+//! Long explanations of the errors, printed by `cliche --explain CODE`.
+
+/// Returns the explanation of the error `code` (see [`crate::error::Error::code`]).
+pub fn explain(code: &str) -> Option<&'static str> {
+    let text = match code.to_ascii_uppercase().as_str() {
+        "FILE_READ" => {
+            "\
+An expectation file or a manifest exists but can't be read (permissions, file removed while
+running etc...)."
+        }
+        "FILE_NOT_UTF8" => {
+            "\
+A text expectation file (pattern, exit code, line count etc...) is not valid UTF-8. Only exact
+snapshots (`foo.out`, `foo.err`) can contain arbitrary bytes."
+        }
+        "FILE_TOO_LARGE" => {
+            "\
+A companion file of a test (snapshot, pattern, exit code, included file...) is larger than the
+maximum size of the files read by cliche, 64 MiB by default. It's not read into memory: a large
+file is usually committed by mistake. The limit is set by `max_file_size` in `cliche.toml`
+(`max_file_size = \"256M\"`)."
+        }
+        "FILE_NOT_INTEGER" => {
+            "\
+The exit code file `foo.exit` must contain an integer (`64`) or an exit code name (`EX_USAGE`,
+`SIGINT`...), any code but one (`!0` for any failure), an inclusive range of codes (`1..5`), a
+signal killing the script (`signal:11` or `signal:SIGSEGV`, on Unix), or `*` for any code."
+        }
+        "FILE_NOT_DURATION" => {
+            "\
+The timeout file `foo.timeout` must contain a duration: a number of seconds (`2`, `1.5`) or a
+number with a unit (`500ms`, `2s`, `1m`)."
+        }
+        "TIMEOUT" => {
+            "\
+The script has not exited before its timeout, given by `foo.timeout` or by the `--timeout` option.
+The script and every process it has spawned are killed, and the outputs written so far are printed
+under the error, prefixed with `|`."
+        }
+        "FILE_NOT_COUNT" => {
+            "\
+The line count file `foo.out.lines` must contain a count (`42`) or a range of counts (`10..20`,
+`10..` or `..20`). Each line of a matching line count file (`foo.out.count` or `foo.err.count`)
+must start with such a count, followed by a regex."
+        }
+        "FILE_NOT_SCREEN_SIZES" => {
+            "\
+The terminal sizes file `foo.screen.sizes` must contain sizes `COLSxROWS` separated by whitespaces
+(`80x24 120x40`)."
+        }
+        "SCRIPT_NOT_EXECUTABLE" => {
+            "\
+The test script has no executable bit, so it can't be run. Make it executable with
+`chmod +x foo.sh`, or run cliche with `--fix-perms` to make every test script executable by
+whoever can read it. The script must also start with a shebang line, like `#!/bin/sh`."
+        }
+        "INTERPRETER_NOT_FOUND" => {
+            "\
+The interpreter given by the shebang line of the test script doesn't exist, like
+`#!/usr/bin/python` on a system with `/usr/bin/python3` only. Fix the path of the interpreter, or
+look it up in the `PATH` with `#!/usr/bin/env python3`."
+        }
+        "FILE_MISSPELLED" => {
+            "\
+A file next to the test script looks like a misspelled expectation file (`foo.ouy` for `foo.out`).
+Such a file is not read, so its expectation is silently ignored. The file is reported as a warning,
+and fails the test with `--strict`."
+        }
+        "SNAPSHOT_DUPLICATED" => {
+            "\
+Several tests have the same stdout (or stderr) snapshot, like a common usage banner: each change
+of the output modifies every snapshot. A snapshot made of a single line `@usage.out` references
+the shared snapshot `usage.out` (relative to the snapshot directory), verified and updated in
+place of the snapshot. Identical snapshots of at least 3 lines, shared by at least 3 tests, are
+reported as warnings with `--suggest-shared`."
+        }
+        "SNAPSHOT_CRLF" => {
+            "\
+A line of a stdout or stderr snapshot ends with CRLF (`\\r\\n`), often because the snapshot has
+been edited or checked out on Windows. The output of a test writing LF line endings doesn't match
+it. Convert the snapshot to LF line endings, or set `text eol=lf` for snapshots in
+`.gitattributes`. Reported by `cliche doctor`."
+        }
+        "SNAPSHOT_ORPHANED" => {
+            "\
+An expectation file (`foo.out`, `foo.exit`...) has no test script `foo.sh` next to it (or in the
+scripts directory of its snapshot root), and no test references it as a shared snapshot: the
+script has probably been renamed or deleted. Remove the file, or rename it after its script.
+Reported by `cliche doctor`."
+        }
+        "FILE_NOT_JSON" => {
+            "\
+The expected state file `foo.state.json` is not a valid JSON document."
+        }
+        "LEFTOVER_PROCESSES" => {
+            "\
+Processes spawned by the test script were still running after the script has exited, like a server
+started in the background and never stopped. They are killed, and reported as a warning naming
+their commands; with `--strict`, they fail the test."
+        }
+        "CHECK_DISK_USAGE" => {
+            "\
+The test script has left more data in its work directory (`CLICHE_WORK_DIR`) than allowed by its
+`max-disk` attribute, like `# cliche: max-disk=10M`. The size is the total size of the files left
+in the work directory once the script has exited."
+        }
+        "PATTERN_MATCHES_EMPTY" => {
+            "\
+A pattern of `foo.out.pattern` can match an empty string, like `<<<.*>>>`. A line made only of such
+patterns matches any line, and can mask real regressions: it's reported as a warning. With
+`--non-empty-patterns`, every pattern must consume at least one character (use `<<<.+>>>`
+instead of `<<<.*>>>` for instance), and the test fails otherwise."
+        }
+        "PATTERN_CONFLICT" => {
+            "\
+A test has both a snapshot `foo.out` and a pattern file `foo.out.pattern` (or `foo.err` and
+`foo.err.pattern`), and the patterns don't match the snapshot: no output can satisfy both files,
+and the test can never pass. The first line of the pattern file not matching the snapshot is
+reported as a warning, before the test is verified. With `--strict`, the test fails. Remove the
+outdated file, or update both from the same output."
+        }
+        "CONFIG_INVALID" => {
+            "\
+The configuration file `cliche.toml` is not valid. Suite invariants are given by arrays of regexes
+in an `[invariants]` table, under the `stdout` and `stderr` keys. The default locales of the tests
+are given by an array under the `locales` key, and the preferred snapshot variants (like `ci` for
+`foo.out.ci`) by an array under the `snapshot_variants` key."
+        }
+        "MANIFEST_INVALID" => {
+            "\
+A line of a manifest file is not valid. Each line is a test script path, optionally followed by
+`name=\"Display name\"`, `tags=a,b`, `nice=10` (priority, from -20 to 19), `cpus=0,2-3` (CPU
+affinity, Linux only), `locales=C,fr_FR.UTF-8` (locales the test is also run under, with
+`LC_ALL` set and snapshots like `foo.fr_FR.UTF-8.out`), `lock=gpu` (names of locks held while
+the test runs, tests holding the same lock being serialized), `max-disk=10M` (maximum size of
+the files left in the work directory), `formatter=\"jq -S .\"` (shell command applied to the
+actual stdout before it's verified), `description=\"...\"` (behavior guarded by the test,
+printed when it fails), `retry-until=\"30s every 2s\"` (reruns of the test until it passes,
+every second by default), `retries=2` (reruns of a failing test, the test being flaky if a rerun
+passes) and `compare=out:json,err:pattern` (comparators of expectation files, among exact,
+pattern, json, binary and checksum). Lines starting with `#` are comments."
+        }
+        "DIRECTIVE_INVALID" => {
+            "\
+A `# cliche: key=value` directive in the header comments of a test script is not valid. The keys
+are the attributes of a manifest line: `name`, `tags`, `nice`, `cpus`, `locales`, `lock`,
+`max-disk`, `formatter`, `description`, `retry-until`, `retries` and `compare`."
+        }
+        "ARGS_INVALID" => {
+            "\
+A line of the arguments file of a script (`foo.args`) is not valid. Each line is an argument
+passed to the script, without shell expansion. Lines are trimmed and empty lines are skipped,
+unless the argument is between double quotes: `\"  two spaces\"` keeps its leading spaces and
+`\"\"` is an empty argument. In a quoted argument, `\\\"` is a double quote and `\\\\` a backslash."
+        }
+        "CWD_INVALID" => {
+            "\
+The working directory file of a script (`foo.cwd`) doesn't give an existing directory. The file
+contains the path of the directory the script is run in, relative to the directory of the
+script. Without `foo.cwd`, a script is run in its own directory."
+        }
+        "ENV_INVALID" => {
+            "\
+A line of the environment file of a script (`foo.env`) is not valid. Each line is a `KEY=VALUE`
+variable added to the environment of the script, the value being optionally between double
+quotes and never expanded. A `# clear-env` line starts the script from an empty environment,
+instead of the environment of cliche. Empty lines and other lines starting with `#` are ignored."
+        }
+        "FRONT_MATTER_INVALID" => {
+            "\
+The front-matter of a snapshot (`foo.out` or `foo.err`) is not valid. A front-matter is made of
+`key: value` options between two `---` lines at the top of the snapshot, stripped before
+comparison. The options are `normalize`, a list of normalizations applied to the expected and
+actual outputs (`crlf` to replace `\\r\\n` by `\\n`, `ansi` to remove ANSI escape sequences), and
+`mode`, `exact` (by default) or `unordered` to compare lines in any order."
+        }
+        "INCLUDE_INVALID" => {
+            "\
+An include directive of an expectation file can't be expanded. A line made only of
+`<<<include:common/header.out>>>` is replaced by the content of `common/header.out` (relative to
+the expectation file directory) before comparison, newline included, so that shared parts of
+outputs (headers, version banners) live in one place. Included files can include other files, up
+to 8 levels deep. Snapshots with includes are not rewritten by `--update`."
+        }
+        "FILE_INVALID_REGEX" => {
+            "\
+A line of a regex file (like `foo.err.only`) is not a valid regular expression."
+        }
+        "CHECK_EXIT_CODE" => {
+            "\
+The exit code of the script is not the expected one. Without a `foo.exit` file, the expected
+exit code is 0. A script killed by a signal is reported with the signal (`signal 11 (SIGSEGV)`),
+and is only expected by `signal:11`, `!0` or `*`. The stderr of the script is printed under the
+error, prefixed with `|`."
+        }
+        "CHECK_STDOUT_LINE" => {
+            "\
+The stdout of the script is not equal to the snapshot `foo.out`. The first different line is
+printed, long lines being compared by chunks of 64 chars. If the snapshot has alternatives
+(`foo.out.alt1`, `foo.out.alt2` etc...), the stdout matches none of them and the difference with
+the closest one is printed.
+
+Values are delimited by `<` and `>`, and invisible chars are made visible: `[\\n]` is a newline,
+`[\\r]` a carriage return and `[\\tab]` a tab. An empty value means that the line is missing."
+        }
+        "CHECK_STDOUT_PATTERN" => {
+            "\
+A line of the stdout of the script doesn't match the pattern snapshot `foo.out.pattern`. In a
+pattern, text between `<<<` and `>>>` is a regex, the rest of the line being matched exactly.
+Common volatile values can be matched with macros, like `<<<%{UUID}>>>`: `NUMBER`, `UUID`,
+`IPV4`, `ISO8601` (a date, with an optional time), `TIMESTAMP` (a date and a time), `SEMVER`,
+`PATH` and `DURATION` (like `1m30s` or `250ms`). A line made only of `[...]` matches zero or more
+arbitrary lines, until the next line of the pattern matches. If the output contains `<<<` or `>>>`
+(like git conflict markers), a first line `# cliche: delimiters={{ }}` changes the delimiters of
+the patterns of the file.
+
+Values are delimited by `<` and `>`, and invisible chars are made visible: `[\\n]` is a newline,
+`[\\r]` a carriage return and `[\\tab]` a tab."
+        }
+        "CHECK_STDERR_PATTERN" => {
+            "\
+A line of the stderr of the script doesn't match the pattern snapshot `foo.err.pattern`. Patterns
+are written like in `foo.out.pattern`: text between `<<<` and `>>>` is a regex, the rest of the
+line being matched exactly. If `foo.err` exists too, stderr must also be equal to it."
+        }
+        "CHECK_STDOUT_LINE_COUNT" => {
+            "\
+The number of lines of the stdout of the script is not in the range of `foo.out.lines`."
+        }
+        "CHECK_MATCH_COUNT" => {
+            "\
+The number of lines of the stdout (or stderr) of the script matching a pattern is not in the
+expected range. Each line of `foo.out.count` (or `foo.err.count`) is a count or a range of counts
+followed by a regex, for instance `0 warning:` or `..3 deprecated`."
+        }
+        "CHECK_STATE" => {
+            "\
+The state written by the script doesn't match `foo.state.json`. Each script is run with a new empty
+directory in `CLICHE_WORK_DIR`, where it can write a `state.json` file. States are compared as JSON
+values: the order of object keys and the formatting are not significant. The path of the first
+difference is given (`$.users[1]` for instance)."
+        }
+        "CHECK_OUTPUT_JSON" => {
+            "\
+The stdout (or stderr) of the script doesn't match the JSON value of `foo.out.json` (or
+`foo.err.json`), or any expectation file compared with `compare=out:json`. Values are compared as
+JSON values: the order of object keys and the formatting are not significant. The path of the
+first difference is given (`$.users[1]` for instance)."
+        }
+        "CHECK_CHECKSUM" => {
+            "\
+The SHA-256 digest of the stdout (or stderr) of the script doesn't match the digest of
+`foo.out.sha256` (or `foo.err.sha256`), for outputs too large or too binary to be kept as
+snapshots. The file holds the digest in hex, like the output of `sha256sum`: the digest can be
+followed by a file name, for instance `./foo.sh | sha256sum > foo.out.sha256`."
+        }
+        "CHECK_INVARIANT" => {
+            "\
+A line of the stdout (or stderr) of the script matches a pattern forbidden for every test of the
+suite, given in the `[invariants]` table of `cliche.toml`. Invariants are checked even if the test
+fails, and reported separately."
+        }
+        "CHECK_STDOUT_STDERR_ONLY" => {
+            "\
+A line of the stdout of the script matches a pattern that must only appear on stderr (given by
+`foo.err.only` or `--stderr-only`): diagnostics are probably written to the wrong stream.
+
+Values are delimited by `<` and `>`, and invisible chars are made visible: `[\\n]` is a newline,
+`[\\r]` a carriage return and `[\\tab]` a tab."
+        }
+        "STDOUT_PATTERN_FILE_INVALID" => {
+            "\
+A line of the pattern snapshot `foo.out.pattern` has an invalid regex between `<<<` and `>>>`."
+        }
+        "STDERR_PATTERN_FILE_INVALID" => {
+            "\
+A line of the pattern snapshot `foo.err.pattern` has an invalid regex between `<<<` and `>>>`."
+        }
+        "CHECK_POST_HOOK" => {
+            "\
+The post-hook `foo.post.sh` has exited with a non-zero exit code. The hook runs after a successful
+verification, with the captured outputs of the script in the files given by `CLICHE_ACTUAL_STDOUT`,
+`CLICHE_ACTUAL_STDERR` and `CLICHE_ACTUAL_EXIT`."
+        }
+        "CHECK_FORMATTER" => {
+            "\
+The formatter of the test has exited with a non-zero exit code. The formatter is a shell command
+given by the `formatter` attribute of the test, like `# cliche: formatter=jq -S .`: it's fed with
+the actual stdout of the script, and its own stdout is verified instead. The stderr of the formatter
+is printed under the error, prefixed with `|`."
+        }
+        "CHECK_SCREEN_LINE" => {
+            "\
+The terminal screen rendered from the stdout of the script is not equal to the snapshot
+`foo.screen`. Stdout is replayed on an 80x24 terminal, applying cursor movements and erasures,
+and the text left on the screen is compared (colors are ignored). The script is run with `COLUMNS`
+and `LINES` set to the size of the terminal.
+
+With a `foo.screen.sizes` file, the script is also run once per listed size, each screen being
+compared to its own snapshot (`foo.120x40.screen` for a 120x40 terminal).
+
+Values are delimited by `<` and `>`, and invisible chars are made visible: `[\\n]` is a newline,
+`[\\r]` a carriage return and `[\\tab]` a tab. An empty value means that the line is missing."
+        }
+        "CHECK_STDERR_LINE" => {
+            "\
+The stderr of the script is not equal to the snapshot `foo.err`. The first different line is
+printed, long lines being compared by chunks of 64 chars. If the snapshot has alternatives
+(`foo.err.alt1`, `foo.err.alt2` etc...), the stderr matches none of them and the difference with
+the closest one is printed.
+
+Values are delimited by `<` and `>`, and invisible chars are made visible: `[\\n]` is a newline,
+`[\\r]` a carriage return and `[\\tab]` a tab. An empty value means that the line is missing."
+        }
+        "CHECK_STDOUT_BYTES" => {
+            "\
+The stdout of the script is not equal to the snapshot `foo.out`. As the snapshot or the stdout is
+not valid UTF-8, they're compared byte to byte: the offset of the first different byte is printed,
+with a hex dump of the bytes around it, the different byte being highlighted."
+        }
+        "CHECK_STDERR_BYTES" => {
+            "\
+The stderr of the script is not equal to the snapshot `foo.err`. As the snapshot or the stderr is
+not valid UTF-8, they're compared byte to byte: the offset of the first different byte is printed,
+with a hex dump of the bytes around it, the different byte being highlighted."
+        }
+        _ => return None,
+    };
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_explain() {
+        let error = Error::FileNotUtf8 {
+            path: PathBuf::from("foo.out.pattern"),
+        };
+        assert!(explain(error.code()).is_some());
+        assert!(explain("check_exit_code").is_some());
+        assert_eq!(explain("UNKNOWN"), None);
+    }
+}
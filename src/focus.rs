@@ -0,0 +1,137 @@
+//! Diagnostics of the focused mode (`--only`), when a single test is run with maximum verbosity.
+use crate::command::{CommandResult, CommandSpec};
+use crate::term_format;
+use crate::text::{Style, StyledString};
+use crate::verify;
+use std::path::Path;
+use std::{fs, io};
+use tempfile::TempDir;
+
+/// Prints the resolved spec of a command: its script and all the expectation files found.
+pub fn print_spec(spec: &CommandSpec) {
+    let blue_bold = Style::new().blue().bold();
+    let mut s = StyledString::new();
+    s.push_with("spec:", Style::new().bold());
+    s.push("\n");
+    s.push_with("  script: ", blue_bold);
+    s.push(&spec.cmd_path().display().to_string());
+    s.push("\n");
+    for (kind, path) in spec.expectation_files() {
+        s.push_with(&format!("  {kind}: "), blue_bold);
+        s.push(&path.display().to_string());
+        s.push("\n");
+    }
+    eprint!("{}", s.to_string(term_format()));
+}
+
+/// Prints the environment the command `spec` runs with. An invalid environment file is reported
+/// when the command is run.
+pub fn print_env(spec: &CommandSpec) {
+    let Ok(vars) = spec.resolved_env() else {
+        return;
+    };
+    let mut s = StyledString::new();
+    s.push_with("env:", Style::new().bold());
+    s.push("\n");
+    for (key, value) in vars {
+        s.push_with(&format!("  {key}"), Style::new().blue());
+        s.push(&format!("={value}\n"));
+    }
+    eprint!("{}", s.to_string(term_format()));
+}
+
+/// Prints the complete diff of stdout and stderr against their expected snapshots.
+pub fn print_full_diff(spec: &CommandSpec, result: &CommandResult) {
+    // Outputs are compared without the front-matter of their snapshots, and normalized as
+    // declared in the front-matter.
+    if spec.has_stdout()
+        && let Ok(snapshot) = spec.stdout()
+        && let Ok(front_matter) = spec.stdout_front_matter(&snapshot)
+    {
+        let expected = front_matter.normalize(front_matter.body(&snapshot));
+        let actual = front_matter.normalize(result.stdout());
+        print_stream_diff("stdout", &expected, &actual);
+    }
+    if spec.has_stderr()
+        && let Ok(snapshot) = spec.stderr()
+        && let Ok(front_matter) = spec.stderr_front_matter(&snapshot)
+    {
+        let expected = front_matter.normalize(front_matter.body(&snapshot));
+        let actual = front_matter.normalize(result.stderr());
+        print_stream_diff("stderr", &expected, &actual);
+    }
+}
+
+fn print_stream_diff(stream: &str, expected: &[u8], actual: &[u8]) {
+    let expected = String::from_utf8_lossy(expected);
+    let actual = String::from_utf8_lossy(actual);
+    if expected == actual {
+        return;
+    }
+    let mut s = StyledString::new();
+    s.push_with(&format!("{stream} diff"), Style::new().bold());
+    s.push(" (");
+    s.push_with("- expected", Style::new().red());
+    s.push(", ");
+    s.push_with("+ actual", Style::new().green());
+    s.push("):\n");
+    s.append(verify::unified_diff(&expected, &actual));
+    eprint!("{}", s.to_string(term_format()));
+}
+
+/// Writes the actual outputs of a test `name` in a new temporary directory, private to this run
+/// and removed when dropped.
+pub fn write_artifacts(name: &str, result: &CommandResult) -> Result<TempDir, io::Error> {
+    let dir = TempDir::with_prefix(format!("cliche-{}-", dir_name(name)))?;
+    let path = dir.path();
+    fs::write(path.join("stdout"), result.stdout())?;
+    fs::write(path.join("stderr"), result.stderr())?;
+    fs::write(
+        path.join("exit"),
+        format!("{}\n", result.termination().to_expectation()),
+    )?;
+    Ok(dir)
+}
+
+/// Returns a directory name for a test `name`, chars other than alphanumerics and dashes being
+/// replaced by underscores.
+pub fn dir_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Prints the directory where artifacts have been written.
+pub fn print_artifacts(dir: &Path) {
+    let mut s = StyledString::new();
+    s.push_with("artifacts:", Style::new().bold());
+    s.push(" ");
+    s.push(&dir.display().to_string());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_artifacts() {
+        let result = CommandResult::new(3.into(), b"foo\n", b"bar\n");
+        let dir = write_artifacts("tests/foo.sh", &result).unwrap();
+        let other = write_artifacts("tests/foo.sh", &result).unwrap();
+        assert_ne!(dir.path(), other.path());
+        assert_eq!(fs::read(dir.path().join("stdout")).unwrap(), b"foo\n");
+        assert_eq!(fs::read(dir.path().join("stderr")).unwrap(), b"bar\n");
+        assert_eq!(fs::read(dir.path().join("exit")).unwrap(), b"3\n");
+
+        let path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,72 @@
+//! Minimal glob patterns used to select tests.
+//!
+//! - `*` matches any sequence of characters except `/`,
+//! - `**` matches any sequence of characters, including `/`,
+//! - `?` matches a single character except `/`.
+//!
+//! A pattern without `/` is matched against the file name of a path, otherwise against the whole
+//! path.
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Glob(String);
+
+impl Glob {
+    pub fn new(pattern: &str) -> Self {
+        Glob(pattern.to_string())
+    }
+
+    /// Returns `true` if `path` matches this glob.
+    pub fn is_match(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let text = if self.0.contains('/') {
+            path.as_str()
+        } else {
+            path.rsplit('/').next().unwrap_or(&path)
+        };
+        let pattern = self.0.chars().collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+        match_from(&pattern, &text)
+    }
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| match_from(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            let max = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=max).any(|i| match_from(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && match_from(rest, &text[1..]),
+        [p, rest @ ..] => matches!(text, [c, ..] if c == p) && match_from(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_file_name() {
+        let glob = Glob::new("login*");
+        assert!(glob.is_match(Path::new("tests/login.sh")));
+        assert!(glob.is_match(Path::new("login_expired.sh")));
+        assert!(!glob.is_match(Path::new("login/logout.sh")));
+
+        let glob = Glob::new("test_??.sh");
+        assert!(glob.is_match(Path::new("a/test_01.sh")));
+        assert!(!glob.is_match(Path::new("a/test_1.sh")));
+    }
+
+    #[test]
+    fn test_match_path() {
+        let glob = Glob::new("tests/*.sh");
+        assert!(glob.is_match(Path::new("tests/login.sh")));
+        assert!(!glob.is_match(Path::new("tests/auth/login.sh")));
+
+        let glob = Glob::new("tests/**/login.sh");
+        assert!(glob.is_match(Path::new("tests/auth/v2/login.sh")));
+        assert!(!glob.is_match(Path::new("other/auth/login.sh")));
+    }
+}
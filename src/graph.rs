@@ -0,0 +1,304 @@
+//! Graph of the tests of a suite and of what they share, to audit why tests are serialized.
+//!
+//! `cliche graph [--format dot|json] [--no-ignore] [--snapshot-root SCRIPTS_DIR=SNAPSHOTS_DIR]...
+//! FILES...`
+//! prints a graph whose nodes are the tests, their post-hooks, the shared snapshots they
+//! reference, the fixtures used by several tests, and their locks. Tests linked to the same lock
+//! are serialized by the scheduler.
+use crate::bundle;
+use crate::command::CommandSpec;
+use crate::json::JsonValue;
+use crate::suite::{self, SnapshotRoot};
+use std::env;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Format of a printed graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/// Kind of a node of the graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum NodeKind {
+    Test,
+    PostHook,
+    Snapshot,
+    Fixture,
+    Lock,
+}
+
+impl NodeKind {
+    fn name(self) -> &'static str {
+        match self {
+            NodeKind::Test => "test",
+            NodeKind::PostHook => "post-hook",
+            NodeKind::Snapshot => "snapshot",
+            NodeKind::Fixture => "fixture",
+            NodeKind::Lock => "lock",
+        }
+    }
+
+    /// Returns the DOT shape of the nodes of this kind.
+    fn shape(self) -> &'static str {
+        match self {
+            NodeKind::Test => "box",
+            NodeKind::PostHook => "component",
+            NodeKind::Snapshot | NodeKind::Fixture => "note",
+            NodeKind::Lock => "diamond",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Node {
+    kind: NodeKind,
+    label: String,
+}
+
+impl Node {
+    /// Returns the identifier of this node, unique in a graph.
+    fn id(&self) -> String {
+        format!("{}:{}", self.kind.name(), self.label)
+    }
+}
+
+/// A graph of tests, each edge going from a test to a node of another kind.
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    /// Adds a node, if not already in the graph, and returns its index.
+    fn add_node(&mut self, kind: NodeKind, label: &str) -> usize {
+        let node = Node {
+            kind,
+            label: label.to_string(),
+        };
+        match self.nodes.iter().position(|n| *n == node) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Adds an edge from the test `from` to a node, added if not already in the graph.
+    fn add_edge(&mut self, from: usize, kind: NodeKind, label: &str) {
+        let to = self.add_node(kind, label);
+        if !self.edges.contains(&(from, to)) {
+            self.edges.push((from, to));
+        }
+    }
+
+    /// Removes the nodes of a `kind` linked to a single test, and their edges.
+    fn retain_shared(&mut self, kind: NodeKind) {
+        let unshared = (0..self.nodes.len())
+            .filter(|i| self.nodes[*i].kind == kind)
+            .filter(|i| self.edges.iter().filter(|(_, to)| to == i).count() < 2)
+            .collect::<Vec<_>>();
+        let nodes = std::mem::take(&mut self.nodes);
+        let edges = std::mem::take(&mut self.edges);
+        let mut indices = vec![];
+        for (i, node) in nodes.into_iter().enumerate() {
+            if unshared.contains(&i) {
+                indices.push(None);
+            } else {
+                indices.push(Some(self.nodes.len()));
+                self.nodes.push(node);
+            }
+        }
+        self.edges = edges
+            .into_iter()
+            .filter_map(|(from, to)| Some((indices[from]?, indices[to]?)))
+            .collect();
+    }
+
+    fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut s = String::new();
+        let _ = writeln!(s, "digraph cliche {{");
+        let _ = writeln!(s, "  rankdir=LR;");
+        for node in &self.nodes {
+            let _ = writeln!(
+                s,
+                "  {} [label={}, shape={}];",
+                quote(&node.id()),
+                quote(&node.label),
+                node.kind.shape()
+            );
+        }
+        for (from, to) in &self.edges {
+            let _ = writeln!(
+                s,
+                "  {} -> {};",
+                quote(&self.nodes[*from].id()),
+                quote(&self.nodes[*to].id())
+            );
+        }
+        let _ = writeln!(s, "}}");
+        s
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                JsonValue::object(vec![
+                    ("id", node.id().into()),
+                    ("kind", node.kind.name().into()),
+                    ("label", node.label.as_str().into()),
+                ])
+            })
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                JsonValue::object(vec![
+                    ("from", self.nodes[*from].id().into()),
+                    ("to", self.nodes[*to].id().into()),
+                ])
+            })
+            .collect();
+        JsonValue::object(vec![
+            ("nodes", JsonValue::Array(nodes)),
+            ("edges", JsonValue::Array(edges)),
+        ])
+    }
+}
+
+/// Runs `cliche graph`, given the arguments after `graph`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut format = GraphFormat::Dot;
+    let mut roots: Vec<SnapshotRoot> = vec![];
+    let mut files = vec![];
+    let mut ignore = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("dot") => format = GraphFormat::Dot,
+                Some("json") => format = GraphFormat::Json,
+                Some(value) => {
+                    return Err(format!(
+                        "invalid value <{value}> for '{arg}', expected dot or json"
+                    ));
+                }
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--snapshot-root" => match args.next() {
+                Some(root) => roots.push(root.parse()?),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            "--no-ignore" => ignore = false,
+            file => files.push(PathBuf::from(file)),
+        }
+    }
+    if files.is_empty() {
+        return Err("no tests to graph".to_string());
+    }
+    let cwd = env::current_dir().map_err(|err| err.to_string())?;
+    let graph = build(&files, &roots, ignore, &cwd)?;
+    match format {
+        GraphFormat::Dot => print!("{}", graph.to_dot()),
+        GraphFormat::Json => println!("{}", graph.to_json()),
+    }
+    Ok(())
+}
+
+/// Builds the graph of the tests of `files`, run in the directory `cwd`.
+fn build(
+    files: &[PathBuf],
+    roots: &[SnapshotRoot],
+    ignore: bool,
+    cwd: &Path,
+) -> Result<Graph, String> {
+    let tests = suite::collect(files, ignore).map_err(|err| err.message())?;
+    let relative = |path: &Path| {
+        let path = path.strip_prefix(cwd).unwrap_or(path);
+        path.display().to_string()
+    };
+    let mut graph = Graph::default();
+    for test in &tests {
+        let snapshot_base = suite::snapshot_base(roots, &test.path);
+        let spec = CommandSpec::with_snapshot_base(&test.path, &snapshot_base)
+            .map_err(|err| format!("cannot read test {}: {err}", test.path.display()))?;
+        let from = graph.add_node(NodeKind::Test, &test.display_name());
+        if let Some(hook) = spec.post_hook() {
+            graph.add_edge(from, NodeKind::PostHook, &relative(hook));
+        }
+        for ext in ["out", "err"] {
+            // An unreadable snapshot is reported when the test is run.
+            if let Ok(Some(shared)) = spec.shared_snapshot(ext) {
+                graph.add_edge(from, NodeKind::Snapshot, &relative(&shared));
+            }
+        }
+        let text = std::fs::read_to_string(&test.path).unwrap_or_default();
+        for fixture in bundle::fixtures(&text, cwd) {
+            graph.add_edge(from, NodeKind::Fixture, &relative(&fixture));
+        }
+        for lock in &test.locks {
+            graph.add_edge(from, NodeKind::Lock, lock);
+        }
+    }
+    // Fixtures used by a single test don't explain anything about the suite.
+    graph.retain_shared(NodeKind::Fixture);
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(dir.join("data.txt"), "").unwrap();
+        fs::write(dir.join("other.txt"), "").unwrap();
+        fs::write(dir.join("a.sh"), "# cliche: lock=db\ncat data.txt\n").unwrap();
+        fs::write(dir.join("a.post.sh"), "").unwrap();
+        fs::write(
+            dir.join("b.sh"),
+            "# cliche: lock=db\ncat data.txt other.txt\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.out"), "@usage.out\n").unwrap();
+        fs::write(dir.join("usage.out"), "Usage: tool\n").unwrap();
+
+        let files = [dir.join("a.sh"), dir.join("b.sh")];
+        let graph = build(&files, &[], true, dir).unwrap();
+        let ids = graph.nodes.iter().map(Node::id).collect::<Vec<_>>();
+        let a = format!("test:{}", dir.join("a.sh").display());
+        let b = format!("test:{}", dir.join("b.sh").display());
+        assert_eq!(
+            ids,
+            vec![
+                a.clone(),
+                "post-hook:a.post.sh".to_string(),
+                "fixture:data.txt".to_string(),
+                "lock:db".to_string(),
+                b.clone(),
+                "snapshot:usage.out".to_string(),
+            ]
+        );
+        assert_eq!(graph.edges.len(), 6);
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph cliche {\n"));
+        assert!(dot.contains(&format!("  \"{b}\" -> \"lock:db\";\n")));
+        assert!(dot.contains("  \"lock:db\" [label=\"db\", shape=diamond];\n"));
+
+        let json = graph.to_json().to_string();
+        assert!(json.contains(r#"{"id":"lock:db","kind":"lock","label":"db"}"#));
+    }
+}
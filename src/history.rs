@@ -0,0 +1,160 @@
+//! History of test durations, used to flag tests that suddenly run much slower than usual.
+//!
+//! The history is a text file, with one line per test: the test path, its mean duration in
+//! microseconds and the number of runs the mean is computed on, separated by tabulations.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+use std::{fs, io};
+
+/// A test is slow if it runs this times slower than its baseline...
+const SLOW_FACTOR: u32 = 3;
+/// ... and at least this slower, to ignore the noise on very fast tests.
+const SLOW_MIN_DELTA: Duration = Duration::from_millis(100);
+/// Number of runs a baseline must be computed on before flagging slow tests.
+const MIN_RUNS: u32 = 3;
+/// The mean is computed as a moving average over (about) this number of runs.
+const WINDOW: u32 = 10;
+
+/// The typical duration of a test.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Baseline {
+    pub mean: Duration,
+    pub runs: u32,
+}
+
+impl Baseline {
+    /// Returns `true` if `duration` is much slower than this baseline.
+    pub fn is_slow(&self, duration: Duration) -> bool {
+        self.runs >= MIN_RUNS
+            && duration > self.mean * SLOW_FACTOR
+            && duration - self.mean > SLOW_MIN_DELTA
+    }
+}
+
+/// Baselines of tests, by test path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct History {
+    baselines: BTreeMap<String, Baseline>,
+}
+
+impl History {
+    /// Loads the history file at `path`, a missing file being an empty history.
+    ///
+    /// The history is a cache: invalid lines are ignored.
+    pub fn load(path: &Path) -> Result<History, io::Error> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(History::parse(&text))
+    }
+
+    fn parse(text: &str) -> History {
+        let baselines = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let test = fields.next()?;
+                let mean = fields.next()?.parse::<u64>().ok()?;
+                let runs = fields.next()?.parse::<u32>().ok()?;
+                let baseline = Baseline {
+                    mean: Duration::from_micros(mean),
+                    runs,
+                };
+                Some((test.to_string(), baseline))
+            })
+            .collect();
+        History { baselines }
+    }
+
+    /// Saves this history to the file at `path`.
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        fs::write(path, self.to_text())
+    }
+
+    fn to_text(&self) -> String {
+        self.baselines
+            .iter()
+            .map(|(test, b)| format!("{test}\t{}\t{}\n", b.mean.as_micros(), b.runs))
+            .collect()
+    }
+
+    /// Returns the baseline of the test at `test` path.
+    pub fn baseline(&self, test: &str) -> Option<Baseline> {
+        self.baselines.get(test).copied()
+    }
+
+    /// Records a run of `duration` for the test at `test` path.
+    pub fn record(&mut self, test: &str, duration: Duration) {
+        let baseline = self.baselines.entry(test.to_string()).or_insert(Baseline {
+            mean: duration,
+            runs: 0,
+        });
+        let weight = (baseline.runs + 1).min(WINDOW);
+        let mean = baseline.mean.as_secs_f64();
+        let mean = mean + (duration.as_secs_f64() - mean) / weight as f64;
+        baseline.mean = Duration::from_secs_f64(mean);
+        baseline.runs = baseline.runs.saturating_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut history = History::default();
+        history.record("a.sh", Duration::from_millis(100));
+        history.record("a.sh", Duration::from_millis(200));
+        history.record("a.sh", Duration::from_millis(300));
+        assert_eq!(
+            history.baseline("a.sh"),
+            Some(Baseline {
+                mean: Duration::from_millis(200),
+                runs: 3
+            })
+        );
+        assert_eq!(history.baseline("b.sh"), None);
+    }
+
+    #[test]
+    fn test_is_slow() {
+        let baseline = Baseline {
+            mean: Duration::from_millis(200),
+            runs: 3,
+        };
+        assert!(!baseline.is_slow(Duration::from_millis(500)));
+        assert!(baseline.is_slow(Duration::from_millis(700)));
+
+        // Fast tests are not flagged on small variations.
+        let baseline = Baseline {
+            mean: Duration::from_millis(10),
+            runs: 3,
+        };
+        assert!(!baseline.is_slow(Duration::from_millis(50)));
+
+        // Not enough runs to have a reliable baseline.
+        let baseline = Baseline {
+            mean: Duration::from_millis(200),
+            runs: 2,
+        };
+        assert!(!baseline.is_slow(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_parse_and_to_text() {
+        let text = "a.sh\t200000\t3\ninvalid line\nb.sh\t1500\t12\n";
+        let history = History::parse(text);
+        assert_eq!(
+            history.baseline("b.sh"),
+            Some(Baseline {
+                mean: Duration::from_micros(1500),
+                runs: 12
+            })
+        );
+        assert_eq!(history.to_text(), "a.sh\t200000\t3\nb.sh\t1500\t12\n");
+    }
+}
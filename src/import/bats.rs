@@ -0,0 +1,194 @@
+//! Conversion of [BATS](https://github.com/bats-core/bats-core) tests.
+//!
+//! Each `@test` block running a single command with `run` is converted. Its exit code and
+//! output are taken from the usual assertions:
+//!
+//! ```text
+//! @test "Print the version" {
+//!   run my-cmd --version
+//!   [ "$status" -eq 0 ]
+//!   [ "$output" = "my-cmd 1.0" ]
+//! }
+//! ```
+//!
+//! Golden files compared with `diff` or `assert_output "$(cat FILE)"` are read from the
+//! directory of the BATS file. As `$output` merges stderr and stdout, commands are run with
+//! `2>&1`. Other lines of a test are ignored.
+use crate::import::ImportedTest;
+use std::fs;
+use std::path::Path;
+
+/// Parses the tests of a BATS file `text`, golden files being relative to `dir`.
+pub fn parse(text: &str, dir: &Path) -> Result<Vec<ImportedTest>, String> {
+    let mut tests = vec![];
+    let mut test: Option<ImportedTest> = None;
+    for (index, line) in text.lines().enumerate() {
+        let row = index + 1;
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix("@test ") {
+            let name = header.trim_end_matches('{').trim();
+            test = Some(ImportedTest {
+                name: Some(parse_word(name).0),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(t) = test.as_mut() else {
+            continue;
+        };
+        if line == "}" {
+            if t.command.is_empty() {
+                return Err(format!("test ending at line {row} has no run command"));
+            }
+            tests.extend(test.take());
+            continue;
+        }
+        if let Some(command) = line.strip_prefix("run ") {
+            t.command = format!("{} 2>&1", command.trim());
+        } else if line == "assert_success" {
+            t.exit_code = Some(0);
+        } else if let Some(code) = line.strip_prefix("assert_failure") {
+            t.exit_code = Some(code.trim().parse::<i32>().unwrap_or(1));
+        } else if let Some(code) = status_assertion(line) {
+            t.exit_code = Some(code);
+        } else if let Some(output) = output_assertion(line, dir)? {
+            t.stdout = Some(output);
+        }
+    }
+    Ok(tests)
+}
+
+/// Returns the exit code checked by a `[ "$status" -eq N ]` line.
+fn status_assertion(line: &str) -> Option<i32> {
+    let condition = line.strip_prefix("[ ")?.strip_suffix(" ]")?;
+    let (status, code) = condition.split_once(" -eq ")?;
+    if !matches!(status, "$status" | "\"$status\"") {
+        return None;
+    }
+    code.trim().trim_matches('"').parse().ok()
+}
+
+/// Returns the output checked by a line, which can be `[ "$output" = "..." ]`,
+/// `assert_output "..."`, `assert_output "$(cat FILE)"` or `diff <(echo "$output") FILE`.
+fn output_assertion(line: &str, dir: &Path) -> Result<Option<String>, String> {
+    let expected =
+        if let Some(condition) = line.strip_prefix("[ ").and_then(|l| l.strip_suffix(" ]")) {
+            let Some((left, right)) = condition
+                .split_once(" = ")
+                .or_else(|| condition.split_once(" == "))
+            else {
+                return Ok(None);
+            };
+            if left != "\"$output\"" {
+                return Ok(None);
+            }
+            parse_word(right.trim()).0
+        } else if let Some(value) = line.strip_prefix("assert_output ") {
+            if value.starts_with('-') {
+                return Ok(None);
+            }
+            parse_word(value.trim()).0
+        } else if let Some(args) = line.strip_prefix("diff ") {
+            let (_, rest) = parse_word(args.trim());
+            let file = rest.split_whitespace().last().unwrap_or("");
+            format!("$(cat {file})")
+        } else {
+            return Ok(None);
+        };
+
+    // A golden file
+    if let Some(file) = expected
+        .strip_prefix("$(cat ")
+        .and_then(|e| e.strip_suffix(')'))
+    {
+        let file = file
+            .trim_matches('"')
+            .replace("$BATS_TEST_DIRNAME/", "")
+            .replace("${BATS_TEST_DIRNAME}/", "");
+        let path = dir.join(&file);
+        let output = fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read golden file {}: {err}", path.display()))?;
+        return Ok(Some(output));
+    }
+    // `$output` is trimmed of its trailing newline.
+    Ok(Some(format!("{expected}\n")))
+}
+
+/// Parses the first shell word of `s`, quoted or not, and returns it with the rest of `s`.
+fn parse_word(s: &str) -> (String, &str) {
+    let mut word = String::new();
+    let mut chars = s.char_indices();
+    let mut quote = None;
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, c) if c.is_whitespace() => return (word, &s[i..]),
+            (Some('"'), '\\') => match chars.next() {
+                Some((_, next)) if "\"\\$`".contains(next) => word.push(next),
+                Some((_, next)) => {
+                    word.push('\\');
+                    word.push(next);
+                }
+                None => word.push('\\'),
+            },
+            (None, '\\') => word.extend(chars.next().map(|(_, next)| next)),
+            (_, c) => word.push(c),
+        }
+    }
+    (word, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(dir.join("help.txt"), "Usage: my-cmd\n").unwrap();
+        let text = r#"#!/usr/bin/env bats
+
+setup() {
+  export FOO=bar
+}
+
+@test "Print the version" {
+  run my-cmd --version
+  [ "$status" -eq 0 ]
+  [ "$output" = "my-cmd \"1.0\"" ]
+}
+
+@test "Print help" {
+  run my-cmd --help
+  assert_failure 64
+  diff <(echo "$output") "$BATS_TEST_DIRNAME/help.txt"
+}
+"#;
+        assert_eq!(
+            parse(text, dir).unwrap(),
+            vec![
+                ImportedTest {
+                    name: Some("Print the version".to_string()),
+                    command: "my-cmd --version 2>&1".to_string(),
+                    stdout: Some("my-cmd \"1.0\"\n".to_string()),
+                    exit_code: Some(0),
+                    ..Default::default()
+                },
+                ImportedTest {
+                    name: Some("Print help".to_string()),
+                    command: "my-cmd --help 2>&1".to_string(),
+                    stdout: Some("Usage: my-cmd\n".to_string()),
+                    exit_code: Some(64),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            parse("@test \"foo\" {\n  true\n}\n", dir),
+            Err("test ending at line 3 has no run command".to_string())
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Conversion of test suites written for other snapshot tools to cliche files.
+//!
+//! The format of a file is detected from its extension:
+//! - trycmd console sessions (`.trycmd`, `.md`) and cases (`.toml`),
+//! - shelltest files (`.test`),
+//! - BATS files (`.bats`).
+//!
+//! Each converted test is written as a script, with its expectation files next to it.
+use crate::command;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod bats;
+mod shelltest;
+mod trycmd;
+
+/// A test converted from another format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportedTest {
+    /// Display name of the test, if the format has one.
+    pub name: Option<String>,
+    /// Shell command line run by the test.
+    pub command: String,
+    pub stdin: Option<String>,
+    pub stdout: Option<String>,
+    /// The expected stdout contains wildcards, it's written as a pattern snapshot.
+    pub stdout_is_pattern: bool,
+    pub stderr: Option<String>,
+    pub exit_code: Option<i32>,
+}
+
+/// Runs `cliche import [-o DIR] FILES...`, given the arguments after `import`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut out_dir = None;
+    let mut files = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(dir) => out_dir = Some(PathBuf::from(dir)),
+                None => return Err(format!("option '{arg}' requires a value")),
+            },
+            file => files.push(PathBuf::from(file)),
+        }
+    }
+    if files.is_empty() {
+        return Err("no file to import".to_string());
+    }
+    for file in &files {
+        for created in import_file(file, out_dir.as_deref())? {
+            eprintln!("Created {}", created.display());
+        }
+    }
+    Ok(())
+}
+
+/// Converts the tests of the file at `path`, writing cliche files in `out_dir` (the directory of
+/// the file if `None`). Returns the paths of the created files.
+pub fn import_file(path: &Path, out_dir: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+    let text =
+        command::read_text(path, command::DEFAULT_MAX_FILE_SIZE).map_err(|err| err.message())?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let tests = match ext {
+        "trycmd" | "md" => trycmd::parse_session(&text),
+        "toml" => trycmd::parse_case(&text, path),
+        "test" => shelltest::parse(&text),
+        "bats" => bats::parse(&text, path.parent().unwrap_or(Path::new(""))),
+        _ => Err(format!("unknown format of file {}", path.display())),
+    };
+    let tests = tests.map_err(|err| format!("invalid file {}: {err}", path.display()))?;
+
+    let dir = out_dir.unwrap_or(path.parent().unwrap_or(Path::new("")));
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut created = vec![];
+    for (index, test) in tests.iter().enumerate() {
+        let base = if tests.len() == 1 {
+            dir.join(stem.as_ref())
+        } else {
+            dir.join(format!("{stem}_{}", index + 1))
+        };
+        created.extend(write(test, &base)?);
+    }
+    Ok(created)
+}
+
+/// Writes the script and the expectation files of a `test`, named after `base` (`base.sh`,
+/// `base.out` etc...). Existing files are never overwritten.
+fn write(test: &ImportedTest, base: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = vec![("sh", script(test))];
+    if let Some(stdout) = test.stdout.as_ref().filter(|s| !s.is_empty()) {
+        let ext = if test.stdout_is_pattern {
+            "out.pattern"
+        } else {
+            "out"
+        };
+        files.push((ext, stdout.clone()));
+    }
+    if let Some(stderr) = test.stderr.as_ref().filter(|s| !s.is_empty()) {
+        files.push(("err", stderr.clone()));
+    }
+    if let Some(code) = test.exit_code.filter(|c| *c != 0) {
+        files.push(("exit", format!("{code}\n")));
+    }
+
+    let paths = files
+        .iter()
+        .map(|(ext, _)| base.with_extension(ext))
+        .collect::<Vec<_>>();
+    if let Some(path) = paths.iter().find(|p| p.exists()) {
+        return Err(format!("file {} already exists", path.display()));
+    }
+    if let Some(dir) = base.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    for (path, (_, content)) in paths.iter().zip(files.iter()) {
+        fs::write(path, content)
+            .map_err(|err| format!("cannot write file {}: {err}", path.display()))?;
+    }
+    set_executable(&paths[0])?;
+    Ok(paths)
+}
+
+/// Returns the shell script running a `test`, stdin being given with a here-document.
+fn script(test: &ImportedTest) -> String {
+    let mut script = "#!/bin/sh\n".to_string();
+    if let Some(name) = &test.name {
+        script.push_str(&format!("# cliche: name={name}\n"));
+    }
+    script.push_str(&test.command);
+    match &test.stdin {
+        Some(stdin) => {
+            script.push_str(" <<'CLICHE_EOF'\n");
+            script.push_str(stdin);
+            if !stdin.ends_with('\n') {
+                script.push('\n');
+            }
+            script.push_str("CLICHE_EOF\n");
+        }
+        None => script.push('\n'),
+    }
+    script
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).map_err(|err| err.to_string())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// Returns `arg` quoted for a POSIX shell, if needed.
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_import_file() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let path = dir.join("cli.test");
+        fs::write(&path, "echo hello\n>>>\nhello\n>>>= 0\n\nfalse\n>>>= 1\n").unwrap();
+
+        let created = import_file(&path, None).unwrap();
+        assert_eq!(
+            created,
+            vec![
+                dir.join("cli_1.sh"),
+                dir.join("cli_1.out"),
+                dir.join("cli_2.sh"),
+                dir.join("cli_2.exit"),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("cli_1.sh")).unwrap(),
+            "#!/bin/sh\necho hello\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("cli_2.exit")).unwrap(), "1\n");
+
+        // Existing files are not overwritten
+        assert!(import_file(&path, None).is_err());
+    }
+
+    #[test]
+    fn test_script() {
+        let test = ImportedTest {
+            name: Some("Sort lines".to_string()),
+            command: "sort".to_string(),
+            stdin: Some("b\na".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            script(&test),
+            "#!/bin/sh\n# cliche: name=Sort lines\nsort <<'CLICHE_EOF'\nb\na\nCLICHE_EOF\n"
+        );
+        assert_eq!(shell_quote("--name=foo"), "--name=foo");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
@@ -0,0 +1,164 @@
+//! Conversion of [shelltest](https://github.com/simonmichael/shelltestrunner) files.
+//!
+//! Both the original format (`<<<`, `>>>`, `>>>2`, `>>>=` sections after the command line) and
+//! the newer one (`$ command` followed by `<`, `>`, `>2`, `>=` sections) are supported:
+//!
+//! ```text
+//! # Lines starting with `#` outside of sections are comments.
+//! cat
+//! <<<
+//! foo
+//! >>>
+//! foo
+//! >>>= 0
+//! ```
+use crate::import::ImportedTest;
+
+/// Section of a shelltest test being parsed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Section {
+    Command,
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+/// Parses the tests of a shelltest file `text`.
+pub fn parse(text: &str) -> Result<Vec<ImportedTest>, String> {
+    let mut tests = vec![];
+    let mut test: Option<ImportedTest> = None;
+    let mut section = Section::Command;
+    for (index, line) in text.lines().enumerate() {
+        let row = index + 1;
+        let (marker, rest) = match line.split_once(' ') {
+            Some((marker, rest)) => (marker, rest.trim()),
+            None => (line.trim_end(), ""),
+        };
+        match marker {
+            "<<<" | "<" if test.is_some() => section = Section::Stdin,
+            ">>>" | ">" if test.is_some() => section = Section::Stdout,
+            ">>>2" | ">2" if test.is_some() => section = Section::Stderr,
+            ">>>=" | ">=" if test.is_some() => {
+                let code = rest
+                    .parse::<i32>()
+                    .map_err(|_| format!("unsupported exit code <{rest}> at line {row}"))?;
+                if let Some(mut t) = test.take() {
+                    t.exit_code = Some(code);
+                    tests.push(t);
+                }
+                section = Section::Command;
+                continue;
+            }
+            "$" => {
+                tests.extend(test.take());
+                test = Some(ImportedTest {
+                    command: rest.to_string(),
+                    ..Default::default()
+                });
+                section = Section::Command;
+                continue;
+            }
+            _ if section == Section::Command => {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                tests.extend(test.take());
+                test = Some(ImportedTest {
+                    command: line.trim().to_string(),
+                    ..Default::default()
+                });
+                continue;
+            }
+            _ => {
+                let Some(t) = test.as_mut() else {
+                    continue;
+                };
+                let content = match section {
+                    Section::Stdin => &mut t.stdin,
+                    Section::Stdout => &mut t.stdout,
+                    Section::Stderr => &mut t.stderr,
+                    Section::Command => unreachable!(),
+                };
+                let content = content.get_or_insert_with(String::new);
+                content.push_str(line);
+                content.push('\n');
+                continue;
+            }
+        }
+        // A section marker followed by a value is a regex expectation.
+        if !rest.is_empty() {
+            return Err(format!("unsupported expectation <{line}> at line {row}"));
+        }
+    }
+    tests.extend(test.take());
+    Ok(tests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format_1() {
+        let text = "\
+# Echo stdin
+cat
+<<<
+foo
+>>>
+foo
+>>>= 0
+
+ls missing
+>>>2
+ls: missing: No such file or directory
+>>>= 2
+";
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                ImportedTest {
+                    command: "cat".to_string(),
+                    stdin: Some("foo\n".to_string()),
+                    stdout: Some("foo\n".to_string()),
+                    exit_code: Some(0),
+                    ..Default::default()
+                },
+                ImportedTest {
+                    command: "ls missing".to_string(),
+                    stderr: Some("ls: missing: No such file or directory\n".to_string()),
+                    exit_code: Some(2),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_format_2() {
+        let text = "$ echo foo\n> \nfoo\n>= 0\n$ true\n";
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                ImportedTest {
+                    command: "echo foo".to_string(),
+                    stdout: Some("foo\n".to_string()),
+                    exit_code: Some(0),
+                    ..Default::default()
+                },
+                ImportedTest {
+                    command: "true".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            parse("echo foo\n>>> /fo+/\n"),
+            Err("unsupported expectation <>>> /fo+/> at line 2".to_string())
+        );
+        assert_eq!(
+            parse("false\n>>>= !0\n"),
+            Err("unsupported exit code <!0> at line 2".to_string())
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Conversion of [trycmd](https://docs.rs/trycmd) tests.
+//!
+//! A console session (`.trycmd` or `.md` file) lists commands prefixed by `$` (continued by lines
+//! prefixed by `>`), followed by their output and an optional `? status` line:
+//!
+//! ```text
+//! $ my-cmd --help
+//! Usage: my-cmd [OPTIONS]
+//! ? failed
+//! ```
+//!
+//! In a markdown file, only `console` and `trycmd` code blocks are read. As trycmd merges stderr
+//! into stdout, commands are run with `2>&1`. Wildcards (`[..]`, `...`, `[CWD]` etc...) make
+//! the expected output a pattern, `...` matching a single line.
+//!
+//! A case (`.toml` file) describes a single command, its outputs being in the case file or in
+//! companion `.stdin`, `.stdout` and `.stderr` files.
+use crate::import::{ImportedTest, shell_quote};
+use crate::toml;
+use crate::toml::Value;
+use std::fs;
+use std::path::Path;
+
+/// Parses the tests of a console session `text`.
+pub fn parse_session(text: &str) -> Result<Vec<ImportedTest>, String> {
+    let is_markdown = text.lines().any(|l| l.starts_with("```"));
+    let mut tests = vec![];
+    let mut test: Option<ImportedTest> = None;
+    // In a markdown file, lines are only read inside console code blocks.
+    let mut in_block = !is_markdown;
+    let mut continued = false;
+    for line in text.lines() {
+        if is_markdown && line.starts_with("```") {
+            let info = line.trim_start_matches('`').trim();
+            in_block = !in_block && matches!(info, "console" | "trycmd");
+            tests.extend(test.take().map(finish));
+            continue;
+        }
+        if !in_block {
+            continue;
+        }
+        if let Some(command) = line.strip_prefix("$ ") {
+            tests.extend(test.take().map(finish));
+            test = Some(ImportedTest {
+                command: command.to_string(),
+                ..Default::default()
+            });
+            continued = true;
+            continue;
+        }
+        let Some(t) = test.as_mut() else {
+            continue;
+        };
+        if let Some(command) = line.strip_prefix("> ").filter(|_| continued) {
+            t.command.push('\n');
+            t.command.push_str(command);
+            continue;
+        }
+        continued = false;
+        if let Some(status) = line.strip_prefix("? ") {
+            t.exit_code = Some(parse_status(status.trim())?);
+            continue;
+        }
+        let stdout = t.stdout.get_or_insert_with(String::new);
+        stdout.push_str(line);
+        stdout.push('\n');
+    }
+    tests.extend(test.take().map(finish));
+    Ok(tests)
+}
+
+/// Completes a test parsed from a console session: stderr is merged to stdout, and the
+/// expected output is converted to a pattern if it contains wildcards.
+fn finish(mut test: ImportedTest) -> ImportedTest {
+    test.command.push_str(" 2>&1");
+    if let Some(stdout) = &test.stdout {
+        let pattern = to_pattern(stdout);
+        if pattern != *stdout {
+            test.stdout = Some(pattern);
+            test.stdout_is_pattern = true;
+        }
+    }
+    test
+}
+
+/// Converts the trycmd wildcards of an `output` to cliche patterns.
+fn to_pattern(output: &str) -> String {
+    output
+        .split_inclusive('\n')
+        .map(|line| {
+            if line.trim_end() == "..." {
+                return line.replace("...", "<<<.*>>>");
+            }
+            line.replace("[..]", "<<<.*>>>")
+                .replace("[CWD]", "<<<.*>>>")
+                .replace("[ROOT]", "<<<.*>>>")
+                .replace("[EXE]", "")
+        })
+        .collect()
+}
+
+/// Parses a trycmd `status`: `success`, `failed` (converted to 1) or an exit code.
+fn parse_status(status: &str) -> Result<i32, String> {
+    match status {
+        "success" => Ok(0),
+        "failed" => Ok(1),
+        code => code
+            .parse::<i32>()
+            .map_err(|_| format!("unsupported status <{code}>")),
+    }
+}
+
+/// Parses the test of a case `text`, read from the file at `path`.
+pub fn parse_case(text: &str, path: &Path) -> Result<Vec<ImportedTest>, String> {
+    let mut bin = None;
+    let mut args = vec![];
+    let mut test = ImportedTest::default();
+    for (key, value) in toml::parse(text)? {
+        match (key.as_str(), value) {
+            ("bin.name", Value::String(name)) => bin = Some(name),
+            ("args", Value::Array(values)) => args = values,
+            ("args", Value::String(values)) => {
+                args = values.split_whitespace().map(str::to_string).collect();
+            }
+            ("status", Value::String(status)) => test.exit_code = Some(parse_status(&status)?),
+            ("status.code", Value::Integer(code)) => test.exit_code = Some(code),
+            ("stdin", Value::String(stdin)) => test.stdin = Some(stdin),
+            ("stdout", Value::String(stdout)) => test.stdout = Some(stdout),
+            ("stderr", Value::String(stderr)) => test.stderr = Some(stderr),
+            (key, _) => return Err(format!("unsupported key <{key}>")),
+        }
+    }
+    let Some(bin) = bin else {
+        return Err("missing key <bin.name>".to_string());
+    };
+    let companion = |ext: &str| fs::read_to_string(path.with_extension(ext)).ok();
+    test.stdin = test.stdin.or_else(|| companion("stdin"));
+    test.stdout = test.stdout.or_else(|| companion("stdout"));
+    test.stderr = test.stderr.or_else(|| companion("stderr"));
+    test.command = std::iter::once(&bin)
+        .chain(args.iter())
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(stdout) = &test.stdout {
+        let pattern = to_pattern(stdout);
+        test.stdout_is_pattern = pattern != *stdout;
+        test.stdout = Some(pattern);
+    }
+    Ok(vec![test])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_session() {
+        let text = "\
+# Usage
+
+```console
+$ my-cmd --help
+Usage: my-cmd [..]
+...
+? failed
+$ my-cmd --version
+my-cmd 1.0
+```
+
+```sh
+$ not read
+```
+";
+        assert_eq!(
+            parse_session(text).unwrap(),
+            vec![
+                ImportedTest {
+                    command: "my-cmd --help 2>&1".to_string(),
+                    stdout: Some("Usage: my-cmd <<<.*>>>\n<<<.*>>>\n".to_string()),
+                    stdout_is_pattern: true,
+                    exit_code: Some(1),
+                    ..Default::default()
+                },
+                ImportedTest {
+                    command: "my-cmd --version 2>&1".to_string(),
+                    stdout: Some("my-cmd 1.0\n".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_case() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("help.toml");
+        fs::write(path.with_extension("stdout"), "Usage: my-cmd\n").unwrap();
+        let text = r#"
+bin.name = "my-cmd"
+args = ["--help", "it's"]
+stdin = """
+foo
+bar
+"""
+
+[status]
+code = 2
+"#;
+        assert_eq!(
+            parse_case(text, &path).unwrap(),
+            vec![ImportedTest {
+                command: "my-cmd --help 'it'\\''s'".to_string(),
+                stdin: Some("foo\nbar\n".to_string()),
+                stdout: Some("Usage: my-cmd\n".to_string()),
+                exit_code: Some(2),
+                ..Default::default()
+            }]
+        );
+        assert_eq!(
+            parse_case("args = \"--help\"\n", &path),
+            Err("missing key <bin.name>".to_string())
+        );
+    }
+}
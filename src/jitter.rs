@@ -0,0 +1,205 @@
+//! Differences between the attempts of a retried test, reported when the test passes after a
+//! failing attempt.
+//!
+//! A test passing on retry is flaky: rather than only counting its attempts, cliche tells what
+//! differed between the last failing attempt and the passing one (outputs, timing, load of the
+//! machine), to help finding the cause of the flakiness.
+use crate::command::CommandResult;
+use crate::job::format_bytes;
+use crate::json::JsonValue;
+use std::time::Duration;
+
+/// Maximum length of an output line shown in a difference.
+const MAX_LINE_LEN: usize = 60;
+
+/// A run of a retried test, with the state of the machine when it started.
+pub struct Attempt {
+    result: CommandResult,
+    /// Start of the attempt, since the start of the first attempt.
+    started: Duration,
+    /// Load average of the machine over the last minute, if available.
+    load: Option<f64>,
+}
+
+/// A difference between the failing attempt and the passing attempt of a test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub what: String,
+    pub failing: String,
+    pub passing: String,
+}
+
+impl Difference {
+    fn new(what: &str, failing: String, passing: String) -> Self {
+        Difference {
+            what: what.to_string(),
+            failing,
+            passing,
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("what", self.what.as_str().into()),
+            ("failing", self.failing.as_str().into()),
+            ("passing", self.passing.as_str().into()),
+        ])
+    }
+}
+
+impl Attempt {
+    /// Returns an attempt of a test, given its `result`, captured with the current state of the
+    /// machine: attempts are captured right after the command has run.
+    pub fn new(result: CommandResult, started: Duration) -> Self {
+        Attempt {
+            result,
+            started,
+            load: load_average(),
+        }
+    }
+}
+
+/// Returns the differences between a `failing` attempt and a `passing` attempt of a test.
+///
+/// Timings are always given, other values only if they differ.
+pub fn diff(failing: &Attempt, passing: &Attempt) -> Vec<Difference> {
+    let (f, p) = (&failing.result, &passing.result);
+    let seconds = |d: Duration| format!("{:.3}s", d.as_secs_f64());
+    let mut differences = vec![
+        Difference::new(
+            "started",
+            seconds(failing.started),
+            seconds(passing.started),
+        ),
+        Difference::new("duration", seconds(f.elapsed()), seconds(p.elapsed())),
+    ];
+    if f.exit_code() != p.exit_code() {
+        differences.push(Difference::new(
+            "exit code",
+            f.exit_code().to_string(),
+            p.exit_code().to_string(),
+        ));
+    }
+    for (what, failing, passing) in [
+        ("stdout", f.stdout(), p.stdout()),
+        ("stderr", f.stderr(), p.stderr()),
+    ] {
+        if let Some((row, failing, passing)) = first_different_line(failing, passing) {
+            differences.push(Difference::new(
+                &format!("{what} line {row}"),
+                failing,
+                passing,
+            ));
+        }
+    }
+    let cpu = |r: &CommandResult| {
+        let usage = r.usage();
+        Some(usage.user_time? + usage.sys_time?)
+    };
+    if let (Some(failing), Some(passing)) = (cpu(f), cpu(p)) {
+        differences.push(Difference::new(
+            "cpu time",
+            seconds(failing),
+            seconds(passing),
+        ));
+    }
+    if let (Some(failing), Some(passing)) = (failing.load, passing.load)
+        && format!("{failing:.2}") != format!("{passing:.2}")
+    {
+        differences.push(Difference::new(
+            "load average",
+            format!("{failing:.2}"),
+            format!("{passing:.2}"),
+        ));
+    }
+    if f.disk_usage() != p.disk_usage() {
+        differences.push(Difference::new(
+            "disk usage",
+            format_bytes(f.disk_usage()),
+            format_bytes(p.disk_usage()),
+        ));
+    }
+    if f.leftovers() != p.leftovers() {
+        differences.push(Difference::new(
+            "leftover processes",
+            f.leftovers().len().to_string(),
+            p.leftovers().len().to_string(),
+        ));
+    }
+    differences
+}
+
+/// Returns the first line differing between two outputs, with its 1-based index.
+fn first_different_line(failing: &[u8], passing: &[u8]) -> Option<(usize, String, String)> {
+    if failing == passing {
+        return None;
+    }
+    let failing = String::from_utf8_lossy(failing);
+    let passing = String::from_utf8_lossy(passing);
+    let (mut f, mut p) = (failing.lines(), passing.lines());
+    let mut row = 1;
+    loop {
+        match (f.next(), p.next()) {
+            (Some(a), Some(b)) if a == b => row += 1,
+            (a, b) => {
+                let show = |line: Option<&str>| match line {
+                    Some(line) if line.chars().count() > MAX_LINE_LEN => {
+                        let line = line.chars().take(MAX_LINE_LEN).collect::<String>();
+                        format!("<{line}...>")
+                    }
+                    Some(line) => format!("<{line}>"),
+                    None => "(none)".to_string(),
+                };
+                return Some((row, show(a), show(b)));
+            }
+        }
+    }
+}
+
+/// Returns the load average of the machine over the last minute, on Linux.
+fn load_average() -> Option<f64> {
+    let text = std::fs::read_to_string("/proc/loadavg").ok()?;
+    text.split_whitespace().next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(exit_code: i32, stdout: &[u8], started: u64) -> Attempt {
+        let result = CommandResult::new(exit_code.into(), stdout, b"");
+        Attempt {
+            result,
+            started: Duration::from_millis(started),
+            load: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let failing = attempt(1, b"Starting\nNot ready\n", 0);
+        let passing = attempt(0, b"Starting\nReady\nDone\n", 1500);
+        let differences = diff(&failing, &passing)
+            .into_iter()
+            .map(|d| format!("{}: {} -> {}", d.what, d.failing, d.passing))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            differences,
+            vec![
+                "started: 0.000s -> 1.500s",
+                "duration: 0.000s -> 0.000s",
+                "exit code: 1 -> 0",
+                "stdout line 2: <Not ready> -> <Ready>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_first_different_line() {
+        assert_eq!(first_different_line(b"a\nb\n", b"a\nb\n"), None);
+        assert_eq!(
+            first_different_line(b"a\n", b"a\nb\n"),
+            Some((2, "(none)".to_string(), "<b>".to_string()))
+        );
+    }
+}
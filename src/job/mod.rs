@@ -0,0 +1,167 @@
+//! Containment of the processes spawned by a test.
+//!
+//! Each test script runs inside a [`Job`]: a process group on Unix, a Job Object on Windows. The
+//! job lets us kill everything the script has spawned (not only the script itself) and gives us
+//! some basic resource accounting.
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(unix)]
+pub use self::unix::Job;
+#[cfg(windows)]
+pub use self::windows::Job;
+
+/// Maximum number of CPUs a process can be pinned to.
+const MAX_CPUS: usize = 1024;
+
+/// Scheduling of the processes of a job.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Scheduling {
+    /// Niceness of the processes, from -20 (highest priority) to 19 (lowest priority). Only
+    /// supported on Unix.
+    pub nice: Option<i32>,
+    /// CPUs the processes are pinned to, all CPUs if empty. Only supported on Linux.
+    pub cpus: Vec<usize>,
+}
+
+impl Scheduling {
+    /// Parses a niceness, from -20 to 19.
+    pub fn parse_nice(s: &str) -> Result<i32, String> {
+        match s.trim().parse::<i32>() {
+            Ok(nice) if (-20..=19).contains(&nice) => Ok(nice),
+            _ => Err(format!("expecting a niceness from -20 to 19, found <{s}>")),
+        }
+    }
+
+    /// Parses a list of CPUs, like `0,2-3`.
+    pub fn parse_cpus(s: &str) -> Result<Vec<usize>, String> {
+        let err = || format!("expecting a list of CPUs like 0,2-3, found <{s}>");
+        let mut cpus = vec![];
+        for item in s.split(',') {
+            let (first, last) = item.split_once('-').unwrap_or((item, item));
+            let first = first.trim().parse::<usize>().map_err(|_| err())?;
+            let last = last.trim().parse::<usize>().map_err(|_| err())?;
+            if first > last || last >= MAX_CPUS {
+                return Err(err());
+            }
+            cpus.extend(first..=last);
+        }
+        Ok(cpus)
+    }
+}
+
+/// Resources consumed by a job.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// CPU time spent in user mode.
+    pub user_time: Option<Duration>,
+    /// CPU time spent in kernel mode.
+    pub sys_time: Option<Duration>,
+    /// Peak memory used by the job, in bytes.
+    pub peak_memory: Option<u64>,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(user_time) = self.user_time {
+            parts.push(format!("user {:.3}s", user_time.as_secs_f64()));
+        }
+        if let Some(sys_time) = self.sys_time {
+            parts.push(format!("sys {:.3}s", sys_time.as_secs_f64()));
+        }
+        if let Some(peak_memory) = self.peak_memory {
+            parts.push(format!("max rss {}", format_bytes(peak_memory)));
+        }
+        if parts.is_empty() {
+            return write!(f, "n/a");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Formats a number of `bytes` with a binary unit prefix.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for u in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    format!("{value:.1} {unit}")
+}
+
+/// Parses a number of bytes, with an optional binary unit prefix like `512K`, `100M` or `1G`.
+pub fn parse_bytes(s: &str) -> Result<u64, String> {
+    let err = || format!("expecting a size like 512K, 100M or 1G, found <{s}>");
+    let text = s.trim();
+    let text = text.strip_suffix("iB").unwrap_or(text);
+    let (digits, shift) = match text.char_indices().last() {
+        Some((i, 'K')) => (&text[..i], 10),
+        Some((i, 'M')) => (&text[..i], 20),
+        Some((i, 'G')) => (&text[..i], 30),
+        Some((i, 'T')) => (&text[..i], 40),
+        _ => (text.strip_suffix('B').unwrap_or(text), 0),
+    };
+    let value = digits.trim().parse::<u64>().map_err(|_| err())?;
+    value.checked_mul(1 << shift).ok_or_else(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scheduling() {
+        assert_eq!(Scheduling::parse_nice("10"), Ok(10));
+        assert!(Scheduling::parse_nice("20").is_err());
+        assert_eq!(Scheduling::parse_cpus("0,2-3"), Ok(vec![0, 2, 3]));
+        assert!(Scheduling::parse_cpus("3-2").is_err());
+        assert!(Scheduling::parse_cpus("").is_err());
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("512"), Ok(512));
+        assert_eq!(parse_bytes("512B"), Ok(512));
+        assert_eq!(parse_bytes("4K"), Ok(4096));
+        assert_eq!(parse_bytes("100M"), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_bytes("1GiB"), Ok(1024 * 1024 * 1024));
+        assert!(parse_bytes("").is_err());
+        assert!(parse_bytes("-1K").is_err());
+        assert!(parse_bytes("12X").is_err());
+    }
+
+    #[test]
+    fn test_display_usage() {
+        let usage = ResourceUsage {
+            user_time: Some(Duration::from_millis(12)),
+            sys_time: Some(Duration::from_micros(4500)),
+            peak_memory: Some(3 * 1024 * 1024 + 512 * 1024),
+        };
+        assert_eq!(
+            usage.to_string(),
+            "user 0.012s, sys 0.004s, max rss 3.5 MiB"
+        );
+
+        let usage = ResourceUsage {
+            peak_memory: Some(512),
+            ..Default::default()
+        };
+        assert_eq!(usage.to_string(), "max rss 512 B");
+        assert_eq!(ResourceUsage::default().to_string(), "n/a");
+    }
+}
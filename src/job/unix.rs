@@ -0,0 +1,233 @@
+use crate::job::{ResourceUsage, Scheduling};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, ExitStatus};
+use std::time::Duration;
+use std::{fs, io, mem};
+
+/// A process group holding a test script and all its descendants.
+pub struct Job {
+    pgid: libc::pid_t,
+}
+
+impl Job {
+    /// Configures `cmd` so the spawned child leads a new process group.
+    pub fn prepare(cmd: &mut Command) {
+        cmd.process_group(0);
+    }
+
+    /// Configures `cmd` so the spawned child runs with a given `scheduling`. CPU affinity is
+    /// ignored on platforms other than Linux.
+    pub fn schedule(cmd: &mut Command, scheduling: &Scheduling) {
+        if scheduling.nice.is_none() && scheduling.cpus.is_empty() {
+            return;
+        }
+        let nice = scheduling.nice;
+        #[cfg(target_os = "linux")]
+        let cpus = (!scheduling.cpus.is_empty()).then(|| {
+            let mut set = unsafe { mem::zeroed::<libc::cpu_set_t>() };
+            for cpu in &scheduling.cpus {
+                unsafe { libc::CPU_SET(*cpu, &mut set) };
+            }
+            set
+        });
+        let setup = move || {
+            if let Some(nice) = nice
+                && unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            #[cfg(target_os = "linux")]
+            if let Some(set) = &cpus {
+                let size = mem::size_of::<libc::cpu_set_t>();
+                if unsafe { libc::sched_setaffinity(0, size, set) } == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        };
+        // Only async-signal-safe functions are called between fork and exec.
+        unsafe { cmd.pre_exec(setup) };
+    }
+
+    /// Creates a job for a `child` spawned from a command configured with [`Job::prepare`].
+    pub fn attach(child: &Child) -> Result<Job, io::Error> {
+        // The child is the leader of its own process group: the group id is the child pid.
+        let pgid = child.id() as libc::pid_t;
+        Ok(Job { pgid })
+    }
+
+    /// Waits for the `child` of this job to exit, and returns its exit status with the resources
+    /// used by the child and the descendants it has waited for.
+    pub fn wait(&self, child: &mut Child) -> Result<(ExitStatus, ResourceUsage), io::Error> {
+        let pid = child.id() as libc::pid_t;
+        let mut status = 0;
+        let mut rusage = unsafe { mem::zeroed::<libc::rusage>() };
+        while unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+        let status = ExitStatus::from_raw(status);
+        let usage = ResourceUsage {
+            user_time: Some(to_duration(rusage.ru_utime)),
+            sys_time: Some(to_duration(rusage.ru_stime)),
+            peak_memory: Some(max_rss_bytes(rusage.ru_maxrss)),
+        };
+        Ok((status, usage))
+    }
+
+    /// Returns the commands of the processes still running in this job, sorted by process id.
+    ///
+    /// Processes are found in `/proc` on Linux, and with `ps` on other Unix systems.
+    pub fn leftovers(&self) -> Result<Vec<String>, io::Error> {
+        let mut processes = list_processes()?
+            .into_iter()
+            .filter(|(_, pgid, _)| *pgid == self.pgid)
+            .map(|(pid, _, command)| (pid, command))
+            .collect::<Vec<_>>();
+        processes.sort();
+        Ok(processes.into_iter().map(|(_, command)| command).collect())
+    }
+
+    /// Kills every process still running in this job.
+    pub fn kill(&self) -> Result<(), io::Error> {
+        let ret = unsafe { libc::kill(-self.pgid, libc::SIGKILL) };
+        if ret == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        // No process left in the group, nothing to kill.
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(());
+        }
+        Err(err)
+    }
+}
+
+/// Returns the pid, process group id and command of every running process.
+#[cfg(target_os = "linux")]
+fn list_processes() -> Result<Vec<(libc::pid_t, libc::pid_t, String)>, io::Error> {
+    let mut processes = vec![];
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(pid) = entry.file_name().to_str().and_then(|p| p.parse().ok()) else {
+            continue;
+        };
+        // The process may have exited since the directory has been read.
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        // The command, in parentheses, may contain spaces: fields are read after it.
+        // `pid (comm) state ppid pgrp ...`
+        let (Some(start), Some(end)) = (stat.find('('), stat.rfind(')')) else {
+            continue;
+        };
+        let command = stat[start + 1..end].to_string();
+        let Some(pgid) = stat[end + 1..]
+            .split_whitespace()
+            .nth(2)
+            .and_then(|p| p.parse().ok())
+        else {
+            continue;
+        };
+        processes.push((pid, pgid, command));
+    }
+    Ok(processes)
+}
+
+/// Returns the pid, process group id and command of every running process.
+#[cfg(not(target_os = "linux"))]
+fn list_processes() -> Result<Vec<(libc::pid_t, libc::pid_t, String)>, io::Error> {
+    let output = Command::new("ps")
+        .args(["-A", "-o", "pid=,pgid=,comm="])
+        .output()?;
+    let processes = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let pgid = fields.next()?.parse().ok()?;
+            let command = fields.collect::<Vec<_>>().join(" ");
+            Some((pid, pgid, command))
+        })
+        .collect();
+    Ok(processes)
+}
+
+fn to_duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+/// Converts the `ru_maxrss` field of `rusage` to bytes: it's expressed in bytes on macOS, and
+/// in kilobytes elsewhere.
+fn max_rss_bytes(max_rss: libc::c_long) -> u64 {
+    let max_rss = max_rss.max(0) as u64;
+    if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[test]
+    fn test_wait_exit_status() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exit 3"]);
+        Job::prepare(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let job = Job::attach(&child).unwrap();
+        let (status, usage) = job.wait(&mut child).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(usage.user_time.is_some());
+        assert!(usage.peak_memory.unwrap() > 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_schedule() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "grep Cpus_allowed_list /proc/self/status; nice"]);
+        let scheduling = Scheduling {
+            nice: Some(19),
+            cpus: vec![0],
+        };
+        Job::schedule(&mut cmd, &scheduling);
+        let output = cmd.output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout, "Cpus_allowed_list:\t0\n19\n");
+    }
+
+    #[test]
+    fn test_kill_leftover_processes() {
+        // The script exits immediately, leaving a background process behind it.
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 60 > /dev/null 2>&1 &"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        Job::prepare(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let job = Job::attach(&child).unwrap();
+        job.wait(&mut child).unwrap();
+
+        // The background process is still alive in the group...
+        assert_eq!(unsafe { libc::kill(-job.pgid, 0) }, 0);
+        // The forked shell may not have executed `sleep` yet.
+        let leftovers = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                job.leftovers().unwrap()
+            })
+            .find(|l| l == &["sleep"]);
+        assert!(leftovers.is_some());
+
+        // ... until we kill the job.
+        job.kill().unwrap();
+        assert!(job.kill().is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::job::{ResourceUsage, Scheduling};
+use std::os::windows::io::AsRawHandle;
+use std::process::{Child, Command, ExitStatus};
+use std::time::Duration;
+use std::{io, mem, ptr};
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+use windows_sys::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    JOBOBJECT_BASIC_ACCOUNTING_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JobObjectBasicAccountingInformation, JobObjectExtendedLimitInformation,
+    QueryInformationJobObject, SetInformationJobObject, TerminateJobObject,
+};
+
+/// A Job Object holding a test script and all its descendants.
+pub struct Job {
+    handle: HANDLE,
+}
+
+impl Job {
+    /// Configures `cmd` before spawning. Nothing to do on Windows, the child is assigned to the
+    /// job once spawned.
+    pub fn prepare(_cmd: &mut Command) {}
+
+    /// Configures `cmd` so the spawned child runs with a given `scheduling`. Niceness and CPU
+    /// affinity are not supported on Windows, the child runs with the default scheduling.
+    pub fn schedule(_cmd: &mut Command, _scheduling: &Scheduling) {}
+
+    /// Creates a job and assigns `child` to it.
+    ///
+    /// The child is assigned right after the spawn, but it already runs: processes it spawns
+    /// before its assignment are not part of the job, so they are neither killed nor accounted
+    /// for. Spawning the child suspended would close this gap, but the standard library doesn't
+    /// give the handle of its main thread to resume it.
+    pub fn attach(child: &Child) -> Result<Job, io::Error> {
+        let handle = unsafe { CreateJobObjectW(ptr::null(), ptr::null()) };
+        if handle.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // From now on, the handle is closed when the job is dropped.
+        let job = Job { handle };
+
+        // Closing the last handle of the job kills every process still in it, even if we exit
+        // abruptly.
+        let mut info = job.extended_limit_info()?;
+        info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        let ret = unsafe {
+            SetInformationJobObject(
+                job.handle,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const _,
+                mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let ret = unsafe { AssignProcessToJobObject(job.handle, child.as_raw_handle() as HANDLE) };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(job)
+    }
+
+    /// Returns the commands of the processes still running in this job. Process names are not
+    /// available from the job, each process is described as `<unknown>`.
+    pub fn leftovers(&self) -> Result<Vec<String>, io::Error> {
+        let info = self.basic_accounting_info()?;
+        Ok(vec!["<unknown>".to_string(); info.ActiveProcesses as usize])
+    }
+
+    /// Kills every process still running in this job.
+    pub fn kill(&self) -> Result<(), io::Error> {
+        let ret = unsafe { TerminateJobObject(self.handle, 1) };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Waits for the `child` of this job to exit, and returns its exit status with the resources
+    /// used by all the processes of the job so far.
+    pub fn wait(&self, child: &mut Child) -> Result<(ExitStatus, ResourceUsage), io::Error> {
+        let status = child.wait()?;
+        let accounting = self.basic_accounting_info().ok();
+        let user_time = accounting.map(|info| to_duration(info.TotalUserTime));
+        let sys_time = accounting.map(|info| to_duration(info.TotalKernelTime));
+        let peak_memory = self
+            .extended_limit_info()
+            .ok()
+            .map(|info| info.PeakJobMemoryUsed as u64);
+        let usage = ResourceUsage {
+            user_time,
+            sys_time,
+            peak_memory,
+        };
+        Ok((status, usage))
+    }
+
+    fn basic_accounting_info(&self) -> Result<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION, io::Error> {
+        let mut info = unsafe { mem::zeroed::<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION>() };
+        let ret = unsafe {
+            QueryInformationJobObject(
+                self.handle,
+                JobObjectBasicAccountingInformation,
+                &mut info as *mut _ as *mut _,
+                mem::size_of::<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION>() as u32,
+                ptr::null_mut(),
+            )
+        };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(info)
+    }
+
+    fn extended_limit_info(&self) -> Result<JOBOBJECT_EXTENDED_LIMIT_INFORMATION, io::Error> {
+        let mut info = unsafe { mem::zeroed::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() };
+        let ret = unsafe {
+            QueryInformationJobObject(
+                self.handle,
+                JobObjectExtendedLimitInformation,
+                &mut info as *mut _ as *mut _,
+                mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+                ptr::null_mut(),
+            )
+        };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(info)
+    }
+}
+
+/// Converts a job accounting time, expressed in 100-nanosecond ticks, to a duration.
+fn to_duration(ticks: i64) -> Duration {
+    Duration::from_nanos(ticks.max(0) as u64 * 100)
+}
+
+// A Job Object handle can be used from any thread, to kill the job from a watchdog thread for
+// instance.
+unsafe impl Send for Job {}
+unsafe impl Sync for Job {}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.handle);
+        }
+    }
+}
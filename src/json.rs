@@ -0,0 +1,373 @@
+//! Minimal JSON values, used for machine-readable outputs and JSON snapshots.
+use std::fmt;
+use std::fmt::{Formatter, Write};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// An object, keys are kept in insertion order.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Creates an object from a list of `(key, value)` pairs.
+    pub fn object(members: Vec<(&str, JsonValue)>) -> JsonValue {
+        let members = members
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        JsonValue::Object(members)
+    }
+
+    /// Parses a JSON `text`.
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("end of text"));
+        }
+        Ok(value)
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> Self {
+        JsonValue::String(value)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> Self {
+        JsonValue::Bool(value)
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(value: usize) -> Self {
+        JsonValue::Number(value as f64)
+    }
+}
+
+impl From<i32> for JsonValue {
+    fn from(value: i32) -> Self {
+        JsonValue::Number(value as f64)
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(value: u64) -> Self {
+        JsonValue::Number(value as f64)
+    }
+}
+
+impl From<f64> for JsonValue {
+    fn from(value: f64) -> Self {
+        JsonValue::Number(value)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(v) => v.into(),
+            None => JsonValue::Null,
+        }
+    }
+}
+
+/// Encodes a JSON value on a single line.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{b}"),
+            JsonValue::Number(n) if n.is_finite() => write!(f, "{n}"),
+            JsonValue::Number(_) => write!(f, "null"),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            JsonValue::Object(members) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// A recursive descent parser of JSON values.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(JsonValue::String(self.string()?)),
+            Some('t') => self.literal("true", JsonValue::Bool(true)),
+            Some('f') => self.literal("false", JsonValue::Bool(false)),
+            Some('n') => self.literal("null", JsonValue::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("a key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error("<:>"));
+            }
+            members.push((key, self.value()?));
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(JsonValue::Object(members));
+            }
+            if !self.eat(',') {
+                return Err(self.error("<,> or <}>"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<JsonValue, String> {
+        self.pos += 1;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(JsonValue::Array(values));
+            }
+            if !self.eat(',') {
+                return Err(self.error("<,> or <]>"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.next() else {
+                return Err(self.error("<\">"));
+            };
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("an escape sequence")),
+                    };
+                    s.push(c);
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Parses the hex digits of a `\uXXXX` escape sequence, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("a valid code point"));
+        }
+        if !(self.eat('\\') && self.eat('u')) {
+            return Err(self.error("a low surrogate"));
+        }
+        let low = self.hex4()?;
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+        char::from_u32(code).ok_or_else(|| self.error("a valid code point"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16));
+            let Some(digit) = digit else {
+                return Err(self.error("4 hex digits"));
+            };
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<JsonValue, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.pos += 1;
+        }
+        let number = self.chars[start..self.pos].iter().collect::<String>();
+        match number.parse::<f64>() {
+            Ok(n) => Ok(JsonValue::Number(n)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("a number"))
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+        for expected in literal.chars() {
+            if !self.eat(expected) {
+                return Err(self.error(&format!("<{literal}>")));
+            }
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    /// Consumes the char `c` if it's the next one.
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.pos += 1;
+        }
+        eaten
+    }
+
+    fn error(&self, expected: &str) -> String {
+        format!("expecting {expected} at offset {}", self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let value = JsonValue::object(vec![
+            ("name", "foo \"bar\"\n".into()),
+            ("row", 3_usize.into()),
+            ("duration", 0.25.into()),
+            ("passed", false.into()),
+            ("actual", None::<String>.into()),
+            ("escape", "\u{1b}".into()),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"foo \"bar\"\n","row":3,"duration":0.25,"passed":false,"actual":null,"escape":"\u001b"}"#
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#" {"name": "caf\u00e9 \ud83d\ude00\n", "tags": [1, -2.5e1, true, null], "empty": {}} "#;
+        let value = JsonValue::parse(text).unwrap();
+        assert_eq!(
+            value,
+            JsonValue::object(vec![
+                ("name", "café 😀\n".into()),
+                (
+                    "tags",
+                    JsonValue::Array(vec![1.into(), (-25.0).into(), true.into(), JsonValue::Null])
+                ),
+                ("empty", JsonValue::Object(vec![])),
+            ])
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"café 😀\n","tags":[1,-25,true,null],"empty":{}}"#
+        );
+
+        assert_eq!(
+            JsonValue::parse("[1, 2"),
+            Err("expecting <,> or <]> at offset 5".to_string())
+        );
+        assert!(JsonValue::parse("{\"a\": 1} x").is_err());
+        assert!(JsonValue::parse("tru").is_err());
+        assert!(JsonValue::parse("").is_err());
+    }
+}
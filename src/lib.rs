@@ -0,0 +1,24 @@
+//! cliche, snapshot tests for CLIs.
+//!
+//! The binary runs test scripts against their companion expectation files. This library exposes
+//! the underlying engine and [`assert_cmd`], to check a single command directly from Rust tests:
+//!
+//! ```no_run
+//! cliche::assert_cmd("echo")
+//!     .arg("Hello")
+//!     .stdout("Hello\n")
+//!     .assert();
+//! ```
+pub mod chunk;
+pub mod command;
+pub mod count;
+pub mod error;
+pub mod job;
+pub mod json;
+pub mod screen;
+pub mod text;
+pub mod verify;
+
+mod assert;
+
+pub use self::assert::{AssertCmd, UPDATE_ENV_VAR, assert_cmd};
@@ -0,0 +1,94 @@
+//! Named locks shared by tests using the same resource (`lock=gpu` attribute).
+//!
+//! A lock is an exclusive lock on a file of the temporary directory: tests holding the same lock
+//! are serialized, even across cliche processes running concurrently on the same machine.
+//!
+//! Lock files persist after their locks are released: removing a file while another process
+//! waits on it would let a third process lock a new file of the same name, and run concurrently.
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::{env, io};
+
+/// Locks held by a test, released when dropped.
+pub struct Locks {
+    _files: Vec<File>,
+}
+
+/// Acquires the locks `names`, waiting for other holders to release them.
+///
+/// Locks are always acquired in the same order, so tests holding several locks can't deadlock.
+pub fn acquire(names: &[String]) -> Result<Locks, io::Error> {
+    acquire_in(&env::temp_dir(), names)
+}
+
+/// Acquires the locks `names` whose files are in the directory `dir`.
+fn acquire_in(dir: &Path, names: &[String]) -> Result<Locks, io::Error> {
+    let mut names = names.iter().collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    let mut files = vec![];
+    for name in names {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path(dir, name))?;
+        file.lock()?;
+        files.push(file);
+    }
+    Ok(Locks { _files: files })
+}
+
+/// Returns the path of the file of the lock `name` in the directory `dir`.
+fn lock_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("cliche-lock-{name}"))
+}
+
+/// Parses a lock `name`, used in the name of a file.
+pub fn parse_name(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !name.starts_with('.');
+    if !valid {
+        return Err(format!("invalid lock name <{name}>"));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_acquire() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path().to_path_buf();
+        let name = "test".to_string();
+        let locks = acquire_in(&dir, &[name.clone(), name.clone()]).unwrap();
+        assert!(dir.join("cliche-lock-test").is_file());
+
+        // Another holder waits for the lock to be released.
+        let (sender, receiver) = mpsc::channel();
+        let other = thread::spawn(move || {
+            let _locks = acquire_in(&dir, &[name]).unwrap();
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(locks);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        other.join().unwrap();
+    }
+
+    #[test]
+    fn test_parse_name() {
+        assert_eq!(parse_name("gpu"), Ok("gpu".to_string()));
+        assert!(parse_name("../gpu").is_err());
+        assert!(parse_name("").is_err());
+    }
+}
@@ -1,96 +1,1489 @@
-use crate::command::CommandSpec;
+use crate::cli::{CliError, CliOptions, ColorChoice, MessageFormat};
+use crate::command::{CommandResult, CommandSpec};
+use crate::config::Config;
+use crate::coverage::{Coverage, CoverageSummary};
+use crate::duplicate::Duplicates;
 use crate::error::Error;
-use crate::text::{Format, Style, StyledString, init_crate_colored};
-use std::path::Path;
-use std::{env, io, process};
+use crate::events::EventStream;
+use crate::history::{Baseline, History};
+use crate::job::format_bytes;
+use crate::json::JsonValue;
+use crate::report::{Report, StreamStats, TestReport};
+use crate::suite::{Shard, SnapshotRoot, Test};
+use crate::text::{ColorSupport, Format, Style, StyledString, init_crate_colored};
+use crate::triage::FailureGroups;
+use crate::verify::VerifyOptions;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use std::{env, io, process, thread};
 
-mod chunk;
-mod command;
-mod error;
-mod text;
-mod verify;
+// Engine modules are shared with the library.
+use cliche::{chunk, command, error, job, json, screen, text, verify};
+
+mod bundle;
+mod cli;
+mod config;
+mod coverage;
+mod doctor;
+mod drift;
+mod duplicate;
+mod events;
+mod exec;
+mod explain;
+mod focus;
+mod glob;
+mod graph;
+mod history;
+mod import;
+mod jitter;
+mod lock;
+mod replay;
+mod report;
+mod review;
+mod suite;
+mod toml;
+mod triage;
+mod update;
+mod watch;
 
 const EXIT_OK: i32 = 0;
 const EXIT_IO_ERROR: i32 = 1;
 const EXIT_VERIFY_ERROR: i32 = 2;
+const EXIT_CLI_ERROR: i32 = 3;
+
+/// Number of tests listed in the table of the slowest tests of a run.
+const SLOWEST_COUNT: usize = 10;
+
+/// Format of the messages printed on stderr, given the capabilities of the terminal.
+static TERM_FORMAT: OnceLock<Format> = OnceLock::new();
 
 fn main() {
     init_crate_colored();
 
-    let args = env::args().collect::<Vec<_>>();
-    if args.len() <= 1 {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let exit_code = match run(&args) {
+        Ok(summary) if summary.failed > 0 => EXIT_VERIFY_ERROR,
+        Ok(_) => EXIT_OK,
+        Err(err) => {
+            err.print();
+            err.exit_code()
+        }
+    };
+    process::exit(exit_code);
+}
+
+/// Counts of the tests of a run.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct RunSummary {
+    passed: usize,
+    failed: usize,
+}
+
+/// An error stopping a run, printed once the run has stopped.
+#[derive(Debug)]
+enum RunError {
+    /// The command line is not valid, the usage is printed with the error.
+    Usage(CliError),
+    /// cliche is not used correctly.
+    Cli(String),
+    /// A subcommand (`bundle`, `import`...) has failed.
+    Command(String),
+    Io(io::Error),
+    /// The configuration or a test of the suite is not valid.
+    Error(Error),
+    /// A test can't be verified, its snapshots can't be updated for instance.
+    Verify(Error),
+    /// The test `name` can't be run, because of `cause`.
+    Test {
+        name: String,
+        cause: Box<RunError>,
+    },
+}
+
+impl RunError {
+    /// Returns this error as the cause of the failure of the test `name`.
+    fn for_test(self, name: &str) -> RunError {
+        RunError::Test {
+            name: name.to_string(),
+            cause: Box::new(self),
+        }
+    }
+
+    /// Returns the exit code of cliche stopped by this error.
+    fn exit_code(&self) -> i32 {
+        match self {
+            RunError::Usage(_) | RunError::Cli(_) => EXIT_CLI_ERROR,
+            RunError::Command(_) | RunError::Io(_) | RunError::Error(_) => EXIT_IO_ERROR,
+            RunError::Verify(_) => EXIT_VERIFY_ERROR,
+            RunError::Test { cause, .. } => cause.exit_code(),
+        }
+    }
+
+    fn print(&self) {
+        match self {
+            RunError::Usage(err) => {
+                eprintln!("--> error: {err}");
+                usage();
+            }
+            RunError::Cli(message) | RunError::Command(message) => {
+                eprintln!("--> error: {message}");
+            }
+            RunError::Io(err) => eprintln!("--> error: {err}"),
+            RunError::Error(err) | RunError::Verify(err) => print_error(err),
+            RunError::Test { name, cause } => {
+                cause.print();
+                print_failure(name, None, None);
+            }
+        }
+    }
+}
+
+/// Runs cliche with the command line arguments `args`, and returns the counts of passed and
+/// failed tests. A run stopped by the first failure (fail fast) is not an error.
+fn run(args: &[String]) -> Result<RunSummary, RunError> {
+    // Commands other than running tests
+    let command = match args.first().map(String::as_str) {
+        Some("bundle" | "doctor" | "drift" | "import" | "graph" | "review")
+            if args.iter().any(|a| a == "-h" || a == "--help") =>
+        {
+            usage();
+            return Ok(RunSummary::default());
+        }
+        Some("bundle") => Some(bundle::run(&args[1..])),
+        Some("doctor") => Some(doctor::run(&args[1..])),
+        Some("drift") => Some(drift::run(&args[1..])),
+        // The arguments of the command run by `exec` may include `--help`.
+        Some("exec") => return exec::run(&args[1..]),
+        Some("graph") => Some(graph::run(&args[1..])),
+        Some("import") => Some(import::run(&args[1..])),
+        Some("review") => Some(review::run(&args[1..])),
+        _ => None,
+    };
+    if let Some(result) = command {
+        return result
+            .map(|()| RunSummary::default())
+            .map_err(RunError::Command);
+    }
+    let options = cli::parse(args).map_err(RunError::Usage)?;
+    // The configuration is loaded before anything is printed, as it may set the colors. Its errors
+    // are reported once the help and version have been handled.
+    let config = match env::current_dir().ok().and_then(|cwd| config::find(&cwd)) {
+        Some(path) => config::load(&path),
+        None => Ok(Config::default()),
+    };
+    let color = options
+        .color
+        .or(config.as_ref().ok().and_then(|c| c.color))
+        .unwrap_or_default();
+    let format = match color {
+        ColorChoice::Auto => ColorSupport::detect().format(),
+        ColorChoice::Always => ColorSupport::detect_forced().format(),
+        ColorChoice::Never => Format::Plain,
+    };
+    let _ = TERM_FORMAT.set(format);
+    if options.help {
+        usage();
+        return Ok(RunSummary::default());
+    }
+    if options.version {
+        println!("cliche {}", env!("CARGO_PKG_VERSION"));
+        return Ok(RunSummary::default());
+    }
+    if let Some(code) = &options.explain {
+        let text = explain::explain(code)
+            .ok_or_else(|| RunError::Cli(format!("unknown error code <{code}>")))?;
+        println!("{text}");
+        return Ok(RunSummary::default());
+    }
+    let config = config.map_err(RunError::Error)?;
+    // Tests given on the command line take precedence over the ones of the configuration.
+    let files = if options.files.is_empty() {
+        &config.tests
+    } else {
+        &options.files
+    };
+    if files.is_empty() {
         usage();
-        process::exit(EXIT_OK);
+        return Ok(RunSummary::default());
     }
-    let files = &args[1..];
-    for f in files {
-        let f = Path::new(f);
+    // Snapshot variants given on the command line take precedence over the configuration.
+    let snapshot_variants = if options.snapshot_variants.is_empty() {
+        config.snapshot_variants.clone()
+    } else {
+        options.snapshot_variants.clone()
+    };
+    let verify_options = VerifyOptions {
+        stderr_only: options.stderr_only.clone(),
+    };
+    let tests = suite::collect(files, !options.no_ignore).map_err(RunError::Error)?;
+    let tests = tests
+        .into_iter()
+        .filter(|t| options.tags.is_empty() || t.tags.iter().any(|tag| options.tags.contains(tag)))
+        .collect::<Vec<_>>();
+    let tests = if options.filters.is_empty() {
+        tests
+    } else {
+        let count = tests.len();
+        let tests = tests
+            .into_iter()
+            .filter(|t| options.filters.iter().any(|f| f.matches(t)))
+            .collect::<Vec<_>>();
+        print_filtered(count - tests.len());
+        tests
+    };
+    let tests = match options.shard {
+        Some(shard) => {
+            let count = tests.len();
+            let tests = tests
+                .into_iter()
+                .filter(|t| shard.contains(t))
+                .collect::<Vec<_>>();
+            print_sharded(shard, tests.len(), count);
+            tests
+        }
+        None => tests,
+    };
+    if options.list {
+        print_list(&tests, &options.snapshot_roots, &snapshot_variants)?;
+        return Ok(RunSummary::default());
+    }
+
+    let mut tests = match &options.only {
+        Some(only) => vec![suite::select_one(tests, only).map_err(RunError::Cli)?],
+        None => tests,
+    };
+    // The seed is printed before running, so that the order can be reproduced even if the run
+    // doesn't end.
+    if options.shuffle {
+        let seed = options.shuffle_seed.unwrap_or_else(suite::random_seed);
+        suite::shuffle(&mut tests, seed);
+        print_shuffled(seed);
+    }
+    let run_suite = |tests| {
+        run_tests(
+            &options,
+            &config,
+            &snapshot_variants,
+            &verify_options,
+            tests,
+        )
+    };
+    // In watch mode, errors stop the current run only.
+    if options.watch {
+        watch::watch(tests, &options.snapshot_roots, |tests| {
+            if let Err(err) = run_suite(tests) {
+                err.print();
+            }
+        });
+    }
+    run_suite(tests)
+}
 
-        print_running(f);
+/// Runs the `tests`, and returns the counts of passed and failed tests.
+fn run_tests(
+    options: &CliOptions,
+    config: &Config,
+    snapshot_variants: &[String],
+    verify_options: &VerifyOptions,
+    tests: Vec<Test>,
+) -> Result<RunSummary, RunError> {
+    // In focused mode, we run a single test with maximum verbosity, the outputs of the test being
+    // streamed to the terminal.
+    let focused = options.only.is_some();
+    // Without capture, outputs are streamed too.
+    let streamed = focused || options.no_capture;
 
-        let cmd_spec = CommandSpec::new(f);
-        let cmd_spec = match cmd_spec {
+    let mut run = SuiteRun::start(options, tests.len())?;
+    for (index, test) in tests.iter().enumerate() {
+        let name = test.display_name();
+
+        print_running(&name);
+        if let Some(events) = &mut run.events {
+            events.test_started(&name, &test.id(), &test.path.display().to_string());
+        }
+
+        let cmd_spec = match test_spec(options, config, snapshot_variants, test, index) {
             Ok(c) => c,
             Err(err) => {
                 clear();
-                print_io_error(err);
-                print_failure(f);
-                process::exit(EXIT_IO_ERROR);
+                return Err(RunError::Io(err).for_test(&name));
             }
         };
+        if focused {
+            focus::print_spec(&cmd_spec);
+            focus::print_env(&cmd_spec);
+        }
+        let looked_up_files = cmd_spec.looked_up_files();
+        if let Some(events) = &mut run.events {
+            events.files(&name, &looked_up_files);
+        }
+        // Tests without any expectation file are recorded instead of being verified, if required.
+        let blessed = options.bless_new_tests && looked_up_files.iter().all(|(_, _, found)| !found);
+        let updating = options.update || blessed;
 
-        // We execute our test
-        let cmd_result = cmd_spec.execute();
-        let cmd_result = match cmd_result {
-            Ok(c) => c,
-            Err(err) => {
+        if options.replay.is_none() {
+            ensure_executable(options, &cmd_spec, test).map_err(|err| err.for_test(&name))?;
+        }
+        // A test whose companion files configuring its command are invalid fails without being
+        // run.
+        if options.replay.is_none()
+            && let Err(err) = cmd_spec.check_command_files()
+        {
+            clear();
+            run.add_not_run(test, &name, &cmd_spec, &looked_up_files, err);
+            if !options.no_fail_fast {
+                run.save();
+                return Ok(run.summary);
+            }
+            continue;
+        }
+
+        // Tests sharing a lock are serialized, the locks being held until the test is verified.
+        let _locks = match options.replay {
+            Some(_) => None,
+            None => match lock::acquire(&test.locks) {
+                Ok(locks) => Some(locks),
+                Err(err) => {
+                    clear();
+                    return Err(RunError::Io(err).for_test(&name));
+                }
+            },
+        };
+
+        let (cmd_result, attempts, jitter) =
+            match execute_test(options, verify_options, &cmd_spec, test, updating) {
+                Ok(c) => c,
+                Err(err) => {
+                    if !streamed {
+                        clear();
+                    }
+                    return Err(RunError::Io(err).for_test(&name));
+                }
+            };
+        if let Some(dir) = &run.record_dir {
+            replay::record(dir, &test.path, &cmd_result)
+                .map_err(|err| RunError::Io(err).for_test(&name))?;
+        }
+        let (cmd_result, formatter_error) = format_result(&cmd_spec, test, cmd_result);
+        let slow = run.time(test, cmd_result.elapsed());
+        if focused {
+            // Artifacts are kept to be inspected once the run has ended.
+            match focus::write_artifacts(&name, &cmd_result) {
+                Ok(dir) => focus::print_artifacts(&dir.keep()),
+                Err(err) => print_io_error(err),
+            }
+        }
+
+        let test_run = TestRun {
+            test,
+            name,
+            spec: cmd_spec,
+            result: cmd_result,
+            attempts,
+            jitter,
+            looked_up_files,
+            slow,
+        };
+        if updating {
+            if !focused {
                 clear();
-                print_io_error(err);
-                print_failure(f);
-                process::exit(EXIT_IO_ERROR);
             }
+            run.update(&test_run, formatter_error, verify_options)
+                .map_err(|err| err.for_test(&test_run.name))?;
+            continue;
+        }
+
+        let verification = verify_test(options, config, verify_options, &test_run, formatter_error);
+        if !streamed {
+            clear();
+        }
+        let passed = run.add(&test_run, verification);
+        if !passed && !options.no_fail_fast {
+            run.save();
+            return Ok(run.summary);
+        }
+    }
+    run.finish()
+}
+
+/// The results of the tests of a run, accumulated test after test.
+struct SuiteRun<'a> {
+    options: &'a CliOptions,
+    history: Option<History>,
+    report: Option<Report>,
+    events: Option<EventStream>,
+    /// Directory where the executions of the tests are recorded, if any.
+    record_dir: Option<PathBuf>,
+    /// In update mode, snapshot changes of all tests are collected and applied at the end.
+    changes: Vec<update::SnapshotChange>,
+    coverage: CoverageSummary,
+    duplicates: Duplicates,
+    /// Without fail fast, failures are collected and grouped by identical diff.
+    failures: FailureGroups,
+    summary: RunSummary,
+    flaky: usize,
+    durations: Vec<(String, Duration)>,
+    started: Instant,
+}
+
+impl<'a> SuiteRun<'a> {
+    /// Starts the run of `count` tests.
+    fn start(options: &'a CliOptions, count: usize) -> Result<Self, RunError> {
+        let history = match &options.history {
+            Some(path) => Some(History::load(path).map_err(RunError::Io)?),
+            None => None,
+        };
+        let report = options.report_json.as_ref().map(|_| Report::new());
+        let mut events = match &options.event_stream {
+            Some(target) => Some(EventStream::open(target).map_err(RunError::Io)?),
+            None => None,
+        };
+        if let Some(events) = &mut events {
+            events.suite_started(count);
+        }
+        // With a number of runs to keep, each run is recorded in its own directory.
+        let record_dir = options
+            .record_exec
+            .as_ref()
+            .map(|dir| match options.record_keep {
+                Some(_) => replay::run_dir(dir),
+                None => dir.clone(),
+            });
+        if let Some(dir) = record_dir
+            .as_ref()
+            .filter(|_| options.record_keep.is_some())
+        {
+            print_recording(dir);
+        }
+        Ok(SuiteRun {
+            options,
+            history,
+            report,
+            events,
+            record_dir,
+            changes: vec![],
+            coverage: CoverageSummary::new(),
+            duplicates: Duplicates::new(),
+            failures: FailureGroups::new(),
+            summary: RunSummary::default(),
+            flaky: 0,
+            durations: vec![],
+            started: Instant::now(),
+        })
+    }
+
+    /// Records the duration `elapsed` of `test` in the history, and returns the historical
+    /// baseline of the test if it's much slower than it. Replayed executions are not timed again.
+    fn time(&mut self, test: &Test, elapsed: Duration) -> Option<Baseline> {
+        if self.options.replay.is_some() {
+            return None;
+        }
+        let history = self.history.as_mut()?;
+        let key = test.path.display().to_string();
+        let baseline = history.baseline(&key);
+        history.record(&key, elapsed);
+        baseline.filter(|b| b.is_slow(elapsed))
+    }
+
+    /// Plans the snapshot changes of a test `run`, to be applied once all the tests have run.
+    fn update(
+        &mut self,
+        run: &TestRun,
+        formatter_error: Option<Error>,
+        verify_options: &VerifyOptions,
+    ) -> Result<(), RunError> {
+        let options = self.options;
+        // Partial updates: only tests matching the update glob, and only failing tests if
+        // required.
+        let selected = options
+            .update_glob
+            .as_ref()
+            .is_none_or(|glob| glob.is_match(&run.test.path));
+        let selected = selected
+            && (!options.update_failed
+                || verify::check_result(&run.spec, &run.result, verify_options).is_err());
+        if !selected {
+            print_skipped(&run.name);
+            return Ok(());
+        }
+        // A killed command has partial outputs that must not be saved as snapshots.
+        verify::check_timeout(&run.spec, &run.result).map_err(RunError::Verify)?;
+        if let Some(err) = formatter_error {
+            return Err(RunError::Verify(err));
+        }
+        let changes = update::plan(&run.spec, &run.result).map_err(RunError::Error)?;
+        print_executed(&run.name);
+        // Missing snapshots are only created if required.
+        let changes = if options.update_existing {
+            update::existing_only(changes)
+        } else {
+            changes
         };
+        // A shared snapshot is changed once, by the first test sharing it.
+        for c in changes {
+            if !self.changes.iter().any(|other| other.path == c.path) {
+                self.changes.push(c);
+            }
+        }
+        Ok(())
+    }
 
-        // Now we can verify against the expected value:
-        let check = verify::check_result(&cmd_spec, &cmd_result);
+    /// Adds a verified test `run` to the results, prints its outcome, and returns `true` if it
+    /// has passed.
+    fn add(&mut self, run: &TestRun, verification: Verification) -> bool {
+        let options = self.options;
+        let coverage = Coverage::new(&run.spec);
+        self.coverage.add(&coverage);
+        if options.suggest_shared {
+            self.duplicates.add(&run.spec);
+        }
+        verification.warnings.iter().for_each(print_warning);
+
+        let check = verification.check;
+        // A test passing on a rerun is flaky, unless it's polling a state until it's reached.
+        let flaky = check.is_ok() && run.attempts > 1 && run.test.retry.is_none();
+        if let Some(report) = &mut self.report {
+            report.add(run.report(&check, flaky));
+        }
+        if let Some(events) = &mut self.events {
+            if let Err(err) = &check {
+                events.diff(&run.name, diagnostic_fields(err, &run.spec));
+            }
+            events.test_finished(&run.name, check.is_ok(), run.result.elapsed());
+        }
+        self.durations
+            .push((run.name.clone(), run.result.elapsed()));
+        let retried = run.test.retry.is_some() || run.retries(options) > 0;
+        let elapsed = Some(run.result.elapsed());
+        // In focused mode, the test is run with maximum verbosity.
+        let verbose = options.verbose || options.only.is_some();
         match check {
             Ok(_) => {
-                clear();
-                print_success(f);
+                self.summary.passed += 1;
+                let exit_only = options.exit_only || options.no_capture;
+                if !exit_only && let Err(err) = update::remove_pending(&run.spec) {
+                    print_io_error(err);
+                }
+                if options.record_failed_only
+                    && let Some(dir) = &self.record_dir
+                    && let Err(err) = replay::remove(dir, &run.test.path)
+                {
+                    print_io_error(err);
+                }
+                print_success(&run.name, elapsed, options.slow_threshold);
+                if flaky {
+                    self.flaky += 1;
+                }
+                if retried {
+                    print_attempts(run.attempts, flaky);
+                    print_jitter(&run.jitter);
+                }
+                run.print_details(&coverage, verbose);
+                true
             }
             Err(err) => {
-                clear();
-                print_error(&err);
-                print_failure(f);
-                process::exit(EXIT_VERIFY_ERROR);
+                self.fail(&run.name, &err);
+                if let Some(violation) = verification.violation.filter(|v| *v != err) {
+                    print_error(&violation);
+                }
+                if options.message_format == MessageFormat::Json {
+                    print_json_diagnostic(&err, &run.spec);
+                }
+                if options.only.is_some() {
+                    focus::print_full_diff(&run.spec, &run.result);
+                }
+                // The actual output is written next to the snapshot, to be inspected or moved
+                // over it.
+                match update::write_pending(&run.spec, &run.result, &err) {
+                    Ok(Some(path)) => print_pending(&path),
+                    Ok(None) => {}
+                    Err(err) => print_io_error(err),
+                }
+                print_failure(&run.name, elapsed, options.slow_threshold);
+                if let Some(description) = &run.test.description {
+                    print_description(description);
+                }
+                if retried {
+                    print_attempts(run.attempts, false);
+                }
+                run.print_details(&coverage, verbose);
+                false
             }
         }
     }
-    process::exit(EXIT_OK);
+
+    /// Adds a `test` that can't be run, failing with `err`, to the results, and prints its
+    /// failure.
+    fn add_not_run(
+        &mut self,
+        test: &Test,
+        name: &str,
+        spec: &CommandSpec,
+        looked_up_files: &[(&'static str, PathBuf, bool)],
+        err: Error,
+    ) {
+        if let Some(report) = &mut self.report {
+            let check = Err(err.clone());
+            report.add(test_report(test, name, spec, looked_up_files, &check));
+        }
+        if let Some(events) = &mut self.events {
+            events.diff(name, diagnostic_fields(&err, spec));
+            events.test_finished(name, false, Duration::ZERO);
+        }
+        self.fail(name, &err);
+        if self.options.message_format == MessageFormat::Json {
+            print_json_diagnostic(&err, spec);
+        }
+        print_failure(name, None, self.options.slow_threshold);
+        if let Some(description) = &test.description {
+            print_description(description);
+        }
+    }
+
+    /// Counts a failure of the test `name` with `err`, and prints it. A failure identical to a
+    /// previous one is not printed again.
+    fn fail(&mut self, name: &str, err: &Error) {
+        self.summary.failed += 1;
+        let same_as = if self.options.no_fail_fast {
+            self.failures.add(name, err)
+        } else {
+            None
+        };
+        match same_as {
+            Some(first) => print_same_failure(first),
+            None => print_error(err),
+        }
+    }
+
+    /// Saves the history and the report of the run, prunes the recordings and ends the event
+    /// stream.
+    fn save(&mut self) {
+        save_history(self.history.as_ref(), &self.options.history);
+        save_report(self.report.as_ref(), &self.options.report_json);
+        prune_recordings(self.options);
+        if let Some(events) = &mut self.events {
+            events.suite_finished(self.summary.passed, self.summary.failed);
+        }
+    }
+
+    /// Ends the run once all the tests have run: the snapshots are updated if required, and the
+    /// aggregate result is printed.
+    fn finish(mut self) -> Result<RunSummary, RunError> {
+        let options = self.options;
+        if options.coverage && !options.update {
+            eprint!("{}", self.coverage.render().to_string(term_format()));
+        }
+        self.duplicates.warnings().iter().for_each(print_warning);
+        self.save();
+        if options.update || options.bless_new_tests {
+            update_snapshots(&self.changes, options.dry_run, options.force)?;
+        }
+        if !self.failures.is_empty() {
+            eprint!("{}", self.failures.render().to_string(term_format()));
+        }
+        // All the tests have run: the summary gives the aggregate result.
+        let summary = self.summary;
+        if options.no_fail_fast && !options.update {
+            print_summary(
+                summary.passed,
+                summary.failed,
+                self.flaky,
+                self.started.elapsed(),
+            );
+        }
+        if self.durations.len() > 1 && !options.update {
+            print_slowest(&self.durations, options.slow_threshold);
+        }
+        Ok(summary)
+    }
+}
+
+/// An executed test.
+struct TestRun<'a> {
+    test: &'a Test,
+    name: String,
+    spec: CommandSpec,
+    result: CommandResult,
+    /// Number of executions of the command, more than 1 if it has been retried.
+    attempts: usize,
+    /// Differences between the last failing attempt and the passing one.
+    jitter: Vec<jitter::Difference>,
+    looked_up_files: Vec<(&'static str, PathBuf, bool)>,
+    /// Historical baseline of the test, if the test is much slower than it.
+    slow: Option<Baseline>,
+}
+
+impl TestRun<'_> {
+    /// Returns the number of times the test is rerun until it passes.
+    fn retries(&self, options: &CliOptions) -> usize {
+        self.test.retries.or(options.retries).unwrap_or(0)
+    }
+
+    /// Returns the report of this run, given the result of its verification.
+    fn report(&self, check: &Result<(), Error>, flaky: bool) -> TestReport {
+        let result = &self.result;
+        TestReport {
+            exit_code: Some(result.exit_code().as_i32()),
+            duration: result.elapsed(),
+            user_time: result.usage().user_time,
+            sys_time: result.usage().sys_time,
+            max_rss: result.usage().peak_memory,
+            disk_usage: result.disk_usage(),
+            stdout: StreamStats::new(result.stdout()),
+            stderr: StreamStats::new(result.stderr()),
+            attempts: self.attempts,
+            flaky,
+            jitter: self.jitter.clone(),
+            ..test_report(
+                self.test,
+                &self.name,
+                &self.spec,
+                &self.looked_up_files,
+                check,
+            )
+        }
+    }
+
+    /// Prints the slowness of this run, and its resources, outputs and files if `verbose`.
+    fn print_details(&self, coverage: &Coverage, verbose: bool) {
+        if let Some(baseline) = &self.slow {
+            print_slow(&self.result, baseline);
+        }
+        if verbose {
+            print_resources(&self.result);
+            print_outputs(&self.result);
+            print_cwd(&self.spec);
+            print_coverage(coverage);
+            print_looked_up_files(&self.looked_up_files);
+        }
+    }
+}
+
+/// Returns the report of a `test` whose command hasn't run, given the result of its verification.
+fn test_report(
+    test: &Test,
+    name: &str,
+    spec: &CommandSpec,
+    looked_up_files: &[(&'static str, PathBuf, bool)],
+    check: &Result<(), Error>,
+) -> TestReport {
+    let diff = check.as_ref().err().map(|err| {
+        diagnostic_fields(err, spec)
+            .into_iter()
+            .filter(|(key, _)| *key != "rendered")
+            .collect()
+    });
+    TestReport {
+        name: name.to_string(),
+        id: test.id(),
+        path: test.path.clone(),
+        passed: check.is_ok(),
+        expected_exit_code: spec
+            .exit_code()
+            .ok()
+            .and_then(|e| e.exact())
+            .map(|e| e.as_i32()),
+        exit_code: None,
+        duration: Duration::ZERO,
+        user_time: None,
+        sys_time: None,
+        max_rss: None,
+        disk_usage: 0,
+        files: looked_up_files.to_vec(),
+        stdout: StreamStats::default(),
+        stderr: StreamStats::default(),
+        diff,
+        attempts: 0,
+        flaky: false,
+        jitter: vec![],
+    }
+}
+
+/// The outcome of the verification of a test.
+struct Verification {
+    check: Result<(), Error>,
+    /// Violation of a suite invariant, reported even if the test has failed otherwise.
+    violation: Option<Error>,
+    /// Problems reported without failing the test.
+    warnings: Vec<Error>,
+}
+
+/// Returns the command of the `index`-th `test` of a run, configured by the command line options
+/// and the configuration.
+fn test_spec(
+    options: &CliOptions,
+    config: &Config,
+    snapshot_variants: &[String],
+    test: &Test,
+    index: usize,
+) -> Result<CommandSpec, io::Error> {
+    let snapshot_base = suite::snapshot_base(&options.snapshot_roots, &test.path);
+    let spec = CommandSpec::with_snapshot_base(&test.path, &snapshot_base)?;
+    let locales = if test.locales.is_empty() {
+        config.locales.clone()
+    } else {
+        test.locales.clone()
+    };
+    let spec = match options.prefix_output {
+        true => spec.with_echo_prefix(&output_tag(test, index)),
+        false => spec,
+    };
+    Ok(spec
+        .with_snapshot_variants(snapshot_variants.to_vec())
+        .with_capture_files(options.capture_files)
+        .with_scheduling(test.scheduling.clone())
+        .with_locales(locales)
+        .with_timeout(options.timeout.or(config.timeout))
+        .with_timeout_scale(
+            options
+                .timeout_scale
+                .or(config.timeout_scale)
+                .unwrap_or(1.0),
+        )
+        .with_comparators(test.comparators.clone())
+        .with_default_env(config.env.clone())
+        .with_default_normalize(config.normalize.clone())
+        .with_max_file_size(
+            config
+                .max_file_size
+                .unwrap_or(command::DEFAULT_MAX_FILE_SIZE),
+        ))
+}
+
+/// Checks that the script of a `test` can be run. A script without executable bit can't be run:
+/// its permissions are fixed if required, the error is reported with guidance otherwise.
+fn ensure_executable(
+    options: &CliOptions,
+    spec: &CommandSpec,
+    test: &Test,
+) -> Result<(), RunError> {
+    let Err(err) = spec.check_executable() else {
+        return Ok(());
+    };
+    clear();
+    if !options.fix_perms {
+        return Err(RunError::Error(err));
+    }
+    spec.fix_permissions().map_err(RunError::Io)?;
+    print_fixed_perms(&test.path);
+    print_running(&test.display_name());
+    Ok(())
+}
+
+/// Executes the command of a `test`, or loads its recorded execution, and returns its result with
+/// the number of attempts and the differences between the failing and the passing attempts.
+fn execute_test(
+    options: &CliOptions,
+    verify_options: &VerifyOptions,
+    spec: &CommandSpec,
+    test: &Test,
+    updating: bool,
+) -> Result<(CommandResult, usize, Vec<jitter::Difference>), io::Error> {
+    let streamed = options.only.is_some() || options.no_capture;
+    let retries = test.retries.or(options.retries).unwrap_or(0);
+    match &options.replay {
+        Some(dir) => replay::load(dir, &test.path).map(|r| (r, 1, vec![])),
+        None if streamed => spec.execute_streamed().map(|r| (r, 1, vec![])),
+        // Snapshots are updated from a single run: retrying until they match makes no sense.
+        None if !updating && (test.retry.is_some() || retries > 0) => {
+            execute_until(spec, test, retries, verify_options)
+        }
+        None => spec.execute().map(|r| (r, 1, vec![])),
+    }
+}
+
+/// Post-processes the stdout of `result` with the formatter of `test`, if any, before it's
+/// verified or saved as a snapshot. Returns the formatted result, and the error of a failing
+/// formatter, failing the test.
+fn format_result(
+    spec: &CommandSpec,
+    test: &Test,
+    result: CommandResult,
+) -> (CommandResult, Option<Error>) {
+    let Some(formatter) = &test.formatter else {
+        return (result, None);
+    };
+    match verify::format_stdout(spec, formatter, result.stdout()) {
+        Ok(stdout) => (result.with_stdout(stdout), None),
+        Err(err) => (result, Some(err)),
+    }
+}
+
+/// Verifies a test `run` against its expectations.
+fn verify_test(
+    options: &CliOptions,
+    config: &Config,
+    verify_options: &VerifyOptions,
+    run: &TestRun,
+    formatter_error: Option<Error>,
+) -> Verification {
+    let (spec, result) = (&run.spec, &run.result);
+    // Snapshots are not read at all when only exit codes are verified.
+    let exit_only = options.exit_only || options.no_capture;
+
+    // Companion files that look like misspelled expectations fail the test in strict mode,
+    // and are only reported otherwise.
+    let misspelled = spec
+        .misspelled_files()
+        .into_iter()
+        .map(|(path, expected)| Error::FileMisspelled { path, expected })
+        .collect::<Vec<_>>();
+
+    // A pattern file contradicting the snapshot of the same stream is a configuration error,
+    // reported as a warning (or failing the test in strict mode).
+    let conflicts = if exit_only {
+        vec![]
+    } else {
+        verify::check_pattern_conflicts(spec)
+    };
+
+    // Patterns matching an empty string fail the test if required. Otherwise, only patterns
+    // making a whole line match anything are reported.
+    let empty_patterns = if exit_only {
+        vec![]
+    } else {
+        spec.empty_patterns()
+    };
+    let empty_patterns = empty_patterns
+        .into_iter()
+        .filter(|(_, p)| options.non_empty_patterns || p.whole_line)
+        .map(|(path, p)| Error::PatternMatchesEmpty {
+            path,
+            pattern: p.pattern,
+            row: p.row,
+        })
+        .collect::<Vec<_>>();
+
+    // Now we can verify against the expected value:
+    let check = match (formatter_error, misspelled.first(), empty_patterns.first()) {
+        _ if exit_only => verify::check_exit_code(spec, result),
+        (Some(err), _, _) => Err(err),
+        (_, Some(err), _) if options.strict => Err(err.clone()),
+        _ if options.strict && !conflicts.is_empty() => Err(conflicts[0].clone()),
+        (_, _, Some(err)) if options.non_empty_patterns => Err(err.clone()),
+        _ => verify::check_result(spec, result, verify_options)
+            .and_then(|_| verify::check_screen_sizes(spec))
+            .and_then(|_| verify::check_locales(spec, verify_options))
+            .and_then(|_| check_post_hook(&run.name, spec, result)),
+    };
+    // Processes left running by the command fail the test in strict mode, and are only
+    // reported otherwise.
+    let leftovers = (!result.leftovers().is_empty()).then(|| Error::LeftoverProcesses {
+        cmd_path: run.test.path.clone(),
+        commands: result.leftovers().to_vec(),
+    });
+    let check = match (check, &leftovers) {
+        (Ok(()), Some(err)) if options.strict => Err(err.clone()),
+        (check, _) => check,
+    };
+    // A command leaving too much data in its work directory fails the test.
+    let check = match run.test.max_disk {
+        Some(max) if check.is_ok() && result.disk_usage() > max => Err(Error::CheckDiskUsage {
+            cmd_path: run.test.path.clone(),
+            max,
+            actual: result.disk_usage(),
+        }),
+        _ => check,
+    };
+    // Suite invariants are checked even if the test has failed, and reported separately.
+    let violation = if exit_only {
+        None
+    } else {
+        verify::check_invariants(spec, result, &config.invariants).err()
+    };
+    let check = match (check, &violation) {
+        (Ok(()), Some(violation)) => Err(violation.clone()),
+        (check, _) => check,
+    };
+
+    let mut warnings = vec![];
+    if !options.strict {
+        warnings.extend(misspelled);
+        warnings.extend(conflicts);
+        warnings.extend(leftovers);
+    }
+    if !options.non_empty_patterns {
+        warnings.extend(empty_patterns);
+    }
+    Verification {
+        check,
+        violation,
+        warnings,
+    }
+}
+
+/// Prints the `tests` on stdout without running them, each one followed by its companion files
+/// and the files that look like misspelled expectation files.
+fn print_list(
+    tests: &[Test],
+    roots: &[SnapshotRoot],
+    snapshot_variants: &[String],
+) -> Result<(), RunError> {
+    let cwd = env::current_dir().unwrap_or_default();
+    let relative = |path: &Path| {
+        path.strip_prefix(&cwd)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    for test in tests {
+        match &test.name {
+            Some(name) => println!("{} ({name})", test.path.display()),
+            None => println!("{}", test.path.display()),
+        }
+        let snapshot_base = suite::snapshot_base(roots, &test.path);
+        let spec = CommandSpec::with_snapshot_base(&test.path, &snapshot_base)
+            .map_err(|err| RunError::Io(err).for_test(&test.display_name()))?
+            .with_snapshot_variants(snapshot_variants.to_vec());
+        for (kind, path) in spec.companion_files() {
+            println!("  {kind}: {}", relative(&path));
+        }
+        for (path, expected) in spec.misspelled_files() {
+            println!(
+                "  misspelled: {} (did you mean {}?)",
+                relative(&path),
+                relative(&expected)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Previews snapshot `changes` and applies them, unless it's a `dry_run`. Without `force`, the
+/// user is asked for confirmation.
+fn update_snapshots(
+    changes: &[update::SnapshotChange],
+    dry_run: bool,
+    force: bool,
+) -> Result<(), RunError> {
+    eprint!("{}", update::preview(changes).to_string(term_format()));
+    if dry_run || changes.is_empty() {
+        return Ok(());
+    }
+    if !force {
+        if !io::stdin().is_terminal() {
+            return Err(RunError::Cli(
+                "use --force to update snapshots without confirmation".to_string(),
+            ));
+        }
+        eprint!("Apply these changes? [y/N] ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+            eprintln!("Snapshots not updated");
+            return Ok(());
+        }
+    }
+    update::apply(changes).map_err(RunError::Io)
+}
+
+/// Runs the post-hook of the test `name`, if any, with the captured outputs of the command
+/// written as artifacts, removed once the hook has run.
+fn check_post_hook(name: &str, spec: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let Some(hook_path) = spec.post_hook() else {
+        return Ok(());
+    };
+    let dir = focus::write_artifacts(name, result).map_err(|err| Error::FileRead {
+        path: hook_path.to_path_buf(),
+        cause: err.to_string(),
+    })?;
+    verify::check_post_hook(spec, dir.path())
+}
+
+/// Removes the oldest recordings of `--record-exec` exceeding the retention limits, if any.
+fn prune_recordings(options: &CliOptions) {
+    let Some(dir) = &options.record_exec else {
+        return;
+    };
+    let retention = replay::Retention {
+        keep_runs: options.record_keep,
+        max_size: options.record_max_size,
+    };
+    if retention == replay::Retention::default() {
+        return;
+    }
+    if let Err(err) = replay::prune(dir, &retention) {
+        print_io_error(err);
+    }
+}
+
+/// Saves the `report` of the run to its file `path`, if any.
+fn save_report(report: Option<&Report>, path: &Option<PathBuf>) {
+    let (Some(report), Some(path)) = (report, path) else {
+        return;
+    };
+    if let Err(err) = report.save(path) {
+        print_io_error(err);
+    }
+}
+
+/// Saves the durations `history` to its file `path`, if any.
+fn save_history(history: Option<&History>, path: &Option<PathBuf>) {
+    let (Some(history), Some(path)) = (history, path) else {
+        return;
+    };
+    if let Err(err) = history.save(path) {
+        print_io_error(err);
+    }
 }
 
-fn print_running(f: &Path) {
+fn print_running(name: &str) {
     let mut s = StyledString::new();
     s.push_with("Running", Style::new().cyan().bold());
     s.push(" ");
-    s.push_with(&f.display().to_string(), Style::new().bold());
-    eprintln!("{}", s.to_string(Format::Ansi));
+    s.push_with(name, Style::new().bold());
+    eprintln!("{}", s.to_string(term_format()));
 }
 
-fn print_success(f: &Path) {
+/// Prints the success of the test `name`, with its duration if `elapsed` is given.
+fn print_success(name: &str, elapsed: Option<Duration>, slow_threshold: Option<Duration>) {
     let mut s = StyledString::new();
     s.push_with("Success", Style::new().green().bold());
     s.push(" ");
-    s.push_with(&f.display().to_string(), Style::new().bold());
-    eprintln!("{}", s.to_string(Format::Ansi));
+    s.push_with(name, Style::new().bold());
+    push_elapsed(&mut s, elapsed, slow_threshold);
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Appends the duration `elapsed` of a test, if any, highlighted if it exceeds `slow_threshold`.
+fn push_elapsed(s: &mut StyledString, elapsed: Option<Duration>, slow_threshold: Option<Duration>) {
+    let Some(elapsed) = elapsed else {
+        return;
+    };
+    s.push(" ");
+    let text = format!("({:.3}s)", elapsed.as_secs_f64());
+    match slow_threshold {
+        Some(threshold) if elapsed > threshold => s.push_with(&text, Style::new().yellow().bold()),
+        _ => s.push(&text),
+    }
+}
+
+/// Returns the `count` slowest tests of `durations`, the slowest first.
+fn slowest(durations: &[(String, Duration)], count: usize) -> Vec<&(String, Duration)> {
+    let mut slowest = durations.iter().collect::<Vec<_>>();
+    slowest.sort_by_key(|(_, elapsed)| std::cmp::Reverse(*elapsed));
+    slowest.truncate(count);
+    slowest
+}
+
+/// Prints the slowest tests of a run, given the duration of each test.
+fn print_slowest(durations: &[(String, Duration)], slow_threshold: Option<Duration>) {
+    let mut s = StyledString::new();
+    s.push_with("Slowest tests:", Style::new().bold());
+    s.push("\n");
+    for (name, elapsed) in slowest(durations, SLOWEST_COUNT) {
+        let text = format!("{:>9.3}s", elapsed.as_secs_f64());
+        s.push("  ");
+        match slow_threshold {
+            Some(threshold) if *elapsed > threshold => {
+                s.push_with(&text, Style::new().yellow().bold())
+            }
+            _ => s.push(&text),
+        }
+        s.push("  ");
+        s.push(name);
+        s.push("\n");
+    }
+    eprint!("{}", s.to_string(term_format()));
+}
+
+/// Prints the counts of `passed` and `failed` tests of a run, the passed tests including `flaky`
+/// tests, and its duration.
+fn print_summary(passed: usize, failed: usize, flaky: usize, elapsed: Duration) {
+    let mut s = StyledString::new();
+    s.push_with("Summary", Style::new().bold());
+    s.push(&format!(" {} tests: ", passed + failed));
+    s.push_with(&format!("{passed} passed"), Style::new().green().bold());
+    if flaky > 0 {
+        s.push(" (");
+        s.push_with(&format!("{flaky} flaky"), Style::new().yellow().bold());
+        s.push(")");
+    }
+    s.push(", ");
+    let style = if failed > 0 {
+        Style::new().red().bold()
+    } else {
+        Style::new().bold()
+    };
+    s.push_with(&format!("{failed} failed"), style);
+    s.push(&format!(" in {:.3}s", elapsed.as_secs_f64()));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_executed(name: &str) {
+    let mut s = StyledString::new();
+    s.push_with("Executed", Style::new().cyan().bold());
+    s.push(" ");
+    s.push_with(name, Style::new().bold());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_skipped(name: &str) {
+    let mut s = StyledString::new();
+    s.push_with("Skipped", Style::new().bright_black().bold());
+    s.push(" ");
+    s.push_with(name, Style::new().bold());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the number of tests not matching the `--filter` patterns.
+fn print_filtered(count: usize) {
+    let mut s = StyledString::new();
+    s.push_with("Filtered", Style::new().bright_black().bold());
+    s.push(&format!(" {count} tests out"));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the directory `dir` where the outputs of the run are recorded.
+fn print_recording(dir: &Path) {
+    let mut s = StyledString::new();
+    s.push_with("Recording", Style::new().bright_black().bold());
+    s.push(&format!(" in {}", dir.display()));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the number of tests of a `shard`, out of the `count` tests of the suite.
+fn print_sharded(shard: Shard, selected: usize, count: usize) {
+    let mut s = StyledString::new();
+    s.push_with("Shard", Style::new().bright_black().bold());
+    s.push(&format!(
+        " {}/{}: {selected} of {count} tests",
+        shard.index, shard.count
+    ));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the `seed` of the random order of the tests.
+fn print_shuffled(seed: u64) {
+    let mut s = StyledString::new();
+    s.push_with("Shuffled", Style::new().bright_black().bold());
+    s.push(&format!(
+        " with seed {seed} (reproduce with --shuffle-seed {seed})"
+    ));
+    eprintln!("{}", s.to_string(term_format()));
 }
 
-fn print_failure(f: &Path) {
+/// Prints the failure of the test `name`, with its duration if `elapsed` is given.
+fn print_failure(name: &str, elapsed: Option<Duration>, slow_threshold: Option<Duration>) {
     let mut s = StyledString::new();
     s.push_with("Failure", Style::new().red().bold());
     s.push(" ");
-    s.push_with(&f.display().to_string(), Style::new().bold());
-    eprintln!("{}", s.to_string(Format::Ansi));
+    s.push_with(name, Style::new().bold());
+    push_elapsed(&mut s, elapsed, slow_threshold);
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_warning(error: &Error) {
+    let mut s = StyledString::new();
+    s.push_with("warning", Style::new().yellow().bold());
+    s.push(": ");
+    s.push(&error.message());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_same_failure(first: &str) {
+    let mut s = StyledString::new();
+    s.push_with("error", Style::new().red().bold());
+    s.push(": same failure as ");
+    s.push_with(first, Style::new().bold());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Runs the command `spec` of a `test` until its verification passes: until the deadline of its
+/// retry is reached if it has one, or at most `retries` more times. Returns the last result with
+/// the number of runs, and the differences between the last failing run and the passing run if
+/// the test has passed after failing.
+fn execute_until(
+    spec: &CommandSpec,
+    test: &Test,
+    retries: usize,
+    options: &VerifyOptions,
+) -> Result<(CommandResult, usize, Vec<jitter::Difference>), io::Error> {
+    let start = Instant::now();
+    let mut attempts = 1;
+    let mut failing: Option<jitter::Attempt> = None;
+    loop {
+        let started = start.elapsed();
+        let result = spec.execute()?;
+        // The verification is the one of the test: after the formatter of stdout, if any.
+        let passed = match &test.formatter {
+            Some(f) => verify::format_stdout(spec, f, result.stdout()).is_ok_and(|stdout| {
+                let formatted = result.clone().with_stdout(stdout);
+                verify::check_result(spec, &formatted, options).is_ok()
+            }),
+            None => verify::check_result(spec, &result, options).is_ok(),
+        };
+        if passed {
+            let differences = match &failing {
+                Some(failing) => {
+                    jitter::diff(failing, &jitter::Attempt::new(result.clone(), started))
+                }
+                None => vec![],
+            };
+            return Ok((result, attempts, differences));
+        }
+        let give_up = match &test.retry {
+            Some(retry) => Instant::now() + retry.interval > start + retry.deadline,
+            None => attempts > retries,
+        };
+        if give_up {
+            return Ok((result, attempts, vec![]));
+        }
+        failing = Some(jitter::Attempt::new(result, started));
+        if let Some(retry) = &test.retry {
+            thread::sleep(retry.interval);
+        }
+        attempts += 1;
+    }
+}
+
+/// Prints the `differences` between the failing attempt and the passing attempt of a flaky test.
+fn print_jitter(differences: &[jitter::Difference]) {
+    if differences.is_empty() {
+        return;
+    }
+    let mut s = StyledString::new();
+    s.push_with(
+        "  differed from the failing attempt:",
+        Style::new().yellow().bold(),
+    );
+    for difference in differences {
+        s.push(&format!(
+            "\n    {}: {} -> {}",
+            difference.what, difference.failing, difference.passing
+        ));
+    }
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints that the script at `path` has been made executable.
+fn print_fixed_perms(path: &Path) {
+    let mut s = StyledString::new();
+    s.push_with("note", Style::new().blue().bold());
+    s.push(&format!(": made {} executable", path.display()));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the number of runs of a retried test, and whether it's flaky.
+fn print_attempts(attempts: usize, flaky: bool) {
+    let mut s = StyledString::new();
+    s.push_with("  attempts:", Style::new().blue().bold());
+    s.push(" ");
+    s.push(&attempts.to_string());
+    if flaky {
+        s.push(" ");
+        s.push_with("(flaky)", Style::new().yellow().bold());
+    }
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the `path` of the pending snapshot written for a failing test.
+fn print_pending(path: &Path) {
+    let mut s = StyledString::new();
+    s.push_with("  pending:", Style::new().blue().bold());
+    s.push(" ");
+    s.push(&path.display().to_string());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the `description` of a failing test, telling what behavior has been broken.
+fn print_description(description: &str) {
+    let mut s = StyledString::new();
+    s.push_with("  description:", Style::new().blue().bold());
+    s.push(" ");
+    s.push(description);
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_slow(result: &CommandResult, baseline: &Baseline) {
+    let mut s = StyledString::new();
+    s.push_with("  slow:", Style::new().yellow().bold());
+    s.push(" ");
+    s.push_with(
+        &format!(
+            "ran in {:.3}s, usually {:.3}s",
+            result.elapsed().as_secs_f64(),
+            baseline.mean.as_secs_f64()
+        ),
+        Style::new().yellow(),
+    );
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_resources(result: &CommandResult) {
+    let mut s = StyledString::new();
+    s.push_with("  resources:", Style::new().blue().bold());
+    s.push(" ");
+    s.push(&result.usage().to_string());
+    s.push(&format!(", disk {}", format_bytes(result.disk_usage())));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_outputs(result: &CommandResult) {
+    let stream = |name: &str, stats: StreamStats| {
+        let lines = if stats.lines == 1 { "line" } else { "lines" };
+        let bytes = format_bytes(stats.bytes as u64);
+        format!("{name} {bytes} ({} {lines})", stats.lines)
+    };
+    let mut s = StyledString::new();
+    s.push_with("  outputs:", Style::new().blue().bold());
+    s.push(" ");
+    s.push(&stream("stdout", StreamStats::new(result.stdout())));
+    s.push(", ");
+    s.push(&stream("stderr", StreamStats::new(result.stderr())));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+fn print_coverage(coverage: &Coverage) {
+    let mut s = StyledString::new();
+    s.push_with("  coverage:", Style::new().blue().bold());
+    s.push(" ");
+    s.append(coverage.render());
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the working directory of a command, and where it comes from.
+fn print_cwd(spec: &CommandSpec) {
+    let Ok(cwd) = spec.cwd() else {
+        return;
+    };
+    let origin = if spec.has_cwd() {
+        "from the cwd file"
+    } else {
+        "directory of the script"
+    };
+    let mut s = StyledString::new();
+    s.push_with("  cwd:", Style::new().blue().bold());
+    s.push(&format!(" {} ({origin})", cwd.display()));
+    eprintln!("{}", s.to_string(term_format()));
+}
+
+/// Prints the expectation files found for a test, and the ones looked up but absent (by file
+/// name only, as they're all next to each other).
+fn print_looked_up_files(files: &[(&str, PathBuf, bool)]) {
+    let names = |found: bool| {
+        let names = files
+            .iter()
+            .filter(|(_, _, f)| *f == found)
+            .map(|(_, path, _)| match path.file_name() {
+                Some(name) if !found => name.to_string_lossy().to_string(),
+                _ => path.display().to_string(),
+            })
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    };
+    let mut s = StyledString::new();
+    s.push_with("  files:", Style::new().blue().bold());
+    s.push(&format!(" {}\n", names(true)));
+    s.push_with("  absent:", Style::new().blue().bold());
+    s.push(&format!(" {}", names(false)));
+    eprintln!("{}", s.to_string(term_format()));
 }
 
 fn print_io_error(error: io::Error) {
@@ -98,15 +1491,159 @@ fn print_io_error(error: io::Error) {
 }
 
 fn print_error(error: &Error) {
-    eprintln!("{}", error.render());
+    eprintln!("{}", error.render(term_format()));
+}
+
+/// Prints a JSON diagnostic of `error` on stdout, to be consumed by editors problem matchers.
+fn print_json_diagnostic(error: &Error, spec: &CommandSpec) {
+    let mut members = vec![("type", "diagnostic".into()), ("level", "error".into())];
+    members.extend(diagnostic_fields(error, spec));
+    println!("{}", JsonValue::object(members));
+}
+
+/// Returns the fields describing an `error` of the command `spec` in machine-readable outputs,
+/// starting with the code of the error, stable across releases, to branch on the kind of failure.
+fn diagnostic_fields(error: &Error, spec: &CommandSpec) -> Vec<(&'static str, JsonValue)> {
+    let (file, line) = match error.expectation() {
+        Some((ext, line)) => (spec.snapshot_path(&ext), line),
+        None => error.location(),
+    };
+    let (expected, actual) = error.expected_actual();
+    vec![
+        ("code", error.code().into()),
+        ("file", file.display().to_string().into()),
+        ("line", line.into()),
+        ("message", error.message().into()),
+        ("expected", expected.into()),
+        ("actual", actual.into()),
+        ("rendered", error.render(Format::Plain).into()),
+    ]
+}
+
+/// Returns the tag prefixing the streamed output lines of the `index`-th test, like `[login-01] `:
+/// the file stem of its script, colored after its index.
+fn output_tag(test: &Test, index: usize) -> String {
+    let styles = [
+        Style::new().cyan(),
+        Style::new().green(),
+        Style::new().magenta(),
+        Style::new().blue(),
+        Style::new().yellow(),
+        Style::new().purple(),
+    ];
+    let stem = test.path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut s = StyledString::new();
+    s.push_with(&format!("[{stem}]"), styles[index % styles.len()]);
+    s.push(" ");
+    s.to_string(term_format())
+}
+
+fn term_format() -> Format {
+    *TERM_FORMAT.get_or_init(|| ColorSupport::detect().format())
 }
 
+/// Clears the last line printed on stderr, if stderr is a terminal: logs don't have escape
+/// sequences.
 fn clear() {
-    eprint!("\x1B[1A\x1B[K");
+    if io::stderr().is_terminal() {
+        eprint!("\x1B[1A\x1B[K");
+    }
 }
 /// Prints command line usage.
 fn usage() {
-    println!("cliche, snapshot tests for CLIs.");
-    println!();
-    println!("cliche [FILES]...");
+    print!("{}", cli::help());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diagnostic_fields() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = tmp_dir.path().join("foo.sh");
+        fs::write(&cmd_path, "").unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+        let error = Error::FileNotUtf8 {
+            path: spec.snapshot_path("out"),
+        };
+        let fields = diagnostic_fields(&error, &spec);
+        assert_eq!(fields[0], ("code", "FILE_NOT_UTF8".into()));
+        let keys = fields.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                "code", "file", "line", "message", "expected", "actual", "rendered"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slowest() {
+        let durations = [
+            ("a".to_string(), Duration::from_millis(20)),
+            ("b".to_string(), Duration::from_millis(300)),
+            ("c".to_string(), Duration::from_millis(5)),
+            ("d".to_string(), Duration::from_millis(40)),
+        ];
+        let names = slowest(&durations, 3)
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b", "d", "a"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for (name, script) in [("ok", "echo Hello"), ("ko", "echo Bye")] {
+            let path = dir.join(format!("{name}.sh"));
+            fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            fs::write(dir.join(format!("{name}.out")), "Hello\n").unwrap();
+        }
+        let ok = dir.join("ok.sh").display().to_string();
+        let ko = dir.join("ko.sh").display().to_string();
+        let run = |args: &[&str]| run(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>());
+
+        let summary = run(&[&ok]).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                passed: 1,
+                failed: 0
+            }
+        );
+        let summary = run(&["--no-fail-fast", &ko, &ok]).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                passed: 1,
+                failed: 1
+            }
+        );
+        // The run stops at the first failure.
+        let summary = run(&[&ko, &ok]).unwrap();
+        assert_eq!(
+            summary,
+            RunSummary {
+                passed: 0,
+                failed: 1
+            }
+        );
+
+        let err = run(&["--explain", "NOT_A_CODE"]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_CLI_ERROR);
+        let err = run(&["--only", "missing", &ok]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_CLI_ERROR);
+        let err = run(&["--replay", &dir.join("none").display().to_string(), &ok]).unwrap_err();
+        assert!(matches!(err, RunError::Test { .. }));
+        assert_eq!(err.exit_code(), EXIT_IO_ERROR);
+    }
 }
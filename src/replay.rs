@@ -0,0 +1,289 @@
+//! Recording of command executions, to verify them again later without running the commands.
+//!
+//! Each test is recorded in its own directory, named after the path of the test script, with
+//! the raw `stdout`, `stderr` and `exit` code of the command. To keep several runs, each run can
+//! be recorded in its own directory, named after its start time; old recordings are then removed
+//! by a retention policy.
+use crate::command::{CommandResult, ExitCode, Termination};
+use crate::focus;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// Prefix of the directories of the runs recorded in their own directory.
+const RUN_DIR_PREFIX: &str = "run-";
+
+/// Limits on the recordings kept in a recordings directory, the oldest recordings being removed
+/// first.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Number of runs recorded in their own directory that are kept.
+    pub keep_runs: Option<usize>,
+    /// Maximum size in bytes of all the recordings.
+    pub max_size: Option<u64>,
+}
+
+/// Records the `result` of the test script at `path` in the recordings directory `dir`.
+pub fn record(dir: &Path, path: &Path, result: &CommandResult) -> Result<(), io::Error> {
+    let dir = recording_dir(dir, path);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("stdout"), result.stdout())?;
+    fs::write(dir.join("stderr"), result.stderr())?;
+    fs::write(
+        dir.join("exit"),
+        format!("{}\n", result.termination().to_expectation()),
+    )?;
+    fs::write(
+        dir.join("elapsed"),
+        format!("{}\n", result.elapsed().as_micros()),
+    )?;
+    if let Some(state) = result.state() {
+        fs::write(dir.join("state.json"), state)?;
+    }
+    Ok(())
+}
+
+/// Loads the result of the test script at `path` from the recordings directory `dir`.
+pub fn load(dir: &Path, path: &Path) -> Result<CommandResult, io::Error> {
+    let dir = recording_dir(dir, path);
+    if !dir.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "no recorded execution of {} in {}",
+                path.display(),
+                dir.display()
+            ),
+        ));
+    }
+    let stdout = fs::read(dir.join("stdout"))?;
+    let stderr = fs::read(dir.join("stderr"))?;
+    let exit = fs::read_to_string(dir.join("exit"))?;
+    let Ok(termination) = exit.parse::<Termination>() else {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid recorded exit code in {}", dir.display()),
+        ));
+    };
+    // The duration is informative, an old recording may not have it.
+    let elapsed = fs::read_to_string(dir.join("elapsed"))
+        .ok()
+        .and_then(|e| e.trim().parse::<u64>().ok())
+        .map(Duration::from_micros)
+        .unwrap_or_default();
+    let state = fs::read(dir.join("state.json")).ok();
+    let result = CommandResult::new(ExitCode::from(0), &stdout, &stderr)
+        .with_termination(termination)
+        .with_elapsed(elapsed)
+        .with_state(state);
+    Ok(result)
+}
+
+/// Removes the recorded execution of the test script at `path` from the recordings directory
+/// `dir`, if any.
+pub fn remove(dir: &Path, path: &Path) -> Result<(), io::Error> {
+    match fs::remove_dir_all(recording_dir(dir, path)) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Returns the directory of a new run recorded in its own directory under the recordings
+/// directory `dir`. Runs are named after their start time, so that they sort chronologically.
+pub fn run_dir(dir: &Path) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    dir.join(format!("{RUN_DIR_PREFIX}{millis:013}"))
+}
+
+/// Removes the recordings of the recordings directory `dir` exceeding the `retention` limits,
+/// oldest first, and returns their paths.
+///
+/// Only the runs recorded in their own directory count in the number of kept runs, and the runs
+/// without any recording (all their tests have passed with `--record-failed-only`) are removed.
+/// Any recording counts in the total size: the recordings of tests recorded directly in `dir` are
+/// removed like runs. Runs are ordered by the start time of their name, other recordings by the
+/// last modification of their files.
+pub fn prune(dir: &Path, retention: &Retention) -> Result<Vec<PathBuf>, io::Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    let is_run = |path: &Path| run_started(path).is_some();
+    // Recordings with their time and their size, oldest first.
+    let mut recordings = vec![];
+    let mut removed = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let (modified, size) = usage(&path);
+        match (run_started(&path), modified) {
+            (Some(_), None) => removed.push(path),
+            (Some(started), Some(_)) => recordings.push((started, path, size)),
+            (None, modified) => recordings.push((modified.unwrap_or(UNIX_EPOCH), path, size)),
+        }
+    }
+    recordings.sort();
+
+    if let Some(keep) = retention.keep_runs {
+        let runs = recordings
+            .iter()
+            .filter(|(_, path, _)| is_run(path))
+            .count();
+        let mut excess = runs.saturating_sub(keep);
+        recordings.retain(|(_, path, _)| {
+            if excess == 0 || !is_run(path) {
+                return true;
+            }
+            excess -= 1;
+            removed.push(path.clone());
+            false
+        });
+    }
+    if let Some(max_size) = retention.max_size {
+        let mut total = recordings.iter().map(|(_, _, size)| size).sum::<u64>();
+        for (_, path, size) in &recordings {
+            if total <= max_size {
+                break;
+            }
+            total -= size;
+            removed.push(path.clone());
+        }
+    }
+    for path in &removed {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(removed)
+}
+
+/// Returns the start time of the run recorded in the directory at `path`, given by its name, if
+/// it's the directory of a run.
+fn run_started(path: &Path) -> Option<SystemTime> {
+    let name = path.file_name()?.to_string_lossy();
+    let millis = name.strip_prefix(RUN_DIR_PREFIX)?.parse::<u64>().ok()?;
+    UNIX_EPOCH.checked_add(Duration::from_millis(millis))
+}
+
+/// Returns the last modification of the files under the directory at `path` (`None` without
+/// files), and their total size in bytes. Entries that can't be read are ignored.
+fn usage(path: &Path) -> (Option<SystemTime>, u64) {
+    let mut modified = None;
+    let mut size = 0;
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let (m, s) = if metadata.is_dir() {
+            usage(&entry.path())
+        } else {
+            (
+                Some(metadata.modified().unwrap_or(UNIX_EPOCH)),
+                metadata.len(),
+            )
+        };
+        modified = modified.max(m);
+        size += s;
+    }
+    (modified, size)
+}
+
+/// Returns the directory where the execution of the test script at `path` is recorded.
+fn recording_dir(dir: &Path, path: &Path) -> PathBuf {
+    dir.join(focus::dir_name(&path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_load() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let path = Path::new("tests/login.sh");
+        let result = CommandResult::new(ExitCode::from(2), b"foo\n", b"bar\n")
+            .with_elapsed(Duration::from_millis(150))
+            .with_state(Some(b"{}".to_vec()));
+        record(dir, path, &result).unwrap();
+        assert!(dir.join("tests_login_sh").is_dir());
+
+        let loaded = load(dir, path).unwrap();
+        assert_eq!(loaded.exit_code(), ExitCode::from(2));
+        assert_eq!(loaded.stdout(), b"foo\n");
+        assert_eq!(loaded.stderr(), b"bar\n");
+        assert_eq!(loaded.elapsed(), Duration::from_millis(150));
+        assert_eq!(loaded.state(), Some(&b"{}"[..]));
+
+        let result = load(dir, Path::new("tests/logout.sh"));
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::NotFound));
+
+        remove(dir, path).unwrap();
+        assert!(!dir.join("tests_login_sh").exists());
+        remove(dir, path).unwrap();
+    }
+
+    #[test]
+    fn test_prune() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let result = CommandResult::new(ExitCode::from(0), &[b'a'; 100], b"");
+        let path = Path::new("tests/login.sh");
+        // Runs are ordered by their names, not by the modification of their files: the oldest
+        // run is recorded last.
+        for run in ["run-4000", "run-3000", "run-2000", "run-1000"] {
+            record(&dir.join(run), path, &result).unwrap();
+        }
+        let names = |paths: Vec<PathBuf>| {
+            paths
+                .iter()
+                .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let retention = Retention::default();
+        assert_eq!(prune(dir, &retention).unwrap(), Vec::<PathBuf>::new());
+
+        let retention = Retention {
+            keep_runs: Some(3),
+            max_size: None,
+        };
+        assert_eq!(names(prune(dir, &retention).unwrap()), vec!["run-1000"]);
+        assert!(dir.join("run-2000").is_dir());
+
+        // A recording out of a run is ordered by the modification of its files, here between
+        // the runs started at 2s and 3s.
+        record(dir, path, &result).unwrap();
+        let modified = UNIX_EPOCH + Duration::from_millis(2500);
+        for entry in fs::read_dir(dir.join("tests_login_sh")).unwrap() {
+            let file = fs::File::options()
+                .write(true)
+                .open(entry.unwrap().path())
+                .unwrap();
+            file.set_modified(modified).unwrap();
+        }
+
+        // Each recording has a 100 bytes stdout, and a few bytes of exit code and duration.
+        let retention = Retention {
+            keep_runs: Some(3),
+            max_size: Some(250),
+        };
+        assert_eq!(
+            names(prune(dir, &retention).unwrap()),
+            vec!["run-2000", "tests_login_sh"]
+        );
+        assert!(dir.join("run-3000").is_dir());
+        assert!(dir.join("run-4000").is_dir());
+
+        fs::create_dir(dir.join("run-5000")).unwrap();
+        assert_eq!(names(prune(dir, &retention).unwrap()), vec!["run-5000"]);
+
+        let missing = dir.join("missing");
+        assert_eq!(prune(&missing, &retention).unwrap(), Vec::<PathBuf>::new());
+    }
+}
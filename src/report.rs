@@ -0,0 +1,212 @@
+//! Machine-readable report of a run, written as a JSON file (`--report-json`).
+use crate::jitter::Difference;
+use crate::json::JsonValue;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io};
+
+/// The results of the tests of a run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    tests: Vec<TestReport>,
+}
+
+/// The result of a single test.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestReport {
+    pub name: String,
+    /// Stable identifier of the test (see [`crate::suite::Test::id`]).
+    pub id: String,
+    pub path: PathBuf,
+    pub passed: bool,
+    /// Expected exit code, `None` if it can't be read.
+    pub expected_exit_code: Option<i32>,
+    /// Exit code of the command, `None` if it hasn't run.
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    /// CPU time spent in user mode, if measured.
+    pub user_time: Option<Duration>,
+    /// CPU time spent in kernel mode, if measured.
+    pub sys_time: Option<Duration>,
+    /// Peak memory used by the test, in bytes, if measured.
+    pub max_rss: Option<u64>,
+    /// Number of bytes left by the test in its work directory.
+    pub disk_usage: u64,
+    /// Expectation files looked up, with their description and whether they've been found.
+    pub files: Vec<(&'static str, PathBuf, bool)>,
+    pub stdout: StreamStats,
+    pub stderr: StreamStats,
+    /// Fields describing the failure of the test, if any.
+    pub diff: Option<Vec<(&'static str, JsonValue)>>,
+    /// Number of runs of the test, more than one if it has been retried, zero if it hasn't run.
+    pub attempts: usize,
+    /// The test has passed on a rerun of a failing run.
+    pub flaky: bool,
+    /// Differences between the failing attempt and the passing attempt of a flaky test.
+    pub jitter: Vec<Difference>,
+}
+
+/// The size of a captured output stream.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    pub bytes: usize,
+    /// Number of lines, a last line without newline included.
+    pub lines: usize,
+}
+
+impl StreamStats {
+    pub fn new(output: &[u8]) -> Self {
+        StreamStats {
+            bytes: output.len(),
+            lines: output.split_inclusive(|b| *b == b'\n').count(),
+        }
+    }
+
+    fn to_json(self) -> JsonValue {
+        JsonValue::object(vec![
+            ("bytes", self.bytes.into()),
+            ("lines", self.lines.into()),
+        ])
+    }
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Report::default()
+    }
+
+    /// Adds the result of a `test`.
+    pub fn add(&mut self, test: TestReport) {
+        self.tests.push(test);
+    }
+
+    /// Writes the report to the file at `path`.
+    pub fn save(&self, path: &Path) -> Result<(), io::Error> {
+        fs::write(path, format!("{}\n", self.to_json()))
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let passed = self.tests.iter().filter(|t| t.passed).count();
+        let failed = self.tests.len() - passed;
+        let tests = self.tests.iter().map(TestReport::to_json).collect();
+        JsonValue::object(vec![
+            ("passed", passed.into()),
+            ("failed", failed.into()),
+            ("tests", JsonValue::Array(tests)),
+        ])
+    }
+}
+
+impl TestReport {
+    fn to_json(&self) -> JsonValue {
+        let status = if self.passed { "passed" } else { "failed" };
+        let files = self
+            .files
+            .iter()
+            .map(|(kind, path, found)| {
+                JsonValue::object(vec![
+                    ("kind", (*kind).into()),
+                    ("path", path.display().to_string().into()),
+                    ("found", (*found).into()),
+                ])
+            })
+            .collect();
+        let diff = match &self.diff {
+            Some(fields) => JsonValue::object(fields.clone()),
+            None => JsonValue::Null,
+        };
+        JsonValue::object(vec![
+            ("name", self.name.as_str().into()),
+            ("id", self.id.as_str().into()),
+            ("path", self.path.display().to_string().into()),
+            ("status", status.into()),
+            ("expected_exit_code", self.expected_exit_code.into()),
+            ("exit_code", self.exit_code.into()),
+            ("duration", self.duration.as_secs_f64().into()),
+            ("user_time", self.user_time.map(|t| t.as_secs_f64()).into()),
+            ("sys_time", self.sys_time.map(|t| t.as_secs_f64()).into()),
+            ("max_rss", self.max_rss.into()),
+            ("disk_usage", self.disk_usage.into()),
+            ("files", JsonValue::Array(files)),
+            ("stdout", self.stdout.to_json()),
+            ("stderr", self.stderr.to_json()),
+            ("diff", diff),
+            ("attempts", self.attempts.into()),
+            ("flaky", self.flaky.into()),
+            (
+                "jitter",
+                JsonValue::Array(self.jitter.iter().map(Difference::to_json).collect()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let mut report = Report::new();
+        report.add(TestReport {
+            name: "foo".to_string(),
+            id: "0123456789abcdef".to_string(),
+            path: PathBuf::from("foo.sh"),
+            passed: true,
+            expected_exit_code: Some(0),
+            exit_code: Some(0),
+            duration: Duration::from_millis(250),
+            user_time: Some(Duration::from_millis(125)),
+            sys_time: Some(Duration::from_millis(50)),
+            max_rss: Some(4096),
+            disk_usage: 12,
+            files: vec![("stdout", PathBuf::from("foo.out"), true)],
+            stdout: StreamStats::new(b"a\nb\nc"),
+            stderr: StreamStats::new(b""),
+            diff: None,
+            attempts: 2,
+            flaky: true,
+            jitter: vec![Difference {
+                what: "exit code".to_string(),
+                failing: "1".to_string(),
+                passing: "0".to_string(),
+            }],
+        });
+        report.add(TestReport {
+            name: "bar".to_string(),
+            id: "fedcba9876543210".to_string(),
+            path: PathBuf::from("bar.sh"),
+            passed: false,
+            expected_exit_code: None,
+            exit_code: Some(1),
+            duration: Duration::from_millis(500),
+            user_time: None,
+            sys_time: None,
+            max_rss: None,
+            disk_usage: 0,
+            files: vec![],
+            stdout: StreamStats::default(),
+            stderr: StreamStats::new(b"error\n"),
+            diff: Some(vec![("code", "CHECK_EXIT_CODE".into()), ("line", 1.into())]),
+            attempts: 1,
+            flaky: false,
+            jitter: vec![],
+        });
+        assert_eq!(
+            report.to_json().to_string(),
+            "{\"passed\":1,\"failed\":1,\"tests\":[\
+             {\"name\":\"foo\",\"id\":\"0123456789abcdef\",\"path\":\"foo.sh\",\"status\":\"passed\",\
+             \"expected_exit_code\":0,\"exit_code\":0,\"duration\":0.25,\
+             \"user_time\":0.125,\"sys_time\":0.05,\"max_rss\":4096,\"disk_usage\":12,\
+             \"files\":[{\"kind\":\"stdout\",\"path\":\"foo.out\",\"found\":true}],\
+             \"stdout\":{\"bytes\":5,\"lines\":3},\"stderr\":{\"bytes\":0,\"lines\":0},\"diff\":null,\"attempts\":2,\"flaky\":true,\
+             \"jitter\":[{\"what\":\"exit code\",\"failing\":\"1\",\"passing\":\"0\"}]},\
+             {\"name\":\"bar\",\"id\":\"fedcba9876543210\",\"path\":\"bar.sh\",\"status\":\"failed\",\
+             \"expected_exit_code\":null,\"exit_code\":1,\"duration\":0.5,\
+             \"user_time\":null,\"sys_time\":null,\"max_rss\":null,\"disk_usage\":0,\
+             \"files\":[],\"stdout\":{\"bytes\":0,\"lines\":0},\
+             \"stderr\":{\"bytes\":6,\"lines\":1},\"diff\":{\"code\":\"CHECK_EXIT_CODE\",\"line\":1},\"attempts\":1,\"flaky\":false,\
+             \"jitter\":[]}]}"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Interactive review of pending snapshots, the actual outputs written next to the snapshots of
+//! failing tests (`foo.out.new`, `foo.err.new`).
+//!
+//! `cliche review [FILES]...`
+//! shows the diff of each pending snapshot found under `FILES` (the current directory by
+//! default) with its snapshot, and asks whether to accept it (the snapshot is overwritten),
+//! reject it (the pending snapshot is deleted) or skip it.
+use crate::command::{self, PENDING_SUFFIX};
+use crate::term_format;
+use crate::text::{Style, StyledString};
+use crate::verify;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Counts of the reviewed pending snapshots, by decision.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ReviewSummary {
+    accepted: usize,
+    rejected: usize,
+    skipped: usize,
+}
+
+/// Runs `cliche review`, given the arguments after `review`.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut files = args.iter().map(PathBuf::from).collect::<Vec<_>>();
+    if files.is_empty() {
+        files.push(PathBuf::from("."));
+    }
+    let mut pending = vec![];
+    for file in &files {
+        find_pending(file, &mut pending).map_err(|err| format!("{}: {err}", file.display()))?;
+    }
+    if pending.is_empty() {
+        eprintln!("No pending snapshot to review");
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    let summary = review(&pending, &mut stdin.lock()).map_err(|err| err.to_string())?;
+    eprintln!(
+        "{} accepted, {} rejected, {} skipped",
+        summary.accepted, summary.rejected, summary.skipped
+    );
+    Ok(())
+}
+
+/// Adds the pending snapshots of `path`, a pending snapshot or a directory walked recursively,
+/// to `pending`.
+fn find_pending(path: &Path, pending: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    if !path.is_dir() {
+        if is_pending(path) {
+            pending.push(path.to_path_buf());
+        }
+        return Ok(());
+    }
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    for path in paths {
+        if path.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+        find_pending(&path, pending)?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if the file at `path` is a pending stdout or stderr snapshot.
+fn is_pending(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    ["out", "err"]
+        .iter()
+        .any(|ext| name.ends_with(&format!(".{ext}.{PENDING_SUFFIX}")))
+}
+
+/// Reviews each of the `pending` snapshots, reading the decisions from `input`.
+fn review(pending: &[PathBuf], input: &mut impl BufRead) -> Result<ReviewSummary, io::Error> {
+    let mut summary = ReviewSummary::default();
+    for (i, path) in pending.iter().enumerate() {
+        let snapshot = snapshot_path(path)?;
+        let old = fs::read(&snapshot).unwrap_or_default();
+        let new = fs::read(path)?;
+
+        let mut s = StyledString::new();
+        s.push_with(
+            &format!("[{}/{}]", i + 1, pending.len()),
+            Style::new().blue().bold(),
+        );
+        s.push(" ");
+        s.push_with(&snapshot.display().to_string(), Style::new().bold());
+        s.push("\n");
+        s.append(verify::unified_diff(
+            &String::from_utf8_lossy(&old),
+            &String::from_utf8_lossy(&new),
+        ));
+        eprint!("{}", s.to_string(term_format()));
+
+        loop {
+            eprint!("[a]ccept, [r]eject, [s]kip or [q]uit? ");
+            let _ = io::stderr().flush();
+            let mut answer = String::new();
+            // The end of the input quits the review.
+            if input.read_line(&mut answer)? == 0 {
+                eprintln!();
+                return Ok(summary);
+            }
+            match answer.trim() {
+                "a" | "accept" => {
+                    fs::write(&snapshot, &new)?;
+                    fs::remove_file(path)?;
+                    summary.accepted += 1;
+                }
+                "r" | "reject" => {
+                    fs::remove_file(path)?;
+                    summary.rejected += 1;
+                }
+                "s" | "skip" => summary.skipped += 1,
+                "q" | "quit" => return Ok(summary),
+                _ => continue,
+            }
+            break;
+        }
+    }
+    Ok(summary)
+}
+
+/// Returns the path of the snapshot of a pending snapshot at `path`: the shared snapshot if the
+/// snapshot references one (like `@usage.out`), the snapshot otherwise.
+fn snapshot_path(path: &Path) -> Result<PathBuf, io::Error> {
+    let snapshot = path.with_extension("");
+    Ok(match fs::read(&snapshot) {
+        Ok(content) => command::shared_snapshot_path(&snapshot, &content).unwrap_or(snapshot),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => snapshot,
+        Err(err) => return Err(err),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_review() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.out"), "Hello\n").unwrap();
+        fs::write(dir.join("a.out.new"), "Hi\n").unwrap();
+        fs::write(dir.join("b.err.new"), "warning\n").unwrap();
+        fs::write(dir.join("sub/c.out"), "@../usage.out\n").unwrap();
+        fs::write(dir.join("sub/c.out.new"), "Usage: tool [OPTIONS]\n").unwrap();
+        fs::write(dir.join("usage.out"), "Usage: tool\n").unwrap();
+        fs::write(dir.join("d.out.new"), "Bye\n").unwrap();
+
+        let mut pending = vec![];
+        find_pending(dir, &mut pending).unwrap();
+        assert_eq!(
+            pending,
+            vec![
+                dir.join("a.out.new"),
+                dir.join("b.err.new"),
+                dir.join("d.out.new"),
+                dir.join("sub/c.out.new"),
+            ]
+        );
+
+        // Unknown answers are asked again.
+        let mut input = Cursor::new("a\nr\nx\ns\na\n");
+        let summary = review(&pending, &mut input).unwrap();
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                accepted: 2,
+                rejected: 1,
+                skipped: 1,
+            }
+        );
+        assert_eq!(fs::read_to_string(dir.join("a.out")).unwrap(), "Hi\n");
+        assert!(!dir.join("a.out.new").exists());
+        assert!(!dir.join("b.err.new").exists());
+        assert!(!dir.join("b.err").exists());
+        assert!(dir.join("d.out.new").exists());
+        // The shared snapshot is updated, not the reference.
+        assert_eq!(
+            fs::read_to_string(dir.join("usage.out")).unwrap(),
+            "Usage: tool [OPTIONS]\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("sub/c.out")).unwrap(),
+            "@../usage.out\n"
+        );
+    }
+}
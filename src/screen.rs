@@ -0,0 +1,341 @@
+//! Virtual terminal screen, to snapshot the final rendering of full-screen programs.
+//!
+//! The output of a command is replayed on a grid of rows × columns cells, applying the cursor
+//! movements and erasures of the most common escape sequences (a VT100 / xterm subset). Colors
+//! and other attributes are ignored: only the text left on the screen is kept.
+use crate::text::char_width;
+use std::fmt;
+use std::str::FromStr;
+
+/// Content of the cell following a wide char, which is not rendered.
+const WIDE_CONTINUATION: char = '\0';
+
+/// Rows of cells of a screen.
+type Cells = Vec<Vec<char>>;
+
+/// Size of a terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScreenSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl ScreenSize {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        ScreenSize { cols, rows }
+    }
+}
+
+/// The default size of a terminal, 80 columns by 24 rows.
+impl Default for ScreenSize {
+    fn default() -> Self {
+        ScreenSize::new(80, 24)
+    }
+}
+
+/// Parses a screen size from `COLSxROWS` (`80x24` for instance).
+impl FromStr for ScreenSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = s
+            .split_once('x')
+            .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)));
+        match size {
+            Some((cols, rows)) if cols > 0 && rows > 0 => Ok(ScreenSize::new(cols, rows)),
+            _ => Err(format!("expecting COLSxROWS, found <{s}>")),
+        }
+    }
+}
+
+impl fmt::Display for ScreenSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.cols, self.rows)
+    }
+}
+
+/// A grid of chars, with a cursor, on which a terminal output is replayed.
+pub struct Screen {
+    size: ScreenSize,
+    cells: Cells,
+    /// 0-based row of the cursor.
+    row: usize,
+    /// 0-based column of the cursor. The column can be equal to the number of columns after a
+    /// char has been printed in the last column: the next char wraps to the next line.
+    col: usize,
+    saved_cursor: (usize, usize),
+    /// The main screen and cursor, saved while the alternate screen is used.
+    main: Option<(Cells, (usize, usize))>,
+}
+
+impl Screen {
+    /// Creates a new blank screen of a given `size`.
+    pub fn new(size: ScreenSize) -> Self {
+        Screen {
+            size,
+            cells: vec![vec![' '; size.cols]; size.rows],
+            row: 0,
+            col: 0,
+            saved_cursor: (0, 0),
+            main: None,
+        }
+    }
+
+    /// Returns the text of a screen of `size` after replaying an `output` on it.
+    pub fn render(size: ScreenSize, output: &[u8]) -> String {
+        let mut screen = Screen::new(size);
+        screen.feed(output);
+        screen.text()
+    }
+
+    /// Replays an `output` on this screen.
+    ///
+    /// As outputs are captured without terminal, a newline moves the cursor at the beginning of
+    /// the next line (like a terminal translating `\n` to `\r\n`).
+    pub fn feed(&mut self, output: &[u8]) {
+        let output = String::from_utf8_lossy(output);
+        let mut chars = output.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => self.escape(&mut chars),
+                '\n' => {
+                    self.col = 0;
+                    self.line_feed();
+                }
+                '\r' => self.col = 0,
+                '\x08' => self.move_to(self.row, self.col.saturating_sub(1)),
+                '\t' => self.move_to(self.row, (self.col / 8 + 1) * 8),
+                c if c.is_control() => {}
+                c => self.print(c),
+            }
+        }
+    }
+
+    /// Returns the text of this screen, one line per row. Trailing spaces of each row and
+    /// trailing blank rows are removed.
+    pub fn text(&self) -> String {
+        let mut lines = self
+            .cells
+            .iter()
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .filter(|c| **c != WIDE_CONTINUATION)
+                    .collect::<String>();
+                line.trim_end_matches(' ').to_string()
+            })
+            .collect::<Vec<_>>();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines.iter().map(|l| format!("{l}\n")).collect()
+    }
+
+    /// Prints a char `c` at the cursor position.
+    fn print(&mut self, c: char) {
+        let width = char_width(c);
+        // Combining chars are dropped, the base char is kept.
+        if width == 0 || width > self.size.cols {
+            return;
+        }
+        if self.col + width > self.size.cols {
+            self.col = 0;
+            self.line_feed();
+        }
+        let row = &mut self.cells[self.row];
+        row[self.col] = c;
+        if width == 2 {
+            row[self.col + 1] = WIDE_CONTINUATION;
+        }
+        self.col += width;
+    }
+
+    /// Moves the cursor down one row, scrolling the screen up at the bottom of the screen.
+    fn line_feed(&mut self) {
+        if self.row + 1 < self.size.rows {
+            self.row += 1;
+        } else {
+            self.cells.remove(0);
+            self.cells.push(vec![' '; self.size.cols]);
+        }
+    }
+
+    /// Moves the cursor at 0-based `row` and `col`, clamped to the screen size.
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.row = row.min(self.size.rows - 1);
+        self.col = col.min(self.size.cols - 1);
+    }
+
+    /// Blanks the cells of a `row` in the range `from..to` of columns.
+    fn erase(&mut self, row: usize, from: usize, to: usize) {
+        let to = to.min(self.size.cols);
+        let from = from.min(to);
+        self.cells[row][from..to].fill(' ');
+    }
+
+    /// Handles an escape sequence, `chars` starting after the escape char.
+    fn escape(&mut self, chars: &mut impl Iterator<Item = char>) {
+        match chars.next() {
+            Some('[') => self.control_sequence(chars),
+            // Operating system commands (window title etc...) end with BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || c == '\x1b' && chars.next() == Some('\\') {
+                        break;
+                    }
+                }
+            }
+            Some('7') => self.saved_cursor = (self.row, self.col),
+            Some('8') => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+            Some('c') => *self = Screen::new(self.size),
+            // Character set designation
+            Some('(' | ')') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles a control sequence `ESC [ params final`, `chars` starting after the bracket.
+    fn control_sequence(&mut self, chars: &mut impl Iterator<Item = char>) {
+        let mut params = String::new();
+        let mut end = None;
+        for c in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&c) {
+                end = Some(c);
+                break;
+            }
+            params.push(c);
+        }
+        let Some(end) = end else {
+            return;
+        };
+        if let Some(modes) = params.strip_prefix('?') {
+            self.private_mode(modes, end);
+            return;
+        }
+        let params = params
+            .split(';')
+            .map(|p| p.parse::<usize>().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let param = |index: usize, default: usize| match params.get(index) {
+            Some(0) | None => default,
+            Some(p) => *p,
+        };
+        let (row, col) = (self.row, self.col.min(self.size.cols - 1));
+        match end {
+            'A' => self.move_to(row.saturating_sub(param(0, 1)), col),
+            'B' => self.move_to(row + param(0, 1), col),
+            'C' => self.move_to(row, col + param(0, 1)),
+            'D' => self.move_to(row, col.saturating_sub(param(0, 1))),
+            'E' => self.move_to(row + param(0, 1), 0),
+            'F' => self.move_to(row.saturating_sub(param(0, 1)), 0),
+            'G' => self.move_to(row, param(0, 1) - 1),
+            'd' => self.move_to(param(0, 1) - 1, col),
+            'H' | 'f' => self.move_to(param(0, 1) - 1, param(1, 1) - 1),
+            'J' => {
+                let (above, below) = match params[0] {
+                    0 => (row + 1..row + 1, row + 1..self.size.rows),
+                    1 => (0..row, row..row),
+                    _ => (0..row, row + 1..self.size.rows),
+                };
+                for r in above.chain(below) {
+                    self.erase(r, 0, self.size.cols);
+                }
+                match params[0] {
+                    0 => self.erase(row, self.col, self.size.cols),
+                    1 => self.erase(row, 0, col + 1),
+                    _ => self.erase(row, 0, self.size.cols),
+                }
+            }
+            'K' => match params[0] {
+                0 => self.erase(row, self.col, self.size.cols),
+                1 => self.erase(row, 0, col + 1),
+                _ => self.erase(row, 0, self.size.cols),
+            },
+            's' => self.saved_cursor = (row, col),
+            'u' => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+            // Colors and other attributes are ignored.
+            _ => {}
+        }
+    }
+
+    /// Handles the setting (`end` is `h`) or resetting (`end` is `l`) of private `modes`. Only the
+    /// alternate screen (mode 1049) changes the rendering.
+    fn private_mode(&mut self, modes: &str, end: char) {
+        if !modes.split(';').any(|m| m == "1049") {
+            return;
+        }
+        match end {
+            'h' if self.main.is_none() => {
+                let blank = vec![vec![' '; self.size.cols]; self.size.rows];
+                let main = std::mem::replace(&mut self.cells, blank);
+                self.main = Some((main, (self.row, self.col)));
+                self.move_to(0, 0);
+            }
+            'l' => {
+                if let Some((main, (row, col))) = self.main.take() {
+                    self.cells = main;
+                    self.row = row;
+                    self.col = col;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!("120x40".parse(), Ok(ScreenSize::new(120, 40)));
+        assert_eq!(ScreenSize::new(80, 24).to_string(), "80x24");
+        assert!("80".parse::<ScreenSize>().is_err());
+        assert!("0x24".parse::<ScreenSize>().is_err());
+    }
+
+    #[test]
+    fn test_render_plain_text() {
+        let size = ScreenSize::new(10, 3);
+        assert_eq!(Screen::render(size, b""), "");
+        assert_eq!(Screen::render(size, b"foo\nbar\n"), "foo\nbar\n");
+        // Long lines wrap, and the screen scrolls at the bottom.
+        assert_eq!(
+            Screen::render(size, b"0123456789abc\nfoo\nbar"),
+            "abc\nfoo\nbar\n"
+        );
+        // Wide chars use two cells.
+        assert_eq!(
+            Screen::render(size, "日本語日本語".as_bytes()),
+            "日本語日本\n語\n"
+        );
+    }
+
+    #[test]
+    fn test_render_escape_sequences() {
+        let size = ScreenSize::new(20, 5);
+        // Progress bar redrawn on the same line, with colors.
+        let output = b"\x1b[32m[##  ]\x1b[0m 50%\r\x1b[32m[####]\x1b[0m 100%\x1b[K\n";
+        assert_eq!(Screen::render(size, output), "[####] 100%\n");
+
+        // Cursor positioning and screen erasure.
+        let output = b"garbage\x1b[2J\x1b[3;5Hhello\x1b[1;1Htitle\x1b]0;window\x07";
+        assert_eq!(Screen::render(size, output), "title\n\n    hello\n");
+
+        // Erase from the cursor to the end of the line.
+        let output = b"foo bar baz\x1b[4D\x1b[K!";
+        assert_eq!(Screen::render(size, output), "foo bar!\n");
+    }
+
+    #[test]
+    fn test_render_alternate_screen() {
+        let size = ScreenSize::new(20, 5);
+        let output = b"$ top\n\x1b[?1049h\x1b[Hfull screen";
+        assert_eq!(Screen::render(size, output), "full screen\n");
+        let output = b"$ top\n\x1b[?1049h\x1b[Hfull screen\x1b[?1049lbye\n";
+        assert_eq!(Screen::render(size, output), "$ top\nbye\n");
+    }
+}
@@ -0,0 +1,74 @@
+//! Directives written in the header comments of a test script.
+//!
+//! A directive is a comment line `# cliche: key=value` placed before the first line of code
+//! of the script:
+//!
+//! ```text
+//! #!/bin/sh
+//! # cliche: name=Login with expired token
+//! # cliche: tags=auth,slow
+//! curl --header "Authorization: Bearer $EXPIRED" http://localhost:8000/login
+//! ```
+use std::fs;
+use std::path::Path;
+
+/// Prefix of a directive comment.
+const DIRECTIVE_PREFIX: &str = "# cliche:";
+
+/// Returns the directives `(row, key, value)` of the script at `path`, `row` being the 1-based
+/// line index of the directive.
+///
+/// An unreadable script has no directive, the error being reported when the test runs.
+pub fn read(path: &Path) -> Vec<(usize, String, String)> {
+    let Ok(text) = fs::read(path) else {
+        return vec![];
+    };
+    parse(&String::from_utf8_lossy(&text))
+}
+
+/// Returns the directives declared in the header comments of a script `text`. The value of a
+/// directive is the rest of the line, optionally between double quotes.
+fn parse(text: &str) -> Vec<(usize, String, String)> {
+    text.lines()
+        .map(str::trim)
+        .enumerate()
+        .take_while(|(_, line)| line.is_empty() || line.starts_with('#'))
+        .filter_map(|(index, line)| {
+            let directive = line.strip_prefix(DIRECTIVE_PREFIX)?;
+            let (key, value) = directive.split_once('=')?;
+            let value = value.trim().trim_matches('"');
+            Some((index + 1, key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "#!/bin/sh\n# Some comment\n# cliche: name=Login with expired token\n# cliche: nice=10\necho foo\n";
+        assert_eq!(
+            parse(text),
+            vec![
+                (
+                    3,
+                    "name".to_string(),
+                    "Login with expired token".to_string()
+                ),
+                (4, "nice".to_string(), "10".to_string()),
+            ]
+        );
+
+        let text = "#!/bin/sh\n# cliche: name=\"Quoted name\"\n";
+        assert_eq!(
+            parse(text),
+            vec![(2, "name".to_string(), "Quoted name".to_string())]
+        );
+
+        // Directives after the first line of code are ignored
+        let text = "#!/bin/sh\necho foo\n# cliche: name=Too late\n";
+        assert_eq!(parse(text), vec![]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Discovery of the test scripts of a directory without manifest.
+//!
+//! Test scripts are the `.sh` files of the directory and its subdirectories (post-hooks excepted),
+//! in the order of their paths. Paths matched by the `.gitignore` files of the walked directories
+//! are skipped, so that generated scripts (build outputs, scratch files) are not run by accident.
+//!
+//! `.gitignore` files are supported with the usual rules: `#` comments, `!` negations, a trailing
+//! `/` for directories only, and patterns with a `/` anchored to the directory of the
+//! `.gitignore` file. Wildcards are the ones of [`Glob`].
+use crate::command::POST_HOOK_EXTENSION;
+use crate::error::Error;
+use crate::glob::Glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the files listing the paths ignored during discovery.
+const IGNORE_FILE_NAME: &str = ".gitignore";
+
+/// A rule of an ignore file.
+#[derive(Clone, Debug)]
+struct Rule {
+    /// Directory of the ignore file, anchored patterns are relative to it.
+    dir: PathBuf,
+    glob: Glob,
+    /// The pattern is matched against the path relative to `dir`, rather than the file name.
+    anchored: bool,
+    /// The rule re-includes matched paths (`!pattern`).
+    negated: bool,
+    /// The rule only matches directories (`pattern/`).
+    dir_only: bool,
+}
+
+/// Parses the rules of an ignore file in `dir`.
+fn parse_rules(text: &str, dir: &Path) -> Vec<Rule> {
+    let mut rules = vec![];
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, line),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        // `**/foo` matches `foo` at any depth, like `foo`.
+        let pattern = match pattern.strip_prefix("**/") {
+            Some(rest) if !rest.contains('/') => rest,
+            _ => pattern,
+        };
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            continue;
+        }
+        // Anchored patterns are matched against `/` + the relative path, so that the glob always
+        // matches the whole path.
+        let glob = match anchored {
+            true => Glob::new(&format!("/{pattern}")),
+            false => Glob::new(pattern),
+        };
+        rules.push(Rule {
+            dir: dir.to_path_buf(),
+            glob,
+            anchored,
+            negated,
+            dir_only,
+        });
+    }
+    rules
+}
+
+/// Returns `true` if the `path` is ignored by `rules`, the last matching rule taking precedence.
+fn is_ignored(rules: &[Rule], path: &Path, is_dir: bool) -> bool {
+    let mut ignored = false;
+    for rule in rules {
+        if rule.dir_only && !is_dir {
+            continue;
+        }
+        let Ok(relative) = path.strip_prefix(&rule.dir) else {
+            continue;
+        };
+        let matched = match rule.anchored {
+            true => rule.glob.is_match(&Path::new("/").join(relative)),
+            false => rule.glob.is_match(relative),
+        };
+        if matched {
+            ignored = !rule.negated;
+        }
+    }
+    ignored
+}
+
+/// Returns `true` if the file at `path` is a test script.
+fn is_script(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".sh") && !name.ends_with(&format!(".{POST_HOOK_EXTENSION}"))
+}
+
+/// Returns the test scripts under `dir`, skipping the paths ignored by `.gitignore` files if
+/// `ignore` is `true`.
+pub fn discover(dir: &Path, ignore: bool) -> Result<Vec<PathBuf>, Error> {
+    let mut scripts = vec![];
+    walk(dir, ignore, &[], &mut scripts)?;
+    Ok(scripts)
+}
+
+fn walk(dir: &Path, ignore: bool, rules: &[Rule], scripts: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let read_error = |err: std::io::Error| Error::FileRead {
+        path: dir.to_path_buf(),
+        cause: err.to_string(),
+    };
+    let mut rules = rules.to_vec();
+    if ignore && let Ok(text) = fs::read_to_string(dir.join(IGNORE_FILE_NAME)) {
+        rules.extend(parse_rules(&text, dir));
+    }
+    let mut paths = fs::read_dir(dir)
+        .map_err(read_error)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_error)?;
+    paths.sort();
+    for path in paths {
+        let is_dir = path.is_dir();
+        if path.file_name().is_some_and(|name| name == ".git") || is_ignored(&rules, &path, is_dir)
+        {
+            continue;
+        }
+        if is_dir {
+            walk(&path, ignore, &rules, scripts)?;
+        } else if is_script(&path) {
+            scripts.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discover() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for path in [
+            "a.sh",
+            "a.post.sh",
+            "a.out",
+            "auth/login.sh",
+            "auth/scratch.sh",
+            "auth/keep.sh",
+            "build/gen.sh",
+            "other/build/gen.sh",
+            "tmp_1.sh",
+        ] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::write(dir.join(".gitignore"), "# Outputs\n/build/\ntmp_*.sh\n").unwrap();
+        fs::write(
+            dir.join("auth/.gitignore"),
+            "*.sh\n!login.sh\n!keep.sh\nkeep.sh\n",
+        )
+        .unwrap();
+
+        let scripts = discover(dir, true).unwrap();
+        assert_eq!(
+            scripts,
+            vec![
+                dir.join("a.sh"),
+                dir.join("auth/login.sh"),
+                dir.join("other/build/gen.sh"),
+            ]
+        );
+
+        let scripts = discover(dir, false).unwrap();
+        assert_eq!(scripts.len(), 7);
+    }
+}
@@ -0,0 +1,146 @@
+//! Manifest files, listing tests explicitly.
+//!
+//! A manifest has one test per line, in the order of execution. Each line is a script path
+//! (relative to the manifest directory) optionally followed by `key=value` attributes:
+//!
+//! ```text
+//! # Lines starting with `#` are comments.
+//! login.sh name="Login with expired token" tags=auth,slow
+//! bench.sh nice=10 cpus=0-1
+//! logout.sh
+//! ```
+use crate::command;
+use crate::error::Error;
+use crate::suite::Test;
+use std::path::Path;
+
+/// Parses the manifest file at `path`.
+pub fn parse_file(path: &Path) -> Result<Vec<Test>, Error> {
+    let text = command::read_text(path, command::DEFAULT_MAX_FILE_SIZE)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse(&text, dir).map_err(|(reason, row)| Error::ManifestInvalid {
+        path: path.to_path_buf(),
+        reason,
+        row,
+    })
+}
+
+/// Parses a manifest `text`, scripts path being relative to `dir`. In case of error, returns
+/// the reason and the 1-based line index of the error.
+fn parse(text: &str, dir: &Path) -> Result<Vec<Test>, (String, usize)> {
+    let mut tests = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let row = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let tokens = tokenize(line).map_err(|reason| (reason, row))?;
+        let mut tokens = tokens.into_iter();
+        let Some(path) = tokens.next() else {
+            continue;
+        };
+        let mut test = Test::new(&dir.join(path));
+        for token in tokens {
+            let Some((key, value)) = token.split_once('=') else {
+                return Err((format!("expecting key=value, found <{token}>"), row));
+            };
+            test.set_attribute(key, value)
+                .map_err(|reason| (reason, row))?;
+        }
+        tests.push(test);
+    }
+    Ok(tests)
+}
+
+/// Splits a manifest `line` on whitespaces, values between double quotes being kept as a
+/// single token (without the quotes).
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(token);
+                    token = String::new();
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if quoted {
+        return Err("missing closing quote".to_string());
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::Scheduling;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_manifest() {
+        let text = r#"
+# Authentication
+login.sh name="Login with expired token" tags=auth,slow
+sub/logout.sh nice=10 cpus=0-1
+"#;
+        let tests = parse(text, Path::new("tests")).unwrap();
+        assert_eq!(
+            tests,
+            vec![
+                Test {
+                    path: PathBuf::from("tests/login.sh"),
+                    name: Some("Login with expired token".to_string()),
+                    tags: vec!["auth".to_string(), "slow".to_string()],
+                    scheduling: Scheduling::default(),
+                    locales: vec![],
+                    locks: vec![],
+                    max_disk: None,
+                    formatter: None,
+                    description: None,
+                    retry: None,
+                    retries: None,
+                    comparators: vec![],
+                },
+                Test {
+                    path: PathBuf::from("tests/sub/logout.sh"),
+                    name: None,
+                    tags: vec![],
+                    scheduling: Scheduling {
+                        nice: Some(10),
+                        cpus: vec![0, 1],
+                    },
+                    locales: vec![],
+                    locks: vec![],
+                    max_disk: None,
+                    formatter: None,
+                    description: None,
+                    retry: None,
+                    retries: None,
+                    comparators: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_manifest() {
+        assert_eq!(
+            parse("a.sh\nb.sh color=red\n", Path::new("")),
+            Err(("unknown attribute <color>".to_string(), 2))
+        );
+        assert_eq!(
+            parse("a.sh name=\"foo\n", Path::new("")),
+            Err(("missing closing quote".to_string(), 1))
+        );
+    }
+}
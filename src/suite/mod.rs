@@ -0,0 +1,691 @@
+//! Collection of the tests to run, from script files given on the command line or from
+//! manifests.
+use crate::chunk::Regex;
+use crate::command;
+use crate::error::Error;
+use crate::glob::Glob;
+use crate::job::{self, Scheduling};
+use crate::lock;
+use crate::verify;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+mod directive;
+mod discover;
+mod manifest;
+
+/// Default name of a manifest file in a test directory.
+pub const MANIFEST_FILE_NAME: &str = "cliche.manifest";
+
+/// A test to run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Test {
+    /// Path of the test script.
+    pub path: PathBuf,
+    /// Optional display name, the path is used otherwise.
+    pub name: Option<String>,
+    /// Tags of this test.
+    pub tags: Vec<String>,
+    /// Priority and CPU affinity of the test processes.
+    pub scheduling: Scheduling,
+    /// Locales the test is also run under, each one with its own snapshots.
+    pub locales: Vec<String>,
+    /// Names of the locks held while the test runs.
+    pub locks: Vec<String>,
+    /// Maximum number of bytes the test can leave in its work directory.
+    pub max_disk: Option<u64>,
+    /// Shell command applied to the actual stdout before it's verified (like `jq -S .`).
+    pub formatter: Option<String>,
+    /// Behavior guarded by the test, printed when it fails.
+    pub description: Option<String>,
+    /// Reruns of the test until it passes, for commands polling an eventually consistent state.
+    pub retry: Option<Retry>,
+    /// Number of reruns of the test if it fails, for flaky tests.
+    pub retries: Option<usize>,
+    /// Comparators overriding the default comparator of expectation files, by extension.
+    pub comparators: Vec<(String, String)>,
+}
+
+impl Test {
+    /// Creates a new test for the script at `path`, without name and tags.
+    pub fn new(path: &Path) -> Self {
+        Test {
+            path: path.to_path_buf(),
+            name: None,
+            tags: vec![],
+            scheduling: Scheduling::default(),
+            locales: vec![],
+            locks: vec![],
+            max_disk: None,
+            formatter: None,
+            description: None,
+            retry: None,
+            retries: None,
+            comparators: vec![],
+        }
+    }
+
+    /// Sets an attribute of this test, given by a `key` and a `value`. Attributes can be given in
+    /// manifests and in script directives.
+    pub fn set_attribute(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "name" => self.name = Some(value.to_string()),
+            "tags" => {
+                self.tags = value
+                    .split(',')
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.to_string())
+                    .collect();
+            }
+            "nice" => self.scheduling.nice = Some(Scheduling::parse_nice(value)?),
+            "cpus" => self.scheduling.cpus = Scheduling::parse_cpus(value)?,
+            "locales" => {
+                self.locales = value
+                    .split(',')
+                    .filter(|l| !l.is_empty())
+                    .map(parse_locale)
+                    .collect::<Result<_, _>>()?;
+            }
+            "lock" => {
+                self.locks = value
+                    .split(',')
+                    .filter(|l| !l.is_empty())
+                    .map(lock::parse_name)
+                    .collect::<Result<_, _>>()?;
+            }
+            "max-disk" => self.max_disk = Some(job::parse_bytes(value)?),
+            "formatter" if value.trim().is_empty() => {
+                return Err("expecting a formatter command".to_string());
+            }
+            "formatter" => self.formatter = Some(value.to_string()),
+            "description" if value.trim().is_empty() => {
+                return Err("expecting a description".to_string());
+            }
+            "description" => self.description = Some(value.trim().to_string()),
+            "retry-until" => self.retry = Some(value.parse()?),
+            "retries" => {
+                let retries = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("expecting a number of retries, got <{value}>"))?;
+                self.retries = Some(retries);
+            }
+            "compare" => {
+                self.comparators = value
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(parse_comparator)
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(format!("unknown attribute <{key}>")),
+        }
+        Ok(())
+    }
+
+    /// Completes the attributes of this test not set yet with the attributes of `other`.
+    fn inherit(&mut self, other: Test) {
+        self.name = self.name.take().or(other.name);
+        if self.tags.is_empty() {
+            self.tags = other.tags;
+        }
+        self.scheduling.nice = self.scheduling.nice.or(other.scheduling.nice);
+        if self.scheduling.cpus.is_empty() {
+            self.scheduling.cpus = other.scheduling.cpus;
+        }
+        if self.locales.is_empty() {
+            self.locales = other.locales;
+        }
+        if self.locks.is_empty() {
+            self.locks = other.locks;
+        }
+        self.max_disk = self.max_disk.or(other.max_disk);
+        self.formatter = self.formatter.take().or(other.formatter);
+        self.description = self.description.take().or(other.description);
+        self.retry = self.retry.or(other.retry);
+        self.retries = self.retries.or(other.retries);
+        if self.comparators.is_empty() {
+            self.comparators = other.comparators;
+        }
+    }
+
+    /// Returns the name of this test used in reports.
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    /// Returns a stable identifier of this test, for external tooling: the hash of its path
+    /// relative to the current directory. The identifier doesn't depend on the display name, nor
+    /// on the way the path is written (`./tests/foo.sh`, `tests//foo.sh` or an absolute path).
+    pub fn id(&self) -> String {
+        format!("{:016x}", self.hash())
+    }
+
+    /// Returns the hash of the path of this test relative to the current directory, see
+    /// [`Test::id`].
+    fn hash(&self) -> u64 {
+        let cwd = env::current_dir().unwrap_or_default();
+        let path = self.path.strip_prefix(&cwd).unwrap_or(&self.path);
+        let path = path
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+        fnv1a(path.as_bytes())
+    }
+}
+
+/// Parses the comparator of an expectation file from `EXT:COMPARATOR`, like `out:json`.
+fn parse_comparator(value: &str) -> Result<(String, String), String> {
+    let Some((ext, name)) = value.split_once(':') else {
+        return Err(format!("expecting EXT:COMPARATOR, found <{value}>"));
+    };
+    if !verify::is_expectation(ext) {
+        return Err(format!("unknown expectation file extension <{ext}>"));
+    }
+    if verify::comparator(name).is_none() {
+        let names = verify::COMPARATORS
+            .iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!(
+            "unknown comparator <{name}>, expecting one of {names}"
+        ));
+    }
+    Ok((ext.to_string(), name.to_string()))
+}
+
+/// Reruns of a test until its verification passes or a deadline is reached.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Retry {
+    /// Maximum duration since the first run, after which the last run is verified.
+    pub deadline: Duration,
+    /// Pause between two runs.
+    pub interval: Duration,
+}
+
+/// Default pause between two runs of a retried test.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parses a retry from `DEADLINE` or `DEADLINE every INTERVAL`, like `30s every 2s`.
+impl FromStr for Retry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (deadline, interval) = match s.split_once(" every ") {
+            Some((deadline, interval)) => (deadline, command::parse_duration(interval)?),
+            None => (s, RETRY_INTERVAL),
+        };
+        let deadline = command::parse_duration(deadline)?;
+        if interval.is_zero() {
+            return Err("expecting a non-zero retry interval".to_string());
+        }
+        Ok(Retry { deadline, interval })
+    }
+}
+
+/// Maps the scripts of a directory to snapshots stored under another directory, with mirrored
+/// paths: with `tests/cli=tests/snapshots`, the stdout of `tests/cli/auth/login.sh` is expected in
+/// `tests/snapshots/auth/login.out`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotRoot {
+    pub scripts: PathBuf,
+    pub snapshots: PathBuf,
+}
+
+impl SnapshotRoot {
+    /// Returns the path the snapshots of the script at `path` are named after, if the script is
+    /// under the scripts directory of this root.
+    pub fn snapshot_base(&self, path: &Path) -> Option<PathBuf> {
+        let scripts = fs::canonicalize(&self.scripts).ok()?;
+        let path = fs::canonicalize(path).ok()?;
+        let relative = path.strip_prefix(scripts).ok()?;
+        Some(self.snapshots.join(relative))
+    }
+}
+
+/// Parses a snapshot root from `SCRIPTS_DIR=SNAPSHOTS_DIR`.
+impl FromStr for SnapshotRoot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((scripts, snapshots)) if !scripts.is_empty() && !snapshots.is_empty() => {
+                Ok(SnapshotRoot {
+                    scripts: PathBuf::from(scripts),
+                    snapshots: PathBuf::from(snapshots),
+                })
+            }
+            _ => Err(format!("expecting SCRIPTS_DIR=SNAPSHOTS_DIR, found <{s}>")),
+        }
+    }
+}
+
+/// Returns the path the snapshots of the script at `path` are named after, using the first
+/// matching snapshot root. Without matching root, snapshots are next to the script.
+pub fn snapshot_base(roots: &[SnapshotRoot], path: &Path) -> PathBuf {
+    roots
+        .iter()
+        .find_map(|root| root.snapshot_base(path))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Collects the tests to run from a list of `inputs`.
+///
+/// An input can be:
+/// - a test script,
+/// - a manifest file (a `.manifest` file) listing tests explicitly,
+/// - a directory with a `cliche.manifest` file,
+/// - a directory without manifest, whose test scripts are discovered (skipping the paths of
+///   `.gitignore` files if `ignore` is `true`).
+///
+/// Tests are returned in the order of the inputs, then in the order of each manifest. Attributes
+/// not given by a manifest are read from the `# cliche: key=value` directives of the scripts.
+pub fn collect(inputs: &[PathBuf], ignore: bool) -> Result<Vec<Test>, Error> {
+    let mut tests = vec![];
+    for input in inputs {
+        if input.is_dir() {
+            let manifest_path = input.join(MANIFEST_FILE_NAME);
+            if manifest_path.exists() {
+                tests.extend(manifest::parse_file(&manifest_path)?);
+            } else {
+                let scripts = discover::discover(input, ignore)?;
+                tests.extend(scripts.iter().map(|path| Test::new(path)));
+            }
+        } else if input.extension().is_some_and(|ext| ext == "manifest") {
+            tests.extend(manifest::parse_file(input)?);
+        } else {
+            tests.push(Test::new(input));
+        }
+    }
+    for test in tests.iter_mut() {
+        let mut scripted = Test::new(&test.path);
+        for (row, key, value) in directive::read(&test.path) {
+            scripted
+                .set_attribute(&key, &value)
+                .map_err(|reason| Error::DirectiveInvalid {
+                    path: test.path.clone(),
+                    reason,
+                    row,
+                })?;
+        }
+        test.inherit(scripted);
+    }
+    Ok(tests)
+}
+
+/// A filter on the names of the tests to run (`--filter`).
+///
+/// A pattern between slashes, like `/^login-\d+$/`, is a regex searched in the display name and
+/// the path of a test. Any other pattern, like `login*`, is a glob matched against them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestFilter {
+    Glob(Glob),
+    Regex(Regex),
+}
+
+impl TestFilter {
+    /// Returns `true` if `test` matches this filter.
+    pub fn matches(&self, test: &Test) -> bool {
+        let name = test.display_name();
+        match self {
+            TestFilter::Glob(glob) => glob.is_match(Path::new(&name)) || glob.is_match(&test.path),
+            TestFilter::Regex(regex) => {
+                regex.is_match(&name) || regex.is_match(&test.path.to_string_lossy())
+            }
+        }
+    }
+}
+
+impl FromStr for TestFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            Some(pattern) if !pattern.is_empty() => Regex::new(pattern)
+                .map(TestFilter::Regex)
+                .map_err(|_| format!("invalid regex <{pattern}>")),
+            _ => Ok(TestFilter::Glob(Glob::new(s))),
+        }
+    }
+}
+
+/// A part of the tests of a suite split across several machines (`--shard`): the shard `index` of
+/// `count` shards, with `index` from 1 to `count`.
+///
+/// A test belongs to a shard given by the hash of its path, so the shards of a suite don't depend
+/// on the order of its tests, and adding a test doesn't move the other tests to other shards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    /// Returns `true` if `test` belongs to this shard.
+    pub fn contains(&self, test: &Test) -> bool {
+        // The low bits of FNV-1a hashes of similar paths are poorly distributed, they are mixed
+        // first.
+        let mut state = test.hash();
+        next_random(&mut state) % self.count == self.index - 1
+    }
+}
+
+/// Parses a shard from `INDEX/COUNT`, like `2/4`.
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("expecting INDEX/COUNT with 1 <= INDEX <= COUNT, found <{s}>");
+        let (index, count) = s.split_once('/').ok_or_else(err)?;
+        let (Ok(index), Ok(count)) = (index.parse(), count.parse()) else {
+            return Err(err());
+        };
+        if index == 0 || index > count {
+            return Err(err());
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+/// Shuffles `tests` in a pseudo-random order given by `seed`: a seed always gives the same order
+/// of the same tests, so that an order revealing a dependency between tests can be reproduced.
+pub fn shuffle(tests: &mut [Test], seed: u64) {
+    let mut state = seed;
+    // Fisher-Yates shuffle.
+    for i in (1..tests.len()).rev() {
+        let j = (next_random(&mut state) % (i as u64 + 1)) as usize;
+        tests.swap(i, j);
+    }
+}
+
+/// Returns a seed for [`shuffle`], different for each run.
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    let mut state = nanos ^ (u64::from(std::process::id()) << 32);
+    next_random(&mut state)
+}
+
+/// Returns the next number of the SplitMix64 sequence of `state`.
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Selects the single test of `tests` named `name`: `name` can be the display name of the test,
+/// its path, or the file name of its script without extension.
+pub fn select_one(tests: Vec<Test>, name: &str) -> Result<Test, String> {
+    let mut matches = tests
+        .into_iter()
+        .filter(|t| {
+            t.display_name() == name
+                || t.path == Path::new(name)
+                || t.path.file_stem().is_some_and(|stem| stem == name)
+        })
+        .collect::<Vec<_>>();
+    match matches.len() {
+        0 => Err(format!("no test named <{name}>")),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{n} tests named <{name}>")),
+    }
+}
+
+/// Parses a locale `name`, as used in `LC_ALL` and in snapshot names (`fr_FR.UTF-8` for instance).
+pub fn parse_locale(name: &str) -> Result<String, String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '@'));
+    if !valid {
+        return Err(format!("invalid locale <{name}>"));
+    }
+    Ok(name.to_string())
+}
+
+/// Returns the 64-bit FNV-1a hash of `bytes`, stable across platforms and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_locales() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("locales", "C,fr_FR.UTF-8").unwrap();
+        assert_eq!(test.locales, vec!["C", "fr_FR.UTF-8"]);
+        assert!(test.set_attribute("locales", "C,../fr").is_err());
+    }
+
+    #[test]
+    fn test_set_lock() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("lock", "gpu,db").unwrap();
+        assert_eq!(test.locks, vec!["gpu", "db"]);
+    }
+
+    #[test]
+    fn test_set_max_disk() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("max-disk", "10M").unwrap();
+        assert_eq!(test.max_disk, Some(10 * 1024 * 1024));
+        assert!(test.set_attribute("max-disk", "lots").is_err());
+    }
+
+    #[test]
+    fn test_set_description() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("description", " Expired tokens are rejected ")
+            .unwrap();
+        assert_eq!(
+            test.description.as_deref(),
+            Some("Expired tokens are rejected")
+        );
+        assert!(test.set_attribute("description", "").is_err());
+    }
+
+    #[test]
+    fn test_set_retry_until() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("retry-until", "30s every 500ms")
+            .unwrap();
+        assert_eq!(
+            test.retry,
+            Some(Retry {
+                deadline: Duration::from_secs(30),
+                interval: Duration::from_millis(500),
+            })
+        );
+        test.set_attribute("retry-until", "1m").unwrap();
+        assert_eq!(test.retry.unwrap().interval, RETRY_INTERVAL);
+        assert!(test.set_attribute("retry-until", "30s every 0s").is_err());
+        assert!(test.set_attribute("retry-until", "forever").is_err());
+    }
+
+    #[test]
+    fn test_set_retries() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("retries", "2").unwrap();
+        assert_eq!(test.retries, Some(2));
+        assert!(test.set_attribute("retries", "-1").is_err());
+    }
+
+    #[test]
+    fn test_set_compare() {
+        let mut test = Test::new(Path::new("foo.sh"));
+        test.set_attribute("compare", "out:json,err.pattern:exact")
+            .unwrap();
+        assert_eq!(
+            test.comparators,
+            vec![
+                ("out".to_string(), "json".to_string()),
+                ("err.pattern".to_string(), "exact".to_string())
+            ]
+        );
+        assert!(test.set_attribute("compare", "out").is_err());
+        assert!(test.set_attribute("compare", "exit:json").is_err());
+        assert!(test.set_attribute("compare", "out:fuzzy").is_err());
+    }
+
+    #[test]
+    fn test_collect() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            "b.sh\na.sh name=\"First\"\nc.sh\n",
+        )
+        .unwrap();
+        fs::write(dir.join("a.sh"), "#!/bin/sh\n# cliche: name=Ignored\n").unwrap();
+        fs::write(dir.join("c.sh"), "#!/bin/sh\n# cliche: name=Third\n").unwrap();
+
+        let inputs = vec![PathBuf::from("foo.sh"), dir.to_path_buf()];
+        let tests = collect(&inputs, true).unwrap();
+        let names = tests.iter().map(|t| t.display_name()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "foo.sh".to_string(),
+                dir.join("b.sh").display().to_string(),
+                "First".to_string(),
+                "Third".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_id() {
+        let id = Test::new(Path::new("tests/foo.sh")).id();
+        assert_eq!(id.len(), 16);
+        assert_eq!(Test::new(Path::new("./tests//foo.sh")).id(), id);
+        let absolute = env::current_dir().unwrap().join("tests/foo.sh");
+        assert_eq!(Test::new(&absolute).id(), id);
+        let mut named = Test::new(Path::new("tests/foo.sh"));
+        named.name = Some("Foo".to_string());
+        assert_eq!(named.id(), id);
+        assert_ne!(Test::new(Path::new("tests/bar.sh")).id(), id);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_shard() {
+        assert_eq!("2/4".parse(), Ok(Shard { index: 2, count: 4 }));
+        for shard in ["0/4", "5/4", "2", "a/4", "1/0", "-1/4"] {
+            assert!(shard.parse::<Shard>().is_err(), "{shard}");
+        }
+        // Each test belongs to exactly one shard, and no shard is empty.
+        let tests = (0..50)
+            .map(|i| Test::new(Path::new(&format!("tests/test{i}.sh"))))
+            .collect::<Vec<_>>();
+        for count in 2..=5 {
+            let shards = (1..=count)
+                .map(|index| Shard { index, count })
+                .collect::<Vec<_>>();
+            for test in &tests {
+                assert_eq!(shards.iter().filter(|s| s.contains(test)).count(), 1);
+            }
+            for shard in &shards {
+                assert!(tests.iter().any(|t| shard.contains(t)), "{shard:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_base() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        fs::create_dir_all(dir.join("cli/auth")).unwrap();
+        fs::write(dir.join("cli/auth/login.sh"), "").unwrap();
+        fs::write(dir.join("other.sh"), "").unwrap();
+
+        let root = format!("{}={}", dir.join("cli").display(), "snapshots")
+            .parse::<SnapshotRoot>()
+            .unwrap();
+        let roots = vec![root];
+        assert_eq!(
+            snapshot_base(&roots, &dir.join("cli/auth/login.sh")),
+            PathBuf::from("snapshots/auth/login.sh")
+        );
+        assert_eq!(
+            snapshot_base(&roots, &dir.join("other.sh")),
+            dir.join("other.sh")
+        );
+        assert!("cli".parse::<SnapshotRoot>().is_err());
+        assert!("cli=".parse::<SnapshotRoot>().is_err());
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let tests = (0..20)
+            .map(|i| Test::new(Path::new(&format!("tests/{i}.sh"))))
+            .collect::<Vec<_>>();
+        let shuffled = |seed| {
+            let mut tests = tests.clone();
+            shuffle(&mut tests, seed);
+            tests
+        };
+        assert_eq!(shuffled(42), shuffled(42));
+        assert_ne!(shuffled(42), tests);
+        assert_ne!(shuffled(42), shuffled(43));
+        let mut sorted = shuffled(42);
+        sorted.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut expected = tests.clone();
+        expected.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_select_one() {
+        let tests = vec![
+            Test::new(Path::new("tests/login.sh")),
+            Test::new(Path::new("tests/logout.sh")),
+            Test::new(Path::new("other/logout.sh")),
+        ];
+        let test = select_one(tests.clone(), "login").unwrap();
+        assert_eq!(test.path, PathBuf::from("tests/login.sh"));
+        let test = select_one(tests.clone(), "other/logout.sh").unwrap();
+        assert_eq!(test.path, PathBuf::from("other/logout.sh"));
+        assert_eq!(
+            select_one(tests.clone(), "logout"),
+            Err("2 tests named <logout>".to_string())
+        );
+        assert!(select_one(tests, "foo").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let login = Test::new(Path::new("tests/login-01.sh"));
+        let mut logout = Test::new(Path::new("tests/logout.sh"));
+        logout.name = Some("auth logout".to_string());
+
+        let filter = "login*".parse::<TestFilter>().unwrap();
+        assert!(filter.matches(&login));
+        assert!(!filter.matches(&logout));
+        let filter = "tests/log*".parse::<TestFilter>().unwrap();
+        assert!(filter.matches(&login));
+        assert!(filter.matches(&logout));
+        let filter = "/^auth/".parse::<TestFilter>().unwrap();
+        assert!(!filter.matches(&login));
+        assert!(filter.matches(&logout));
+        let filter = "/-\\d+\\.sh$/".parse::<TestFilter>().unwrap();
+        assert!(filter.matches(&login));
+        assert!("/[/".parse::<TestFilter>().is_err());
+    }
+}
@@ -1,9 +1,13 @@
 mod style;
 mod styledstring;
+mod terminal;
+mod width;
 
 use colored::control;
 pub use style::*;
 pub use styledstring::*;
+pub use terminal::ColorSupport;
+pub use width::{char_width, str_width};
 
 #[cfg(target_family = "unix")]
 pub fn init_crate_colored() {
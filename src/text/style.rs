@@ -17,6 +17,34 @@ pub enum Color {
     Yellow,
 }
 
+impl Color {
+    /// Returns the index of this color in the 256 colors palette.
+    pub fn ansi256(self) -> u8 {
+        match self {
+            Color::Blue => 32,
+            Color::BrightBlack => 242,
+            Color::Cyan => 37,
+            Color::Green => 35,
+            Color::Magenta | Color::Purple => 127,
+            Color::Red => 160,
+            Color::Yellow => 184,
+        }
+    }
+
+    /// Returns the RGB value of this color.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Blue => (36, 114, 200),
+            Color::BrightBlack => (102, 102, 102),
+            Color::Cyan => (17, 168, 205),
+            Color::Green => (13, 188, 121),
+            Color::Magenta | Color::Purple => (188, 63, 188),
+            Color::Red => (205, 49, 49),
+            Color::Yellow => (229, 229, 16),
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl Style {
     pub fn new() -> Style {
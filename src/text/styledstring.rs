@@ -18,6 +18,7 @@
 use colored::Colorize;
 
 use crate::text::style::{Color, Style};
+use crate::text::width::{char_width, str_width};
 
 /// A String with style.
 ///
@@ -46,7 +47,12 @@ struct Token {
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Format {
     Plain,
+    /// ANSI escape codes with the 16 basic colors.
     Ansi,
+    /// ANSI escape codes with colors of the 256 colors palette.
+    Ansi256,
+    /// ANSI escape codes with 24-bit RGB colors.
+    TrueColor,
 }
 
 #[allow(dead_code)]
@@ -131,32 +137,35 @@ impl StyledString {
         self.len() == 0
     }
 
-    /// Add newlines so each lines of this string has a maximum of `max_width` chars.
+    /// Returns the display width of this string in a terminal, wide chars (CJK, emojis)
+    /// using two columns.
+    pub fn width(&self) -> usize {
+        self.tokens.iter().map(|t| str_width(&t.content)).sum()
+    }
+
+    /// Add newlines so each lines of this string has a maximum display width of `max_width`.
     pub fn wrap(&self, max_width: usize) -> StyledString {
         let mut string = StyledString::new();
         let mut width = 0;
 
         for token in &self.tokens {
             let mut chunk = String::new();
-            let mut it = token.content.chars().peekable();
 
             // Iterate over each chars of the current token, splitting the current
             // token if necessary
-            while let Some(c) = it.next() {
-                chunk.push(c);
-                width += 1;
-
-                if width >= max_width {
-                    let token = Token::new(&chunk, token.style);
-                    string.push_token(token);
-                    if it.peek().is_some() {
-                        // New lines are always plain
-                        let nl = Token::new("\n", Style::new());
-                        string.push_token(nl);
+            for c in token.content.chars() {
+                let char_width = char_width(c);
+                if width > 0 && width + char_width > max_width {
+                    if !chunk.is_empty() {
+                        string.push_token(Token::new(&chunk, token.style));
+                        chunk = String::new();
                     }
-                    chunk = String::new();
+                    // New lines are always plain
+                    string.push_token(Token::new("\n", Style::new()));
                     width = 0;
                 }
+                chunk.push(c);
+                width += char_width;
             }
 
             // Append the last chunk
@@ -167,6 +176,32 @@ impl StyledString {
         }
         string
     }
+
+    /// Truncates this string to a maximum display width of `max_width`, an ellipsis ending the
+    /// string if it has been truncated.
+    pub fn truncate(&self, max_width: usize) -> StyledString {
+        if self.width() <= max_width {
+            return self.clone();
+        }
+        let mut string = StyledString::new();
+        let mut width = 0;
+        let max_width = max_width.saturating_sub(1);
+        'tokens: for token in &self.tokens {
+            let mut chunk = String::new();
+            for c in token.content.chars() {
+                let char_width = char_width(c);
+                if width + char_width > max_width {
+                    string.push_with(&chunk, token.style);
+                    string.push_with("…", token.style);
+                    break 'tokens;
+                }
+                chunk.push(c);
+                width += char_width;
+            }
+            string.push_with(&chunk, token.style);
+        }
+        string
+    }
 }
 
 /// Represents part of a styled string.
@@ -180,7 +215,29 @@ impl Token {
         match format {
             Format::Plain => self.plain(),
             Format::Ansi => self.ansi(),
+            Format::Ansi256 | Format::TrueColor => self.ansi_extended(format),
+        }
+    }
+
+    /// Renders this token with a 256 colors palette or RGB colors.
+    fn ansi_extended(&self, format: Format) -> String {
+        let mut params = vec![];
+        if self.style.bold {
+            params.push("1".to_string());
         }
+        if let Some(color) = self.style.fg {
+            let param = if format == Format::TrueColor {
+                let (r, g, b) = color.rgb();
+                format!("38;2;{r};{g};{b}")
+            } else {
+                format!("38;5;{}", color.ansi256())
+            };
+            params.push(param);
+        }
+        if params.is_empty() {
+            return self.plain();
+        }
+        format!("\x1b[{}m{}\x1b[0m", params.join(";"), self.content)
     }
 
     fn plain(&self) -> String {
@@ -274,6 +331,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_extended_colors() {
+        let mut message = StyledString::new();
+        message.push("Hello ");
+        message.push_with("Bob", Style::new().red().bold());
+        message.push_with("!", Style::new().bold());
+        assert_eq!(
+            message.to_string(Format::Ansi256),
+            "Hello \u{1b}[1;38;5;160mBob\u{1b}[0m\u{1b}[1m!\u{1b}[0m"
+        );
+        assert_eq!(
+            message.to_string(Format::TrueColor),
+            "Hello \u{1b}[1;38;2;205;49;49mBob\u{1b}[0m\u{1b}[1m!\u{1b}[0m"
+        );
+    }
+
     #[test]
     fn test_push() {
         let mut message = StyledString::new();
@@ -435,4 +508,40 @@ mod tests {
 
         assert_eq!(line.wrap(4), wrapped);
     }
+
+    #[test]
+    fn wrap_wide_chars() {
+        let mut line = StyledString::new();
+        line.push_with("aaaa", Style::new().blue());
+        line.push_with("日本語", Style::new().green());
+
+        let mut wrapped = StyledString::new();
+        wrapped.push_with("aaaa", Style::new().blue());
+        wrapped.push("\n");
+        wrapped.push_with("日本", Style::new().green());
+        wrapped.push("\n");
+        wrapped.push_with("語", Style::new().green());
+
+        assert_eq!(line.wrap(4), wrapped);
+        assert_eq!(line.width(), 10);
+    }
+
+    #[test]
+    fn truncate() {
+        let mut line = StyledString::new();
+        line.push("abc");
+        line.push_with("日本語", Style::new().red());
+
+        assert_eq!(line.truncate(9), line);
+
+        let mut truncated = StyledString::new();
+        truncated.push("abc");
+        truncated.push_with("日…", Style::new().red());
+        assert_eq!(line.truncate(7), truncated);
+        assert_eq!(line.truncate(7).width(), 6);
+
+        let mut truncated = StyledString::new();
+        truncated.push("ab…");
+        assert_eq!(line.truncate(3), truncated);
+    }
 }
@@ -0,0 +1,131 @@
+use crate::text::Format;
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+
+/// Color capabilities of a terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSupport {
+    /// No color, text is rendered plain.
+    None,
+    /// The 16 basic ANSI colors.
+    Basic,
+    /// The 256 colors palette.
+    Ansi256,
+    /// 24-bit RGB colors.
+    TrueColor,
+}
+
+impl ColorSupport {
+    /// Detects the color capabilities of stderr, where messages are printed.
+    ///
+    /// Following <https://no-color.org>, `NO_COLOR` (not empty) disables colors. Following
+    /// <https://bixense.com/clicolors/>, `CLICOLOR=0` disables colors and `CLICOLOR_FORCE` (not
+    /// `0`) enables them even if stderr is not a terminal.
+    pub fn detect() -> ColorSupport {
+        let var = |name: &str| env::var(name).ok();
+        ColorSupport::from_env(var, io::stderr().is_terminal())
+    }
+
+    /// Detects the color capabilities of the terminal, colors being enabled even if stderr is not
+    /// a terminal, or if they're disabled by the environment.
+    pub fn detect_forced() -> ColorSupport {
+        let var = |name: &str| env::var(name).ok();
+        ColorSupport::from_palette(var)
+    }
+
+    /// Detects color capabilities from environment variables given by `var`, for an output that
+    /// is a terminal or not.
+    fn from_env(var: impl Fn(&str) -> Option<String>, is_terminal: bool) -> ColorSupport {
+        if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return ColorSupport::None;
+        }
+        let forced = var("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0");
+        if !forced {
+            if !is_terminal || var("CLICOLOR").as_deref() == Some("0") {
+                return ColorSupport::None;
+            }
+            if var("TERM").as_deref() == Some("dumb") {
+                return ColorSupport::None;
+            }
+        }
+        ColorSupport::from_palette(var)
+    }
+
+    /// Detects the colors palette of the terminal from environment variables given by `var`.
+    fn from_palette(var: impl Fn(&str) -> Option<String>) -> ColorSupport {
+        let colorterm = var("COLORTERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorSupport::TrueColor;
+        }
+        if var("TERM").is_some_and(|term| term.contains("256color")) {
+            return ColorSupport::Ansi256;
+        }
+        ColorSupport::Basic
+    }
+
+    /// Returns the best format to render styled strings with these capabilities.
+    pub fn format(self) -> Format {
+        match self {
+            ColorSupport::None => Format::Plain,
+            ColorSupport::Basic => Format::Ansi,
+            ColorSupport::Ansi256 => Format::Ansi256,
+            ColorSupport::TrueColor => Format::TrueColor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)], is_terminal: bool) -> ColorSupport {
+        let var = |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        };
+        ColorSupport::from_env(var, is_terminal)
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&[], true), ColorSupport::Basic);
+        assert_eq!(detect(&[], false), ColorSupport::None);
+        assert_eq!(
+            detect(&[("TERM", "xterm-256color")], true),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            detect(
+                &[("TERM", "xterm-256color"), ("COLORTERM", "truecolor")],
+                true
+            ),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(detect(&[("TERM", "dumb")], true), ColorSupport::None);
+        assert_eq!(detect(&[("CLICOLOR", "0")], true), ColorSupport::None);
+        assert_eq!(detect(&[("NO_COLOR", "1")], true), ColorSupport::None);
+        assert_eq!(detect(&[("NO_COLOR", "")], true), ColorSupport::Basic);
+        assert_eq!(
+            detect(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")], false),
+            ColorSupport::None
+        );
+    }
+
+    #[test]
+    fn test_detect_forced() {
+        assert_eq!(
+            detect(&[("CLICOLOR_FORCE", "1")], false),
+            ColorSupport::Basic
+        );
+        assert_eq!(
+            detect(&[("CLICOLOR_FORCE", "1"), ("CLICOLOR", "0")], true),
+            ColorSupport::Basic
+        );
+        assert_eq!(
+            detect(&[("CLICOLOR_FORCE", "0")], false),
+            ColorSupport::None
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Display width of text in a terminal.
+
+/// Returns the number of columns used to display the char `c` in a terminal.
+///
+/// Control chars and combining marks have no width, East Asian wide chars and most emojis use
+/// two columns.
+pub fn char_width(c: char) -> usize {
+    let c = c as u32;
+    if c < 0x20 || (0x7f..0xa0).contains(&c) {
+        return 0;
+    }
+    const ZERO_WIDTH: [(u32, u32); 7] = [
+        (0x0300, 0x036f), // Combining diacritical marks
+        (0x1ab0, 0x1aff),
+        (0x1dc0, 0x1dff),
+        (0x200b, 0x200f), // Zero width space, joiners and direction marks
+        (0x20d0, 0x20ff),
+        (0xfe00, 0xfe0f), // Variation selectors
+        (0xfe20, 0xfe2f),
+    ];
+    const WIDE: [(u32, u32); 15] = [
+        (0x1100, 0x115f), // Hangul Jamo
+        (0x2e80, 0x303e), // CJK radicals, punctuation
+        (0x3041, 0x33ff), // Hiragana, Katakana, CJK compatibility
+        (0x3400, 0x4dbf), // CJK unified ideographs extension A
+        (0x4e00, 0x9fff), // CJK unified ideographs
+        (0xa000, 0xa4cf), // Yi
+        (0xac00, 0xd7a3), // Hangul syllables
+        (0xf900, 0xfaff), // CJK compatibility ideographs
+        (0xfe30, 0xfe4f), // CJK compatibility forms
+        (0xff00, 0xff60), // Fullwidth forms
+        (0xffe0, 0xffe6),
+        (0x1f300, 0x1f64f), // Emojis
+        (0x1f680, 0x1f6ff),
+        (0x1f900, 0x1f9ff),
+        (0x20000, 0x3fffd), // CJK unified ideographs extensions
+    ];
+    let in_ranges = |ranges: &[(u32, u32)]| ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c));
+    if in_ranges(&ZERO_WIDTH) {
+        0
+    } else if in_ranges(&WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Returns the number of columns used to display `s` in a terminal.
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_width() {
+        assert_eq!(str_width("hello"), 5);
+        assert_eq!(str_width("café"), 4);
+        assert_eq!(str_width("cafe\u{301}"), 4);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(str_width("ok 👍"), 5);
+        assert_eq!(str_width("\u{1b}"), 0);
+    }
+}
@@ -0,0 +1,305 @@
+//! Parsing of a subset of [TOML](https://toml.io), used by trycmd cases and the configuration
+//! file.
+//!
+//! Only `key = value` pairs and `[table]` headers are supported, values being strings (basic,
+//! literal and multi-line), integers and arrays of strings, possibly spanning several lines.
+//! Comments can end any line. Other values (floats, booleans, arrays of other values, inline
+//! tables...) are reported as unsupported.
+
+/// A TOML value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i32),
+    Array(Vec<String>),
+}
+
+/// Parses the `key = value` pairs of a TOML `text`. Keys of a `[table]` are prefixed by the table
+/// name.
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut pairs = vec![];
+    let mut table = String::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let header = line
+            .split_once('#')
+            .map_or(line, |(header, _)| header)
+            .trim();
+        if let Some(name) = header.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = format!("{}.", name.trim());
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("expecting key = value, found <{line}>"));
+        };
+        let key = format!("{table}{}", key.trim());
+        let value = value.trim();
+        let value = if let Some(start) = value.strip_prefix("\"\"\"") {
+            // Multi-line string, a newline right after the opening quotes is trimmed.
+            let mut content = start.to_string();
+            while !content.contains("\"\"\"") {
+                let Some(next) = lines.next() else {
+                    return Err(format!("unterminated string for key <{key}>"));
+                };
+                content.push('\n');
+                content.push_str(next);
+            }
+            let end = content.find("\"\"\"").unwrap_or(content.len());
+            check_end(&content[end + 3..])?;
+            let content = content[..end].strip_prefix('\n').unwrap_or(&content[..end]);
+            Value::String(unescape(content))
+        } else if value.starts_with('[') {
+            // The array may continue on the next lines, up to its closing bracket.
+            let mut content = value.to_string();
+            loop {
+                if let Some((items, end)) = split_array(&content)? {
+                    check_end(&content[end..])?;
+                    break Value::Array(items);
+                }
+                let Some(next) = lines.next() else {
+                    return Err(format!("unterminated array for key <{key}>"));
+                };
+                content.push('\n');
+                content.push_str(next);
+            }
+        } else if value.starts_with(['"', '\'']) {
+            let end = string_end(value).ok_or_else(|| format!("unterminated string <{value}>"))?;
+            check_end(&value[end..])?;
+            Value::String(parse_string(&value[..end])?)
+        } else {
+            let value = value
+                .split_once('#')
+                .map_or(value, |(value, _)| value)
+                .trim();
+            match value.parse::<i32>() {
+                Ok(integer) => Value::Integer(integer),
+                Err(_) => return Err(format!("unsupported value <{value}>")),
+            }
+        };
+        pairs.push((key, value));
+    }
+    Ok(pairs)
+}
+
+/// Splits the comma separated strings of a TOML array `text`, starting with its opening bracket.
+/// Items can be on several lines, with comments.
+///
+/// Returns the strings with the byte index following the closing bracket, or `None` if the array
+/// doesn't end in `text`.
+fn split_array(text: &str) -> Result<Option<(Vec<String>, usize)>, String> {
+    let mut values = vec![];
+    // Index of the next char to read, after the opening bracket.
+    let mut i = 1;
+    let mut expecting_item = true;
+    loop {
+        i = skip_blanks(text, i);
+        let Some(c) = text[i..].chars().next() else {
+            return Ok(None);
+        };
+        match c {
+            ']' => return Ok(Some((values, i + 1))),
+            ',' if !expecting_item => {
+                expecting_item = true;
+                i += 1;
+            }
+            '"' | '\'' if expecting_item => {
+                let rest = &text[i..];
+                let line = rest.lines().next().unwrap_or(rest);
+                let end =
+                    string_end(line).ok_or_else(|| format!("unterminated string <{line}>"))?;
+                values.push(parse_string(&rest[..end])?);
+                expecting_item = false;
+                i += end;
+            }
+            '0'..='9' | '-' | '+' if expecting_item => {
+                return Err(
+                    "unsupported array of integers, only arrays of strings are supported"
+                        .to_string(),
+                );
+            }
+            _ => {
+                let line = text[i..].lines().next().unwrap_or_default();
+                return Err(format!(
+                    "unsupported array item <{line}>, expecting a string"
+                ));
+            }
+        }
+    }
+}
+
+/// Returns the byte index of the first char of `text` from the byte index `i` that is neither a
+/// whitespace nor part of a comment.
+fn skip_blanks(text: &str, mut i: usize) -> usize {
+    loop {
+        let rest = &text[i..];
+        let trimmed = rest.trim_start();
+        i += rest.len() - trimmed.len();
+        if !trimmed.starts_with('#') {
+            return i;
+        }
+        i += trimmed.find('\n').unwrap_or(trimmed.len());
+    }
+}
+
+/// Returns the byte index following the closing quote of the basic (`"..."`) or literal
+/// (`'...'`) string starting `text`, if the string is closed.
+fn string_end(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Some(i + quote.len_utf8());
+        }
+        // Literal strings have no escape sequence.
+        if c == '\\' && quote == '"' {
+            chars.next();
+        }
+    }
+    None
+}
+
+/// Checks that the `rest` of a line following a value is empty or a comment.
+fn check_end(rest: &str) -> Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected <{rest}> after value"))
+    }
+}
+
+/// Parses a single-line TOML string, basic (`"..."`) or literal (`'...'`).
+fn parse_string(value: &str) -> Result<String, String> {
+    if let Some(s) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Ok(s.to_string());
+    }
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(s) => Ok(unescape(s)),
+        None => Err(format!("unsupported value <{value}>")),
+    }
+}
+
+/// Replaces the escape sequences of a TOML basic string.
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+# Comment
+bin.name = "foo"
+args = ["--verbose", 'C:\dir']
+status.code = 2
+
+[invariants]
+stderr = """
+panicked at"""
+"#;
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                ("bin.name".to_string(), Value::String("foo".to_string())),
+                (
+                    "args".to_string(),
+                    Value::Array(vec!["--verbose".to_string(), "C:\\dir".to_string()])
+                ),
+                ("status.code".to_string(), Value::Integer(2)),
+                (
+                    "invariants.stderr".to_string(),
+                    Value::String("panicked at".to_string())
+                ),
+            ])
+        );
+        assert!(parse("foo").is_err());
+        assert!(parse("foo = bar").is_err());
+    }
+
+    #[test]
+    fn test_parse_comments_and_multi_line_arrays() {
+        let text = r#"
+[table] # Comment
+args = [ # Options
+    "--verbose", # Verbose
+    "a # b", 'c"d',
+]
+name = "foo # bar" # Comment
+code = 2 # Comment
+empty = []
+"#;
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                (
+                    "table.args".to_string(),
+                    Value::Array(vec![
+                        "--verbose".to_string(),
+                        "a # b".to_string(),
+                        "c\"d".to_string()
+                    ])
+                ),
+                (
+                    "table.name".to_string(),
+                    Value::String("foo # bar".to_string())
+                ),
+                ("table.code".to_string(), Value::Integer(2)),
+                ("table.empty".to_string(), Value::Array(vec![])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported() {
+        assert_eq!(
+            parse("args = [é]"),
+            Err("unsupported array item <é]>, expecting a string".to_string())
+        );
+        assert_eq!(
+            parse("codes = [1, 2]"),
+            Err("unsupported array of integers, only arrays of strings are supported".to_string())
+        );
+        assert_eq!(
+            parse("args = [\"a\",\n\"b\""),
+            Err("unterminated array for key <args>".to_string())
+        );
+        assert_eq!(
+            parse("args = [\"a\" \"b\"]"),
+            Err("unsupported array item <\"b\"]>, expecting a string".to_string())
+        );
+        assert_eq!(
+            parse("name = \"foo\" bar"),
+            Err("unexpected <bar> after value".to_string())
+        );
+        assert_eq!(
+            parse("quiet = true"),
+            Err("unsupported value <true>".to_string())
+        );
+        assert_eq!(
+            parse("name = \"é"),
+            Err("unterminated string <\"é>".to_string())
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Triage of failures: tests failing with an identical diff are grouped together.
+use crate::error::Error;
+use crate::text::{Style, StyledString};
+
+/// Failures of a run, grouped by identical diff.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FailureGroups {
+    groups: Vec<FailureGroup>,
+}
+
+/// Tests that have failed with the same diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FailureGroup {
+    message: String,
+    expected: Option<String>,
+    actual: Option<String>,
+    tests: Vec<String>,
+}
+
+impl FailureGroups {
+    pub fn new() -> Self {
+        FailureGroups::default()
+    }
+
+    /// Adds the failure of the test `name` with an `error`.
+    ///
+    /// Returns the name of the first test that has failed with the same diff, if any.
+    pub fn add(&mut self, name: &str, error: &Error) -> Option<&str> {
+        let message = error.message();
+        let (expected, actual) = error.expected_actual();
+        let index = self
+            .groups
+            .iter()
+            .position(|g| g.message == message && g.expected == expected && g.actual == actual);
+        match index {
+            Some(index) => {
+                let group = &mut self.groups[index];
+                group.tests.push(name.to_string());
+                Some(&group.tests[0])
+            }
+            None => {
+                self.groups.push(FailureGroup {
+                    message,
+                    expected,
+                    actual,
+                    tests: vec![name.to_string()],
+                });
+                None
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Renders the failures, tests with an identical diff being listed under a single entry.
+    pub fn render(&self) -> StyledString {
+        let mut s = StyledString::new();
+        s.push_with("Failures", Style::new().bold());
+        s.push(":\n");
+        for group in &self.groups {
+            match group.tests.as_slice() {
+                [test] => {
+                    s.push("  ");
+                    s.push_with(test, Style::new().bold());
+                    s.push(&format!(": {}\n", group.message));
+                }
+                tests => {
+                    s.push("  ");
+                    s.push_with(&format!("{} tests", tests.len()), Style::new().red().bold());
+                    s.push(&format!(" failed with identical diff: {}\n", group.message));
+                    for test in tests {
+                        s.push("    - ");
+                        s.push_with(test, Style::new().bold());
+                        s.push("\n");
+                    }
+                }
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Format;
+    use std::path::PathBuf;
+
+    fn stdout_error(cmd_path: &str, actual: &str) -> Error {
+        Error::CheckStdoutLine {
+            cmd_path: PathBuf::from(cmd_path),
+            expected: Some("v1.0.0\n".to_string()),
+            actual: Some(actual.to_string()),
+            row: 1,
+            chunk: 0,
+        }
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut groups = FailureGroups::new();
+        assert!(groups.is_empty());
+        assert_eq!(groups.add("a.sh", &stdout_error("a.sh", "v1.1.0\n")), None);
+        assert_eq!(
+            groups.add("b.sh", &stdout_error("b.sh", "v1.1.0\n")),
+            Some("a.sh")
+        );
+        assert_eq!(groups.add("c.sh", &stdout_error("c.sh", "v2.0.0\n")), None);
+        assert_eq!(
+            groups.add("d.sh", &stdout_error("d.sh", "v1.1.0\n")),
+            Some("a.sh")
+        );
+        assert_eq!(
+            groups.render().to_string(Format::Plain),
+            "Failures:\n\
+             \x20 3 tests failed with identical diff: Stdout doesn't match at line 1\n\
+             \x20   - a.sh\n\
+             \x20   - b.sh\n\
+             \x20   - d.sh\n\
+             \x20 c.sh: Stdout doesn't match at line 1\n"
+        );
+    }
+}
@@ -0,0 +1,560 @@
+//! Update of snapshot files from actual command results.
+//!
+//! Changes are first planned for every test, then previewed, and finally applied.
+use crate::command::{CommandResult, CommandSpec, ExitCode, FrontMatter, PENDING_SUFFIX};
+use crate::error::Error;
+use crate::screen::Screen;
+use crate::text::{Style, StyledString};
+use crate::verify;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// The kind of change made to a snapshot file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Delete,
+}
+
+/// A planned change of a snapshot file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    /// Current content of the snapshot (empty if the snapshot doesn't exist).
+    pub old: Vec<u8>,
+    /// New content of the snapshot (empty if the snapshot is deleted).
+    pub new: Vec<u8>,
+}
+
+/// Plans the changes of the snapshot files of `spec` so they match an actual `result`.
+///
+/// Exact snapshots of stdout (`.out`), stderr (`.err`), exit code (`.exit`), terminal screen
+/// (`.screen`) and state (`.state.json`) are updated:
+/// - an existing snapshot is modified if it doesn't match the result, or deleted if its absence
+///   is equivalent (empty output, zero exit code),
+/// - a missing snapshot is created if the result is not empty. A stdout or stderr snapshot is not
+///   created if the output is already checked otherwise (with a pattern for instance).
+///
+/// Snapshots of each locale of `spec` are updated the same way, from a run under the locale.
+pub fn plan(spec: &CommandSpec, result: &CommandResult) -> Result<Vec<SnapshotChange>, Error> {
+    let mut changes = vec![];
+
+    let old = spec.has_stdout().then(|| spec.stdout()).transpose()?;
+    let new = match &old {
+        Some(old) => {
+            let front_matter = spec.stdout_front_matter(old)?;
+            let matches =
+                verify::check_stdout_snapshot(spec.cmd_path(), old, &front_matter, result.stdout())
+                    .is_ok()
+                    || matches_alternative(spec, "out", result.stdout())?;
+            snapshot_content(old, &front_matter, result.stdout(), matches)
+        }
+        None => result.stdout().to_vec(),
+    };
+    if is_snapshot(spec, "out") && (spec.has_stdout() || !spec.has_stdout_expectation()) {
+        changes.extend(plan_snapshot(spec, "out", old, new)?);
+    }
+
+    // A screen snapshot is only modified, never created nor deleted: stdout of most commands is
+    // not meant to be rendered on a terminal, and a blank screen is a valid expectation.
+    if spec.has_screen() {
+        let old = spec.screen()?.into_bytes();
+        let new = Screen::render(spec.screen_size(), result.stdout()).into_bytes();
+        if old != new {
+            changes.push(SnapshotChange {
+                path: spec.snapshot_path("screen"),
+                kind: ChangeKind::Modify,
+                old,
+                new,
+            });
+        }
+    }
+
+    // The command is run once more per terminal size, to create or modify the snapshot of each
+    // size.
+    for size in spec.screen_sizes()? {
+        let path = spec.sized_screen_path(size);
+        let old = path.exists().then(|| spec.sized_screen(size)).transpose()?;
+        let result = spec
+            .execute_with_screen_size(size)
+            .map_err(|err| Error::FileRead {
+                path: spec.cmd_path().to_path_buf(),
+                cause: err.to_string(),
+            })?;
+        let new = Screen::render(size, result.stdout()).into_bytes();
+        let change = match old {
+            Some(old) if old.as_bytes() == new => None,
+            Some(old) => Some(SnapshotChange {
+                path,
+                kind: ChangeKind::Modify,
+                old: old.into_bytes(),
+                new,
+            }),
+            None => Some(SnapshotChange {
+                path,
+                kind: ChangeKind::Create,
+                old: vec![],
+                new,
+            }),
+        };
+        changes.extend(change);
+    }
+
+    let old = spec.has_stderr().then(|| spec.stderr()).transpose()?;
+    let new = match &old {
+        Some(old) => {
+            let front_matter = spec.stderr_front_matter(old)?;
+            let matches =
+                verify::check_stderr_snapshot(spec.cmd_path(), old, &front_matter, result.stderr())
+                    .is_ok()
+                    || matches_alternative(spec, "err", result.stderr())?;
+            snapshot_content(old, &front_matter, result.stderr(), matches)
+        }
+        None => result.stderr().to_vec(),
+    };
+    if is_snapshot(spec, "err") && (spec.has_stderr() || !spec.has_stderr_expectation()) {
+        changes.extend(plan_snapshot(spec, "err", old, new)?);
+    }
+
+    let path = spec.snapshot_path("exit");
+    let old = spec.has_exit_code().then(|| fs::read(&path));
+    let old = match old {
+        Some(Ok(old)) => Some(old),
+        Some(Err(err)) => {
+            return Err(Error::FileRead {
+                path,
+                cause: err.to_string(),
+            });
+        }
+        None => None,
+    };
+    let expected = spec.exit_code()?;
+    let actual = result.termination();
+    let success = actual == ExitCode::from(0).into();
+    let new = if success {
+        vec![]
+    } else {
+        format!("{}\n", actual.to_expectation()).into_bytes()
+    };
+    // The exit code file is not rewritten if it already expects the actual exit code (it may be
+    // written with a symbolic name, or be a range). A file expecting exactly `0` is redundant.
+    let redundant = success && expected.exact().is_some();
+    if !expected.matches(actual) || old.is_some() && redundant {
+        changes.extend(plan_file(path, old, new));
+    }
+
+    // The state is updated if the command has written one, or if a state is expected.
+    let path = spec.snapshot_path("state.json");
+    let old = spec.has_state().then(|| fs::read(&path));
+    let old = match old {
+        Some(Ok(old)) => Some(old),
+        Some(Err(err)) => {
+            return Err(Error::FileRead {
+                path,
+                cause: err.to_string(),
+            });
+        }
+        None => None,
+    };
+    let new = result.state().unwrap_or_default().to_vec();
+    changes.extend(plan_file(path, old, new));
+
+    // The command is run once more per locale, to update the snapshots of each locale.
+    for locale in spec.locales() {
+        let to_file_error = |err: io::Error| Error::FileRead {
+            path: spec.cmd_path().to_path_buf(),
+            cause: err.to_string(),
+        };
+        let localized = spec.for_locale(locale).map_err(to_file_error)?;
+        let result = localized.execute().map_err(to_file_error)?;
+        changes.extend(plan(&localized, &result)?);
+    }
+
+    Ok(changes)
+}
+
+/// Returns `true` if the expectation file with extension `ext` (`out` or `err`) is a snapshot of
+/// the output, compared exactly. Files compared otherwise (with `compare=out:json` for instance)
+/// and files including other files are not rewritten.
+fn is_snapshot(spec: &CommandSpec, ext: &str) -> bool {
+    spec.comparator(ext).is_none_or(|name| name == "exact") && !spec.has_includes(ext)
+}
+
+/// Returns `true` if an `actual` output matches one of the alternatives of the snapshot with
+/// extension `ext` (`out` or `err`). The snapshot is then kept as is.
+fn matches_alternative(spec: &CommandSpec, ext: &str, actual: &[u8]) -> Result<bool, Error> {
+    let check = match ext {
+        "out" => verify::check_stdout_snapshot,
+        _ => verify::check_stderr_snapshot,
+    };
+    Ok(spec
+        .alternatives(ext)?
+        .iter()
+        .any(|(_, snapshot, front_matter)| {
+            check(spec.cmd_path(), snapshot, front_matter, actual).is_ok()
+        }))
+}
+
+/// Returns the new content of an existing stdout or stderr `snapshot` for an `actual` output.
+///
+/// The front-matter of the snapshot is kept, and the actual output is normalized as declared in
+/// the front-matter. A snapshot that `matches` the actual output given its options (unordered
+/// lines for instance) is kept as is.
+fn snapshot_content(
+    snapshot: &[u8],
+    front_matter: &FrontMatter,
+    actual: &[u8],
+    matches: bool,
+) -> Vec<u8> {
+    if matches {
+        return snapshot.to_vec();
+    }
+    let mut content = front_matter.header(snapshot).to_vec();
+    content.extend(front_matter.normalize(actual));
+    content
+}
+
+/// Plans the change of the stdout or stderr snapshot with extension `ext` (`out` or `err`), given
+/// its `old` content and its `new` content.
+///
+/// A snapshot referencing a shared snapshot (like `@usage.out`) is changed through the shared
+/// snapshot, for every test sharing it. The reference is deleted if the snapshot is not needed
+/// anymore, the shared snapshot being kept for the other tests.
+fn plan_snapshot(
+    spec: &CommandSpec,
+    ext: &str,
+    old: Option<Vec<u8>>,
+    new: Vec<u8>,
+) -> Result<Option<SnapshotChange>, Error> {
+    let path = spec.snapshot_path(ext);
+    let change = match spec.shared_snapshot(ext)? {
+        Some(_) if new.is_empty() => plan_file(path, Some(spec.read_expectation(ext)?), new),
+        Some(shared_path) => plan_file(shared_path, old, new),
+        None => plan_file(path, old, new),
+    };
+    Ok(change)
+}
+
+/// Plans the change of a snapshot file at `path`, given its `old` content (if it exists) and its
+/// `new` content (empty meaning the snapshot is not needed).
+fn plan_file(path: PathBuf, old: Option<Vec<u8>>, new: Vec<u8>) -> Option<SnapshotChange> {
+    match old {
+        Some(old) if old == new => None,
+        Some(old) if new.is_empty() => Some(SnapshotChange {
+            path,
+            kind: ChangeKind::Delete,
+            old,
+            new,
+        }),
+        Some(old) => Some(SnapshotChange {
+            path,
+            kind: ChangeKind::Modify,
+            old,
+            new,
+        }),
+        None if new.is_empty() => None,
+        None => Some(SnapshotChange {
+            path,
+            kind: ChangeKind::Create,
+            old: vec![],
+            new,
+        }),
+    }
+}
+
+/// Renders a preview of `changes`: a colored diff per file, followed by the list of files to be
+/// created, modified or deleted.
+pub fn preview(changes: &[SnapshotChange]) -> StyledString {
+    let mut s = StyledString::new();
+    if changes.is_empty() {
+        s.push("No snapshot to update\n");
+        return s;
+    }
+    for change in changes {
+        let (label, style) = label(change.kind);
+        s.push_with(label, style.bold());
+        s.push(" ");
+        s.push_with(&change.path.display().to_string(), Style::new().bold());
+        s.push("\n");
+        let old = String::from_utf8_lossy(&change.old);
+        let new = String::from_utf8_lossy(&change.new);
+        s.append(verify::unified_diff(&old, &new));
+    }
+    s.push("\n");
+    for change in changes {
+        let (label, style) = label(change.kind);
+        s.push("  ");
+        s.push_with(label, style);
+        s.push(" ");
+        s.push(&change.path.display().to_string());
+        s.push("\n");
+    }
+    let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+    s.push(&format!(
+        "{} to create, {} to modify, {} to delete\n",
+        count(ChangeKind::Create),
+        count(ChangeKind::Modify),
+        count(ChangeKind::Delete)
+    ));
+    s
+}
+
+fn label(kind: ChangeKind) -> (&'static str, Style) {
+    match kind {
+        ChangeKind::Create => ("create", Style::new().green()),
+        ChangeKind::Modify => ("modify", Style::new().yellow()),
+        ChangeKind::Delete => ("delete", Style::new().red()),
+    }
+}
+
+/// Returns the `changes` of existing snapshots only: missing snapshots are not created.
+pub fn existing_only(changes: Vec<SnapshotChange>) -> Vec<SnapshotChange> {
+    changes
+        .into_iter()
+        .filter(|c| c.kind != ChangeKind::Create)
+        .collect()
+}
+
+/// Applies `changes` to the snapshot files.
+pub fn apply(changes: &[SnapshotChange]) -> Result<(), io::Error> {
+    for change in changes {
+        match change.kind {
+            ChangeKind::Create => {
+                // Snapshots may be stored in a directory that doesn't exist yet.
+                if let Some(dir) = change.path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&change.path, &change.new)?;
+            }
+            ChangeKind::Modify => fs::write(&change.path, &change.new)?,
+            ChangeKind::Delete => fs::remove_file(&change.path)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the actual output of the stream whose verification failed with `err` to a pending
+/// snapshot (`foo.out.new` or `foo.err.new`), to be inspected or moved over the snapshot, and
+/// returns its path. The pending snapshot of the other stream, if any, is stale and removed.
+pub fn write_pending(
+    spec: &CommandSpec,
+    result: &CommandResult,
+    err: &Error,
+) -> Result<Option<PathBuf>, io::Error> {
+    let Some((ext, _)) = err.expectation() else {
+        return Ok(None);
+    };
+    let (ext, actual, other) = if ext == "out" || ext.starts_with("out.") {
+        ("out", result.stdout(), "err")
+    } else if ext == "err" || ext.starts_with("err.") {
+        ("err", result.stderr(), "out")
+    } else {
+        return Ok(None);
+    };
+    remove_file(&pending_path(spec, other))?;
+    let path = pending_path(spec, ext);
+    fs::write(&path, actual)?;
+    Ok(Some(path))
+}
+
+/// Removes the pending snapshots of `spec`, stale once the command passes.
+pub fn remove_pending(spec: &CommandSpec) -> Result<(), io::Error> {
+    remove_file(&pending_path(spec, "out"))?;
+    remove_file(&pending_path(spec, "err"))
+}
+
+fn pending_path(spec: &CommandSpec, ext: &str) -> PathBuf {
+    spec.snapshot_path(&format!("{ext}.{PENDING_SUFFIX}"))
+}
+
+/// Removes the file at `path`, if it exists.
+fn remove_file(path: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Format;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "echo foo").unwrap();
+        fs::write(dir.join("foo.out"), "bar\n").unwrap();
+        fs::write(dir.join("foo.exit"), "3\n").unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+        let cmd_path = spec.cmd_path().to_path_buf();
+
+        let result = CommandResult::new(0.into(), b"foo\n", b"warning\n");
+        let changes = plan(&spec, &result).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                SnapshotChange {
+                    path: cmd_path.with_extension("out"),
+                    kind: ChangeKind::Modify,
+                    old: b"bar\n".to_vec(),
+                    new: b"foo\n".to_vec(),
+                },
+                SnapshotChange {
+                    path: cmd_path.with_extension("err"),
+                    kind: ChangeKind::Create,
+                    old: vec![],
+                    new: b"warning\n".to_vec(),
+                },
+                SnapshotChange {
+                    path: cmd_path.with_extension("exit"),
+                    kind: ChangeKind::Delete,
+                    old: b"3\n".to_vec(),
+                    new: vec![],
+                },
+            ]
+        );
+
+        apply(&changes).unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+        assert!(plan(&spec, &result).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_existing_only() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "echo foo").unwrap();
+        fs::write(dir.join("foo.out"), "bar\n").unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+        let cmd_path = spec.cmd_path().to_path_buf();
+
+        let result = CommandResult::new(0.into(), b"foo\n", b"warning\n");
+        let changes = existing_only(plan(&spec, &result).unwrap());
+        assert_eq!(
+            changes,
+            vec![SnapshotChange {
+                path: cmd_path.with_extension("out"),
+                kind: ChangeKind::Modify,
+                old: b"bar\n".to_vec(),
+                new: b"foo\n".to_vec(),
+            }]
+        );
+
+        apply(&changes).unwrap();
+        assert_eq!(fs::read(cmd_path.with_extension("out")).unwrap(), b"foo\n");
+        assert!(!cmd_path.with_extension("err").exists());
+    }
+
+    #[test]
+    fn test_write_pending() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "echo foo").unwrap();
+        fs::write(dir.join("foo.out"), "bar\n").unwrap();
+        fs::write(dir.join("foo.err.new"), "stale\n").unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+        let cmd_path = spec.cmd_path().to_path_buf();
+
+        let result = CommandResult::new(0.into(), b"foo\n", b"");
+        let err = verify::check_result(&spec, &result, &Default::default()).unwrap_err();
+        let path = write_pending(&spec, &result, &err).unwrap();
+        assert_eq!(path, Some(cmd_path.with_extension("out.new")));
+        assert_eq!(
+            fs::read(cmd_path.with_extension("out.new")).unwrap(),
+            b"foo\n"
+        );
+        assert!(!cmd_path.with_extension("err.new").exists());
+
+        // Errors unrelated to stdout or stderr have no pending snapshot.
+        let err = Error::FileNotUtf8 {
+            path: cmd_path.clone(),
+        };
+        assert_eq!(write_pending(&spec, &result, &err).unwrap(), None);
+
+        remove_pending(&spec).unwrap();
+        assert!(!cmd_path.with_extension("out.new").exists());
+    }
+
+    #[test]
+    fn test_plan_shared_snapshot() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "echo usage").unwrap();
+        fs::write(dir.join("foo.out"), "@usage.out\n").unwrap();
+        fs::write(dir.join("usage.out"), "Usage: foo\n").unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+
+        // The shared snapshot is changed, not the reference.
+        let result = CommandResult::new(0.into(), b"Usage: foo [-h]\n", b"");
+        assert_eq!(
+            plan(&spec, &result).unwrap(),
+            vec![SnapshotChange {
+                path: dir.join("usage.out"),
+                kind: ChangeKind::Modify,
+                old: b"Usage: foo\n".to_vec(),
+                new: b"Usage: foo [-h]\n".to_vec(),
+            }]
+        );
+
+        // The reference is deleted if the snapshot is not needed anymore.
+        let result = CommandResult::new(0.into(), b"", b"");
+        assert_eq!(
+            plan(&spec, &result).unwrap(),
+            vec![SnapshotChange {
+                path: spec.snapshot_path("out"),
+                kind: ChangeKind::Delete,
+                old: b"@usage.out\n".to_vec(),
+                new: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_plan_front_matter() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = dir.join("foo.sh");
+        fs::write(&cmd_path, "echo foo").unwrap();
+        fs::write(
+            dir.join("foo.out"),
+            "---\nnormalize: ansi\nmode: unordered\n---\na\nb\n",
+        )
+        .unwrap();
+        let spec = CommandSpec::new(&cmd_path).unwrap();
+
+        // Lines in another order match the snapshot, which is kept as is.
+        let result = CommandResult::new(0.into(), b"b\n\x1b[1ma\x1b[0m\n", b"");
+        assert!(plan(&spec, &result).unwrap().is_empty());
+
+        // The front-matter is kept, the actual output being normalized.
+        let result = CommandResult::new(0.into(), b"\x1b[1mc\x1b[0m\n", b"");
+        let changes = plan(&spec, &result).unwrap();
+        assert_eq!(
+            changes[0].new,
+            b"---\nnormalize: ansi\nmode: unordered\n---\nc\n"
+        );
+    }
+
+    #[test]
+    fn test_preview() {
+        let changes = vec![SnapshotChange {
+            path: Path::new("foo.out").to_path_buf(),
+            kind: ChangeKind::Modify,
+            old: b"a\nb\n".to_vec(),
+            new: b"a\nc\n".to_vec(),
+        }];
+        assert_eq!(
+            preview(&changes).to_string(Format::Plain),
+            "modify foo.out\n  a\n- b\n+ c\n\n  modify foo.out\n0 to create, 1 to modify, 0 to delete\n"
+        );
+    }
+}
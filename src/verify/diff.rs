@@ -1,3 +1,5 @@
+use crate::chunk::SegmentMismatch;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum Diff {
@@ -5,13 +7,26 @@ pub enum Diff {
         expected: Option<String>,
         actual: Option<String>,
         row: usize,
+        /// 0-based index of the chunk in the line (see [`crate::chunk::ChunkedLines`]).
+        chunk: usize,
     },
     PatternLine {
         expected: Option<String>,
         actual: Option<String>,
         row: usize,
+        /// The first mismatching segment of a composite pattern line.
+        mismatch: Option<SegmentMismatch>,
+    },
+    Byte {
+        /// 0-based offset of the first differing byte.
+        offset: usize,
+        /// 0-based offset of the first byte of the `expected` and `actual` windows.
+        start: usize,
+        /// Expected bytes around the difference.
+        expected: Vec<u8>,
+        /// Actual bytes around the difference.
+        actual: Vec<u8>,
     },
-    Byte,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -1,5 +1,6 @@
+use crate::chunk::ChunkedLines;
 use crate::verify::diff::Diff;
-use std::cmp::max;
+use std::collections::HashMap;
 
 pub fn eval_exact_diff(expected: &[u8], actual: &[u8]) -> Option<Diff> {
     // If we can convert actual and expected stdout to text, we split them to line chunks
@@ -23,58 +24,110 @@ pub fn eval_exact_diff(expected: &[u8], actual: &[u8]) -> Option<Diff> {
 }
 
 /// Returns the first line difference between an `expected` string and an `actual` string.
+///
+/// Lines are compared by chunks of at most 64 chars, so the reported difference is bounded even
+/// for very long lines.
 fn eval_exact_diff_as_str(expected: &str, actual: &str) -> Option<Diff> {
-    let expected_lines = expected.split_inclusive('\n').collect::<Vec<_>>();
-    let actual_lines = actual.split_inclusive('\n').collect::<Vec<_>>();
-    let max_lines = max(actual.len(), expected.len());
-    for line in 0..max_lines {
-        let expected_line = expected_lines.get(line);
-        let actual_line = actual_lines.get(line);
-        let row = line + 1;
-        match (expected_line, actual_line) {
-            // On the same line, two stdout differs
-            (Some(expected_line), Some(actual_line)) => {
-                if expected_line == actual_line {
-                    continue;
-                } else {
-                    let diff = Diff::Line {
-                        expected: Some(expected_line.to_string()),
-                        actual: Some(actual_line.to_string()),
-                        row,
-                    };
-                    return Some(diff);
-                }
-            }
-            // There are more actual lines that expected lines
-            (None, Some(actual_line)) => {
-                let diff = Diff::Line {
-                    expected: None,
-                    actual: Some(actual_line.to_string()),
-                    row,
-                };
-                return Some(diff);
-            }
-            // There are less actual lines that expected lines
-            (Some(expected_line), None) => {
-                let diff = Diff::Line {
-                    expected: Some(expected_line.to_string()),
-                    actual: None,
-                    row,
-                };
-                return Some(diff);
-            }
-            // End of diff, everything is good
-            (None, None) => {
-                break;
+    let mut expected_chunks = ChunkedLines::new(expected);
+    let mut actual_chunks = ChunkedLines::new(actual);
+    loop {
+        // As long as chunks are equal, expected and actual chunks are at the same position.
+        let (diff_chunk, expected_chunk, actual_chunk) =
+            match (expected_chunks.next(), actual_chunks.next()) {
+                // On the same chunk, two stdout differs
+                (Some(e), Some(a)) if e.text == a.text => continue,
+                (Some(e), Some(a)) => (e, Some(e.text), Some(a.text)),
+                // There are more actual lines that expected lines
+                (None, Some(a)) => (a, None, Some(a.text)),
+                // There are less actual lines that expected lines
+                (Some(e), None) => (e, Some(e.text), None),
+                // End of diff, everything is good
+                (None, None) => return None,
+            };
+        let diff = Diff::Line {
+            expected: expected_chunk.map(str::to_string),
+            actual: actual_chunk.map(str::to_string),
+            row: diff_chunk.row,
+            chunk: diff_chunk.index,
+        };
+        return Some(diff);
+    }
+}
+
+/// Returns the lines of `actual` reordered to follow the order of the `expected` lines, to compare
+/// outputs whose lines can come in any order.
+///
+/// Lines of `actual` found in `expected` are placed at the row of their expected counterpart, and
+/// the unexpected lines fill the rows of the missing ones. The reordered `actual` equals
+/// `expected` if and only if both have the same lines, and otherwise the first difference is at
+/// the first expected line missing in `actual`.
+pub fn align_lines(expected: &[u8], actual: &[u8]) -> Vec<u8> {
+    let expected = expected
+        .split_inclusive(|b| *b == b'\n')
+        .collect::<Vec<_>>();
+    let actual = actual.split_inclusive(|b| *b == b'\n').collect::<Vec<_>>();
+
+    let mut available = HashMap::<&[u8], usize>::new();
+    for line in &actual {
+        *available.entry(line).or_default() += 1;
+    }
+    let found = expected
+        .iter()
+        .map(|line| match available.get_mut(line) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
             }
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    // Lines still available once the expected ones are taken are the unexpected lines.
+    let mut unexpected = vec![];
+    for line in actual.iter().rev() {
+        let count = available.get_mut(line).unwrap();
+        if *count > 0 {
+            *count -= 1;
+            unexpected.push(*line);
         }
     }
-    None
+    unexpected.reverse();
+
+    let mut unexpected = unexpected.into_iter();
+    let mut aligned = vec![];
+    for (line, found) in expected.iter().zip(found) {
+        if found {
+            aligned.extend_from_slice(line);
+        } else if let Some(line) = unexpected.next() {
+            aligned.extend_from_slice(line);
+        }
+    }
+    unexpected.for_each(|line| aligned.extend_from_slice(line));
+    aligned
 }
 
+/// Number of bytes of a row of hex dump.
+const BYTES_PER_ROW: usize = 16;
+
 /// Returns the first byte difference between an `expected` string and an `actual` string.
-fn eval_exact_diff_as_bytes(_expected: &[u8], _actual: &[u8]) -> Option<Diff> {
-    todo!("make exact byte to byte comparison")
+///
+/// The difference comes with a window of at most two rows of [`BYTES_PER_ROW`] bytes: the row
+/// containing the first differing byte, and the row before it.
+pub fn eval_exact_diff_as_bytes(expected: &[u8], actual: &[u8]) -> Option<Diff> {
+    let offset = match expected.iter().zip(actual).position(|(e, a)| e != a) {
+        Some(offset) => offset,
+        None if expected.len() == actual.len() => return None,
+        // One of the values is a prefix of the other one.
+        None => expected.len().min(actual.len()),
+    };
+    let start = (offset / BYTES_PER_ROW).saturating_sub(1) * BYTES_PER_ROW;
+    let end = start + 2 * BYTES_PER_ROW;
+    let window = |bytes: &[u8]| bytes[start.min(bytes.len())..end.min(bytes.len())].to_vec();
+    Some(Diff::Byte {
+        offset,
+        start,
+        expected: window(expected),
+        actual: window(actual),
+    })
 }
 
 #[cfg(test)]
@@ -92,11 +145,61 @@ mod tests {
             Diff::Line {
                 expected: Some("café".to_string()),
                 actual: Some("caf�".to_string()),
-                row: 1
+                row: 1,
+                chunk: 0,
             }
         );
     }
 
+    #[test]
+    fn test_align_lines() {
+        let expected = b"a\nb\nc\nb\n";
+        assert_eq!(align_lines(expected, b"c\nb\nb\na\n"), expected);
+
+        // The unexpected line takes the place of the missing one.
+        assert_eq!(align_lines(expected, b"b\nx\nb\na\n"), b"a\nb\nx\nb\n");
+
+        // Additional lines come last.
+        assert_eq!(
+            align_lines(expected, b"b\ny\nc\nx\nb\na\n"),
+            b"a\nb\nc\nb\ny\nx\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_as_bytes() {
+        let expected = [0xff, 0x00, 0x01];
+        assert!(eval_exact_diff(&expected, &expected).is_none());
+
+        let expected = (0..64).collect::<Vec<u8>>();
+        let mut actual = expected.clone();
+        actual[40] = 0xff;
+        let diff = eval_exact_diff_as_bytes(&expected, &actual);
+        assert_eq!(
+            diff,
+            Some(Diff::Byte {
+                offset: 40,
+                start: 16,
+                expected: expected[16..48].to_vec(),
+                actual: actual[16..48].to_vec(),
+            })
+        );
+
+        // Actual is shorter than expected
+        let expected = [0x63, 0x61, 0x66, 0xe9];
+        let actual = [0x63, 0x61];
+        let diff = eval_exact_diff_as_bytes(&expected, &actual);
+        assert_eq!(
+            diff,
+            Some(Diff::Byte {
+                offset: 2,
+                start: 0,
+                expected: expected.to_vec(),
+                actual: actual.to_vec(),
+            })
+        );
+    }
+
     #[test]
     fn test_diff_as_str() {
         let expected = "foo\nbar\nbaz\n";
@@ -111,7 +214,8 @@ mod tests {
             Diff::Line {
                 expected: Some("cccc\n".to_string()),
                 actual: Some("cc-c\n".to_string()),
-                row: 3
+                row: 3,
+                chunk: 0,
             }
         );
 
@@ -124,11 +228,12 @@ mod tests {
             Diff::Line {
                 expected: None,
                 actual: Some("dddd\n".to_string()),
-                row: 4
+                row: 4,
+                chunk: 0,
             }
         );
 
-        // A very long line
+        // A very long line, only the first different chunk of 64 chars is reported
         let expected = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis xxx nostrud exercitation ullamco laboris";
         let actual = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris";
         let diff = eval_exact_diff_as_str(expected, actual).unwrap();
@@ -136,12 +241,13 @@ mod tests {
             diff,
             Diff::Line {
                 expected: Some(
-                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis xxx nostrud exercitation ullamco laboris".to_string()
+                    "nim ad minim veniam, quis xxx nostrud exercitation ullamco labor".to_string()
                 ),
                 actual: Some(
-                    "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris".to_string()
+                    "nim ad minim veniam, quis nostrud exercitation ullamco laboris".to_string()
                 ),
-                row: 1
+                row: 1,
+                chunk: 2,
             }
         );
     }
@@ -0,0 +1,128 @@
+use crate::json::JsonValue;
+
+/// A difference between two JSON values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonDiff {
+    /// Path of the difference, `$.items[2].name` for instance.
+    pub path: String,
+    /// Expected value at this path, `None` if the value is unexpected.
+    pub expected: Option<String>,
+    /// Actual value at this path, `None` if the value is missing.
+    pub actual: Option<String>,
+}
+
+/// Returns the first difference between an `expected` and an `actual` JSON value.
+///
+/// Objects are equal if they have the same members, regardless of the order of their keys, and
+/// numbers are compared by value (`1.0` is equal to `1`).
+pub fn eval_json_diff(expected: &JsonValue, actual: &JsonValue) -> Option<JsonDiff> {
+    eval_diff_at("$", expected, actual)
+}
+
+fn eval_diff_at(path: &str, expected: &JsonValue, actual: &JsonValue) -> Option<JsonDiff> {
+    let diff = |expected: Option<&JsonValue>, actual: Option<&JsonValue>| JsonDiff {
+        path: path.to_string(),
+        expected: expected.map(|v| v.to_string()),
+        actual: actual.map(|v| v.to_string()),
+    };
+    match (expected, actual) {
+        (JsonValue::Object(expected_members), JsonValue::Object(actual_members)) => {
+            for (key, expected) in expected_members {
+                let path = format!("{path}.{key}");
+                match actual_members.iter().find(|(k, _)| k == key) {
+                    Some((_, actual)) => {
+                        if let Some(d) = eval_diff_at(&path, expected, actual) {
+                            return Some(d);
+                        }
+                    }
+                    None => {
+                        return Some(JsonDiff {
+                            path,
+                            expected: Some(expected.to_string()),
+                            actual: None,
+                        });
+                    }
+                }
+            }
+            let unexpected = actual_members
+                .iter()
+                .find(|(key, _)| expected_members.iter().all(|(k, _)| k != key));
+            unexpected.map(|(key, actual)| JsonDiff {
+                path: format!("{path}.{key}"),
+                expected: None,
+                actual: Some(actual.to_string()),
+            })
+        }
+        (JsonValue::Array(expected_values), JsonValue::Array(actual_values)) => {
+            let len = expected_values.len().max(actual_values.len());
+            (0..len).find_map(|i| {
+                let path = format!("{path}[{i}]");
+                match (expected_values.get(i), actual_values.get(i)) {
+                    (Some(expected), Some(actual)) => eval_diff_at(&path, expected, actual),
+                    (expected, actual) => Some(JsonDiff {
+                        path,
+                        expected: expected.map(|v| v.to_string()),
+                        actual: actual.map(|v| v.to_string()),
+                    }),
+                }
+            })
+        }
+        (expected, actual) if expected == actual => None,
+        (expected, actual) => Some(diff(Some(expected), Some(actual))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> JsonValue {
+        JsonValue::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_eval_json_diff() {
+        let expected = parse(r#"{"user": {"name": "bob", "id": 1}, "items": [1, 2]}"#);
+        let actual = parse(r#"{"items": [1, 2.0], "user": {"id": 1, "name": "bob"}}"#);
+        assert_eq!(eval_json_diff(&expected, &actual), None);
+
+        let actual = parse(r#"{"user": {"name": "alice", "id": 1}, "items": [1, 2]}"#);
+        assert_eq!(
+            eval_json_diff(&expected, &actual),
+            Some(JsonDiff {
+                path: "$.user.name".to_string(),
+                expected: Some("\"bob\"".to_string()),
+                actual: Some("\"alice\"".to_string()),
+            })
+        );
+
+        let actual = parse(r#"{"user": {"name": "bob", "id": 1}, "items": [1]}"#);
+        assert_eq!(
+            eval_json_diff(&expected, &actual),
+            Some(JsonDiff {
+                path: "$.items[1]".to_string(),
+                expected: Some("2".to_string()),
+                actual: None,
+            })
+        );
+
+        let actual = parse(r#"{"user": {"name": "bob", "id": 1}, "items": [1, 2], "x": null}"#);
+        assert_eq!(
+            eval_json_diff(&expected, &actual),
+            Some(JsonDiff {
+                path: "$.x".to_string(),
+                expected: None,
+                actual: Some("null".to_string()),
+            })
+        );
+
+        assert_eq!(
+            eval_json_diff(&parse("[]"), &parse("{}")),
+            Some(JsonDiff {
+                path: "$".to_string(),
+                expected: Some("[]".to_string()),
+                actual: Some("{}".to_string()),
+            })
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::chunk::{Delimiters, PatternLine, PatternLines, find_segment_mismatch};
+use crate::verify::diff::{Diff, Error};
+
+/// Returns the difference between an `expected` single line and an `actual` line at `row`.
+///
+/// The expected line can contain patterns; contrary to a pattern file, the trailing newline of
+/// each line is not significant and a pattern must match the whole actual line.
+pub fn eval_line_diff(
+    expected: &str,
+    actual: Option<&str>,
+    row: usize,
+) -> Result<Option<Diff>, Error> {
+    let expected = expected.strip_suffix('\n').unwrap_or(expected);
+    let actual = actual.map(|a| a.strip_suffix('\n').unwrap_or(a));
+
+    let mut expected_lines = PatternLines::new(expected);
+    let expected_line = match expected_lines.next() {
+        None => PatternLine::NoPattern(String::new()),
+        Some(Ok(line)) => line,
+        Some(Err(error)) => return Err(Error::InvalidPattern { reason: error, row }),
+    };
+    if expected_lines.next().is_some() {
+        return Err(Error::InvalidPattern {
+            reason: "a single line is expected".to_string(),
+            row,
+        });
+    }
+
+    match expected_line {
+        PatternLine::NoPattern(expected_line) => {
+            if actual == Some(expected_line.as_str()) {
+                return Ok(None);
+            }
+            Ok(Some(Diff::Line {
+                expected: Some(expected_line),
+                actual: actual.map(|a| a.to_string()),
+                row,
+                chunk: 0,
+            }))
+        }
+        PatternLine::Pattern(expected_line) => {
+            if let Some(actual) = actual
+                && let Some(mat) = expected_line.find(actual)
+                && mat.start() == 0
+                && mat.end() == actual.len()
+            {
+                return Ok(None);
+            }
+            Ok(Some(Diff::PatternLine {
+                expected: Some(expected_line.to_string()),
+                actual: actual.map(|a| a.to_string()),
+                row,
+                mismatch: actual
+                    .and_then(|a| find_segment_mismatch(expected, a, &Delimiters::default())),
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_none_diff() {
+        assert_eq!(eval_line_diff("foo\n", Some("foo\n"), 1), Ok(None));
+        assert_eq!(eval_line_diff("foo", Some("foo\n"), 1), Ok(None));
+        assert_eq!(eval_line_diff("v<<<\\d+>>>\n", Some("v12"), 3), Ok(None));
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            eval_line_diff("foo\n", Some("bar\n"), 1),
+            Ok(Some(Diff::Line {
+                expected: Some("foo".to_string()),
+                actual: Some("bar".to_string()),
+                row: 1,
+                chunk: 0,
+            }))
+        );
+        assert_eq!(
+            eval_line_diff("foo\n", None, 1),
+            Ok(Some(Diff::Line {
+                expected: Some("foo".to_string()),
+                actual: None,
+                row: 1,
+                chunk: 0,
+            }))
+        );
+        // The pattern must match the whole line.
+        assert_eq!(
+            eval_line_diff("v<<<\\d+>>>", Some("v12 beta"), 5),
+            Ok(Some(Diff::PatternLine {
+                expected: Some("v\\d+".to_string()),
+                actual: Some("v12 beta".to_string()),
+                row: 5,
+                mismatch: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_line_invalid() {
+        assert!(eval_line_diff("foo\nbar\n", Some("foo"), 1).is_err());
+        assert!(eval_line_diff("<<<*>>>", Some("foo"), 1).is_err());
+    }
+}
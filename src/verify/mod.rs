@@ -1,49 +1,188 @@
-use crate::command::{CommandResult, CommandSpec};
+use crate::chunk::{PatternLines, Regex, SegmentMismatch};
+use crate::command;
+use crate::command::{
+    CommandResult, CommandSpec, CompareMode, ExitCode, ExpectedExitCode, FrontMatter, MatchCount,
+};
 use crate::error::Error;
+use crate::json::JsonValue;
+use crate::screen::{Screen, ScreenSize};
 use crate::verify::diff::Diff;
+use std::path::Path;
+use std::process::Command;
 
+mod checksum;
 mod diff;
 mod exact;
+mod json;
+mod line;
 mod pattern;
+mod registry;
+mod unified;
 
-pub fn check_result(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+pub use self::registry::{COMPARATORS, Stream, comparator, is_expectation};
+pub use self::unified::unified_diff;
+
+/// Options applied to the verification of every command.
+#[derive(Clone, Debug, Default)]
+pub struct VerifyOptions {
+    /// Patterns of lines that must only appear on stderr.
+    pub stderr_only: Vec<Regex>,
+}
+
+/// A suite-level assertion on the output of every test: no line of the stream may match the
+/// pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Invariant {
+    /// `true` if the invariant applies to stderr, `false` to stdout.
+    pub stderr: bool,
+    pub pattern: Regex,
+}
+
+pub fn check_result(
+    cmd: &CommandSpec,
+    result: &CommandResult,
+    options: &VerifyOptions,
+) -> Result<(), Error> {
     check_exit_code(cmd, result)?;
 
     // Possible cases:
-    // - only `foo.out` exists: we check the expected stdout against the actual stdout,
-    // - only `foo.out.pattern` exists: we check the expected pattern against the actual stdout,
-    // - `foo.out.pattern` and `foo.out` exist: we both check the expected pattern and the expected
-    // stdout against the actual stdout
-    // - neither `foo.out.pattern` nor `foo.out` exist: we chgeck that actual stdout is empty.
-
-    if cmd.has_stdout() && cmd.has_stdout_pat() {
-        check_equal_stdout(cmd, result)?;
-        check_equal_stdout_pat(cmd, result)?;
-    } else if cmd.has_stdout() {
-        check_equal_stdout(cmd, result)?;
-    } else if cmd.has_stdout_pat() {
-        check_equal_stdout_pat(cmd, result)?;
-    } else {
-        check_empty_stdout(cmd, result)?;
+    // - `foo.out`, `foo.out.pattern`, `foo.out.json` or `foo.out.sha256` exist: we check the
+    // actual stdout against each of them, with their comparator (see `registry`),
+    // - `foo.out.lines` exists: we check the number of lines of the actual stdout, in addition to
+    // any other stdout check,
+    // - `foo.out.count` exists: we check the number of lines of the actual stdout matching
+    // patterns, in addition to any other stdout check,
+    // - `foo.out.first` or `foo.out.last` exist: we check the first or last line of the actual
+    // stdout, in addition to any other stdout check,
+    // - `foo.screen` exists: we check the terminal screen rendered from the actual stdout, in
+    // addition to any other stdout check,
+    // - none of these files exist: stdout is not checked, like stderr without expectation file.
+
+    registry::check_stream(cmd, Stream::Stdout, result.stdout())?;
+    if cmd.has_stdout_lines() {
+        check_stdout_line_count(cmd, result)?;
+    }
+    if cmd.has_stdout_count() {
+        check_match_count(cmd, &cmd.stdout_count()?, result.stdout(), false)?;
+    }
+    if cmd.has_stdout_first() {
+        check_stdout_first_line(cmd, result)?;
+    }
+    if cmd.has_stdout_last() {
+        check_stdout_last_line(cmd, result)?;
     }
+    if cmd.has_screen() {
+        check_screen(cmd, result)?;
+    }
+
+    // Lines reserved to stderr can't appear on stdout, even if they're in the expected stdout.
+    check_stderr_only(cmd, result, options)?;
 
-    // We apply the same check for stderr:
-    if cmd.has_stderr() {
-        check_equal_stderr(cmd, result)?;
+    // We apply the same checks for stderr, every existing expectation file being checked:
+    registry::check_stream(cmd, Stream::Stderr, result.stderr())?;
+    if cmd.has_stderr_count() {
+        check_match_count(cmd, &cmd.stderr_count()?, result.stderr(), true)?;
     }
 
+    // The state written by the command in its work directory is checked if `foo.state.json`
+    // exists.
+    if cmd.has_state() {
+        check_state(cmd, result)?;
+    }
+
+    Ok(())
+}
+
+/// Checks the outputs of a `result` against suite `invariants`. Invariants are checked
+/// independently of the expectations of the command.
+pub fn check_invariants(
+    cmd: &CommandSpec,
+    result: &CommandResult,
+    invariants: &[Invariant],
+) -> Result<(), Error> {
+    for invariant in invariants {
+        let actual = if invariant.stderr {
+            result.stderr()
+        } else {
+            result.stdout()
+        };
+        let actual = String::from_utf8_lossy(actual);
+        let line = actual
+            .lines()
+            .enumerate()
+            .find(|(_, line)| invariant.pattern.is_match(line));
+        if let Some((index, line)) = line {
+            return Err(Error::CheckInvariant {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                stderr: invariant.stderr,
+                pattern: invariant.pattern.to_string(),
+                actual: line.to_string(),
+                row: index + 1,
+            });
+        }
+    }
     Ok(())
 }
 
+/// Returns the pattern files of `cmd` that don't match the exact snapshot of the same stream: no
+/// output can satisfy both files. Snapshots with a front-matter, alternatives or a comparator
+/// override are not checked, and unreadable or invalid files are reported when the test is
+/// verified.
+pub fn check_pattern_conflicts(cmd: &CommandSpec) -> Vec<Error> {
+    let mut conflicts = vec![];
+    for (ext, pattern_ext) in [("out", "out.pattern"), ("err", "err.pattern")] {
+        if !cmd.has_expectation(ext)
+            || !cmd.has_expectation(pattern_ext)
+            || cmd.comparator(ext).is_some()
+            || cmd.comparator(pattern_ext).is_some()
+            || !cmd.alternatives(ext).is_ok_and(|a| a.is_empty())
+        {
+            continue;
+        }
+        let (Ok(snapshot), Ok(patterns)) = (
+            cmd.read_snapshot(ext),
+            cmd.read_expectation_text(pattern_ext),
+        ) else {
+            continue;
+        };
+        let Ok(front_matter) = cmd.snapshot_front_matter(ext, &snapshot) else {
+            continue;
+        };
+        if !front_matter.normalize.is_empty() || front_matter.mode != CompareMode::Exact {
+            continue;
+        }
+        if let Ok(Some(mismatch)) = eval_pat_mismatch(&patterns, front_matter.body(&snapshot)) {
+            conflicts.push(Error::PatternConflict {
+                path: cmd.snapshot_path(pattern_ext),
+                snapshot: cmd.snapshot_path(ext),
+                row: mismatch.row,
+            });
+        }
+    }
+    conflicts
+}
+
 /// Check the exit code of the `cmd` against a `result` exit code.
-fn check_exit_code(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
-    let expected_exit_code = cmd.exit_code()?;
-    let actual_exit_code = result.exit_code();
-    if expected_exit_code != actual_exit_code {
+///
+/// A command killed by its timeout has no meaningful exit code: the timeout is reported instead.
+pub fn check_exit_code(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    check_timeout(cmd, result)?;
+    let expected = cmd.exit_code()?;
+    check_exit_code_value(cmd.cmd_path(), expected, result)
+}
+
+/// Checks the exit code of a `result` against an `expected` exit code.
+pub fn check_exit_code_value(
+    cmd_path: &Path,
+    expected: ExpectedExitCode,
+    result: &CommandResult,
+) -> Result<(), Error> {
+    let actual = result.termination();
+    if !expected.matches(actual) {
         let err = Error::CheckExitCode {
-            cmd_path: cmd.cmd_path().to_path_buf(),
-            expected: expected_exit_code,
-            actual: actual_exit_code,
+            cmd_path: cmd_path.to_path_buf(),
+            expected,
+            actual,
             stderr: result.stderr().to_vec(),
         };
         return Err(err);
@@ -51,103 +190,614 @@ fn check_exit_code(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Erro
     Ok(())
 }
 
-fn check_equal_stdout(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
-    let expected = cmd.stdout()?;
-    let actual = result.stdout().to_vec();
+/// Checks an `actual` output against a `snapshot` with extension `ext` (`out` or `err`), and
+/// against its alternatives (`foo.out.alt1`, `foo.out.alt2` etc...) if it doesn't match.
+///
+/// If no snapshot matches, the error of the closest one is returned: the one with the fewest
+/// lines added or removed.
+fn check_alternatives(
+    cmd: &CommandSpec,
+    ext: &str,
+    snapshot: (Vec<u8>, FrontMatter),
+    actual: &[u8],
+    check: impl Fn(&Path, &[u8], &FrontMatter, &[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let (snapshot, front_matter) = snapshot;
+    let err = match check(cmd.cmd_path(), &snapshot, &front_matter, actual) {
+        Ok(()) => return Ok(()),
+        Err(err) => err,
+    };
+    let alternatives = cmd.alternatives(ext)?;
+    if alternatives.is_empty() {
+        return Err(err);
+    }
+    let count = alternatives.len();
+    let mut failures = vec![(ext.to_string(), snapshot, front_matter, err)];
+    for (ext, snapshot, front_matter) in alternatives {
+        match check(cmd.cmd_path(), &snapshot, &front_matter, actual) {
+            Ok(()) => return Ok(()),
+            Err(err) => failures.push((ext, snapshot, front_matter, err)),
+        }
+    }
+    let closest = failures
+        .into_iter()
+        .min_by_key(|(_, snapshot, front_matter, _)| {
+            let expected = front_matter.normalize(front_matter.body(snapshot));
+            let actual = front_matter.normalize(actual);
+            unified::distance(
+                &String::from_utf8_lossy(&expected),
+                &String::from_utf8_lossy(&actual),
+            )
+        })
+        .expect("at least one snapshot");
+    let (ext, _, _, err) = closest;
+    Err(Error::CheckAlternative {
+        cmd_path: cmd.cmd_path().to_path_buf(),
+        ext,
+        alternatives: count,
+        cause: Box::new(err),
+    })
+}
+
+/// Checks an `actual` stdout against an expected stdout `snapshot`, given the options of its
+/// `front_matter`.
+pub fn check_stdout_snapshot(
+    cmd_path: &Path,
+    snapshot: &[u8],
+    front_matter: &FrontMatter,
+    actual: &[u8],
+) -> Result<(), Error> {
+    check_snapshot(snapshot, front_matter, actual, |expected, actual| {
+        check_stdout_value(cmd_path, expected, actual)
+    })
+}
 
-    let diff = exact::eval_exact_diff(&expected, &actual);
+/// Checks an `actual` stdout against an `expected` stdout.
+pub fn check_stdout_value(cmd_path: &Path, expected: &[u8], actual: &[u8]) -> Result<(), Error> {
+    let diff = exact::eval_exact_diff(expected, actual);
     match diff {
         None => Ok(()),
         Some(Diff::Line {
             expected,
             actual,
             row,
+            chunk,
         }) => Err(Error::CheckStdoutLine {
-            cmd_path: cmd.cmd_path().to_path_buf(),
+            cmd_path: cmd_path.to_path_buf(),
             expected,
             actual,
             row,
+            chunk,
+        }),
+        Some(Diff::Byte {
+            offset,
+            start,
+            expected,
+            actual,
+        }) => Err(Error::CheckStdoutBytes {
+            cmd_path: cmd_path.to_path_buf(),
+            offset,
+            start,
+            expected,
+            actual,
         }),
-        Some(Diff::Byte) => todo!(),
         Some(Diff::PatternLine { .. }) => unreachable!(),
     }
 }
 
-fn check_equal_stderr(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
-    let expected = cmd.stderr()?;
-    let actual = result.stderr().to_vec();
+/// Checks the terminal screen rendered from the actual stdout against the expected screen.
+fn check_screen(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let expected = cmd.screen()?;
+    let actual = Screen::render(cmd.screen_size(), result.stdout());
+    check_screen_value(cmd.cmd_path(), None, &expected, &actual)
+}
+
+/// Runs the command once per terminal size of `cmd`, checking the rendered screen against the
+/// screen snapshot of each size.
+pub fn check_screen_sizes(cmd: &CommandSpec) -> Result<(), Error> {
+    for size in cmd.screen_sizes()? {
+        let expected = cmd.sized_screen(size)?;
+        let result = match cmd.execute_with_screen_size(size) {
+            Ok(r) => r,
+            Err(err) => {
+                return Err(Error::FileRead {
+                    path: cmd.cmd_path().to_path_buf(),
+                    cause: err.to_string(),
+                });
+            }
+        };
+        let actual = Screen::render(size, result.stdout());
+        check_screen_value(cmd.cmd_path(), Some(size), &expected, &actual)?;
+    }
+    Ok(())
+}
+
+/// Runs the command once per locale of `cmd`, checking each result against the snapshots of its
+/// locale.
+pub fn check_locales(cmd: &CommandSpec, options: &VerifyOptions) -> Result<(), Error> {
+    for locale in cmd.locales() {
+        let to_file_error = |err: std::io::Error| Error::FileRead {
+            path: cmd.cmd_path().to_path_buf(),
+            cause: err.to_string(),
+        };
+        let localized = cmd.for_locale(locale).map_err(to_file_error)?;
+        let result = localized.execute().map_err(to_file_error)?;
+        check_result(&localized, &result, options)
+            .and_then(|_| check_screen_sizes(&localized))
+            .map_err(|err| Error::CheckLocale {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                locale: locale.clone(),
+                cause: Box::new(err),
+            })?;
+    }
+    Ok(())
+}
 
-    let diff = exact::eval_exact_diff(&expected, &actual);
+/// Checks an `actual` terminal screen of a given `size` (`None` for the default size) against
+/// an `expected` screen.
+pub fn check_screen_value(
+    cmd_path: &Path,
+    size: Option<ScreenSize>,
+    expected: &str,
+    actual: &str,
+) -> Result<(), Error> {
+    let diff = exact::eval_exact_diff(expected.as_bytes(), actual.as_bytes());
     match diff {
         None => Ok(()),
         Some(Diff::Line {
             expected,
             actual,
             row,
+            chunk,
+        }) => Err(Error::CheckScreenLine {
+            cmd_path: cmd_path.to_path_buf(),
+            size,
+            expected,
+            actual,
+            row,
+            chunk,
+        }),
+        Some(Diff::Byte { .. }) | Some(Diff::PatternLine { .. }) => unreachable!(),
+    }
+}
+
+/// Checks an `actual` stderr against an expected stderr `snapshot`, given the options of its
+/// `front_matter`.
+pub fn check_stderr_snapshot(
+    cmd_path: &Path,
+    snapshot: &[u8],
+    front_matter: &FrontMatter,
+    actual: &[u8],
+) -> Result<(), Error> {
+    check_snapshot(snapshot, front_matter, actual, |expected, actual| {
+        check_stderr_value(cmd_path, expected, actual)
+    })
+}
+
+/// Checks an `actual` output against the body of a `snapshot` with a `check` function, after
+/// having applied the options of the snapshot `front_matter`. Lines of the errors are located in
+/// the snapshot, front-matter included.
+fn check_snapshot(
+    snapshot: &[u8],
+    front_matter: &FrontMatter,
+    actual: &[u8],
+    check: impl Fn(&[u8], &[u8]) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let expected = front_matter.normalize(front_matter.body(snapshot));
+    let actual = front_matter.normalize(actual);
+    let actual = match front_matter.mode {
+        CompareMode::Exact => actual,
+        CompareMode::Unordered => exact::align_lines(&expected, &actual),
+    };
+    check(&expected, &actual).map_err(|err| match err {
+        Error::CheckStdoutLine {
+            cmd_path,
+            expected,
+            actual,
+            row,
+            chunk,
+        } => Error::CheckStdoutLine {
+            cmd_path,
+            expected,
+            actual,
+            row: row + front_matter.rows(),
+            chunk,
+        },
+        Error::CheckStderrLine {
+            cmd_path,
+            expected,
+            actual,
+            row,
+            chunk,
+        } => Error::CheckStderrLine {
+            cmd_path,
+            expected,
+            actual,
+            row: row + front_matter.rows(),
+            chunk,
+        },
+        err => err,
+    })
+}
+
+/// Checks an `actual` stderr against an `expected` stderr.
+pub fn check_stderr_value(cmd_path: &Path, expected: &[u8], actual: &[u8]) -> Result<(), Error> {
+    let diff = exact::eval_exact_diff(expected, actual);
+    match diff {
+        None => Ok(()),
+        Some(Diff::Line {
+            expected,
+            actual,
+            row,
+            chunk,
         }) => Err(Error::CheckStderrLine {
-            cmd_path: cmd.cmd_path().to_path_buf(),
+            cmd_path: cmd_path.to_path_buf(),
             expected,
             actual,
             row,
+            chunk,
+        }),
+        Some(Diff::Byte {
+            offset,
+            start,
+            expected,
+            actual,
+        }) => Err(Error::CheckStderrBytes {
+            cmd_path: cmd_path.to_path_buf(),
+            offset,
+            start,
+            expected,
+            actual,
         }),
-        Some(Diff::Byte) => todo!(),
         Some(Diff::PatternLine { .. }) => unreachable!(),
     }
 }
 
-fn check_equal_stdout_pat(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
-    let expected_stdout_pat = cmd.stdout_pat()?;
-    let actual_stdout = result.stdout().to_vec();
-    let diff = pattern::eval_pat_diff(&expected_stdout_pat, &actual_stdout);
-    let diff = match diff {
-        Ok(d) => d,
-        Err(diff::Error::InvalidPattern { reason, row }) => {
-            return Err(Error::StdoutPatternFileInvalid {
+/// Checks an `actual` stdout against an `expected` stdout pattern.
+pub fn check_stdout_pat_value(cmd_path: &Path, expected: &str, actual: &[u8]) -> Result<(), Error> {
+    match eval_pat_mismatch(expected, actual) {
+        Ok(None) => Ok(()),
+        Ok(Some(m)) => Err(Error::CheckStdoutPattern {
+            cmd_path: cmd_path.to_path_buf(),
+            expected: m.expected,
+            actual: m.actual,
+            row: m.row,
+            mismatch: m.mismatch.map(Box::new),
+            matched: m.matched,
+        }),
+        Err(diff::Error::InvalidPattern { reason, row }) => Err(Error::StdoutPatternFileInvalid {
+            cmd_path: cmd_path.to_path_buf(),
+            reason,
+            row,
+        }),
+    }
+}
+
+/// Checks an `actual` stderr against an `expected` stderr pattern.
+pub fn check_stderr_pat_value(cmd_path: &Path, expected: &str, actual: &[u8]) -> Result<(), Error> {
+    match eval_pat_mismatch(expected, actual) {
+        Ok(None) => Ok(()),
+        Ok(Some(m)) => Err(Error::CheckStderrPattern {
+            cmd_path: cmd_path.to_path_buf(),
+            expected: m.expected,
+            actual: m.actual,
+            row: m.row,
+            mismatch: m.mismatch.map(Box::new),
+            matched: m.matched,
+        }),
+        Err(diff::Error::InvalidPattern { reason, row }) => Err(Error::StderrPatternFileInvalid {
+            cmd_path: cmd_path.to_path_buf(),
+            reason,
+            row,
+        }),
+    }
+}
+
+/// The first line of an output that doesn't match a pattern file.
+struct PatternMismatch {
+    expected: Option<String>,
+    actual: Option<String>,
+    row: usize,
+    mismatch: Option<SegmentMismatch>,
+    /// The last actual line matched before the mismatch, if any.
+    matched: Option<String>,
+}
+
+/// Returns the first line of an `actual` output that doesn't match an `expected` pattern file.
+fn eval_pat_mismatch(
+    expected: &str,
+    actual: &[u8],
+) -> Result<Option<PatternMismatch>, diff::Error> {
+    // Lines of a pattern file match actual lines one to one: the actual line before the
+    // mismatching one is the last matched line. Skip lines break this correspondence, the last
+    // matched line is unknown then. A header line declaring delimiters is not a pattern line.
+    let has_skip_lines = expected.split_inclusive('\n').any(pattern::is_skip_line);
+    let header_rows = PatternLines::new(expected).header_rows();
+    let matched = |row: usize| {
+        if has_skip_lines {
+            return None;
+        }
+        let actual = String::from_utf8_lossy(actual);
+        let line = row.checked_sub(2 + header_rows)?;
+        actual.split_inclusive('\n').nth(line).map(str::to_string)
+    };
+    let mismatch = match pattern::eval_pat_diff(expected, actual)? {
+        None => None,
+        Some(Diff::Line {
+            expected,
+            actual,
+            row,
+            ..
+        }) => Some(PatternMismatch {
+            expected,
+            actual,
+            row,
+            mismatch: None,
+            matched: matched(row),
+        }),
+        Some(Diff::Byte { .. }) => unreachable!(),
+        Some(Diff::PatternLine {
+            expected,
+            actual,
+            row,
+            mismatch,
+        }) => Some(PatternMismatch {
+            expected,
+            actual,
+            row,
+            mismatch,
+            matched: matched(row),
+        }),
+    };
+    Ok(mismatch)
+}
+
+/// Checks that the number of lines of the actual stdout is in the expected range.
+fn check_stdout_line_count(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let expected = cmd.stdout_lines()?;
+    let actual = result.stdout().split_inclusive(|b| *b == b'\n').count();
+    if !expected.contains(actual) {
+        return Err(Error::CheckStdoutLineCount {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            expected,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the number of lines of an `actual` output matching each pattern of `counts` is
+/// in the expected range.
+fn check_match_count(
+    cmd: &CommandSpec,
+    counts: &[MatchCount],
+    actual: &[u8],
+    stderr: bool,
+) -> Result<(), Error> {
+    let actual = String::from_utf8_lossy(actual);
+    for count in counts {
+        let matches = actual.lines().filter(|l| count.pattern.is_match(l)).count();
+        if !count.expected.contains(matches) {
+            return Err(Error::CheckMatchCount {
                 cmd_path: cmd.cmd_path().to_path_buf(),
-                reason,
-                row,
+                stderr,
+                pattern: count.pattern.to_string(),
+                expected: count.expected,
+                actual: matches,
+                row: count.row,
             });
         }
+    }
+    Ok(())
+}
+
+/// Checks the state written by the command against the expected state, comparing JSON values.
+fn check_state(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let expected = cmd.state()?;
+    let error = |expected: Option<String>, actual: Option<String>| Error::CheckState {
+        cmd_path: cmd.cmd_path().to_path_buf(),
+        pointer: "$".to_string(),
+        expected,
+        actual,
+    };
+    let Some(actual) = result.state() else {
+        return Err(error(Some(expected.to_string()), None));
     };
+    let actual = String::from_utf8_lossy(actual);
+    let Ok(actual) = JsonValue::parse(&actual) else {
+        return Err(error(Some(expected.to_string()), Some(actual.into_owned())));
+    };
+    match json::eval_json_diff(&expected, &actual) {
+        None => Ok(()),
+        Some(diff) => Err(Error::CheckState {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            pointer: diff.path,
+            expected: diff.expected,
+            actual: diff.actual,
+        }),
+    }
+}
 
+/// Checks the first line of the actual stdout against the expected first line.
+fn check_stdout_first_line(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let expected = cmd.stdout_first()?;
+    let actual = String::from_utf8_lossy(result.stdout());
+    let actual = actual.split_inclusive('\n').next();
+    let diff = line::eval_line_diff(&expected, actual, 1);
+    to_stdout_line_error(cmd, diff)
+}
+
+/// Checks the last line of the actual stdout against the expected last line.
+fn check_stdout_last_line(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    let expected = cmd.stdout_last()?;
+    let actual = String::from_utf8_lossy(result.stdout());
+    let lines = actual.split_inclusive('\n').collect::<Vec<_>>();
+    let row = lines.len().max(1);
+    let diff = line::eval_line_diff(&expected, lines.last().copied(), row);
+    to_stdout_line_error(cmd, diff)
+}
+
+/// Converts the result of a single line stdout evaluation to a verification error.
+fn to_stdout_line_error(
+    cmd: &CommandSpec,
+    diff: Result<Option<Diff>, diff::Error>,
+) -> Result<(), Error> {
+    let cmd_path = cmd.cmd_path().to_path_buf();
     match diff {
-        None => Ok(()),
-        Some(Diff::Line {
+        Ok(None) => Ok(()),
+        Ok(Some(Diff::Line {
             expected,
             actual,
             row,
-        }) => Err(Error::CheckStdoutLine {
-            cmd_path: cmd.cmd_path().to_path_buf(),
+            chunk,
+        })) => Err(Error::CheckStdoutLine {
+            cmd_path,
             expected,
             actual,
             row,
+            chunk,
         }),
-        Some(Diff::Byte) => unreachable!(),
-        Some(Diff::PatternLine {
+        Ok(Some(Diff::PatternLine {
             expected,
             actual,
             row,
-        }) => Err(Error::CheckStdoutPattern {
-            cmd_path: cmd.cmd_path().to_path_buf(),
+            mismatch,
+        })) => Err(Error::CheckStdoutPattern {
+            cmd_path,
             expected,
             actual,
             row,
+            mismatch: mismatch.map(Box::new),
+            matched: None,
         }),
+        Ok(Some(Diff::Byte { .. })) => unreachable!(),
+        Err(diff::Error::InvalidPattern { reason, row }) => Err(Error::StdoutPatternFileInvalid {
+            cmd_path,
+            reason,
+            row,
+        }),
+    }
+}
+
+/// Checks that no line of the actual stdout matches a pattern reserved to stderr.
+fn check_stderr_only(
+    cmd: &CommandSpec,
+    result: &CommandResult,
+    options: &VerifyOptions,
+) -> Result<(), Error> {
+    let cmd_patterns = cmd.stderr_only()?;
+    let patterns = options.stderr_only.iter().chain(cmd_patterns.iter());
+    let patterns = patterns.collect::<Vec<_>>();
+    if patterns.is_empty() {
+        return Ok(());
     }
+    let actual = String::from_utf8_lossy(result.stdout());
+    for (index, line) in actual.lines().enumerate() {
+        if let Some(pattern) = patterns.iter().find(|p| p.is_match(line)) {
+            return Err(Error::CheckStdoutStderrOnly {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                pattern: pattern.to_string(),
+                actual: line.to_string(),
+                row: index + 1,
+            });
+        }
+    }
+    Ok(())
 }
 
-// TODO:
-fn check_empty_stdout(_cmd: &CommandSpec, _result: &CommandResult) -> Result<(), Error> {
+/// Runs the post-hook script of `cmd`, if any, after a successful verification.
+///
+/// The captured outputs of the command have been written in `artifacts_dir`: the hook gets the
+/// path of the stdout, stderr and exit code files in `CLICHE_ACTUAL_STDOUT`,
+/// `CLICHE_ACTUAL_STDERR` and `CLICHE_ACTUAL_EXIT` environment variables. The hook must exit with
+/// a zero exit code.
+pub fn check_post_hook(cmd: &CommandSpec, artifacts_dir: &Path) -> Result<(), Error> {
+    let Some(hook_path) = cmd.post_hook() else {
+        return Ok(());
+    };
+    let mut hook = Command::new(hook_path);
+    hook.env("CLICHE_ACTUAL_STDOUT", artifacts_dir.join("stdout"))
+        .env("CLICHE_ACTUAL_STDERR", artifacts_dir.join("stderr"))
+        .env("CLICHE_ACTUAL_EXIT", artifacts_dir.join("exit"));
+    let result = match command::capture(hook, command::Stdin::Null, false) {
+        Ok(r) => r,
+        Err(err) => {
+            return Err(Error::FileRead {
+                path: hook_path.to_path_buf(),
+                cause: err.to_string(),
+            });
+        }
+    };
+    if result.exit_code() != ExitCode::from(0) {
+        return Err(Error::CheckPostHook {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            hook_path: hook_path.to_path_buf(),
+            exit_code: result.exit_code(),
+            stderr: result.stderr().to_vec(),
+        });
+    }
     Ok(())
 }
 
+/// Checks that the command of a `result` has exited before its timeout.
+pub fn check_timeout(cmd: &CommandSpec, result: &CommandResult) -> Result<(), Error> {
+    match result.timed_out() {
+        Some(timeout) => Err(Error::Timeout {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            timeout,
+            stdout: result.stdout().to_vec(),
+            stderr: result.stderr().to_vec(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Applies a `formatter` to the `actual` stdout of a command, and returns the formatted stdout.
+///
+/// The formatter is a shell command line (like `jq -S .` or `sort`), fed with the actual stdout
+/// on its stdin. It must exit with a zero exit code.
+pub fn format_stdout(cmd: &CommandSpec, formatter: &str, actual: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(formatter);
+    let result = match command::capture(shell, command::Stdin::Bytes(actual), false) {
+        Ok(r) => r,
+        Err(err) => {
+            return Err(Error::FileRead {
+                path: cmd.cmd_path().to_path_buf(),
+                cause: err.to_string(),
+            });
+        }
+    };
+    if result.exit_code() != ExitCode::from(0) {
+        return Err(Error::CheckFormatter {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            formatter: formatter.to_string(),
+            exit_code: result.exit_code(),
+            stderr: result.stderr().to_vec(),
+        });
+    }
+    Ok(result.stdout().to_vec())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::command::{CommandResult, CommandSpec};
+    use crate::text::Format;
+    use std::fs;
     use std::fs::File;
     use std::io;
     use std::io::Write;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
     use std::path::{Path, PathBuf};
     use tempfile::TempDir;
 
@@ -165,6 +815,524 @@ mod tests {
 
         let cmd = CommandSpec::new(&cmd_path).unwrap();
         let res = CommandResult::new(0.into(), &[], &[]);
-        assert!(check_result(&cmd, &res).is_ok())
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok())
+    }
+
+    #[test]
+    fn test_stdout_line_count() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "seq 3").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out.lines", "2..3").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), b"1\n2\n3\n", &[]);
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        let res = CommandResult::new(0.into(), b"1\n2\n3\n4", &[]);
+        assert_eq!(
+            check_result(&cmd, &res, &VerifyOptions::default()),
+            Err(Error::CheckStdoutLineCount {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                expected: "2..3".parse().unwrap(),
+                actual: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_match_count() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out.count", "1 ^Done$\n").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.err.count",
+            "0 ^error:\n..1 ^warning:\n",
+        )
+        .unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), b"Building\nDone\n", b"warning: unused\n");
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        let res = CommandResult::new(
+            0.into(),
+            b"Building\nDone\n",
+            b"warning: unused\nwarning: deprecated\n",
+        );
+        assert_eq!(
+            check_result(&cmd, &res, &VerifyOptions::default()),
+            Err(Error::CheckMatchCount {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                stderr: true,
+                pattern: "^warning:".to_string(),
+                expected: "..1".parse().unwrap(),
+                actual: 2,
+                row: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invariants() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let invariants = [Invariant {
+            stderr: true,
+            pattern: Regex::new("panicked at").unwrap(),
+        }];
+
+        let res = CommandResult::new(1.into(), b"panicked at\n", b"error: failed\n");
+        assert!(check_invariants(&cmd, &res, &invariants).is_ok());
+
+        let res = CommandResult::new(
+            0.into(),
+            &[],
+            b"foo\nthread 'main' panicked at src/main.rs\n",
+        );
+        assert_eq!(
+            check_invariants(&cmd, &res, &invariants),
+            Err(Error::CheckInvariant {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                stderr: true,
+                pattern: "panicked at".to_string(),
+                actual: "thread 'main' panicked at src/main.rs".to_string(),
+                row: 2,
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_locales() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path =
+            write_file_with(tmp_dir.path(), "foo.sh", "#!/bin/sh\necho $LC_ALL").unwrap();
+        fs::set_permissions(&cmd_path, fs::Permissions::from_mode(0o755)).unwrap();
+        write_file_with(tmp_dir.path(), "foo.C.out", "C").unwrap();
+        write_file_with(tmp_dir.path(), "foo.fr_FR.UTF-8.out", "en_US.UTF-8").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path)
+            .unwrap()
+            .with_locales(vec!["C".to_string()]);
+        assert!(check_locales(&cmd, &VerifyOptions::default()).is_ok());
+
+        let cmd = cmd.with_locales(vec!["C".to_string(), "fr_FR.UTF-8".to_string()]);
+        let err = check_locales(&cmd, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(
+            err.location(),
+            (tmp_dir.path().join("foo.fr_FR.UTF-8.out"), Some(1))
+        );
+    }
+
+    #[test]
+    fn test_state() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./init.sh").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.state.json",
+            r#"{"users": ["bob"], "v": 1}"#,
+        )
+        .unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+
+        let res = CommandResult::new(0.into(), &[], &[])
+            .with_state(Some(br#"{"v": 1.0, "users": ["bob"]}"#.to_vec()));
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        let res = CommandResult::new(0.into(), &[], &[])
+            .with_state(Some(br#"{"v": 1, "users": ["bob", "alice"]}"#.to_vec()));
+        assert_eq!(
+            check_result(&cmd, &res, &VerifyOptions::default()),
+            Err(Error::CheckState {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                pointer: "$.users[1]".to_string(),
+                expected: None,
+                actual: Some("\"alice\"".to_string()),
+            })
+        );
+
+        let res = CommandResult::new(0.into(), &[], &[]);
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.message(), "State file state.json has not been written");
+    }
+
+    #[test]
+    fn test_stderr_pattern() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.err.pattern",
+            "warning: <<<\\d+>>> issues",
+        )
+        .unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), &[], b"warning: 12 issues\n");
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        let res = CommandResult::new(0.into(), &[], b"warning: many issues\n");
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "CHECK_STDERR_PATTERN");
+        assert_eq!(
+            err.message(),
+            "Stderr doesn't match at line 1, column 10 (segment \"<<<\\d+>>>\")"
+        );
+        assert_eq!(
+            err.expectation(),
+            Some(("err.pattern".to_string(), Some(1)))
+        );
+
+        // Exact and pattern snapshots are both checked.
+        write_file_with(tmp_dir.path(), "foo.err", "warning: 13 issues").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), &[], b"warning: 12 issues\n");
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "CHECK_STDERR_LINE");
+
+        write_file_with(tmp_dir.path(), "foo.err.pattern", "<<<(>>>").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), &[], b"warning: 13 issues\n");
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "STDERR_PATTERN_FILE_INVALID");
+    }
+
+    #[test]
+    fn test_stdout_pattern_matched_lines() {
+        let cmd_path = Path::new("foo.sh");
+        let expected = "Building v<<<\\d+>>>...\nstep 1\nDone\n";
+        let err = check_stdout_pat_value(cmd_path, expected, b"Building v2...\nstep 1\nFailed\n");
+        assert_eq!(
+            err,
+            Err(Error::CheckStdoutPattern {
+                cmd_path: cmd_path.to_path_buf(),
+                expected: Some("Done\n".to_string()),
+                actual: Some("Failed\n".to_string()),
+                row: 3,
+                mismatch: None,
+                matched: Some("step 1\n".to_string()),
+            })
+        );
+        assert!(
+            err.unwrap_err()
+                .render(Format::Plain)
+                .contains("matched lines   : 2, last <step 1")
+        );
+
+        let err = check_stdout_pat_value(cmd_path, expected, b"Building\n").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::CheckStdoutPattern { matched: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_stdout_front_matter() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.out",
+            "---\nnormalize: [crlf, ansi]\nmode: unordered\n---\nok 1\nok 2\nok 3",
+        )
+        .unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+
+        let res = CommandResult::new(0.into(), b"ok 3\r\n\x1b[32mok 1\x1b[0m\r\nok 2\r\n", &[]);
+        assert_eq!(check_result(&cmd, &res, &VerifyOptions::default()), Ok(()));
+
+        // Rows are located in the snapshot, after the front-matter.
+        let res = CommandResult::new(0.into(), b"ok 3\nok 1\nfailed 2\n", &[]);
+        assert_eq!(
+            check_result(&cmd, &res, &VerifyOptions::default()),
+            Err(Error::CheckStdoutLine {
+                cmd_path: cmd_path.clone(),
+                expected: Some("ok 2\n".to_string()),
+                actual: Some("failed 2\n".to_string()),
+                row: 6,
+                chunk: 0,
+            })
+        );
+
+        write_file_with(tmp_dir.path(), "foo.out", "---\nsort: true\n---").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "FRONT_MATTER_INVALID");
+        assert_eq!(err.location(), (cmd_path.with_extension("out"), Some(2)));
+    }
+
+    #[test]
+    fn test_comparators() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./users.sh").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.out.json",
+            r#"{"users": [{"name": "bob"}, {"name": "alice"}]}"#,
+        )
+        .unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.err.sha256",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  -",
+        )
+        .unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert!(cmd.misspelled_files().is_empty());
+
+        let stdout = br#"{
+  "users": [{"name": "bob"}, {"name": "alice"}]
+}"#;
+        let res = CommandResult::new(0.into(), stdout, b"abc");
+        assert_eq!(check_result(&cmd, &res, &VerifyOptions::default()), Ok(()));
+
+        let stdout = br#"{"users": [{"name": "bob"}, {"name": "carol"}]}"#;
+        let res = CommandResult::new(0.into(), stdout, b"abc");
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "CHECK_OUTPUT_JSON");
+        assert_eq!(err.message(), "Stdout doesn't match at $.users[1].name");
+        assert_eq!(err.location(), (cmd_path.with_extension("out.json"), None));
+
+        let res = CommandResult::new(0.into(), b"{\"users\": []}", b"abd");
+        let cmd = cmd.with_comparators(vec![("out.json".to_string(), "binary".to_string())]);
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(err.code(), "CHECK_STDOUT_BYTES");
+
+        let stdout = br#"{"users": [{"name": "bob"}, {"name": "alice"}]}"#;
+        let res = CommandResult::new(0.into(), stdout, b"abd");
+        let cmd = cmd.with_comparators(vec![]);
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            Error::CheckChecksum {
+                cmd_path: cmd_path.clone(),
+                ext: "err.sha256".to_string(),
+                expected: "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+                    .to_string(),
+                actual: checksum::sha256_hex(b"abd"),
+            }
+        );
+        assert_eq!(err.message(), "Stderr checksum doesn't match");
+    }
+
+    #[test]
+    fn test_pattern_conflicts() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        let cmd_path = write_file_with(dir, "foo.sh", "./build.sh").unwrap();
+        write_file_with(dir, "foo.out", "Built in 1 step\nDone\n").unwrap();
+        write_file_with(dir, "foo.out.pattern", "Built in <<<\\d+>>> steps\nDone\n").unwrap();
+        write_file_with(dir, "foo.err", "warning: cache miss\n").unwrap();
+        write_file_with(dir, "foo.err.pattern", "warning: <<<.+>>>\n").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert_eq!(
+            check_pattern_conflicts(&cmd),
+            vec![Error::PatternConflict {
+                path: dir.join("foo.out.pattern"),
+                snapshot: dir.join("foo.out"),
+                row: 1,
+            }]
+        );
+
+        // A snapshot normalized by its front-matter isn't compared to the patterns.
+        write_file_with(
+            dir,
+            "foo.out",
+            "---\nnormalize: [crlf]\n---\nBuilt in 1 step\nDone\n",
+        )
+        .unwrap();
+        assert!(check_pattern_conflicts(&cmd).is_empty());
+    }
+
+    #[test]
+    fn test_stdout_alternatives() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out", "Built in 1 step\nDone").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out.alt1", "Nothing to build").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.out.alt2",
+            "Cache hit\nBuilt in 0 step\nDone",
+        )
+        .unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        assert!(cmd.misspelled_files().is_empty());
+
+        for stdout in [
+            "Built in 1 step\nDone\n",
+            "Nothing to build\n",
+            "Cache hit\nBuilt in 0 step\nDone\n",
+        ] {
+            let res = CommandResult::new(0.into(), stdout.as_bytes(), &[]);
+            assert_eq!(check_result(&cmd, &res, &VerifyOptions::default()), Ok(()));
+        }
+
+        // The difference with the closest snapshot is reported.
+        let res = CommandResult::new(0.into(), b"Cache hit\nBuilt in 2 steps\nDone\n", &[]);
+        let err = check_result(&cmd, &res, &VerifyOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            Error::CheckAlternative {
+                cmd_path: cmd_path.clone(),
+                ext: "out.alt2".to_string(),
+                alternatives: 2,
+                cause: Box::new(Error::CheckStdoutLine {
+                    cmd_path: cmd_path.clone(),
+                    expected: Some("Built in 0 step\n".to_string()),
+                    actual: Some("Built in 2 steps\n".to_string()),
+                    row: 2,
+                    chunk: 0,
+                }),
+            }
+        );
+        assert_eq!(err.code(), "CHECK_STDOUT_LINE");
+        assert_eq!(
+            err.location(),
+            (cmd_path.with_extension("out.alt2"), Some(2))
+        );
+    }
+
+    #[test]
+    fn test_stdout_bytes() {
+        let cmd_path = Path::new("foo.sh");
+        // An expected stdout in latin 1, not valid UTF-8.
+        let expected = b"Caf\xe9 cr\xe8me\n";
+        assert!(check_stdout_value(cmd_path, expected, expected).is_ok());
+
+        let err = check_stdout_value(cmd_path, expected, b"Caf\xe9 cr\xeame\n").unwrap_err();
+        assert_eq!(err.code(), "CHECK_STDOUT_BYTES");
+        assert_eq!(err.message(), "Stdout doesn't match at byte 7 (0x00000007)");
+        assert_eq!(
+            err.render(Format::Plain),
+            "error: Stdout doesn't match at byte 7 (0x00000007)
+  script  : foo.sh
+  expected:
+    00000000  43 61 66 e9 20 63 72 e8 6d 65 0a                 |Caf. cr.me.|
+  actual  :
+    00000000  43 61 66 e9 20 63 72 ea 6d 65 0a                 |Caf. cr.me.|
+"
+        );
+
+        let err = check_stderr_value(cmd_path, expected, b"Caf").unwrap_err();
+        assert_eq!(err.code(), "CHECK_STDERR_BYTES");
+        assert_eq!(err.expectation(), Some(("err".to_string(), None)));
+        assert_eq!(
+            err.expected_actual(),
+            (
+                Some("43 61 66 e9 20 63 72 e8 6d 65 0a".to_string()),
+                Some("43 61 66".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn test_stdout_first_and_last_line() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out.first", "Building v<<<\\d+>>>...").unwrap();
+        write_file_with(tmp_dir.path(), "foo.out.last", "Done").unwrap();
+
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+        let res = CommandResult::new(0.into(), b"Building v2...\nstep 1\nstep 2\nDone\n", &[]);
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        let res = CommandResult::new(0.into(), b"Building v2...\nstep 1\nFailed\n", &[]);
+        assert_eq!(
+            check_result(&cmd, &res, &VerifyOptions::default()),
+            Err(Error::CheckStdoutLine {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                expected: Some("Done".to_string()),
+                actual: Some("Failed".to_string()),
+                row: 3,
+                chunk: 0,
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_post_hook() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "echo 'Hello'").unwrap();
+        let hook_path = write_file_with(
+            tmp_dir.path(),
+            "foo.post.sh",
+            "#!/bin/sh\ngrep -q Hello \"$CLICHE_ACTUAL_STDOUT\" || { echo 'no hello' >&2; exit 1; }",
+        )
+        .unwrap();
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+
+        let artifacts_dir = tmp_dir.path().join("artifacts");
+        fs::create_dir(&artifacts_dir).unwrap();
+        fs::write(artifacts_dir.join("stdout"), "Hello\n").unwrap();
+        assert!(check_post_hook(&cmd, &artifacts_dir).is_ok());
+
+        fs::write(artifacts_dir.join("stdout"), "Bye\n").unwrap();
+        assert_eq!(
+            check_post_hook(&cmd, &artifacts_dir),
+            Err(Error::CheckPostHook {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                hook_path: cmd.post_hook().unwrap().to_path_buf(),
+                exit_code: ExitCode::from(1),
+                stderr: b"no hello\n".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_format_stdout() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+
+        let formatted = format_stdout(&cmd, "sort", b"b\nc\na\n").unwrap();
+        assert_eq!(formatted, b"a\nb\nc\n");
+
+        assert_eq!(
+            format_stdout(&cmd, "echo 'invalid input' >&2; exit 4", b"a\n"),
+            Err(Error::CheckFormatter {
+                cmd_path: cmd_path.clone(),
+                formatter: "echo 'invalid input' >&2; exit 4".to_string(),
+                exit_code: ExitCode::from(4),
+                stderr: b"invalid input\n".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_stderr_only() {
+        let tmp_dir = TempDir::new().unwrap();
+        let cmd_path = write_file_with(tmp_dir.path(), "foo.sh", "./build.sh").unwrap();
+        write_file_with(
+            tmp_dir.path(),
+            "foo.out",
+            "Building...\nerror: no such file",
+        )
+        .unwrap();
+        write_file_with(tmp_dir.path(), "foo.err.only", "^warning:").unwrap();
+        let cmd = CommandSpec::new(&cmd_path).unwrap();
+
+        let res = CommandResult::new(0.into(), b"Building...\nerror: no such file\n", &[]);
+        assert!(check_result(&cmd, &res, &VerifyOptions::default()).is_ok());
+
+        // Patterns from options and from the spec are both checked.
+        let options = VerifyOptions {
+            stderr_only: vec![Regex::new("^error:").unwrap()],
+        };
+        assert_eq!(
+            check_result(&cmd, &res, &options),
+            Err(Error::CheckStdoutStderrOnly {
+                cmd_path: cmd.cmd_path().to_path_buf(),
+                pattern: "^error:".to_string(),
+                actual: "error: no such file".to_string(),
+                row: 2,
+            })
+        );
     }
 }
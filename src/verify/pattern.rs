@@ -1,16 +1,33 @@
-use crate::chunk::{PatternLine, PatternLines};
+use crate::chunk::{Delimiters, PatternLine, PatternLines, find_segment_mismatch};
 use crate::verify::diff::{Diff, Error};
 
+/// A line of a pattern file made only of this directive matches zero or more arbitrary lines,
+/// until the next expected line matches.
+pub const SKIP_LINE: &str = "[...]";
+
+/// Returns `true` if the `raw_line` of a pattern file is a [`SKIP_LINE`] directive.
+pub fn is_skip_line(raw_line: &str) -> bool {
+    raw_line.trim_end_matches(['\n', '\r']) == SKIP_LINE
+}
+
 pub fn eval_pat_diff(expected: &str, actual: &[u8]) -> Result<Option<Diff>, Error> {
     // We accept lossy UTF-8 string for actual to detect encoding errors.
     let actual = String::from_utf8_lossy(actual).to_string();
     let mut actual_lines = actual.split_inclusive('\n');
     let expected_lines = PatternLines::new(expected);
+    let delimiters = expected_lines.delimiters();
+    // The header line declaring delimiters is not a pattern line.
+    let header_rows = expected_lines.header_rows();
+    let mut raw_lines = expected.split_inclusive('\n').skip(header_rows);
 
     // We consume line pattern by line pattern and test each pattern. At the end, we must have
     // consume all the actual string, otherwise we have a mismatch.
-    let mut row = 1;
+    let mut row = 1 + header_rows;
+    // `true` if actual lines can be skipped until the next expected line matches.
+    let mut skipping = false;
     for expected_line in expected_lines {
+        let raw_line = raw_lines.next().unwrap_or_default();
+
         // Do we have a valid expected line?
         let expected_line = match expected_line {
             Err(error) => {
@@ -22,75 +39,50 @@ pub fn eval_pat_diff(expected: &str, actual: &[u8]) -> Result<Option<Diff>, Erro
             Ok(line) => line,
         };
 
-        // No we test all the possible chunks variant.
-        match expected_line {
-            PatternLine::NoPattern(expected_line) => {
-                // Do we have something in value to compare against?
-                let Some(actual_line) = actual_lines.next() else {
-                    let diff = Diff::Line {
-                        expected: Some(expected_line),
-                        actual: None,
-                        row,
-                    };
-                    return Ok(Some(diff));
-                };
-
-                // We know that there is some actual value left
-                if expected_line != actual_line {
-                    let diff = Diff::Line {
-                        expected: Some(expected_line),
-                        actual: Some(actual_line.to_string()),
-                        row,
-                    };
-                    return Ok(Some(diff));
-                }
-            }
-            PatternLine::Pattern(expected_line) => {
-                // Do we have something in value to compare against?
-                let Some(actual_line) = actual_lines.next() else {
-                    let diff = Diff::PatternLine {
-                        expected: Some(expected_line.to_string()),
-                        actual: None,
-                        row,
-                    };
-                    return Ok(Some(diff));
-                };
-
-                let mat = expected_line.find(actual_line);
-                match mat {
-                    Some(mat) => {
-                        // We have a match but not at the beginning of expected line
-                        if mat.start() != 0 {
-                            let diff = Diff::PatternLine {
-                                expected: Some(expected_line.to_string()),
-                                actual: Some(actual_line.to_string()),
-                                row,
-                            };
-                            return Ok(Some(diff));
-                        }
-                    }
-                    None => {
-                        // We don't have any match
-                        let diff = Diff::PatternLine {
-                            expected: Some(expected_line.to_string()),
-                            actual: Some(actual_line.to_string()),
-                            row,
-                        };
-                        return Ok(Some(diff));
-                    }
+        if is_skip_line(raw_line) {
+            skipping = true;
+            row += 1;
+            continue;
+        }
+
+        if skipping {
+            // We skip actual lines up to the first one matching the expected line. If there is
+            // none, the expected line is missing.
+            loop {
+                let actual_line = actual_lines.next();
+                let diff = eval_line_diff(&expected_line, raw_line, actual_line, row, &delimiters);
+                match (diff, actual_line) {
+                    (None, _) => break,
+                    (Some(_), Some(_)) => continue,
+                    (Some(diff), None) => return Ok(Some(diff)),
                 }
             }
+            skipping = false;
+        } else if let Some(diff) = eval_line_diff(
+            &expected_line,
+            raw_line,
+            actual_lines.next(),
+            row,
+            &delimiters,
+        ) {
+            return Ok(Some(diff));
         }
 
         row += 1;
     }
 
+    // A trailing skip line matches the remaining actual lines.
+    if skipping {
+        return Ok(None);
+    }
+
     // We have consumed all the expected lines, do we have cosumed all the actual?
     if let Some(actual_line) = actual_lines.next() {
         let diff = Diff::Line {
             expected: None,
             actual: Some(actual_line.to_string()),
             row,
+            chunk: 0,
         };
         return Ok(Some(diff));
     }
@@ -98,9 +90,67 @@ pub fn eval_pat_diff(expected: &str, actual: &[u8]) -> Result<Option<Diff>, Erro
     Ok(None)
 }
 
+/// Returns the difference between an `expected_line`, parsed from the `raw_line` at `row` of the
+/// pattern file, and an `actual_line` (`None` if actual lines have all been consumed).
+fn eval_line_diff(
+    expected_line: &PatternLine,
+    raw_line: &str,
+    actual_line: Option<&str>,
+    row: usize,
+    delimiters: &Delimiters,
+) -> Option<Diff> {
+    match expected_line {
+        PatternLine::NoPattern(expected_line) => {
+            // Do we have something in value to compare against?
+            let Some(actual_line) = actual_line else {
+                return Some(Diff::Line {
+                    expected: Some(expected_line.clone()),
+                    actual: None,
+                    row,
+                    chunk: 0,
+                });
+            };
+
+            // We know that there is some actual value left
+            if expected_line != actual_line {
+                return Some(Diff::Line {
+                    expected: Some(expected_line.clone()),
+                    actual: Some(actual_line.to_string()),
+                    row,
+                    chunk: 0,
+                });
+            }
+            None
+        }
+        PatternLine::Pattern(expected_line) => {
+            // Do we have something in value to compare against?
+            let Some(actual_line) = actual_line else {
+                return Some(Diff::PatternLine {
+                    expected: Some(expected_line.to_string()),
+                    actual: None,
+                    row,
+                    mismatch: None,
+                });
+            };
+
+            // We have a match only if it starts at the beginning of the actual line.
+            match expected_line.find(actual_line) {
+                Some(mat) if mat.start() == 0 => None,
+                _ => Some(Diff::PatternLine {
+                    expected: Some(expected_line.to_string()),
+                    actual: Some(actual_line.to_string()),
+                    row,
+                    mismatch: find_segment_mismatch(raw_line, actual_line, delimiters),
+                }),
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chunk::SegmentMismatch;
 
     #[test]
     fn test_pat_none_diff() {
@@ -134,6 +184,7 @@ mod tests {
                 expected: Some("bar".to_string()),
                 actual: Some("baz".to_string()),
                 row: 2,
+                chunk: 0,
             })
         );
 
@@ -147,6 +198,86 @@ mod tests {
                 expected: Some("[ab]{2}\n".to_string()),
                 actual: Some("baz\n".to_string()),
                 row: 3,
+                mismatch: None,
+            })
+        );
+
+        // Diff with a composite pattern line
+        let expected = "id: <<<\\d+>>> items: <<<\\d+>>>\n";
+        let actual = "id: 12 items: none\n".as_bytes();
+        let diff = eval_pat_diff(expected, actual).unwrap();
+        assert_eq!(
+            diff,
+            Some(Diff::PatternLine {
+                expected: Some("id: \\d+ items: \\d+\n".to_string()),
+                actual: Some("id: 12 items: none\n".to_string()),
+                row: 1,
+                mismatch: Some(SegmentMismatch {
+                    segment: "<<<\\d+>>>".to_string(),
+                    column: 15,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_pat_delimiters() {
+        let expected = "# cliche: delimiters={{ }}\n<<<<<<< HEAD\nid: {{\\d+}}\n>>>>>>> main\n";
+        let diff = eval_pat_diff(expected, b"<<<<<<< HEAD\nid: 12\n>>>>>>> main\n").unwrap();
+        assert!(diff.is_none());
+
+        // Rows are located in the pattern file, header included.
+        let diff = eval_pat_diff(expected, b"<<<<<<< HEAD\nid: 12\n>>>>>>> dev\n").unwrap();
+        assert_eq!(
+            diff,
+            Some(Diff::Line {
+                expected: Some(">>>>>>> main\n".to_string()),
+                actual: Some(">>>>>>> dev\n".to_string()),
+                row: 4,
+                chunk: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_pat_skip_lines() {
+        let expected = "Compiling\n[...]\nFinished in <<<\\d+>>>s\n";
+        for actual in [
+            "Compiling\nFinished in 3s\n",
+            "Compiling\nfoo v1.0\nbar v2.0\nFinished in 12s\n",
+        ] {
+            let diff = eval_pat_diff(expected, actual.as_bytes()).unwrap();
+            assert!(diff.is_none());
+        }
+
+        // A trailing skip line matches the remaining lines.
+        let expected = "Compiling\n[...]\n";
+        let diff = eval_pat_diff(expected, b"Compiling\nfoo\nbar\n").unwrap();
+        assert!(diff.is_none());
+
+        // The expected line following a skip line is missing.
+        let expected = "Compiling\n[...]\nFinished\n";
+        let diff = eval_pat_diff(expected, b"Compiling\nfoo\nFailed\n").unwrap();
+        assert_eq!(
+            diff,
+            Some(Diff::Line {
+                expected: Some("Finished\n".to_string()),
+                actual: None,
+                row: 3,
+                chunk: 0,
+            })
+        );
+
+        // Lines after a skipped section are matched one to one again.
+        let expected = "[...]\nFinished\ndone\n";
+        let diff = eval_pat_diff(expected, b"foo\nFinished\nfailed\n").unwrap();
+        assert_eq!(
+            diff,
+            Some(Diff::Line {
+                expected: Some("done\n".to_string()),
+                actual: Some("failed\n".to_string()),
+                row: 3,
+                chunk: 0,
             })
         );
     }
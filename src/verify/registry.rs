@@ -0,0 +1,221 @@
+//! Registry of the comparators of the stdout and stderr expectation files.
+//!
+//! Each expectation file extension has a default comparator (`foo.out` is compared exactly,
+//! `foo.out.pattern` with patterns...), that a test can override with its `compare` attribute,
+//! `compare=out:json` for instance. A new comparison mode is added by registering a comparator in
+//! [`COMPARATORS`].
+use crate::command::CommandSpec;
+use crate::error::Error;
+use crate::json::JsonValue;
+use crate::verify::checksum::sha256_hex;
+use crate::verify::diff::Diff;
+use crate::verify::{exact, json};
+
+/// An output stream of a command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A comparison mode of an actual output against an expectation file.
+pub struct Comparator {
+    pub name: &'static str,
+    /// Compares the `actual` output of a `stream` against the expectation file of a command with
+    /// a given extension.
+    compare: fn(&CommandSpec, &str, Stream, &[u8]) -> Result<(), Error>,
+}
+
+/// The registered comparators.
+pub const COMPARATORS: [Comparator; 5] = [
+    Comparator {
+        name: "exact",
+        compare: compare_exact,
+    },
+    Comparator {
+        name: "pattern",
+        compare: compare_pattern,
+    },
+    Comparator {
+        name: "json",
+        compare: compare_json,
+    },
+    Comparator {
+        name: "binary",
+        compare: compare_binary,
+    },
+    Comparator {
+        name: "checksum",
+        compare: compare_checksum,
+    },
+];
+
+/// The expectation files compared with a comparator: their extension, the stream they apply to
+/// and their default comparator.
+pub const EXPECTATIONS: [(&str, Stream, &str); 8] = [
+    ("out", Stream::Stdout, "exact"),
+    ("out.pattern", Stream::Stdout, "pattern"),
+    ("out.json", Stream::Stdout, "json"),
+    ("out.sha256", Stream::Stdout, "checksum"),
+    ("err", Stream::Stderr, "exact"),
+    ("err.pattern", Stream::Stderr, "pattern"),
+    ("err.json", Stream::Stderr, "json"),
+    ("err.sha256", Stream::Stderr, "checksum"),
+];
+
+/// Returns the comparator registered under `name`, if any.
+pub fn comparator(name: &str) -> Option<&'static Comparator> {
+    COMPARATORS.iter().find(|c| c.name == name)
+}
+
+/// Returns `true` if `ext` is the extension of an expectation file compared with a comparator.
+pub fn is_expectation(ext: &str) -> bool {
+    EXPECTATIONS.iter().any(|(e, _, _)| *e == ext)
+}
+
+impl Comparator {
+    /// Compares the `actual` output of a `stream` against the expectation file of `cmd` with
+    /// extension `ext`.
+    pub fn compare(
+        &self,
+        cmd: &CommandSpec,
+        ext: &str,
+        stream: Stream,
+        actual: &[u8],
+    ) -> Result<(), Error> {
+        (self.compare)(cmd, ext, stream, actual)
+    }
+}
+
+/// Checks the `actual` output of a `stream` against every existing expectation file of this
+/// stream, each one with its comparator.
+pub fn check_stream(cmd: &CommandSpec, stream: Stream, actual: &[u8]) -> Result<(), Error> {
+    for (ext, s, default) in EXPECTATIONS {
+        if s != stream || !cmd.has_expectation(ext) {
+            continue;
+        }
+        let name = cmd.comparator(ext).unwrap_or(default);
+        // Comparator names are validated when the test attributes are parsed.
+        let comparator = comparator(name).expect("registered comparator");
+        comparator.compare(cmd, ext, stream, actual)?;
+    }
+    Ok(())
+}
+
+/// Compares an output to a snapshot with its front-matter options, and to its alternatives.
+fn compare_exact(cmd: &CommandSpec, ext: &str, stream: Stream, actual: &[u8]) -> Result<(), Error> {
+    let snapshot = cmd.read_snapshot(ext)?;
+    let front_matter = cmd.snapshot_front_matter(ext, &snapshot)?;
+    let check = match stream {
+        Stream::Stdout => super::check_stdout_snapshot,
+        Stream::Stderr => super::check_stderr_snapshot,
+    };
+    super::check_alternatives(cmd, ext, (snapshot, front_matter), actual, check)
+}
+
+/// Compares an output line by line to patterns.
+fn compare_pattern(
+    cmd: &CommandSpec,
+    ext: &str,
+    stream: Stream,
+    actual: &[u8],
+) -> Result<(), Error> {
+    let expected = cmd.read_expectation_text(ext)?;
+    match stream {
+        Stream::Stdout => super::check_stdout_pat_value(cmd.cmd_path(), &expected, actual),
+        Stream::Stderr => super::check_stderr_pat_value(cmd.cmd_path(), &expected, actual),
+    }
+}
+
+/// Compares an output to a JSON value: the order of object keys and the formatting are not
+/// significant.
+fn compare_json(cmd: &CommandSpec, ext: &str, _stream: Stream, actual: &[u8]) -> Result<(), Error> {
+    let text = cmd.read_expectation_text(ext)?;
+    let expected = JsonValue::parse(&text).map_err(|reason| Error::FileNotJson {
+        path: cmd.snapshot_path(ext),
+        reason,
+    })?;
+    let error = |pointer: String, expected: Option<String>, actual: Option<String>| {
+        Error::CheckOutputJson {
+            cmd_path: cmd.cmd_path().to_path_buf(),
+            ext: ext.to_string(),
+            pointer,
+            expected,
+            actual,
+        }
+    };
+    let actual = String::from_utf8_lossy(actual);
+    let Ok(actual) = JsonValue::parse(&actual) else {
+        return Err(error(
+            "$".to_string(),
+            Some(expected.to_string()),
+            Some(actual.into_owned()),
+        ));
+    };
+    match json::eval_json_diff(&expected, &actual) {
+        None => Ok(()),
+        Some(diff) => Err(error(diff.path, diff.expected, diff.actual)),
+    }
+}
+
+/// Compares an output byte by byte, without front-matter nor alternatives.
+fn compare_binary(
+    cmd: &CommandSpec,
+    ext: &str,
+    stream: Stream,
+    actual: &[u8],
+) -> Result<(), Error> {
+    let expected = cmd.read_expectation(ext)?;
+    let Some(Diff::Byte {
+        offset,
+        start,
+        expected,
+        actual,
+    }) = exact::eval_exact_diff_as_bytes(&expected, actual)
+    else {
+        return Ok(());
+    };
+    let cmd_path = cmd.cmd_path().to_path_buf();
+    Err(match stream {
+        Stream::Stdout => Error::CheckStdoutBytes {
+            cmd_path,
+            offset,
+            start,
+            expected,
+            actual,
+        },
+        Stream::Stderr => Error::CheckStderrBytes {
+            cmd_path,
+            offset,
+            start,
+            expected,
+            actual,
+        },
+    })
+}
+
+/// Compares the SHA-256 digest of an output to the digest of the expectation file, given as hex
+/// digits in the format of `sha256sum` (the digest possibly followed by a file name).
+fn compare_checksum(
+    cmd: &CommandSpec,
+    ext: &str,
+    _stream: Stream,
+    actual: &[u8],
+) -> Result<(), Error> {
+    let text = cmd.read_expectation_text(ext)?;
+    let expected = text
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let actual = sha256_hex(actual);
+    if expected == actual {
+        return Ok(());
+    }
+    Err(Error::CheckChecksum {
+        cmd_path: cmd.cmd_path().to_path_buf(),
+        ext: ext.to_string(),
+        expected,
+        actual,
+    })
+}
@@ -0,0 +1,138 @@
+use crate::text::{Style, StyledString};
+
+/// Maximum size of the LCS table computed for a diff; above it, we fall back to a diff without
+/// common lines detection.
+const MAX_LCS_SIZE: usize = 4_000_000;
+
+/// Returns a complete line diff between an `expected` text and an `actual` text.
+///
+/// Each line of the diff is prefixed with `-` for an expected line missing in actual, `+` for an
+/// actual line not expected, and a space for a common line.
+pub fn unified_diff(expected: &str, actual: &str) -> StyledString {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // Common prefix and suffix are trimmed to keep the LCS table small.
+    let prefix = expected
+        .iter()
+        .zip(actual.iter())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let expected_mid = &expected[prefix..expected.len() - suffix];
+    let actual_mid = &actual[prefix..actual.len() - suffix];
+
+    let mut s = StyledString::new();
+    for line in &expected[..prefix] {
+        push_line(&mut s, ' ', line);
+    }
+    for (op, line) in diff_lines(expected_mid, actual_mid) {
+        push_line(&mut s, op, line);
+    }
+    for line in &expected[expected.len() - suffix..] {
+        push_line(&mut s, ' ', line);
+    }
+    s
+}
+
+/// Returns the number of lines added or removed between an `expected` text and an `actual` text.
+pub fn distance(expected: &str, actual: &str) -> usize {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    let prefix = expected
+        .iter()
+        .zip(actual.iter())
+        .take_while(|(e, a)| e == a)
+        .count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(e, a)| e == a)
+        .count();
+    diff_lines(
+        &expected[prefix..expected.len() - suffix],
+        &actual[prefix..actual.len() - suffix],
+    )
+    .iter()
+    .filter(|(op, _)| *op != ' ')
+    .count()
+}
+
+fn push_line(s: &mut StyledString, op: char, line: &str) {
+    let style = match op {
+        '-' => Style::new().red(),
+        '+' => Style::new().green(),
+        _ => Style::new(),
+    };
+    s.push_with(&format!("{op} {line}"), style);
+    s.push("\n");
+}
+
+/// Returns the edit script from `expected` lines to `actual` lines, using the longest common
+/// subsequence of lines.
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<(char, &'a str)> {
+    let n = expected.len();
+    let m = actual.len();
+    if (n + 1) * (m + 1) > MAX_LCS_SIZE {
+        let removed = expected.iter().map(|l| ('-', *l));
+        let added = actual.iter().map(|l| ('+', *l));
+        return removed.chain(added).collect();
+    }
+
+    // lcs[i][j] is the length of the LCS of expected[i..] and actual[j..].
+    let mut lcs = vec![vec![0_usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if expected[i] == actual[j] {
+            ops.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(('-', expected[i]));
+            i += 1;
+        } else {
+            ops.push(('+', actual[j]));
+            j += 1;
+        }
+    }
+    ops.extend(expected[i..].iter().map(|l| ('-', *l)));
+    ops.extend(actual[j..].iter().map(|l| ('+', *l)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Format;
+
+    #[test]
+    fn test_unified_diff() {
+        let expected = "a\nb\nc\nd\ne\n";
+        let actual = "a\nc\nx\nd\ne\nf\n";
+        let diff = unified_diff(expected, actual).to_string(Format::Plain);
+        assert_eq!(diff, "  a\n- b\n  c\n+ x\n  d\n  e\n+ f\n");
+    }
+
+    #[test]
+    fn test_unified_diff_no_change() {
+        let diff = unified_diff("a\nb\n", "a\nb\n").to_string(Format::Plain);
+        assert_eq!(diff, "  a\n  b\n");
+    }
+}
@@ -0,0 +1,147 @@
+//! Watch mode: tests are rerun when their script or companion files change (`--watch`).
+//!
+//! Files are polled rather than watched with a filesystem notifier: the files of a test are the
+//! files of the directories of its script and snapshots whose name starts with the stem of the
+//! script (`foo.sh`, `foo.out`, `foo.post.sh`...), so that created and removed companion files
+//! are noticed too.
+use crate::command::PENDING_SUFFIX;
+use crate::suite::{self, SnapshotRoot, Test};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Interval between two polls of the files of the tests.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// State of the files of a test: path, size and modification time of each file.
+type Fingerprint = Vec<(PathBuf, u64, Option<SystemTime>)>;
+
+/// Runs the `tests` with `run`, then reruns the tests whose files change, until interrupted.
+pub fn watch<F>(tests: Vec<Test>, roots: &[SnapshotRoot], mut run: F) -> !
+where
+    F: FnMut(Vec<Test>),
+{
+    let mut fingerprints = tests
+        .iter()
+        .map(|test| fingerprint(test, roots))
+        .collect::<Vec<_>>();
+    run(tests.clone());
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let changed = changed_tests(&tests, roots, &mut fingerprints);
+        if changed.is_empty() {
+            continue;
+        }
+        // Files are usually saved in bursts (a script and its snapshot for instance): we wait for
+        // the end of the burst before running the tests.
+        thread::sleep(POLL_INTERVAL);
+        let mut changed = changed;
+        for test in changed_tests(&tests, roots, &mut fingerprints) {
+            if !changed.contains(&test) {
+                changed.push(test);
+            }
+        }
+        if io::stderr().is_terminal() {
+            eprint!("\x1B[2J\x1B[H");
+        }
+        run(changed);
+    }
+}
+
+/// Returns the `tests` whose files have changed since their `fingerprints`, which are updated.
+fn changed_tests(
+    tests: &[Test],
+    roots: &[SnapshotRoot],
+    fingerprints: &mut [Fingerprint],
+) -> Vec<Test> {
+    let mut changed = vec![];
+    for (test, previous) in tests.iter().zip(fingerprints.iter_mut()) {
+        let current = fingerprint(test, roots);
+        if current != *previous {
+            *previous = current;
+            changed.push(test.clone());
+        }
+    }
+    changed
+}
+
+/// Returns the fingerprint of the files of `test`.
+fn fingerprint(test: &Test, roots: &[SnapshotRoot]) -> Fingerprint {
+    let snapshot_base = suite::snapshot_base(roots, &test.path);
+    let mut dirs = vec![parent(&test.path)];
+    if !dirs.contains(&parent(&snapshot_base)) {
+        dirs.push(parent(&snapshot_base));
+    }
+    let stem = test.path.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{stem}.");
+    let mut files = vec![];
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Pending snapshots are written by the run itself.
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(&prefix) || name.ends_with(&format!(".{PENDING_SUFFIX}")) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            files.push((entry.path(), metadata.len(), metadata.modified().ok()));
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Returns the directory of a file at `path`, the current directory for a bare file name.
+fn parent(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changed_tests() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.path();
+        for name in ["a.sh", "a.out", "b.sh", "bar.out"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let tests = vec![Test::new(&dir.join("a.sh")), Test::new(&dir.join("b.sh"))];
+        let mut fingerprints = tests
+            .iter()
+            .map(|test| fingerprint(test, &[]))
+            .collect::<Vec<_>>();
+        assert_eq!(fingerprints[0].len(), 2);
+        assert!(changed_tests(&tests, &[], &mut fingerprints).is_empty());
+
+        fs::write(dir.join("a.out"), "Hello\n").unwrap();
+        fs::write(dir.join("bar.out"), "Hello\n").unwrap();
+        assert_eq!(
+            changed_tests(&tests, &[], &mut fingerprints),
+            vec![tests[0].clone()]
+        );
+        assert!(changed_tests(&tests, &[], &mut fingerprints).is_empty());
+
+        // Pending snapshots are not a change.
+        fs::write(dir.join("a.out.new"), "Hi\n").unwrap();
+        assert!(changed_tests(&tests, &[], &mut fingerprints).is_empty());
+
+        // A new companion file is a change.
+        fs::write(dir.join("b.exit"), "1\n").unwrap();
+        assert_eq!(
+            changed_tests(&tests, &[], &mut fingerprints),
+            vec![tests[1].clone()]
+        );
+    }
+}